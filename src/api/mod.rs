@@ -1,5 +1,8 @@
 pub mod vyos;
+pub mod vyos_op;
 pub mod proxmox;
+pub mod ipfix;
+pub mod syslog;
 
 use anyhow::Result;
 
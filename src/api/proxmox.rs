@@ -1,8 +1,8 @@
 use anyhow::{Result, Context, anyhow};
-use reqwest::{Client, StatusCode};
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
-use log::{debug, error, info};
+use log::{debug, info};
 
 use crate::api::Provider;
 
@@ -143,7 +143,7 @@ impl ProxmoxClient {
                 let auth_header = format!("PVEAPIToken={}={}", token_id, token_secret);
                 
                 // Test connection with a simple API call
-                let response = client.get(&format!("https://{}:{}/api2/json/version", self.config.host, self.config.port))
+                let response = client.get(format!("https://{}:{}/api2/json/version", self.config.host, self.config.port))
                     .header("Authorization", auth_header)
                     .send()
                     .await
@@ -278,15 +278,76 @@ impl ProxmoxClient {
         self.api_call(&format!("nodes/{}/qemu", node), "POST", Some(params)).await
     }
     
+    /// Get the next free VMID in the cluster
+    pub async fn next_vmid(&mut self) -> Result<u64> {
+        let id = self.api_call("cluster/nextid", "GET", None).await?;
+        id.as_u64()
+            .or_else(|| id.as_str().and_then(|s| s.parse().ok()))
+            .ok_or_else(|| anyhow!("Invalid next VMID: {}", id))
+    }
+    
+    /// Clone a VM or template, returns the UPID of the clone task
+    pub async fn clone_vm(&mut self, node: &str, vmid: u64, params: serde_json::Value) -> Result<String> {
+        let upid = self.api_call(&format!("nodes/{}/qemu/{}/clone", node, vmid), "POST", Some(params)).await?;
+        upid.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Invalid clone task: {}", upid))
+    }
+    
+    /// Get the status of a task
+    pub async fn get_task_status(&mut self, node: &str, upid: &str) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/tasks/{}/status", node, upid), "GET", None).await
+    }
+    
     /// Delete a VM
     pub async fn delete_vm(&mut self, node: &str, vmid: u64) -> Result<serde_json::Value> {
         self.api_call(&format!("nodes/{}/qemu/{}", node, vmid), "DELETE", None).await
     }
     
+    /// Get the firewall rules of a VM
+    pub async fn get_vm_firewall_rules(&mut self, node: &str, vmid: u64) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/qemu/{}/firewall/rules", node, vmid), "GET", None).await
+    }
+    
+    /// Add a firewall rule to a VM
+    pub async fn add_vm_firewall_rule(&mut self, node: &str, vmid: u64, rule: serde_json::Value) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/qemu/{}/firewall/rules", node, vmid), "POST", Some(rule)).await
+    }
+    
+    /// Delete a VM firewall rule by position
+    pub async fn delete_vm_firewall_rule(&mut self, node: &str, vmid: u64, pos: u64) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/qemu/{}/firewall/rules/{}", node, vmid, pos), "DELETE", None).await
+    }
+    
+    /// Update the firewall options of a VM
+    pub async fn set_vm_firewall_options(&mut self, node: &str, vmid: u64, options: serde_json::Value) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/qemu/{}/firewall/options", node, vmid), "PUT", Some(options)).await
+    }
+    
     /// Get storage information
     pub async fn get_storage(&mut self, node: &str) -> Result<serde_json::Value> {
         self.api_call(&format!("nodes/{}/storage", node), "GET", None).await
     }
+
+    /// Get the configuration of a VM
+    pub async fn get_vm_config(&mut self, node: &str, vmid: u64) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/qemu/{}/config", node, vmid), "GET", None).await
+    }
+
+    /// Update the configuration of a VM
+    pub async fn update_vm_config(&mut self, node: &str, vmid: u64, params: serde_json::Value) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/qemu/{}/config", node, vmid), "PUT", Some(params)).await
+    }
+
+    /// List the disk images on a storage
+    pub async fn get_storage_content(&mut self, node: &str, storage: &str) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/storage/{}/content?content=images", node, storage), "GET", None).await
+    }
+
+    /// Delete a volume from a storage
+    pub async fn delete_storage_content(&mut self, node: &str, storage: &str, volume: &str) -> Result<serde_json::Value> {
+        self.api_call(&format!("nodes/{}/storage/{}/content/{}", node, storage, volume), "DELETE", None).await
+    }
 }
 
 impl Provider for ProxmoxClient {
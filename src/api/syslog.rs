@@ -0,0 +1,206 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Utc};
+
+/// Default syslog port
+pub const SYSLOG_PORT: u16 = 514;
+
+/// Facility names indexed by facility code (RFC 5424 section 6.2.1)
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news",
+    "uucp", "cron", "authpriv", "ftp", "ntp", "security", "console", "solaris-cron",
+    "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+];
+
+/// Name of a facility code
+pub fn facility_name(code: u8) -> &'static str {
+    FACILITIES.get(code as usize).copied().unwrap_or("unknown")
+}
+
+/// Syslog message in either RFC 5424 or RFC 3164 format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyslogMessage {
+    /// Facility code
+    pub facility: u8,
+    /// Severity code (0 emergency to 7 debug)
+    pub severity: u8,
+    /// Time the message was generated, when the sender included one
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Host name of the sender
+    pub hostname: Option<String>,
+    /// Application or tag that logged the message
+    pub app_name: Option<String>,
+    /// Message text
+    pub message: String,
+}
+
+/// Treat the RFC 5424 nil value as absent
+fn nil(field: &str) -> Option<String> {
+    if field == "-" || field.is_empty() { None } else { Some(field.to_string()) }
+}
+
+/// Parse a syslog message
+///
+/// RFC 5424 messages are recognized by their version after the priority,
+/// anything else is parsed leniently as RFC 3164. Messages without a priority
+/// are kept with facility user and severity notice, as RFC 3164 suggests.
+pub fn parse_syslog(line: &str) -> Result<SyslogMessage> {
+    let line = line.trim_end_matches(['\r', '\n', '\0']);
+    if line.trim().is_empty() {
+        return Err(anyhow!("Empty syslog message"));
+    }
+
+    let (priority, rest) = match line.strip_prefix('<').and_then(|s| s.split_once('>')) {
+        Some((priority, rest)) => {
+            let priority: u8 = priority.parse()
+                .map_err(|_| anyhow!("Invalid syslog priority '{}'", priority))?;
+            if priority > 191 {
+                return Err(anyhow!("Invalid syslog priority {}", priority));
+            }
+            (priority, rest)
+        },
+        None => (13, line),
+    };
+
+    let facility = priority / 8;
+    let severity = priority % 8;
+
+    match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(facility, severity, rest),
+        None => Ok(parse_rfc3164(facility, severity, rest)),
+    }
+}
+
+/// Parse the part of an RFC 5424 message after `<PRI>1 `
+fn parse_rfc5424(facility: u8, severity: u8, rest: &str) -> Result<SyslogMessage> {
+    let mut fields = rest.splitn(6, ' ');
+    let mut next = || fields.next().ok_or_else(|| anyhow!("Truncated RFC 5424 message"));
+
+    let timestamp = next()?;
+    let hostname = next()?;
+    let app_name = next()?;
+    let _procid = next()?;
+    let _msgid = next()?;
+    let remainder = fields.next().unwrap_or("");
+
+    // Structured data is either nil or a sequence of bracketed elements
+    let message = if let Some(message) = remainder.strip_prefix('-') {
+        message
+    } else if remainder.starts_with('[') {
+        skip_structured_data(remainder)
+    } else {
+        remainder
+    };
+    let message = message.strip_prefix(' ').unwrap_or(message);
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message);
+
+    Ok(SyslogMessage {
+        facility,
+        severity,
+        timestamp: DateTime::parse_from_rfc3339(timestamp).ok().map(|t| t.with_timezone(&Utc)),
+        hostname: nil(hostname),
+        app_name: nil(app_name),
+        message: message.to_string(),
+    })
+}
+
+/// Skip the structured data elements at the start of an RFC 5424 remainder
+fn skip_structured_data(s: &str) -> &str {
+    let mut in_element = false;
+    let mut escaped = false;
+    let mut in_value = false;
+
+    for (idx, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_value => escaped = true,
+            '"' if in_element => in_value = !in_value,
+            '[' if !in_element => in_element = true,
+            ']' if in_element && !in_value => in_element = false,
+            _ if !in_element => return &s[idx..],
+            _ => {},
+        }
+    }
+
+    ""
+}
+
+/// Parse the part of an RFC 3164 message after `<PRI>`
+fn parse_rfc3164(facility: u8, severity: u8, rest: &str) -> SyslogMessage {
+    // The timestamp has no year or zone: assume the current year in UTC
+    let (timestamp, rest) = match rest.get(..15) {
+        Some(stamp) => {
+            let now = Utc::now();
+            let stamp = format!("{} {}", now.year(), stamp.split_whitespace().collect::<Vec<_>>().join(" "));
+            match NaiveDateTime::parse_from_str(&stamp, "%Y %b %d %H:%M:%S") {
+                Ok(naive) => (Some(Utc.from_utc_datetime(&naive)), rest[15..].trim_start()),
+                Err(_) => (None, rest),
+            }
+        },
+        None => (None, rest),
+    };
+
+    // A host name only follows a timestamp
+    let (hostname, rest) = match (timestamp, rest.split_once(' ')) {
+        (Some(_), Some((hostname, rest))) if !hostname.ends_with(':') => (Some(hostname.to_string()), rest),
+        _ => (None, rest),
+    };
+
+    // The tag ends at the first colon, optionally with a PID in brackets
+    let (app_name, message) = match rest.split_once(": ") {
+        Some((tag, message)) if !tag.is_empty() && !tag.contains(' ') => {
+            let tag = tag.split('[').next().unwrap_or(tag);
+            (Some(tag.to_string()), message)
+        },
+        _ => (None, rest),
+    };
+
+    SyslogMessage {
+        facility,
+        severity,
+        timestamp,
+        hostname,
+        app_name,
+        message: message.to_string(),
+    }
+}
+
+/// Split complete messages off a TCP syslog stream buffer (RFC 6587)
+///
+/// Both octet-counted (`<len> <msg>`) and newline-terminated framing are
+/// accepted. Complete frames are removed from the buffer, an incomplete
+/// trailing frame is left for the next read.
+pub fn split_frames(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut frames = Vec::new();
+    let mut start = 0;
+
+    while start < buffer.len() {
+        let data = &buffer[start..];
+
+        if data[0].is_ascii_digit() {
+            let Some(space) = data.iter().position(|b| *b == b' ') else {
+                break;
+            };
+            let length = std::str::from_utf8(&data[..space]).ok().and_then(|s| s.parse::<usize>().ok());
+            if let Some(length) = length {
+                if data.len() < space + 1 + length {
+                    break;
+                }
+                frames.push(String::from_utf8_lossy(&data[space + 1..space + 1 + length]).into_owned());
+                start += space + 1 + length;
+                continue;
+            }
+        }
+
+        let Some(newline) = data.iter().position(|b| *b == b'\n') else {
+            break;
+        };
+        let frame = String::from_utf8_lossy(&data[..newline]).into_owned();
+        if !frame.trim().is_empty() {
+            frames.push(frame);
+        }
+        start += newline + 1;
+    }
+
+    buffer.drain(..start);
+    frames
+}
@@ -1,5 +1,5 @@
 use anyhow::{Result, Context, anyhow};
-use reqwest::{Client, StatusCode};
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 use tokio::process::Command as AsyncCommand;
@@ -8,6 +8,37 @@ use log::{debug, error, info};
 
 use crate::api::Provider;
 
+/// VyOS release train, which determines the configuration syntax to generate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VyOSVersion {
+    /// 1.4 (sagitta)
+    #[default]
+    V1_4,
+    /// 1.5 (circinus)
+    V1_5,
+}
+
+impl std::fmt::Display for VyOSVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VyOSVersion::V1_4 => write!(f, "1.4"),
+            VyOSVersion::V1_5 => write!(f, "1.5"),
+        }
+    }
+}
+
+impl std::str::FromStr for VyOSVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "1.4" | "sagitta" => Ok(VyOSVersion::V1_4),
+            "1.5" | "circinus" => Ok(VyOSVersion::V1_5),
+            _ => Err(anyhow!("Unsupported VyOS version: {} (expected 1.4 or 1.5)", s)),
+        }
+    }
+}
+
 /// VyOS API client configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VyOSConfig {
@@ -44,6 +75,138 @@ impl Default for VyOSConfig {
     }
 }
 
+/// A single configuration-mode operation against the VyOS config tree
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConfigCommand {
+    /// `set <path>`
+    Set(Vec<String>),
+    /// `delete <path>`
+    Delete(Vec<String>),
+}
+
+impl ConfigCommand {
+    /// Build a `set` command from path components
+    pub fn set<I, S>(path: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ConfigCommand::Set(path.into_iter().map(Into::into).collect())
+    }
+
+    /// Build a `delete` command from path components
+    pub fn delete<I, S>(path: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ConfigCommand::Delete(path.into_iter().map(Into::into).collect())
+    }
+
+    /// Get the config path of the command
+    pub fn path(&self) -> &[String] {
+        match self {
+            ConfigCommand::Set(path) | ConfigCommand::Delete(path) => path,
+        }
+    }
+
+    /// Convert to the operation object accepted by the `/configure` endpoint
+    pub fn to_api_op(&self) -> serde_json::Value {
+        match self {
+            ConfigCommand::Set(path) => serde_json::json!({ "op": "set", "path": path }),
+            ConfigCommand::Delete(path) => serde_json::json!({ "op": "delete", "path": path }),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (op, path) = match self {
+            ConfigCommand::Set(path) => ("set", path),
+            ConfigCommand::Delete(path) => ("delete", path),
+        };
+
+        write!(f, "{}", op)?;
+        for component in path {
+            // Quote anything the VyOS CLI would otherwise split or expand
+            if component.is_empty() || component.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"') {
+                write!(f, " '{}'", component.replace('\'', "'\\''"))?;
+            } else {
+                write!(f, " {}", component)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Difference between the running configuration and a desired configuration
+#[derive(Debug, Clone, Default)]
+pub struct ConfigDiff {
+    /// Paths present on the router that are not desired
+    pub removed: Vec<ConfigCommand>,
+    /// Desired paths missing from the router
+    pub added: Vec<ConfigCommand>,
+}
+
+impl ConfigDiff {
+    /// Compute the diff between two sets of `set` commands
+    pub fn between(current: &[ConfigCommand], desired: &[ConfigCommand]) -> Self {
+        let removed = current.iter()
+            .filter(|c| !desired.contains(c))
+            .map(|c| ConfigCommand::Delete(c.path().to_vec()))
+            .collect();
+        let added = desired.iter()
+            .filter(|c| !current.contains(c))
+            .cloned()
+            .collect();
+
+        Self { removed, added }
+    }
+
+    /// Check if there is nothing to change
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+
+    /// Commands that turn the current configuration into the desired one
+    pub fn commands(&self) -> Vec<ConfigCommand> {
+        self.removed.iter().chain(self.added.iter()).cloned().collect()
+    }
+}
+
+/// Flatten a config tree as returned by `showConfig` into `set` commands
+pub fn flatten_config(prefix: &[String], value: &serde_json::Value) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+
+    match value {
+        serde_json::Value::Object(map) if map.is_empty() => {
+            // Valueless leaf node
+            commands.push(ConfigCommand::Set(prefix.to_vec()));
+        },
+        serde_json::Value::Object(map) => {
+            for (key, child) in map {
+                let mut path = prefix.to_vec();
+                path.push(key.clone());
+                commands.extend(flatten_config(&path, child));
+            }
+        },
+        serde_json::Value::Array(values) => {
+            // Multi-value leaf node
+            for child in values {
+                commands.extend(flatten_config(prefix, child));
+            }
+        },
+        serde_json::Value::Null => {},
+        other => {
+            let mut path = prefix.to_vec();
+            path.push(other.as_str().map(|s| s.to_string()).unwrap_or_else(|| other.to_string()));
+            commands.push(ConfigCommand::Set(path));
+        },
+    }
+
+    commands
+}
+
 /// VyOS API client
 #[derive(Debug)]
 pub struct VyOSClient {
@@ -184,6 +347,133 @@ impl VyOSClient {
         self.api_call("save", "POST", None).await
     }
     
+    /// Apply a batch of configuration commands through the `/configure` endpoint
+    ///
+    /// VyOS commits the whole batch as a single transaction.
+    pub async fn configure(&mut self, commands: &[ConfigCommand]) -> Result<serde_json::Value> {
+        if commands.is_empty() {
+            return Ok(serde_json::Value::Null);
+        }
+        
+        let ops: Vec<serde_json::Value> = commands.iter().map(ConfigCommand::to_api_op).collect();
+        debug!("Applying {} configuration commands", ops.len());
+        
+        self.api_call("configure", "POST", Some(serde_json::Value::Array(ops))).await
+    }
+    
+    /// Check whether a configuration path exists in the running configuration
+    pub async fn config_exists(&mut self, path: &[&str]) -> Result<bool> {
+        let response = self.api_call("retrieve", "POST", Some(serde_json::json!({
+            "op": "exists",
+            "path": path,
+        }))).await?;
+        
+        response.get("data").and_then(serde_json::Value::as_bool)
+            .ok_or_else(|| anyhow!("Unexpected response to exists: {}", response))
+    }
+    
+    /// Retrieve the running configuration below a path as `set` commands
+    pub async fn get_config_commands(&mut self, path: &[&str]) -> Result<Vec<ConfigCommand>> {
+        // VyOS answers showConfig with an error when the path does not exist yet
+        if !self.config_exists(path).await? {
+            return Ok(Vec::new());
+        }
+        
+        let response = self.api_call("retrieve", "POST", Some(serde_json::json!({
+            "op": "showConfig",
+            "path": path,
+        }))).await?;
+        
+        let tree = response.get("data").unwrap_or(&response);
+        let prefix: Vec<String> = path.iter().map(|s| s.to_string()).collect();
+        Ok(flatten_config(&prefix, tree))
+    }
+    
+    /// Run an operational mode `show` command and return its text output
+    pub async fn show(&mut self, path: &[&str]) -> Result<String> {
+        let response = self.api_call("show", "POST", Some(serde_json::json!({
+            "op": "show",
+            "path": path,
+        }))).await?;
+        
+        // The API wraps op-mode output in a `data` string
+        match response.get("data") {
+            Some(serde_json::Value::String(output)) => Ok(output.clone()),
+            Some(other) => Ok(other.to_string()),
+            None => Ok(response.as_str().map(|s| s.to_string()).unwrap_or_else(|| response.to_string())),
+        }
+    }
+    
+    /// Run an op-mode command over SSH through the op-mode wrapper
+    pub async fn execute_op_command(&self, command: &str) -> Result<String> {
+        self.execute_ssh_command(&format!("/opt/vyatta/bin/vyatta-op-cmd-wrapper {}", command)).await
+    }
+    
+    /// Apply configuration commands over SSH, committing and saving them
+    ///
+    /// Used before the HTTP API is available. A password is passed through
+    /// `sshpass` when one is configured, otherwise key or agent auth is used.
+    pub async fn configure_over_ssh(&self, commands: &[ConfigCommand]) -> Result<String> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut script = String::from("source /opt/vyatta/etc/functions/script-template\nconfigure\n");
+        for command in commands {
+            script.push_str(&format!("{}\n", command));
+        }
+        script.push_str("commit\nsave\nexit\n");
+
+        let target = format!("{}@{}", self.config.username, self.config.host);
+        let port = self.config.ssh_port.to_string();
+        let mut ssh = match &self.config.password {
+            Some(password) => {
+                let mut ssh = AsyncCommand::new("sshpass");
+                ssh.env("SSHPASS", password).args(["-e", "ssh"]);
+                ssh
+            },
+            None => AsyncCommand::new("ssh"),
+        };
+        ssh.args(["-o", "StrictHostKeyChecking=no", "-p", port.as_str()]);
+        if let Some(key_path) = &self.config.key_path {
+            ssh.args(["-i", key_path.as_str()]);
+        }
+        ssh.args([target.as_str(), "vbash", "-s"])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        debug!("Applying {} configuration commands over SSH", commands.len());
+        let mut child = ssh.spawn().context("Failed to start SSH")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(script.as_bytes()).await.context("Failed to send configuration script")?;
+        }
+        let output = child.wait_with_output().await.context("Failed to execute SSH command")?;
+
+        // vbash keeps going after a failed set or commit, so check the output as well
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if !output.status.success() || stdout.to_lowercase().contains("failed") {
+            error!("SSH configuration failed: {}{}", stdout, stderr);
+            return Err(anyhow!("Configuration over SSH failed: {}",
+                               if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() }));
+        }
+
+        debug!("SSH configuration output: {}", stdout);
+        Ok(stdout)
+    }
+
+    /// Pull a container image onto the router through the `/container-image` endpoint
+    pub async fn add_container_image(&mut self, image: &str) -> Result<serde_json::Value> {
+        self.api_call("container-image", "POST", Some(serde_json::json!({
+            "op": "add",
+            "name": image,
+        }))).await
+    }
+    
+    /// Get the configured host of this client
+    pub fn host(&self) -> &str {
+        &self.config.host
+    }
+    
     /// Check if connected to VyOS
     pub fn is_connected(&self) -> bool {
         self.connected
@@ -0,0 +1,700 @@
+use serde::{Deserialize, Serialize};
+
+/// BGP session state of a single neighbor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BgpNeighbor {
+    /// Neighbor address (or interface name for unnumbered peers)
+    pub neighbor: String,
+    /// Remote AS number
+    pub remote_as: u32,
+    /// Messages received
+    pub msg_rcvd: u64,
+    /// Messages sent
+    pub msg_sent: u64,
+    /// Session uptime (or time since it went down)
+    pub up_down: String,
+    /// Session state (`Established` when prefixes are being received)
+    pub state: String,
+    /// Prefixes received (only known when established)
+    pub prefixes_received: Option<u64>,
+}
+
+impl BgpNeighbor {
+    /// Check whether the session is established
+    pub fn is_established(&self) -> bool {
+        self.state == "Established"
+    }
+}
+
+/// Parse the neighbor table from `show bgp ... summary` output
+///
+/// Works for any address family since FRR prints the same table layout
+/// for `ipv4 unicast`, `l2vpn evpn` and friends.
+pub fn parse_bgp_summary(output: &str) -> Vec<BgpNeighbor> {
+    let mut neighbors = Vec::new();
+    let mut in_table = false;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("Neighbor") {
+            in_table = true;
+            continue;
+        }
+
+        if !in_table {
+            continue;
+        }
+
+        // The table ends with a blank line or the totals footer
+        if trimmed.is_empty() || trimmed.starts_with("Total number") {
+            in_table = false;
+            continue;
+        }
+
+        let fields: Vec<&str> = trimmed.split_whitespace().collect();
+        if fields.len() < 10 {
+            continue;
+        }
+
+        let Ok(remote_as) = fields[2].parse::<u32>() else {
+            continue;
+        };
+
+        // Established sessions print a prefix count instead of a state name
+        let (state, prefixes_received) = match fields[9].parse::<u64>() {
+            Ok(count) => ("Established".to_string(), Some(count)),
+            Err(_) => (fields[9].to_string(), None),
+        };
+
+        neighbors.push(BgpNeighbor {
+            neighbor: fields[0].to_string(),
+            remote_as,
+            msg_rcvd: fields[3].parse().unwrap_or(0),
+            msg_sent: fields[4].parse().unwrap_or(0),
+            up_down: fields[8].to_string(),
+            state,
+            prefixes_received,
+        });
+    }
+
+    neighbors
+}
+
+/// State of a VRRP group on one router
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VrrpGroup {
+    /// Group name
+    pub name: String,
+    /// Interface the group runs on
+    pub interface: String,
+    /// Virtual router ID
+    pub vrid: u8,
+    /// State (`MASTER`, `BACKUP` or `FAULT`)
+    pub state: String,
+    /// Effective priority
+    pub priority: u8,
+}
+
+impl VrrpGroup {
+    /// Check whether this router currently owns the virtual address
+    pub fn is_master(&self) -> bool {
+        self.state.eq_ignore_ascii_case("master")
+    }
+}
+
+/// Parse the group table from `show vrrp` output
+pub fn parse_vrrp_summary(output: &str) -> Vec<VrrpGroup> {
+    output.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("Name") && !line.starts_with('-'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 5 {
+                return None;
+            }
+
+            Some(VrrpGroup {
+                name: fields[0].to_string(),
+                interface: fields[1].to_string(),
+                vrid: fields[2].parse().ok()?,
+                state: fields[3].to_string(),
+                priority: fields[4].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Interface as listed by `show interfaces`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceStatus {
+    /// Interface name
+    pub name: String,
+    /// Configured addresses
+    pub addresses: Vec<String>,
+    /// Admin state (`u`, `D` or `A`)
+    pub admin_state: String,
+    /// Link state (`u` or `D`)
+    pub link_state: String,
+    /// Interface description
+    pub description: Option<String>,
+}
+
+impl InterfaceStatus {
+    /// Check whether the interface is administratively and physically up
+    pub fn is_up(&self) -> bool {
+        self.admin_state == "u" && self.link_state == "u"
+    }
+}
+
+/// Parse the interface table from `show interfaces` output
+///
+/// Additional addresses are printed on continuation lines below the interface.
+pub fn parse_interfaces(output: &str) -> Vec<InterfaceStatus> {
+    let mut interfaces: Vec<InterfaceStatus> = Vec::new();
+    let mut in_table = false;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("Interface") {
+            in_table = true;
+            continue;
+        }
+
+        if !in_table || trimmed.is_empty() || trimmed.starts_with('-') {
+            continue;
+        }
+
+        // Continuation lines only carry another address
+        if line.starts_with(char::is_whitespace) {
+            if let Some(interface) = interfaces.last_mut() {
+                interface.addresses.push(trimmed.to_string());
+            }
+            continue;
+        }
+
+        let fields: Vec<&str> = trimmed.split_whitespace().collect();
+        if fields.len() < 3 {
+            continue;
+        }
+
+        let Some((admin_state, link_state)) = fields[2].split_once('/') else {
+            continue;
+        };
+
+        let addresses = match fields[1] {
+            "-" => Vec::new(),
+            address => vec![address.to_string()],
+        };
+        let description = (fields.len() > 3).then(|| fields[3..].join(" "));
+
+        interfaces.push(InterfaceStatus {
+            name: fields[0].to_string(),
+            addresses,
+            admin_state: admin_state.to_string(),
+            link_state: link_state.to_string(),
+            description,
+        });
+    }
+
+    interfaces
+}
+
+/// Route as listed by `show ip route`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Route {
+    /// Protocol code (`K`, `C`, `S`, `O`, `B`, ...)
+    pub protocol: String,
+    /// Destination prefix
+    pub prefix: String,
+    /// Whether the route is selected (`>`)
+    pub selected: bool,
+    /// Whether the route is installed in the FIB (`*`)
+    pub fib: bool,
+    /// Administrative distance and metric, e.g. `110/10`
+    pub distance_metric: Option<String>,
+    /// Next hops (address, or `directly connected`)
+    pub next_hops: Vec<String>,
+    /// Outgoing interfaces, one per next hop
+    pub interfaces: Vec<String>,
+}
+
+impl Route {
+    /// Human readable protocol name
+    pub fn protocol_name(&self) -> &'static str {
+        match self.protocol.as_str() {
+            "K" => "kernel",
+            "C" => "connected",
+            "L" => "local",
+            "S" => "static",
+            "R" => "rip",
+            "O" => "ospf",
+            "I" => "isis",
+            "B" => "bgp",
+            "E" => "eigrp",
+            "N" => "nhrp",
+            "T" => "table",
+            "v" => "vnc",
+            "A" => "babel",
+            "f" => "openfabric",
+            _ => "other",
+        }
+    }
+}
+
+/// Parse the routing table from `show ip route [vrf <name>]` output
+///
+/// ECMP routes print extra next hops on continuation lines; they are added to
+/// the route above.
+pub fn parse_routes(output: &str) -> Vec<Route> {
+    let mut routes: Vec<Route> = Vec::new();
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        // Continuation of an ECMP route
+        if trimmed.starts_with("via") || (line.starts_with(char::is_whitespace) && trimmed.contains(" via ")) {
+            if let Some(route) = routes.last_mut() {
+                let (next_hop, interface) = parse_next_hop(trimmed);
+                route.next_hops.extend(next_hop);
+                route.interfaces.extend(interface);
+            }
+            continue;
+        }
+
+        let mut chars = trimmed.chars();
+        let Some(protocol) = chars.next().filter(|c| c.is_ascii_alphabetic()) else {
+            continue;
+        };
+
+        // Codes are followed by selection flags, then the prefix
+        let rest: String = chars.collect();
+        let flags: String = rest.chars().take_while(|c| !c.is_whitespace()).collect();
+        if !flags.chars().all(|c| matches!(c, '>' | '*' | 'q' | 'r' | 'b' | 't' | 'o' | 'x')) {
+            continue;
+        }
+
+        let fields: Vec<&str> = rest[flags.len()..].split_whitespace().collect();
+        let Some(prefix) = fields.first().filter(|p| p.contains('/')) else {
+            continue;
+        };
+
+        let distance_metric = fields.get(1)
+            .filter(|f| f.starts_with('['))
+            .map(|f| f.trim_matches(|c| c == '[' || c == ']').to_string());
+
+        let (next_hop, interface) = parse_next_hop(&fields[1..].join(" "));
+
+        routes.push(Route {
+            protocol: protocol.to_string(),
+            prefix: prefix.to_string(),
+            selected: flags.contains('>'),
+            fib: flags.contains('*'),
+            distance_metric,
+            next_hops: next_hop.into_iter().collect(),
+            interfaces: interface.into_iter().collect(),
+        });
+    }
+
+    routes
+}
+
+/// Extract the next hop and interface from the tail of a route line
+fn parse_next_hop(text: &str) -> (Option<String>, Option<String>) {
+    let parts: Vec<&str> = text.split(',').map(str::trim).collect();
+    let head = parts.first().copied().unwrap_or("");
+
+    let next_hop = if let Some(idx) = head.find("via ") {
+        head[idx + 4..].split_whitespace().next().map(str::to_string)
+    } else if head.contains("directly connected") {
+        Some("directly connected".to_string())
+    } else {
+        None
+    };
+
+    let interface = parts.get(1)
+        .filter(|p| !p.is_empty() && !p.starts_with("weight") && !p.contains(':'))
+        .map(|p| p.to_string());
+
+    (next_hop, interface)
+}
+
+/// OSPF neighbor as listed by `show ip ospf neighbor`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OspfNeighbor {
+    /// Neighbor router ID
+    pub neighbor_id: String,
+    /// Priority
+    pub priority: u8,
+    /// Adjacency state, e.g. `Full/DR`
+    pub state: String,
+    /// Dead timer
+    pub dead_time: String,
+    /// Neighbor interface address
+    pub address: String,
+    /// Local interface (with its address)
+    pub interface: String,
+}
+
+impl OspfNeighbor {
+    /// Check whether the adjacency is fully formed
+    pub fn is_full(&self) -> bool {
+        self.state.starts_with("Full")
+    }
+}
+
+/// Parse the neighbor table from `show ip ospf neighbor` output
+///
+/// Newer FRR releases add an `Up Time` column, which is skipped when present.
+pub fn parse_ospf_neighbors(output: &str) -> Vec<OspfNeighbor> {
+    let mut has_uptime = false;
+    let mut neighbors = Vec::new();
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("Neighbor ID") {
+            has_uptime = trimmed.contains("Up Time");
+            continue;
+        }
+
+        let fields: Vec<&str> = trimmed.split_whitespace().collect();
+        let offset = if has_uptime { 1 } else { 0 };
+        if fields.len() < 6 + offset {
+            continue;
+        }
+
+        let Ok(priority) = fields[1].parse::<u8>() else {
+            continue;
+        };
+
+        neighbors.push(OspfNeighbor {
+            neighbor_id: fields[0].to_string(),
+            priority,
+            state: fields[2].to_string(),
+            dead_time: fields[3 + offset].to_string(),
+            address: fields[4 + offset].to_string(),
+            interface: fields[5 + offset].to_string(),
+        });
+    }
+
+    neighbors
+}
+
+/// ARP entry as listed by `show arp`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArpEntry {
+    /// IP address
+    pub address: String,
+    /// Interface
+    pub interface: String,
+    /// MAC address (absent for incomplete entries)
+    pub mac: Option<String>,
+    /// Neighbor state, e.g. `REACHABLE`
+    pub state: String,
+}
+
+/// Parse the neighbor table from `show arp` output
+pub fn parse_arp(output: &str) -> Vec<ArpEntry> {
+    output.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("Address") && !line.starts_with('-'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 3 || fields[0].parse::<std::net::IpAddr>().is_err() {
+                return None;
+            }
+
+            // Incomplete entries have no link layer address column
+            let (mac, state) = match fields.len() {
+                3 => (None, fields[2]),
+                _ => (Some(fields[2].to_lowercase()), fields[3]),
+            };
+
+            Some(ArpEntry {
+                address: fields[0].to_string(),
+                interface: fields[1].to_string(),
+                mac,
+                state: state.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Connection tracking counters of one CPU from `show conntrack statistics`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConntrackStats {
+    /// CPU the counters belong to (`None` for a total)
+    pub cpu: Option<u32>,
+    /// Lookups that found an existing entry
+    pub found: u64,
+    /// Packets that could not be tracked
+    pub invalid: u64,
+    /// Entries inserted
+    pub insert: u64,
+    /// Inserts that failed (e.g. clashes)
+    pub insert_failed: u64,
+    /// Packets dropped because the table was full
+    pub drop: u64,
+    /// Entries evicted early to make room
+    pub early_drop: u64,
+    /// ICMP errors that could not be matched
+    pub error: u64,
+    /// Lookups restarted after a table resize
+    pub search_restart: u64,
+}
+
+impl ConntrackStats {
+    /// Sum the counters of all CPUs
+    pub fn total(stats: &[ConntrackStats]) -> ConntrackStats {
+        stats.iter().fold(ConntrackStats::default(), |mut total, s| {
+            total.found += s.found;
+            total.invalid += s.invalid;
+            total.insert += s.insert;
+            total.insert_failed += s.insert_failed;
+            total.drop += s.drop;
+            total.early_drop += s.early_drop;
+            total.error += s.error;
+            total.search_restart += s.search_restart;
+            total
+        })
+    }
+}
+
+/// Parse the per-CPU table from `show conntrack statistics` output
+pub fn parse_conntrack_statistics(output: &str) -> Vec<ConntrackStats> {
+    output.lines()
+        .filter_map(|line| {
+            let fields: Vec<u64> = line.split_whitespace()
+                .map(|f| f.parse::<u64>())
+                .collect::<Result<_, _>>()
+                .ok()?;
+            if fields.len() < 9 {
+                return None;
+            }
+
+            Some(ConntrackStats {
+                cpu: Some(fields[0] as u32),
+                found: fields[1],
+                invalid: fields[2],
+                insert: fields[3],
+                insert_failed: fields[4],
+                drop: fields[5],
+                early_drop: fields[6],
+                error: fields[7],
+                search_restart: fields[8],
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BGP_SUMMARY: &str = "\
+IPv4 Unicast Summary (VRF default):
+BGP router identifier 192.0.2.1, local AS number 65001 vrf-id 0
+BGP table version 12
+RIB entries 9, using 1728 bytes of memory
+Peers 2, using 40 KiB of memory
+
+Neighbor        V         AS   MsgRcvd   MsgSent   TblVer  InQ OutQ  Up/Down State/PfxRcd   PfxSnt Desc
+192.0.2.2       4      65002      1234      1230        0    0    0 2d03h04m            4        5 N/A
+192.0.2.3       4      65003         0         0        0    0    0    never       Active        0 N/A
+
+Total number of neighbors 2
+";
+
+    #[test]
+    fn bgp_summary_reports_state_and_prefixes() {
+        let neighbors = parse_bgp_summary(BGP_SUMMARY);
+        assert_eq!(neighbors.len(), 2);
+
+        assert_eq!(neighbors[0].neighbor, "192.0.2.2");
+        assert_eq!(neighbors[0].remote_as, 65002);
+        assert_eq!((neighbors[0].msg_rcvd, neighbors[0].msg_sent), (1234, 1230));
+        assert_eq!(neighbors[0].up_down, "2d03h04m");
+        assert!(neighbors[0].is_established());
+        assert_eq!(neighbors[0].prefixes_received, Some(4));
+
+        assert_eq!(neighbors[1].state, "Active");
+        assert!(!neighbors[1].is_established());
+        assert_eq!(neighbors[1].prefixes_received, None);
+    }
+
+    #[test]
+    fn vrrp_summary_skips_the_header() {
+        let output = "\
+Name      Interface      VRID  State      Priority  Last Transition
+--------  -----------  ------  -------  ----------  -----------------
+bb-web    eth1             10  MASTER          200  2h14m
+bb-db     eth1.20          11  BACKUP          100  2h14m
+";
+        let groups = parse_vrrp_summary(output);
+        assert_eq!(groups.len(), 2);
+        assert_eq!((groups[0].name.as_str(), groups[0].interface.as_str()), ("bb-web", "eth1"));
+        assert_eq!((groups[0].vrid, groups[0].priority), (10, 200));
+        assert!(groups[0].is_master());
+        assert_eq!(groups[1].interface, "eth1.20");
+        assert!(!groups[1].is_master());
+    }
+
+    #[test]
+    fn interfaces_collect_continuation_addresses() {
+        let output = "\
+Codes: S - State, L - Link, u - Up, D - Down, A - Admin Down
+Interface        IP Address                        S/L  Description
+---------        ----------                        ---  -----------
+eth0             192.0.2.1/24                      u/u  Uplink to ISP
+                 2001:db8::1/64
+eth1             10.0.0.1/24                       u/u
+eth2             -                                 A/D
+lo               127.0.0.1/8                       u/u
+                 ::1/128
+";
+        let interfaces = parse_interfaces(output);
+        assert_eq!(interfaces.len(), 4);
+
+        assert_eq!(interfaces[0].name, "eth0");
+        assert_eq!(interfaces[0].addresses, ["192.0.2.1/24", "2001:db8::1/64"]);
+        assert_eq!(interfaces[0].description.as_deref(), Some("Uplink to ISP"));
+        assert!(interfaces[0].is_up());
+
+        assert_eq!(interfaces[1].description, None);
+        assert!(interfaces[2].addresses.is_empty());
+        assert_eq!((interfaces[2].admin_state.as_str(), interfaces[2].link_state.as_str()), ("A", "D"));
+        assert!(!interfaces[2].is_up());
+        assert_eq!(interfaces[3].addresses, ["127.0.0.1/8", "::1/128"]);
+    }
+
+    const ROUTES_1_4: &str = "\
+Codes: K - kernel route, C - connected, S - static, R - RIP,
+       O - OSPF, I - IS-IS, B - BGP, E - EIGRP, N - NHRP,
+       T - Table, v - VNC, V - VNC-Direct, A - Babel, F - PBR,
+       f - OpenFabric,
+       > - selected route, * - FIB route, q - queued, r - rejected, b - backup
+       t - trapped, o - offload failure
+
+S>* 0.0.0.0/0 [1/0] via 192.0.2.254, eth0, weight 1, 2d03h04m
+O   10.0.0.0/24 [110/1] is directly connected, eth1, weight 1, 2d03h03m
+C>* 10.0.0.0/24 is directly connected, eth1, 2d03h04m
+B>* 10.1.0.0/24 [20/0] via 192.0.2.2, eth0, weight 1, 01:02:03
+  *                    via 192.0.2.3, eth0, weight 1, 01:02:03
+";
+
+    const ROUTES_1_5: &str = "\
+Codes: K - kernel route, C - connected, L - local, S - static,
+       R - RIP, O - OSPF, I - IS-IS, B - BGP, E - EIGRP, N - NHRP,
+       T - Table, v - VNC, V - VNC-Direct, A - Babel, F - PBR,
+       f - OpenFabric, t - Table-Direct,
+       > - selected route, * - FIB route, q - queued, r - rejected, b - backup
+       t - trapped, o - offload failure
+
+IPv4 unicast VRF default:
+S>* 0.0.0.0/0 [1/0] via 192.0.2.254, eth0, weight 1, 2d03h04m
+C>* 10.0.0.0/24 is directly connected, eth1, weight 1, 2d03h04m
+L>* 10.0.0.1/32 is directly connected, eth1, weight 1, 2d03h04m
+";
+
+    #[test]
+    fn routes_parse_1_4_output() {
+        let routes = parse_routes(ROUTES_1_4);
+        assert_eq!(routes.len(), 4);
+
+        let default = &routes[0];
+        assert_eq!((default.protocol_name(), default.prefix.as_str()), ("static", "0.0.0.0/0"));
+        assert!(default.selected && default.fib);
+        assert_eq!(default.distance_metric.as_deref(), Some("1/0"));
+        assert_eq!(default.next_hops, ["192.0.2.254"]);
+        assert_eq!(default.interfaces, ["eth0"]);
+
+        let ospf = &routes[1];
+        assert_eq!(ospf.protocol_name(), "ospf");
+        assert!(!ospf.selected && !ospf.fib);
+        assert_eq!(ospf.distance_metric.as_deref(), Some("110/1"));
+
+        let connected = &routes[2];
+        assert_eq!(connected.distance_metric, None);
+        assert_eq!(connected.next_hops, ["directly connected"]);
+        assert_eq!(connected.interfaces, ["eth1"]);
+
+        let ecmp = &routes[3];
+        assert_eq!(ecmp.protocol_name(), "bgp");
+        assert_eq!(ecmp.next_hops, ["192.0.2.2", "192.0.2.3"]);
+        assert_eq!(ecmp.interfaces, ["eth0", "eth0"]);
+    }
+
+    #[test]
+    fn routes_parse_1_5_output() {
+        let routes = parse_routes(ROUTES_1_5);
+        let prefixes: Vec<_> = routes.iter().map(|r| (r.protocol_name(), r.prefix.as_str())).collect();
+        assert_eq!(prefixes, [("static", "0.0.0.0/0"), ("connected", "10.0.0.0/24"), ("local", "10.0.0.1/32")]);
+        assert_eq!(routes[2].interfaces, ["eth1"]);
+    }
+
+    #[test]
+    fn ospf_neighbors_with_and_without_uptime() {
+        let with_uptime = "\
+Neighbor ID     Pri State           Up Time         Dead Time Address         Interface                        RXmtL RqstL DBsmL
+192.0.2.2         1 Full/DR         2d03h04m          33.512s 10.0.0.2        eth1:10.0.0.1                        0     0     0
+192.0.2.3         1 2-Way/DROther   2d03h04m          35.100s 10.0.0.3        eth1:10.0.0.1                        0     0     0
+";
+        let without_uptime = "\
+Neighbor ID     Pri State           Dead Time Address         Interface                        RXmtL RqstL DBsmL
+192.0.2.2         1 Full/DR           33.512s 10.0.0.2        eth1:10.0.0.1                        0     0     0
+";
+
+        for output in [with_uptime, without_uptime] {
+            let neighbors = parse_ospf_neighbors(output);
+            assert_eq!(neighbors[0].neighbor_id, "192.0.2.2");
+            assert_eq!(neighbors[0].priority, 1);
+            assert!(neighbors[0].is_full());
+            assert_eq!(neighbors[0].dead_time, "33.512s");
+            assert_eq!(neighbors[0].address, "10.0.0.2");
+            assert_eq!(neighbors[0].interface, "eth1:10.0.0.1");
+        }
+        let neighbors = parse_ospf_neighbors(with_uptime);
+        assert_eq!(neighbors.len(), 2);
+        assert!(!neighbors[1].is_full());
+    }
+
+    #[test]
+    fn arp_entries_allow_missing_macs() {
+        let output = "\
+Address      Interface    Link layer address    State
+-----------  -----------  --------------------  ----------
+10.0.0.10    eth1         52:54:00:AB:CD:EF     REACHABLE
+10.0.0.11    eth1                               INCOMPLETE
+192.0.2.254  eth0         00:11:22:33:44:55     STALE
+";
+        let entries = parse_arp(output);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].mac.as_deref(), Some("52:54:00:ab:cd:ef"));
+        assert_eq!(entries[0].state, "REACHABLE");
+        assert_eq!((entries[1].address.as_str(), entries[1].mac.as_deref()), ("10.0.0.11", None));
+        assert_eq!(entries[1].state, "INCOMPLETE");
+        assert_eq!(entries[2].interface, "eth0");
+    }
+
+    #[test]
+    fn conntrack_statistics_sum_per_cpu_counters() {
+        let output = "\
+CPU    found    invalid    insert    insert_failed    drop    early_drop    errors    search_restart
+-----  -------  ---------  --------  ---------------  ------  ------------  --------  ----------------
+0      0        12         0         0                0       0             3         0
+1      0        8          0         2                1       0             0         1
+";
+        let stats = parse_conntrack_statistics(output);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[1].cpu, Some(1));
+
+        let total = ConntrackStats::total(&stats);
+        assert_eq!((total.invalid, total.insert_failed, total.drop), (20, 2, 1));
+        assert_eq!((total.error, total.search_restart), (3, 1));
+    }
+}
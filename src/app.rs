@@ -1,5 +1,8 @@
 use std::error;
 
+use crate::models::logs::LogEntry;
+use crate::services::router::RouterStatus;
+
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
@@ -9,6 +12,7 @@ pub enum AppMode {
     Instances,
     Volumes,
     Networks,
+    Logs,
     Settings,
     Help,
 }
@@ -58,13 +62,22 @@ pub struct App {
     pub volumes: Vec<Volume>,
     /// List of networks
     pub networks: Vec<Network>,
+    /// Router status shown next to the networks, by router name
+    pub routers: Vec<(String, Result<RouterStatus, String>)>,
+    /// Whether router status should be reloaded
+    pub refresh_routers: bool,
+    /// Whether router status is being read in the background
+    pub loading_routers: bool,
+    /// Latest collected log messages, oldest first
+    pub logs: Vec<LogEntry>,
+    /// Whether the log pane should be reloaded from the store
+    pub refresh_logs: bool,
 }
 
 impl Default for App {
     fn default() -> Self {
-        let mut instances = Vec::new();
         // Add some demo instances for testing
-        instances.push(Instance {
+        let instances = vec![Instance {
             id: "i-01234567".to_string(),
             name: "web-1".to_string(),
             status: "running".to_string(),
@@ -74,9 +87,8 @@ impl Default for App {
             cpu: 2,
             memory_gb: 4,
             disk_gb: 80,
-        });
-        
-        instances.push(Instance {
+        },
+        Instance {
             id: "i-89abcdef".to_string(),
             name: "db-1".to_string(),
             status: "running".to_string(),
@@ -86,24 +98,22 @@ impl Default for App {
             cpu: 4,
             memory_gb: 16,
             disk_gb: 160,
-        });
+        }];
         
-        let mut volumes = Vec::new();
-        volumes.push(Volume {
+        let volumes = vec![Volume {
             id: "vol-01234567".to_string(),
             name: "db-data".to_string(),
             size_gb: 100,
             attached_to: Some("i-89abcdef".to_string()),
             region: "nyc".to_string(),
-        });
+        }];
         
-        let mut networks = Vec::new();
-        networks.push(Network {
+        let networks = vec![Network {
             id: "net-01234567".to_string(),
             name: "default".to_string(),
             cidr: "192.168.1.0/24".to_string(),
             instances: vec!["i-01234567".to_string(), "i-89abcdef".to_string()],
-        });
+        }];
         
         Self {
             running: true,
@@ -112,6 +122,11 @@ impl Default for App {
             instances,
             volumes,
             networks,
+            routers: Vec::new(),
+            refresh_routers: false,
+            loading_routers: false,
+            logs: Vec::new(),
+            refresh_logs: false,
         }
     }
 }
@@ -135,6 +150,7 @@ impl App {
             AppMode::Instances => self.instances.len().saturating_sub(1),
             AppMode::Volumes => self.volumes.len().saturating_sub(1),
             AppMode::Networks => self.networks.len().saturating_sub(1),
+            AppMode::Logs => self.logs.len().saturating_sub(1),
             _ => 0,
         };
         
@@ -152,6 +168,7 @@ impl App {
             AppMode::Instances => self.instances.len().saturating_sub(1),
             AppMode::Volumes => self.volumes.len().saturating_sub(1),
             AppMode::Networks => self.networks.len().saturating_sub(1),
+            AppMode::Logs => self.logs.len().saturating_sub(1),
             _ => 0,
         };
         
@@ -167,5 +184,6 @@ impl App {
     pub fn change_mode(&mut self, mode: AppMode) {
         self.mode = mode;
         self.selected_index = 0;
+        self.refresh_logs = mode == AppMode::Logs;
     }
 }
@@ -1,10 +1,9 @@
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, Context, anyhow};
 use std::collections::HashMap;
-use log::{debug, info, error};
+use log::{debug, info};
 
 use crate::config::{read_config_file, write_config_file, CREDENTIALS_FILE};
-use crate::models::provider::ProviderType;
 
 /// VyOS credentials
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,20 +65,12 @@ pub enum ProviderCredentials {
 }
 
 /// Credentials storage
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Credentials {
     /// Credentials by provider name
     pub credentials: HashMap<String, ProviderCredentials>,
 }
 
-impl Default for Credentials {
-    fn default() -> Self {
-        Self {
-            credentials: HashMap::new(),
-        }
-    }
-}
-
 impl Credentials {
     /// Load credentials from file
     pub fn load() -> Result<Self> {
@@ -118,6 +109,7 @@ impl Credentials {
     }
     
     /// Add VyOS credentials
+    #[allow(clippy::too_many_arguments)]
     pub fn add_vyos_credentials(
         &mut self,
         provider_name: &str,
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, Context, anyhow};
+use std::collections::HashMap;
+use log::{debug, info};
+
+use crate::config::{read_config_file, write_config_file, DNS_RECORDS_FILE};
+use crate::models::dns::DnsRecord;
+
+/// User-managed DNS record storage
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsRecords {
+    /// Records by fully qualified name
+    pub records: HashMap<String, DnsRecord>,
+}
+
+impl DnsRecords {
+    /// Load DNS records from file
+    pub fn load() -> Result<Self> {
+        debug!("Loading DNS records from file");
+        
+        // Read DNS records file
+        let content = match read_config_file(DNS_RECORDS_FILE) {
+            Ok(content) => content,
+            Err(e) => {
+                info!("Failed to read DNS records file, using defaults: {}", e);
+                return Ok(Self::default());
+            }
+        };
+        
+        // Parse TOML
+        let records: DnsRecords = toml::from_str(&content)
+            .context("Failed to parse DNS records TOML")?;
+        
+        Ok(records)
+    }
+    
+    /// Save DNS records to file
+    pub fn save(&self) -> Result<()> {
+        debug!("Saving DNS records to file");
+        
+        // Serialize to TOML
+        let content = toml::to_string_pretty(self)
+            .context("Failed to serialize DNS records")?;
+        
+        // Write to file
+        write_config_file(DNS_RECORDS_FILE, &content)
+            .context("Failed to write DNS records file")?;
+        
+        info!("DNS records saved successfully");
+        Ok(())
+    }
+    
+    /// Add a new record
+    pub fn add_record(&mut self, record: DnsRecord) -> Result<()> {
+        if self.records.contains_key(&record.name) {
+            return Err(anyhow!("DNS record '{}' already exists", record.name));
+        }
+        
+        info!("Added DNS record: {}", record.name);
+        self.records.insert(record.name.clone(), record);
+        Ok(())
+    }
+    
+    /// Remove a record
+    pub fn remove_record(&mut self, name: &str) -> Result<DnsRecord> {
+        self.records.remove(name)
+            .ok_or_else(|| anyhow!("DNS record '{}' does not exist", name))
+    }
+    
+    /// Get a record by name
+    pub fn get_record(&self, name: &str) -> Option<&DnsRecord> {
+        self.records.get(name)
+    }
+    
+    /// Get all records
+    pub fn get_all_records(&self) -> &HashMap<String, DnsRecord> {
+        &self.records
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, Context, anyhow};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use uuid::Uuid;
+use log::{debug, info};
+
+use crate::config::{read_config_file, write_config_file, FLOATING_IPS_FILE};
+use crate::models::floating_ip::{FloatingIp, FloatingIpPool};
+
+/// Floating IP pool and allocation storage
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FloatingIps {
+    /// Address pools by name
+    pub pools: HashMap<String, FloatingIpPool>,
+    /// Allocated addresses by IP
+    pub addresses: HashMap<String, FloatingIp>,
+}
+
+impl FloatingIps {
+    /// Load floating IPs from file
+    pub fn load() -> Result<Self> {
+        debug!("Loading floating IPs from file");
+        
+        // Read floating IPs file
+        let content = match read_config_file(FLOATING_IPS_FILE) {
+            Ok(content) => content,
+            Err(e) => {
+                info!("Failed to read floating IPs file, using defaults: {}", e);
+                return Ok(Self::default());
+            }
+        };
+        
+        // Parse TOML
+        let floating_ips: FloatingIps = toml::from_str(&content)
+            .context("Failed to parse floating IPs TOML")?;
+        
+        Ok(floating_ips)
+    }
+    
+    /// Save floating IPs to file
+    pub fn save(&self) -> Result<()> {
+        debug!("Saving floating IPs to file");
+        
+        // Serialize to TOML
+        let content = toml::to_string_pretty(self)
+            .context("Failed to serialize floating IPs")?;
+        
+        // Write to file
+        write_config_file(FLOATING_IPS_FILE, &content)
+            .context("Failed to write floating IPs file")?;
+        
+        info!("Floating IPs saved successfully");
+        Ok(())
+    }
+    
+    /// Add a new pool
+    pub fn add_pool(&mut self, pool: FloatingIpPool) -> Result<()> {
+        if self.pools.contains_key(&pool.name) {
+            return Err(anyhow!("Pool with name '{}' already exists", pool.name));
+        }
+        
+        info!("Added floating IP pool: {}", pool.name);
+        self.pools.insert(pool.name.clone(), pool);
+        Ok(())
+    }
+    
+    /// Remove a pool that has no allocated addresses
+    pub fn remove_pool(&mut self, name: &str) -> Result<()> {
+        if !self.pools.contains_key(name) {
+            return Err(anyhow!("Pool with name '{}' does not exist", name));
+        }
+        
+        if self.addresses.values().any(|a| a.pool == name) {
+            return Err(anyhow!("Pool '{}' still has allocated addresses", name));
+        }
+        
+        self.pools.remove(name);
+        info!("Removed floating IP pool: {}", name);
+        Ok(())
+    }
+    
+    /// Get a pool by name
+    pub fn get_pool(&self, name: &str) -> Option<&FloatingIpPool> {
+        self.pools.get(name)
+    }
+    
+    /// Record an allocated address
+    pub fn add_address(&mut self, address: FloatingIp) -> Result<()> {
+        let key = address.ip.to_string();
+        if self.addresses.contains_key(&key) {
+            return Err(anyhow!("Address {} is already allocated", key));
+        }
+        
+        self.addresses.insert(key, address);
+        Ok(())
+    }
+    
+    /// Remove an allocated address
+    pub fn remove_address(&mut self, ip: &Ipv4Addr) -> Result<FloatingIp> {
+        self.addresses.remove(&ip.to_string())
+            .ok_or_else(|| anyhow!("Address {} is not allocated", ip))
+    }
+    
+    /// Get an allocated address
+    pub fn get_address(&self, ip: &Ipv4Addr) -> Option<&FloatingIp> {
+        self.addresses.get(&ip.to_string())
+    }
+    
+    /// Get a mutable reference to an allocated address
+    pub fn get_address_mut(&mut self, ip: &Ipv4Addr) -> Option<&mut FloatingIp> {
+        self.addresses.get_mut(&ip.to_string())
+    }
+    
+    /// Get the addresses associated with an instance
+    pub fn get_addresses_by_instance(&self, instance_id: &Uuid) -> Vec<&FloatingIp> {
+        self.addresses.values()
+            .filter(|a| a.instance_id.as_ref() == Some(instance_id))
+            .collect()
+    }
+}
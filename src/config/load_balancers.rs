@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, Context, anyhow};
+use std::collections::HashMap;
+use log::{debug, info};
+
+use crate::config::{read_config_file, write_config_file, LOAD_BALANCERS_FILE};
+use crate::models::load_balancer::LoadBalancer;
+
+/// Load balancer storage
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoadBalancers {
+    /// Load balancers by name
+    pub load_balancers: HashMap<String, LoadBalancer>,
+}
+
+impl LoadBalancers {
+    /// Load load balancers from file
+    pub fn load() -> Result<Self> {
+        debug!("Loading load balancers from file");
+        
+        // Read load balancers file
+        let content = match read_config_file(LOAD_BALANCERS_FILE) {
+            Ok(content) => content,
+            Err(e) => {
+                info!("Failed to read load balancers file, using defaults: {}", e);
+                return Ok(Self::default());
+            }
+        };
+        
+        // Parse TOML
+        let load_balancers: LoadBalancers = toml::from_str(&content)
+            .context("Failed to parse load balancers TOML")?;
+        
+        Ok(load_balancers)
+    }
+    
+    /// Save load balancers to file
+    pub fn save(&self) -> Result<()> {
+        debug!("Saving load balancers to file");
+        
+        // Serialize to TOML
+        let content = toml::to_string_pretty(self)
+            .context("Failed to serialize load balancers")?;
+        
+        // Write to file
+        write_config_file(LOAD_BALANCERS_FILE, &content)
+            .context("Failed to write load balancers file")?;
+        
+        info!("Load balancers saved successfully");
+        Ok(())
+    }
+    
+    /// Add a load balancer, replacing one with the same name
+    pub fn upsert_load_balancer(&mut self, load_balancer: LoadBalancer) -> Option<LoadBalancer> {
+        info!("Stored load balancer: {}", load_balancer.name);
+        self.load_balancers.insert(load_balancer.name.clone(), load_balancer)
+    }
+    
+    /// Remove a load balancer
+    pub fn remove_load_balancer(&mut self, name: &str) -> Result<LoadBalancer> {
+        self.load_balancers.remove(name)
+            .ok_or_else(|| anyhow!("Load balancer '{}' does not exist", name))
+    }
+    
+    /// Get a load balancer by name
+    pub fn get_load_balancer(&self, name: &str) -> Option<&LoadBalancer> {
+        self.load_balancers.get(name)
+    }
+    
+    /// Get all load balancers
+    pub fn get_all_load_balancers(&self) -> &HashMap<String, LoadBalancer> {
+        &self.load_balancers
+    }
+}
@@ -1,10 +1,14 @@
 pub mod provider;
 pub mod settings;
 pub mod credentials;
+pub mod security_groups;
+pub mod floating_ips;
+pub mod dns_records;
+pub mod load_balancers;
 
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use anyhow::{Result, Context, anyhow};
-use log::{debug, info, error};
+use log::{debug, info};
 use std::fs;
 use dirs::home_dir;
 
@@ -13,6 +17,10 @@ pub const APP_DIR_NAME: &str = ".bbctl";
 pub const SETTINGS_FILE: &str = "settings.toml";
 pub const PROVIDERS_FILE: &str = "providers.toml";
 pub const CREDENTIALS_FILE: &str = "credentials.toml";
+pub const SECURITY_GROUPS_FILE: &str = "security_groups.toml";
+pub const FLOATING_IPS_FILE: &str = "floating_ips.toml";
+pub const DNS_RECORDS_FILE: &str = "dns_records.toml";
+pub const LOAD_BALANCERS_FILE: &str = "load_balancers.toml";
 
 /// Get the application config directory
 pub fn get_config_dir() -> Result<PathBuf> {
@@ -1,13 +1,13 @@
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, Context, anyhow};
 use std::collections::HashMap;
-use log::{debug, info, error};
+use log::{debug, info};
 
 use crate::config::{read_config_file, write_config_file, PROVIDERS_FILE};
 use crate::models::provider::{ProviderType, ProviderConfig, Region};
 
 /// Provider configuration storage
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Providers {
     /// Provider configurations by name
     pub providers: HashMap<String, ProviderConfig>,
@@ -15,15 +15,6 @@ pub struct Providers {
     pub regions: HashMap<String, Region>,
 }
 
-impl Default for Providers {
-    fn default() -> Self {
-        Self {
-            providers: HashMap::new(),
-            regions: HashMap::new(),
-        }
-    }
-}
-
 impl Providers {
     /// Load providers from file
     pub fn load() -> Result<Self> {
@@ -100,10 +91,9 @@ impl Providers {
             return Err(anyhow!("Region with ID '{}' already exists", region.id));
         }
         
-        // Ensure the provider exists
-        let provider_name = region.provider.to_string();
-        if !self.providers.iter().any(|(name, p)| p.provider_type == region.provider) {
-            return Err(anyhow!("Provider '{}' does not exist", provider_name));
+        // Ensure a provider of the region's type exists
+        if !self.providers.values().any(|p| p.provider_type == region.provider) {
+            return Err(anyhow!("No {} provider is configured", region.provider));
         }
         
         self.regions.insert(region.id.clone(), region.clone());
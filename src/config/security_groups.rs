@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, Context, anyhow};
+use std::collections::HashMap;
+use log::{debug, info};
+
+use crate::config::{read_config_file, write_config_file, SECURITY_GROUPS_FILE};
+use crate::models::security_group::SecurityGroup;
+
+/// Security group storage
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SecurityGroups {
+    /// Security groups by name
+    pub groups: HashMap<String, SecurityGroup>,
+}
+
+impl SecurityGroups {
+    /// Load security groups from file
+    pub fn load() -> Result<Self> {
+        debug!("Loading security groups from file");
+        
+        // Read security groups file
+        let content = match read_config_file(SECURITY_GROUPS_FILE) {
+            Ok(content) => content,
+            Err(e) => {
+                info!("Failed to read security groups file, using defaults: {}", e);
+                return Ok(Self::default());
+            }
+        };
+        
+        // Parse TOML
+        let groups: SecurityGroups = toml::from_str(&content)
+            .context("Failed to parse security groups TOML")?;
+        
+        Ok(groups)
+    }
+    
+    /// Save security groups to file
+    pub fn save(&self) -> Result<()> {
+        debug!("Saving security groups to file");
+        
+        // Serialize to TOML
+        let content = toml::to_string_pretty(self)
+            .context("Failed to serialize security groups")?;
+        
+        // Write to file
+        write_config_file(SECURITY_GROUPS_FILE, &content)
+            .context("Failed to write security groups file")?;
+        
+        info!("Security groups saved successfully");
+        Ok(())
+    }
+    
+    /// Add a new security group
+    pub fn add_group(&mut self, group: SecurityGroup) -> Result<()> {
+        if self.groups.contains_key(&group.name) {
+            return Err(anyhow!("Security group with name '{}' already exists", group.name));
+        }
+        
+        info!("Added security group: {}", group.name);
+        self.groups.insert(group.name.clone(), group);
+        Ok(())
+    }
+    
+    /// Remove a security group
+    pub fn remove_group(&mut self, name: &str) -> Result<SecurityGroup> {
+        self.groups.remove(name)
+            .ok_or_else(|| anyhow!("Security group with name '{}' does not exist", name))
+    }
+    
+    /// Get a security group by name
+    pub fn get_group(&self, name: &str) -> Option<&SecurityGroup> {
+        self.groups.get(name)
+    }
+    
+    /// Get a mutable reference to a security group
+    pub fn get_group_mut(&mut self, name: &str) -> Option<&mut SecurityGroup> {
+        self.groups.get_mut(name)
+    }
+    
+    /// Get all security groups
+    pub fn get_all_groups(&self) -> &HashMap<String, SecurityGroup> {
+        &self.groups
+    }
+}
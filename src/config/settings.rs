@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, Context, anyhow};
-use std::fs;
-use log::{debug, info, error};
+use std::collections::BTreeMap;
+use log::{debug, info};
 
+use crate::models::template::InstanceTemplate;
 use crate::config::{read_config_file, write_config_file, SETTINGS_FILE};
 
 /// User settings
@@ -26,6 +27,9 @@ pub struct Settings {
     pub default_disk_gb: u8,
     /// Logging level
     pub log_level: String,
+    /// Instance templates by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, InstanceTemplate>,
 }
 
 impl Default for Settings {
@@ -40,6 +44,7 @@ impl Default for Settings {
             default_memory_gb: 2,
             default_disk_gb: 10,
             log_level: "info".to_string(),
+            templates: BTreeMap::new(),
         }
     }
 }
@@ -81,6 +86,12 @@ impl Settings {
         Ok(())
     }
     
+    /// Get an instance template by name
+    pub fn get_template(&self, name: &str) -> Result<&InstanceTemplate> {
+        self.templates.get(name)
+            .ok_or_else(|| anyhow!("Template not found: {}", name))
+    }
+    
     /// Update a setting
     pub fn update(&mut self, key: &str, value: &str) -> Result<()> {
         match key {
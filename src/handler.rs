@@ -13,10 +13,8 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
             }
         }
         // Exit application on `Ctrl-C`
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
-                app.quit();
-            }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit();
         }
         // Navigation
         KeyCode::Down | KeyCode::Char('j') => {
@@ -40,6 +38,9 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
             app.change_mode(AppMode::Networks);
         }
         KeyCode::Char('5') => {
+            app.change_mode(AppMode::Logs);
+        }
+        KeyCode::Char('6') => {
             app.change_mode(AppMode::Settings);
         }
         KeyCode::Char('?') => {
@@ -52,7 +53,8 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
                 AppMode::Home => app.change_mode(AppMode::Instances),
                 AppMode::Instances => app.change_mode(AppMode::Volumes),
                 AppMode::Volumes => app.change_mode(AppMode::Networks),
-                AppMode::Networks => app.change_mode(AppMode::Settings),
+                AppMode::Networks => app.change_mode(AppMode::Logs),
+                AppMode::Logs => app.change_mode(AppMode::Settings),
                 AppMode::Settings => app.change_mode(AppMode::Help),
                 AppMode::Help => app.change_mode(AppMode::Home),
             }
@@ -65,11 +67,22 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
                 AppMode::Instances => app.change_mode(AppMode::Home),
                 AppMode::Volumes => app.change_mode(AppMode::Instances),
                 AppMode::Networks => app.change_mode(AppMode::Volumes),
-                AppMode::Settings => app.change_mode(AppMode::Networks),
+                AppMode::Logs => app.change_mode(AppMode::Networks),
+                AppMode::Settings => app.change_mode(AppMode::Logs),
                 AppMode::Help => app.change_mode(AppMode::Settings),
             }
         }
         
+        // Reload router status in the network view
+        KeyCode::Char('r') if app.mode == AppMode::Networks => {
+            app.refresh_routers = true;
+        }
+        
+        // Reload the log pane from the store
+        KeyCode::Char('r') if app.mode == AppMode::Logs => {
+            app.refresh_logs = true;
+        }
+        
         // Other handlers
         _ => {}
     }
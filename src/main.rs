@@ -1,22 +1,16 @@
 use std::io;
 use std::env;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use ratatui::{backend::CrosstermBackend, Terminal};
 
-use crate::{
-    app::{App, AppResult},
+use bbctl::{
+    app::{App, AppMode, AppResult},
     event::{Event, EventHandler},
     handler::handle_key_events,
     tui::Tui,
 };
 
-pub mod app;
-pub mod event;
-pub mod handler;
-pub mod tui;
-pub mod ui;
-
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
@@ -28,19 +22,65 @@ struct Cli {
 enum Commands {
     /// Initialize a new BitBuilder Cloud project
     Init {
+        /// Application name (defaults to the directory name)
         #[arg(long)]
         name: Option<String>,
+        /// Write a preset project without asking (web, worker or vm)
+        #[arg(long)]
+        from_template: Option<String>,
+        /// Provider to deploy to
+        #[arg(long)]
+        provider: Option<String>,
+        /// Region to deploy to
+        #[arg(long)]
+        region: Option<String>,
+        /// Overwrite an existing project file
+        #[arg(long)]
+        force: bool,
     },
     /// Deploy an application to BitBuilder Cloud
+    ///
+    /// Containers that publish ports are removed before their replacements
+    /// are created, since a router port can only be published once. The
+    /// application is unreachable on that router until the new container
+    /// passes its health check.
     Deploy {
+        /// Path to the project file
+        #[arg(long, default_value = "bbctl.toml")]
+        config: String,
+        /// Only show the changes the deploy would make
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List the releases of an application
+    Releases {
+        /// Application (defaults to the one in ./bbctl.toml, or every application)
         #[arg(long)]
-        config: Option<String>,
+        app: Option<String>,
+        /// Print the releases as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Manage infrastructure providers
+    Providers {
+        #[command(subcommand)]
+        action: ProvidersCommands,
+    },
+    /// Manage regions and their resource limits
+    Regions {
+        #[command(subcommand)]
+        action: RegionsCommands,
     },
     /// Manage instances
     Instances {
         #[command(subcommand)]
         action: InstancesCommands,
     },
+    /// Inspect the instance templates defined in the settings
+    Templates {
+        #[command(subcommand)]
+        action: TemplatesCommands,
+    },
     /// Manage volumes
     Volumes {
         #[command(subcommand)]
@@ -51,6 +91,154 @@ enum Commands {
         #[command(subcommand)]
         action: NetworksCommands,
     },
+    /// Manage tenants
+    Tenants {
+        #[command(subcommand)]
+        action: TenantsCommands,
+    },
+    /// Manage the EVPN/VXLAN fabric between VyOS routers
+    Fabric {
+        #[command(subcommand)]
+        action: FabricCommands,
+    },
+    /// Manage the OSPF underlay of a site
+    Site {
+        #[command(subcommand)]
+        action: SiteCommands,
+    },
+    /// Manage zone-based firewall policies on VyOS routers
+    Firewall {
+        #[command(subcommand)]
+        action: FirewallCommands,
+    },
+    /// Manage instance security groups
+    SecurityGroups {
+        #[command(subcommand)]
+        action: SecurityGroupsCommands,
+    },
+    /// Expose an instance port on a public floating IP
+    Expose {
+        /// Instance ID
+        instance: String,
+        /// Port to forward, either `443` or `public:private` such as `443:8443`
+        #[arg(long)]
+        port: String,
+        /// Protocol (tcp or udp)
+        #[arg(long, default_value = "tcp")]
+        protocol: String,
+        /// Public address to use, or `auto` to allocate one
+        #[arg(long, default_value = "auto")]
+        public_ip: String,
+        /// Pool to allocate from
+        #[arg(long)]
+        pool: Option<String>,
+        /// Private address of the instance (defaults to its first network address)
+        #[arg(long)]
+        private_ip: Option<String>,
+    },
+    /// Manage public floating IPs
+    FloatingIps {
+        #[command(subcommand)]
+        action: FloatingIpsCommands,
+    },
+    /// Manage reverse-proxy load balancers on VyOS routers
+    LoadBalancers {
+        #[command(subcommand)]
+        action: LoadBalancersCommands,
+    },
+    /// Manage internal DNS names
+    Dns {
+        #[command(subcommand)]
+        action: DnsCommands,
+    },
+    /// Inspect VyOS routers
+    Routers {
+        #[command(subcommand)]
+        action: RoutersCommands,
+    },
+    /// Collect flow exports from VyOS routers
+    Collector {
+        #[command(subcommand)]
+        action: CollectorCommands,
+    },
+    /// Report tenant resource usage
+    Usage {
+        #[command(subcommand)]
+        action: UsageCommands,
+    },
+    /// Collect and search router and hypervisor logs
+    Logs {
+        #[command(subcommand)]
+        action: LogsCommands,
+    },
+    /// Compare stored resources with the providers and record drift
+    Refresh {
+        /// Only refresh this provider
+        #[arg(long)]
+        provider: Option<String>,
+        /// Drop records whose resources are gone instead of flagging them missing
+        #[arg(long)]
+        fix: bool,
+        /// Print the drift as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Adopt existing provider resources into bbctl management
+    #[command(args_conflicts_with_subcommands = true)]
+    Import {
+        #[command(subcommand)]
+        action: Option<ImportCommands>,
+        /// Provider to import from
+        #[arg(long)]
+        provider: Option<String>,
+        /// Import every VM (Proxmox) or DHCP subnet (VyOS) of the provider
+        #[arg(long)]
+        all: bool,
+        /// Only import VMs carrying this Proxmox tag (repeatable)
+        #[arg(long = "tag-filter")]
+        tag_filter: Vec<String>,
+        /// Hand the DHCP of imported VyOS networks over to bbctl
+        #[arg(long)]
+        adopt_dhcp: bool,
+        #[command(flatten)]
+        options: ImportOptions,
+    },
+    /// Show the changes a manifest would make to instances, volumes, networks and security groups
+    Plan {
+        /// Path to the manifest
+        #[arg(short, long)]
+        file: String,
+        /// Take over existing resources with the same names as the manifest's
+        #[arg(long)]
+        adopt: bool,
+        /// Print the plan as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print stored instances, volumes, networks and security groups as a manifest
+    Export {
+        /// Manifest name (defaults to the tenant, or "infra")
+        #[arg(long)]
+        name: Option<String>,
+        /// Only export this tenant's networks and the instances connected to them
+        #[arg(long)]
+        tenant: Option<String>,
+        /// Only export resources in this region
+        #[arg(long)]
+        region: Option<String>,
+    },
+    /// Apply a manifest after previewing the changes
+    Apply {
+        /// Path to the manifest
+        #[arg(short, long)]
+        file: String,
+        /// Take over existing resources with the same names as the manifest's
+        #[arg(long)]
+        adopt: bool,
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
     /// Test connectivity to a VyOS router
     TestVyOS {
         /// VyOS host to connect to
@@ -86,16 +274,24 @@ enum InstancesCommands {
     /// Create a new instance
     Create {
         name: String,
+        /// Template from the settings to create the instance from
         #[arg(long)]
-        provider: String,
+        template: Option<String>,
+        /// Provider (defaults to the template's, then the configured default provider)
         #[arg(long)]
-        region: String,
+        provider: Option<String>,
+        /// Region (defaults to the template's, then the configured default region)
+        #[arg(long)]
+        region: Option<String>,
         #[arg(long)]
         cpu: Option<u8>,
         #[arg(long)]
-        memory: Option<u8>,
+        memory: Option<u16>,
         #[arg(long)]
-        disk: Option<u8>,
+        disk: Option<u16>,
+        /// Container settings for VyOS providers
+        #[command(flatten)]
+        container: Box<ContainerArgs>,
     },
     /// Delete an instance
     Delete {
@@ -113,6 +309,225 @@ enum InstancesCommands {
     Show {
         id: String,
     },
+    /// Set tags on an instance, such as the ones load balancer backends select
+    Tag {
+        id: String,
+        /// Tags as key=value
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplatesCommands {
+    /// List all templates
+    List,
+    /// Show a template
+    Show {
+        name: String,
+    },
+    /// Check templates against the configured providers and networks
+    Validate {
+        /// Template to check (all when omitted)
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Import one Proxmox VM as an instance
+    Instance {
+        /// Proxmox provider hosting the VM
+        #[arg(long)]
+        provider: String,
+        /// VMID of the VM
+        #[arg(long)]
+        vmid: u64,
+        #[command(flatten)]
+        options: ImportOptions,
+    },
+    /// Import the DHCP subnets of a VyOS router as networks
+    Network {
+        /// VyOS provider serving the networks
+        #[arg(long)]
+        provider: String,
+        /// DHCP shared network to import (all when omitted)
+        #[arg(long)]
+        name: Option<String>,
+        /// Hand the DHCP of imported networks over to bbctl
+        #[arg(long)]
+        adopt_dhcp: bool,
+        #[command(flatten)]
+        options: ImportOptions,
+    },
+}
+
+#[derive(Args)]
+struct ImportOptions {
+    /// Region to record imported resources in
+    #[arg(long)]
+    region: Option<String>,
+    /// Print the results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum ProvidersCommands {
+    /// List all configured providers
+    List,
+    /// Add a provider, enabling the VyOS HTTP API over SSH when no API key is given
+    Add {
+        name: String,
+        #[command(flatten)]
+        provider: Box<ProviderArgs>,
+    },
+    /// Remove a provider and its credentials
+    Remove {
+        name: String,
+    },
+    /// Test connectivity to a provider
+    Test {
+        name: String,
+        /// Show detailed connection information
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RegionsCommands {
+    /// List all regions
+    List,
+    /// Add a region for a provider type
+    Add {
+        /// Region ID, such as nyc
+        id: String,
+        /// Provider type hosting the region (vyos or proxmox)
+        #[arg(long = "type")]
+        provider_type: String,
+        /// Display name (defaults to the ID)
+        #[arg(long)]
+        name: Option<String>,
+        /// Location, such as "New York"
+        #[arg(long, default_value = "")]
+        location: String,
+        /// Do not offer the region for new deployments
+        #[arg(long)]
+        unavailable: bool,
+        #[command(flatten)]
+        limits: RegionLimitArgs,
+    },
+    /// Remove a region
+    Remove {
+        id: String,
+    },
+    /// Show a region and its resource limits
+    Show {
+        id: String,
+    },
+}
+
+/// Resource limits of `regions add`
+#[derive(Args)]
+struct RegionLimitArgs {
+    /// Maximum number of instances
+    #[arg(long)]
+    max_instances: Option<u32>,
+    /// Maximum number of volumes
+    #[arg(long)]
+    max_volumes: Option<u32>,
+    /// Maximum number of networks
+    #[arg(long)]
+    max_networks: Option<u32>,
+    /// Maximum CPU cores per instance
+    #[arg(long)]
+    max_cpu: Option<u8>,
+    /// Maximum memory per instance in GB
+    #[arg(long)]
+    max_memory: Option<u16>,
+    /// Maximum disk per instance in GB
+    #[arg(long)]
+    max_disk: Option<u16>,
+}
+
+/// Connection settings of `providers add`
+#[derive(Args)]
+struct ProviderArgs {
+    /// Provider type (vyos or proxmox)
+    #[arg(long = "type")]
+    provider_type: String,
+    /// Hostname or IP address
+    #[arg(long)]
+    host: String,
+    /// Username for authentication
+    #[arg(long)]
+    username: Option<String>,
+    /// Password (SSH for VyOS, API for Proxmox)
+    #[arg(long)]
+    password: Option<String>,
+    /// API key for VyOS providers, generated over SSH when omitted
+    #[arg(long)]
+    api_key: Option<String>,
+    /// SSH key for VyOS providers
+    #[arg(long)]
+    key_path: Option<String>,
+    /// SSH port for VyOS providers
+    #[arg(long)]
+    ssh_port: Option<u16>,
+    /// API port
+    #[arg(long)]
+    port: Option<u16>,
+    /// Token ID for Proxmox providers
+    #[arg(long)]
+    token_id: Option<String>,
+    /// Token secret for Proxmox providers
+    #[arg(long)]
+    token_secret: Option<String>,
+    /// Authentication realm for Proxmox providers
+    #[arg(long, default_value = "pam")]
+    realm: String,
+    /// Verify SSL certificates (Proxmox)
+    #[arg(long)]
+    verify_ssl: bool,
+    /// Source address allowed to reach the VyOS HTTP API (repeatable), defaults to this host
+    #[arg(long = "api-allow")]
+    api_allow: Vec<String>,
+    /// VyOS version of the router (1.4 or 1.5)
+    #[arg(long, default_value = "1.4")]
+    vyos_version: String,
+    /// Do not enable the VyOS HTTP API over SSH
+    #[arg(long)]
+    skip_api_bootstrap: bool,
+    /// Enable the VyOS HTTP API without asking
+    #[arg(short, long)]
+    yes: bool,
+}
+
+/// Container settings of `instances create` on VyOS providers
+#[derive(Args)]
+struct ContainerArgs {
+    /// Container image on VyOS, VM template (name or VMID) to clone on Proxmox
+    #[arg(long)]
+    image: Option<String>,
+    /// Container network on the router
+    #[arg(long)]
+    network: Option<String>,
+    /// Prefix of the container network, creates it when missing
+    #[arg(long, requires = "network")]
+    network_prefix: Option<String>,
+    /// Static container address on the network
+    #[arg(long, requires = "network")]
+    address: Option<std::net::Ipv4Addr>,
+    /// Environment variable as KEY=value (repeatable)
+    #[arg(long = "env")]
+    env: Vec<String>,
+    /// Volume as <source>:<destination>[:ro|rw] (repeatable)
+    #[arg(long = "volume")]
+    volumes: Vec<String>,
+    /// Published port as <router port>:<container port>[/udp] (repeatable)
+    #[arg(long = "port")]
+    ports: Vec<String>,
 }
 
 #[derive(Subcommand)]
@@ -122,10 +537,18 @@ enum VolumesCommands {
     /// Create a new volume
     Create {
         name: String,
+        /// Size in GB
         #[arg(long)]
-        size: u8,
+        size: u16,
+        /// Region (defaults to the configured default region)
         #[arg(long)]
         region: Option<String>,
+        /// Provider hosting the volume (defaults to the configured default provider)
+        #[arg(long)]
+        provider: Option<String>,
+        /// Proxmox storage to allocate the disk on
+        #[arg(long)]
+        storage: Option<String>,
     },
     /// Delete a volume
     Delete {
@@ -136,6 +559,9 @@ enum VolumesCommands {
         id: String,
         #[arg(long)]
         instance: String,
+        /// Mount path inside a VyOS container (defaults to /mnt/<volume>)
+        #[arg(long)]
+        path: Option<String>,
     },
     /// Detach a volume from an instance
     Detach {
@@ -156,6 +582,32 @@ enum NetworksCommands {
         name: String,
         #[arg(long)]
         cidr: String,
+        /// Provider hosting the network (defaults to the first HA router)
+        #[arg(long, required_unless_present = "ha_routers")]
+        provider: Option<String>,
+        #[arg(long, default_value = "default")]
+        region: String,
+        /// Network type (bridged, routed, isolated, vxlan, vpn)
+        #[arg(long = "type", default_value = "routed")]
+        network_type: String,
+        /// VyOS routers sharing the gateway through VRRP, in order of preference
+        #[arg(long, value_delimiter = ',', requires = "interface")]
+        ha_routers: Vec<String>,
+        /// Router interface that carries the network
+        #[arg(long)]
+        interface: Option<String>,
+        /// Proxmox bridge VMs plug into to reach the network (default vmbr0)
+        #[arg(long)]
+        bridge: Option<String>,
+        /// Tenant owning the network
+        #[arg(long)]
+        tenant: Option<String>,
+        /// VRRP virtual router ID (picked automatically by default)
+        #[arg(long)]
+        vrid: Option<u8>,
+        /// Keep the gateway on the current master when the preferred router recovers
+        #[arg(long)]
+        no_preempt: bool,
     },
     /// Delete a network
     Delete {
@@ -166,6 +618,9 @@ enum NetworksCommands {
         id: String,
         #[arg(long)]
         instance: String,
+        /// Address to assign (defaults to the next free address)
+        #[arg(long)]
+        ip: Option<std::net::IpAddr>,
     },
     /// Disconnect an instance from a network
     Disconnect {
@@ -179,265 +634,2697 @@ enum NetworksCommands {
     },
 }
 
-fn cli_handler(cli: Cli) -> AppResult<()> {
-    match cli.command {
-        Some(Commands::Init { name }) => {
-            println!("Initializing BitBuilder Cloud project: {}", 
-                    name.unwrap_or_else(|| "bitbuilder-app".to_string()));
-            // Actual implementation would initialize config files, etc.
-        }
-        Some(Commands::Deploy { config }) => {
-            println!("Deploying to BitBuilder Cloud using config: {}", 
-                    config.unwrap_or_else(|| "fly.toml".to_string()));
-            // Actual implementation would handle the deployment
-        }
-        Some(Commands::Instances { action }) => {
-            match action {
-                InstancesCommands::List => {
-                    println!("Listing instances...");
-                    println!("ID\t\tNAME\tSTATUS\tREGION\tPROVIDER");
-                    println!("i-01234567\tweb-1\trunning\tnyc\tvyos");
-                    println!("i-89abcdef\tdb-1\trunning\tnyc\tproxmox");
-                }
-                InstancesCommands::Create { name, provider, region, cpu, memory, disk } => {
-                    println!("Creating instance '{}' with provider '{}' in region '{}'", 
-                            name, provider, region);
-                    println!("Resources: CPU: {}, Memory: {} GB, Disk: {} GB", 
-                            cpu.unwrap_or(1), memory.unwrap_or(2), disk.unwrap_or(10));
-                }
-                InstancesCommands::Delete { id } => {
-                    println!("Deleting instance '{}'", id);
-                }
-                InstancesCommands::Start { id } => {
-                    println!("Starting instance '{}'", id);
-                }
-                InstancesCommands::Stop { id } => {
-                    println!("Stopping instance '{}'", id);
-                }
-                InstancesCommands::Show { id } => {
-                    println!("Instance details for '{}':", id);
-                    println!("ID: {}", id);
-                    println!("Name: web-1");
-                    println!("Status: running");
-                    println!("Provider: vyos");
-                    println!("Region: nyc");
-                    println!("IP: 192.168.1.10");
-                    println!("CPU: 2");
-                    println!("Memory: 4 GB");
-                    println!("Disk: 80 GB");
-                }
-            }
-        }
-        Some(Commands::Volumes { action }) => {
-            match action {
-                VolumesCommands::List => {
-                    println!("Listing volumes...");
-                    println!("ID\t\tNAME\tSIZE\tREGION\tATTACHED TO");
-                    println!("vol-01234567\tdb-data\t100 GB\tnyc\ti-89abcdef");
-                }
-                VolumesCommands::Create { name, size, region } => {
-                    println!("Creating volume '{}' with size {} GB in region '{}'", 
-                            name, size, region.unwrap_or_else(|| "nyc".to_string()));
-                }
-                VolumesCommands::Delete { id } => {
-                    println!("Deleting volume '{}'", id);
-                }
-                VolumesCommands::Attach { id, instance } => {
-                    println!("Attaching volume '{}' to instance '{}'", id, instance);
-                }
-                VolumesCommands::Detach { id } => {
-                    println!("Detaching volume '{}'", id);
-                }
-                VolumesCommands::Show { id } => {
-                    println!("Volume details for '{}':", id);
-                    println!("ID: {}", id);
-                    println!("Name: db-data");
-                    println!("Size: 100 GB");
-                    println!("Region: nyc");
-                    println!("Attached to: i-89abcdef (db-1)");
-                }
-            }
-        }
-        Some(Commands::Networks { action }) => {
-            match action {
-                NetworksCommands::List => {
-                    println!("Listing networks...");
-                    println!("ID\t\tNAME\tCIDR\t\tINSTANCES");
-                    println!("net-01234567\tdefault\t192.168.1.0/24\t2");
-                }
-                NetworksCommands::Create { name, cidr } => {
-                    println!("Creating network '{}' with CIDR '{}'", name, cidr);
-                }
-                NetworksCommands::Delete { id } => {
-                    println!("Deleting network '{}'", id);
-                }
-                NetworksCommands::Connect { id, instance } => {
-                    println!("Connecting instance '{}' to network '{}'", instance, id);
-                }
-                NetworksCommands::Disconnect { id, instance } => {
-                    println!("Disconnecting instance '{}' from network '{}'", instance, id);
-                }
-                NetworksCommands::Show { id } => {
-                    println!("Network details for '{}':", id);
-                    println!("ID: {}", id);
-                    println!("Name: default");
-                    println!("CIDR: 192.168.1.0/24");
-                    println!("Instances: i-01234567 (web-1), i-89abcdef (db-1)");
-                }
-            }
-        }
-        Some(Commands::TestVyOS { host, port, username }) => {
-            // This would block, so we need to call it outside the CLI handler
-            // Will be implemented in main()
-            return Err("Use tokio runtime to test VyOS connectivity".into());
-        }
-        None => {
-            // If no subcommand is provided, we'll exit and let the main function
-            // launch the TUI mode
-            return Ok(());
-        }
-    }
-    
-    Ok(())
+#[derive(Subcommand)]
+enum TenantsCommands {
+    /// Limit the bandwidth of a tenant on the routers carrying its networks
+    SetBandwidth {
+        tenant: String,
+        /// Limit on traffic sent by the tenant (e.g. 200mbit), unlimited when omitted
+        #[arg(long)]
+        egress: Option<String>,
+        /// Limit on traffic received by the tenant (e.g. 500mbit), unlimited when omitted
+        #[arg(long)]
+        ingress: Option<String>,
+    },
 }
 
-async fn run_tui() -> AppResult<()> {
-    // Create an application.
-    let mut app = App::new();
+#[derive(Subcommand)]
+enum FabricCommands {
+    /// Generate and push fabric configuration to every router
+    Apply {
+        /// Path to the fabric description
+        #[arg(short, long)]
+        file: String,
+        /// Print the generated configuration without pushing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show EVPN neighbor state on every router
+    Status {
+        /// Path to the fabric description
+        #[arg(short, long)]
+        file: String,
+    },
+}
 
-    // Initialize the terminal user interface.
-    let backend = CrosstermBackend::new(io::stdout());
-    let terminal = Terminal::new(backend)?;
-    let events = EventHandler::new(250);
-    let mut tui = Tui::new(terminal, events);
-    tui.init()?;
+#[derive(Subcommand)]
+enum SiteCommands {
+    /// Show the OSPF changes a site description would make on its routers
+    Plan {
+        /// Path to the site description
+        #[arg(short, long)]
+        file: String,
+        /// Only plan this router
+        #[arg(long)]
+        router: Option<String>,
+    },
+    /// Apply a site description to its routers after previewing the changes
+    Apply {
+        /// Path to the site description
+        #[arg(short, long)]
+        file: String,
+        /// Only configure this router
+        #[arg(long)]
+        router: Option<String>,
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
+}
 
-    // Start the main loop.
-    while app.running {
-        // Render the user interface.
-        tui.draw(&mut app)?;
-        // Handle events.
-        match tui.events.next().await? {
-            Event::Tick => app.tick(),
-            Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
-        }
-    }
+#[derive(Subcommand)]
+enum FirewallCommands {
+    /// Show the configuration changes a policy would make on a router
+    Plan {
+        /// Path to the firewall policy
+        #[arg(short, long)]
+        file: String,
+        /// VyOS provider to compare against
+        #[arg(long)]
+        provider: String,
+        /// VyOS version of the router (1.4 or 1.5)
+        #[arg(long, default_value = "1.4")]
+        vyos_version: String,
+    },
+    /// Apply a policy to a router after previewing the changes
+    Apply {
+        /// Path to the firewall policy
+        #[arg(short, long)]
+        file: String,
+        /// VyOS provider to configure
+        #[arg(long)]
+        provider: String,
+        /// VyOS version of the router (1.4 or 1.5)
+        #[arg(long, default_value = "1.4")]
+        vyos_version: String,
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Print the configuration generated from a policy without contacting a router
+    Compile {
+        /// Path to the firewall policy
+        #[arg(short, long)]
+        file: String,
+        /// VyOS version to generate syntax for (1.4 or 1.5)
+        #[arg(long, default_value = "1.4")]
+        vyos_version: String,
+    },
+}
 
-    // Exit the user interface.
-    tui.exit()?;
-    Ok(())
+#[derive(Subcommand)]
+enum SecurityGroupsCommands {
+    /// List all security groups
+    List,
+    /// Show a security group and its rules
+    Show {
+        name: String,
+    },
+    /// Create a new security group
+    Create {
+        name: String,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Delete a security group
+    Delete {
+        name: String,
+    },
+    /// Add a rule to a security group
+    AddRule {
+        group: String,
+        /// Rule direction (ingress or egress)
+        #[arg(long, default_value = "ingress")]
+        direction: String,
+        /// Protocol (tcp, udp, icmp or all)
+        #[arg(long, default_value = "tcp")]
+        protocol: String,
+        /// Port or port range, e.g. 443 or 8000-8080
+        #[arg(long)]
+        port: Option<String>,
+        /// Remote CIDR block
+        #[arg(long, conflicts_with = "peer_group")]
+        cidr: Option<String>,
+        /// Remote security group
+        #[arg(long)]
+        peer_group: Option<String>,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Remove a rule from a security group
+    RemoveRule {
+        group: String,
+        /// Rule direction (ingress or egress)
+        #[arg(long, default_value = "ingress")]
+        direction: String,
+        /// Rule position as shown by `security-groups show`
+        #[arg(long)]
+        index: usize,
+    },
+    /// Attach an instance to a security group
+    Attach {
+        group: String,
+        #[arg(long)]
+        instance: String,
+    },
+    /// Detach an instance from a security group
+    Detach {
+        group: String,
+        #[arg(long)]
+        instance: String,
+    },
+    /// Re-enforce security groups on their member instances
+    Sync {
+        /// Only sync this group
+        group: Option<String>,
+    },
 }
 
-#[tokio::main]
-async fn main() -> AppResult<()> {
-    // Setup logging
-    env_logger::init();
-    
-    // Initialize configuration
-    if let Err(e) = crate::config::init_config() {
-        eprintln!("Warning: Failed to initialize configuration: {}", e);
-        eprintln!("Some functionality may be limited.");
-    }
+#[derive(Subcommand)]
+enum FloatingIpsCommands {
+    /// List allocated floating IPs
+    List,
+    /// Allocate a floating IP
+    Allocate {
+        /// Pool to allocate from
+        #[arg(long)]
+        pool: Option<String>,
+        /// Specific address to allocate
+        #[arg(long)]
+        ip: Option<String>,
+    },
+    /// Associate a floating IP with an instance as a 1:1 NAT
+    Associate {
+        ip: String,
+        #[arg(long)]
+        instance: String,
+        /// Private address of the instance (defaults to its first network address)
+        #[arg(long)]
+        private_ip: Option<String>,
+    },
+    /// Remove the association and port forwards of a floating IP
+    Disassociate {
+        ip: String,
+    },
+    /// Release a floating IP back to its pool
+    Release {
+        ip: String,
+    },
+    /// List floating IP pools
+    Pools,
+    /// Create a floating IP pool
+    CreatePool {
+        name: String,
+        /// Public CIDR block
+        #[arg(long)]
+        cidr: String,
+        /// VyOS provider that performs NAT for the block
+        #[arg(long)]
+        router: String,
+        /// Public-facing interface on the router
+        #[arg(long)]
+        interface: String,
+        /// Addresses that must never be allocated (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+    },
+    /// Delete an empty floating IP pool
+    DeletePool {
+        name: String,
+    },
+    /// Push the NAT configuration to every router carrying a pool
+    Sync,
+}
+
+#[derive(Subcommand)]
+enum RoutersCommands {
+    /// Show interfaces, routes, neighbors, ARP and conntrack counters of a router
+    Status {
+        /// VyOS provider name
+        provider: String,
+        /// Read the routing table of this VRF
+        #[arg(long)]
+        vrf: Option<String>,
+        /// Site description to check OSPF adjacencies against
+        #[arg(long)]
+        site: Option<String>,
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CollectorCommands {
+    /// Receive IPFIX exports and record per-tenant traffic until interrupted
+    Run {
+        /// Address to listen on
+        #[arg(long, default_value = "0.0.0.0:4739")]
+        listen: String,
+        /// Seconds between writes to the usage store
+        #[arg(long, default_value_t = 60)]
+        flush_interval: u64,
+        /// Tenant network as <tenant>=<cidr> (repeatable)
+        #[arg(long = "cidr")]
+        cidrs: Vec<String>,
+    },
+    /// Configure a router to export flows to the collector
+    Configure {
+        /// VyOS provider name
+        router: String,
+        /// Address of the collector as seen from the router
+        #[arg(long)]
+        address: String,
+        /// Collector port
+        #[arg(long, default_value_t = 4739)]
+        port: u16,
+        /// Interface to account (repeatable)
+        #[arg(long = "interface", required = true)]
+        interfaces: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum UsageCommands {
+    /// Show traffic per tenant
+    Traffic {
+        /// Only show this tenant
+        #[arg(long)]
+        tenant: Option<String>,
+        /// How far back to look (e.g. 12h, 7d, 2w)
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// Show one line per time bucket instead of totals
+        #[arg(long)]
+        buckets: bool,
+        /// Print the usage as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LogsCommands {
+    /// Receive syslog messages and store them until interrupted
+    Collect {
+        /// Address to listen on
+        #[arg(long, default_value = "0.0.0.0:514")]
+        listen: String,
+        /// Transport to accept (udp, tcp or both)
+        #[arg(long, default_value = "both")]
+        protocol: String,
+        /// Do not print received messages
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Configure a router to send its logs to the collector
+    Configure {
+        /// VyOS provider name
+        router: String,
+        /// Address of the collector as seen from the router
+        #[arg(long)]
+        address: String,
+        /// Collector port
+        #[arg(long, default_value_t = 514)]
+        port: u16,
+        /// Transport (udp or tcp)
+        #[arg(long, default_value = "udp")]
+        protocol: String,
+        /// Least severe level to send
+        #[arg(long, default_value = "info")]
+        level: String,
+    },
+    /// Show the latest collected messages
+    Tail {
+        /// Only show messages from this provider
+        #[arg(long)]
+        provider: Option<String>,
+        /// Only show messages containing this text (case-insensitive)
+        #[arg(long)]
+        grep: Option<String>,
+        /// Only show messages at least this severe
+        #[arg(long)]
+        severity: Option<String>,
+        /// Number of messages to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+        /// Keep printing new messages as they arrive
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LoadBalancersCommands {
+    /// List all load balancers
+    List,
+    /// Show a load balancer and its backend servers
+    Show {
+        name: String,
+    },
+    /// Create or replace a load balancer from a description and push it to its router
+    Apply {
+        /// Path to the load balancer description
+        #[arg(short, long)]
+        file: String,
+    },
+    /// Delete a load balancer and remove it from its router
+    Delete {
+        name: String,
+    },
+    /// Push load balancers and their current backend members to every router
+    Sync,
+}
+
+#[derive(Subcommand)]
+enum DnsCommands {
+    /// Manage user-defined records in the internal domain
+    Records {
+        #[command(subcommand)]
+        action: DnsRecordsCommands,
+    },
+    /// Push instance and user records to every VyOS router
+    Sync,
+}
+
+#[derive(Subcommand)]
+enum DnsRecordsCommands {
+    /// List user-defined records
+    List,
+    /// Add an A or CNAME record
+    Add {
+        /// Record name, e.g. `www.acme` (the `.internal` suffix is implied)
+        name: String,
+        /// Record type (a or cname)
+        #[arg(long = "type", default_value = "a")]
+        record_type: String,
+        /// Address (A) or target name (CNAME)
+        #[arg(long)]
+        value: String,
+    },
+    /// Delete a record
+    Delete {
+        name: String,
+    },
+}
+
+async fn cli_handler(cli: Cli) -> AppResult<()> {
+    match &cli.command {
+        Some(Commands::Init { name, from_template, provider, region, force }) => {
+            handle_init_command(
+                name.as_deref(), from_template.as_deref(), provider.as_deref(), region.as_deref(), *force,
+            )?;
+        }
+        Some(Commands::Deploy { config, dry_run }) => handle_deploy_command(config, *dry_run).await?,
+        Some(Commands::Releases { app, json }) => handle_releases_command(app.as_deref(), *json)?,
+        Some(Commands::Instances { action }) => handle_instances_command(action).await?,
+        Some(Commands::Templates { action }) => handle_templates_command(action)?,
+        Some(Commands::Volumes { action }) => handle_volumes_command(action).await?,
+        Some(Commands::Networks { action }) => handle_networks_command(action).await?,
+        Some(Commands::Providers { action }) => handle_providers_command(action).await?,
+        Some(Commands::Regions { action }) => handle_regions_command(action)?,
+        Some(Commands::Tenants { action }) => handle_tenants_command(action).await?,
+        Some(Commands::Fabric { action }) => handle_fabric_command(action).await?,
+        Some(Commands::Site { action }) => handle_site_command(action).await?,
+        Some(Commands::Firewall { action }) => handle_firewall_command(action).await?,
+        Some(Commands::SecurityGroups { action }) => handle_security_groups_command(action).await?,
+        Some(Commands::Expose { instance, port, protocol, public_ip, pool, private_ip }) => {
+            handle_expose_command(
+                instance, port, protocol, public_ip, pool.as_deref(), private_ip.as_deref(),
+            ).await?;
+        }
+        Some(Commands::FloatingIps { action }) => handle_floating_ips_command(action).await?,
+        Some(Commands::LoadBalancers { action }) => handle_load_balancers_command(action).await?,
+        Some(Commands::Dns { action }) => handle_dns_command(action).await?,
+        Some(Commands::Routers { action }) => handle_routers_command(action).await?,
+        Some(Commands::Collector { action }) => handle_collector_command(action).await?,
+        Some(Commands::Usage { action }) => handle_usage_command(action).await?,
+        Some(Commands::Logs { action }) => handle_logs_command(action).await?,
+        Some(Commands::Refresh { provider, fix, json }) => handle_refresh_command(provider.as_deref(), *fix, *json).await?,
+        Some(Commands::Import { action, provider, all, tag_filter, adopt_dhcp, options }) => {
+            handle_import_command(action.as_ref(), provider.as_deref(), *all, tag_filter, *adopt_dhcp, options).await?;
+        }
+        Some(Commands::Plan { file, adopt, json }) => handle_manifest_command(file, *adopt, false, *json, false).await?,
+        Some(Commands::Apply { file, adopt, yes }) => handle_manifest_command(file, *adopt, true, false, *yes).await?,
+        Some(Commands::Export { name, tenant, region }) => {
+            handle_export_command(name.as_deref(), tenant.as_deref(), region.as_deref())?;
+        }
+        Some(Commands::TestVyOS { host, port, username, password, key_path, api_key }) => {
+            handle_test_vyos_command(host, *port, username, password, key_path, api_key).await?;
+        }
+        None => {
+            // If no subcommand is provided, we'll exit and let the main function
+            // launch the TUI mode
+        }
+    }
     
-    // Parse command line arguments
-    let cli = Cli::parse();
+    Ok(())
+}
+
+fn handle_init_command(
+    name: Option<&str>,
+    from_template: Option<&str>,
+    provider: Option<&str>,
+    region: Option<&str>,
+    force: bool,
+) -> AppResult<()> {
+    use bbctl::config::settings::Settings;
+    use bbctl::services::deploy::APP_FILE;
+    use bbctl::services::project::{detect_project_files, get_preset, resolve_target, use_exposed_port, write_app_spec, PRESETS};
+    use bbctl::services::provider::ProviderService;
+    use std::io::IsTerminal;
     
-    // If we have command-line arguments, handle them
-    if env::args().len() > 1 {
-        // Handle special async commands first
-        match &cli.command {
-            Some(Commands::TestVyOS { host, port, username, password, key_path, api_key }) => {
-                println!("Testing connection to VyOS router at {}:{}...", host, port);
-                
-                // Create a VyOS client using our API
-                use crate::api::vyos::{VyOSClient, VyOSConfig};
-                use crate::api::Provider;
-                
-                let config = VyOSConfig {
-                    host: host.clone(),
-                    ssh_port: port,
-                    api_port: 443, // Default API port
-                    username: username.clone(),
-                    password: password.clone(),
-                    key_path: key_path.clone(),
-                    api_key: api_key.clone(),
-                    timeout: 30,
-                };
-                
-                let client = VyOSClient::new(config);
+    let dir = env::current_dir()?;
+    let files = detect_project_files(&dir);
+    let interactive = from_template.is_none();
+    if interactive && !io::stdin().is_terminal() {
+        return Err("Not running interactively, pass --from-template to write a preset project".into());
+    }
+    
+    if let Some(project) = &files.project {
+        let overwrite = force 
+            || (interactive && confirm(&format!("{} already exists, overwrite it?", project.display()))?);
+        if !overwrite {
+            return Err(format!("{} already exists, pass --force to overwrite it", project.display()).into());
+        }
+    }
+    for other in &files.others {
+        println!("Found {}, its settings are not carried over", other.display());
+    }
+    
+    let settings = Settings::load()?;
+    let provider_service = ProviderService::new()?;
+    let default_name = dir.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "bitbuilder-app".to_string());
+    
+    let spec = match from_template {
+        Some(template) => {
+            let preset = get_preset(template).ok_or_else(|| format!(
+                "Unknown template '{}', use one of: {}", 
+                template, PRESETS.iter().map(|p| p.name).collect::<Vec<_>>().join(", "),
+            ))?;
+            let (provider, region) = resolve_target(&provider_service, &settings, preset.provider_type, provider, region)?;
+            let name = name.unwrap_or(&default_name);
+            let mut spec = preset.spec(name, &provider, &region, &settings);
+            if let Some(port) = files.exposed_port {
+                use_exposed_port(&mut spec, port);
+            }
+            spec
+        }
+        None => prompt_app_spec(name.unwrap_or(&default_name), &provider_service, &settings, files.exposed_port)?,
+    };
+    
+    write_app_spec(&dir.join(APP_FILE), &spec)?;
+    println!("✅ Wrote {} for '{}', deploy it with `bbctl deploy`", APP_FILE, spec.name);
+    Ok(())
+}
+
+/// Ask for the parts of a project on the terminal
+fn prompt_app_spec(
+    default_name: &str,
+    provider_service: &bbctl::services::provider::ProviderService,
+    settings: &bbctl::config::settings::Settings,
+    exposed_port: Option<u16>,
+) -> AppResult<bbctl::models::app::AppSpec> {
+    use bbctl::models::app::AppHealthCheck;
+    use bbctl::models::provider::ProviderType;
+    use bbctl::services::network::NetworkService;
+    use bbctl::services::project::{base_app_spec, regions_for};
+    
+    let name = prompt_line("Application name", default_name)?;
+    
+    let mut providers: Vec<_> = provider_service.get_providers().iter().collect();
+    providers.sort_by(|a, b| a.0.cmp(b.0));
+    if providers.is_empty() {
+        return Err("No providers configured, add one with `bbctl providers add`".into());
+    }
+    let options: Vec<_> = providers.iter()
+        .map(|(name, config)| format!("{} ({}, {})", name, config.provider_type, config.host))
+        .collect();
+    let default = providers.iter()
+        .position(|(name, _)| Some(name.as_str()) == settings.default_provider.as_deref())
+        .unwrap_or(0);
+    let (provider, config) = providers[prompt_choice("Provider", &options, default)?];
+    
+    let regions = regions_for(provider_service, config.provider_type);
+    let region = if regions.is_empty() {
+        prompt_line("Region", settings.default_region.as_deref().unwrap_or("default"))?
+    } else {
+        let options: Vec<_> = regions.iter().map(|r| format!("{} ({})", r.id, r.location)).collect();
+        let default = regions.iter()
+            .position(|r| Some(r.id.as_str()) == settings.default_region.as_deref())
+            .unwrap_or(0);
+        regions[prompt_choice("Region", &options, default)?].id.clone()
+    };
+    
+    let mut spec = base_app_spec(&name, provider, &region, settings);
+    spec.cpu = prompt_line("CPU cores", &spec.cpu.to_string())?.parse()
+        .map_err(|_| "CPU cores must be a number")?;
+    spec.memory_gb = prompt_line("Memory (GB)", &spec.memory_gb.to_string())?.parse()
+        .map_err(|_| "Memory must be a number")?;
+    spec.disk_gb = prompt_line("Disk (GB)", &spec.disk_gb.to_string())?.parse()
+        .map_err(|_| "Disk must be a number")?;
+    spec.regions[0].count = prompt_line("Instances", "1")?.parse()
+        .map_err(|_| "The instance count must be a number")?;
+    
+    if config.provider_type == ProviderType::Proxmox {
+        spec.template = Some(prompt_line("VM template (name or VMID)", "debian-12")?);
+        
+        let network_service = NetworkService::new(provider_service.clone())?;
+        let mut networks: Vec<_> = network_service.list_networks().into_iter()
+            .filter(|n| n.provider == ProviderType::Proxmox && n.region == region)
+            .map(|n| n.name.clone())
+            .collect();
+        networks.sort();
+        if !networks.is_empty() {
+            let mut options = vec!["none".to_string()];
+            options.extend(networks.iter().map(|n| n.to_string()));
+            spec.network = match prompt_choice("Network", &options, 0)? {
+                0 => None,
+                choice => Some(networks[choice - 1].clone()),
+            };
+        }
+        
+        if confirm("Wait for SSH on new instances?")? {
+            spec.health_check = Some(AppHealthCheck {
+                port: 22,
+                path: None,
+                expect_status: None,
+                timeout_secs: 300,
+                interval_secs: 10,
+            });
+        }
+    } else {
+        let default_image = match exposed_port {
+            Some(_) => format!("{}:latest", name),
+            None => "docker.io/library/nginx:stable".to_string(),
+        };
+        spec.image = Some(prompt_line("Container image", &default_image)?);
+        
+        let network = prompt_line("Container network (none for host networking)", "bbctl-apps")?;
+        if network != "none" {
+            spec.network_prefix = Some(prompt_line("Network prefix", "172.30.0.0/24")?);
+            spec.network = Some(network);
+        }
+        
+        let container_port = exposed_port.unwrap_or(80);
+        let ports = prompt_line("Published ports (router:container, comma separated, none to skip)", 
+                                &format!("8080:{}", container_port))?;
+        if ports != "none" {
+            spec.ports = ports.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+        }
+        
+        let check = prompt_line("Health check port (none to skip)", &container_port.to_string())?;
+        if check != "none" {
+            let path = prompt_line("Health check HTTP path (none for a TCP check)", "/")?;
+            spec.health_check = Some(AppHealthCheck {
+                port: check.parse().map_err(|_| "The health check port must be a number")?,
+                path: Some(path).filter(|p| p != "none"),
+                expect_status: None,
+                timeout_secs: 120,
+                interval_secs: 5,
+            });
+        }
+    }
+    
+    Ok(spec)
+}
+
+async fn handle_deploy_command(config: &str, dry_run: bool) -> AppResult<()> {
+    use bbctl::models::app::ReleaseStatus;
+    use bbctl::services::deploy::{load_app_spec, DeployService};
+    use bbctl::services::provider::ProviderService;
+    
+    let spec = load_app_spec(std::path::Path::new(config))?;
+    let deploy_service = DeployService::new(ProviderService::new()?)?;
+    let rollout = deploy_service.plan(&spec)?;
+    
+    println!("{:<16} {:<16} {:<6} {:<8} {:<7} REMOVE", "REGION", "PROVIDER", "KEEP", "REPLACE", "CREATE");
+    for region in &rollout.regions {
+        println!("{:<16} {:<16} {:<6} {:<8} {:<7} {}", 
+                region.region.region, region.region.provider, region.keep.len(), 
+                region.replace.len(), region.create, region.remove.len());
+    }
+    if !rollout.retire.is_empty() {
+        println!("{} instances in regions no longer listed will be removed", rollout.retire.len());
+    }
+    let outages: Vec<&str> = rollout.regions.iter()
+        .filter(|r| !spec.ports.is_empty() && !r.replace.is_empty())
+        .map(|r| r.region.provider.as_str())
+        .collect();
+    if !outages.is_empty() {
+        println!("⚠️  Published ports move to the new containers: '{}' is unreachable on {} until they pass the health check", 
+                spec.name, outages.join(", "));
+    }
+    
+    if dry_run {
+        return Ok(());
+    }
+    
+    let release = deploy_service.deploy(&spec).await?;
+    match release.status {
+        ReleaseStatus::Succeeded => {
+            println!("\n✅ Release {} of '{}' deployed, {} instances running", 
+                    release.number, spec.name, release.instances.len());
+            Ok(())
+        }
+        status => {
+            eprintln!("\n❌ Release {} of '{}' {}: {}", 
+                    release.number, spec.name, status, release.error.as_deref().unwrap_or("unknown error"));
+            Err(format!("Release {} was not deployed", release.number).into())
+        }
+    }
+}
+
+fn handle_releases_command(app: Option<&str>, json: bool) -> AppResult<()> {
+    use bbctl::services::deploy::{load_app_spec, DeployService, APP_FILE};
+    use bbctl::services::provider::ProviderService;
+    
+    let deploy_service = DeployService::new(ProviderService::new()?)?;
+    let project = std::path::Path::new(APP_FILE);
+    let apps = match app {
+        Some(app) => vec![app.to_string()],
+        None if project.exists() => vec![load_app_spec(project)?.name],
+        None => deploy_service.list_apps()?,
+    };
+    
+    let mut releases = Vec::new();
+    for app in &apps {
+        releases.extend(deploy_service.list_releases(app)?.into_iter().map(|release| (app.clone(), release)));
+    }
+    
+    if json {
+        let releases: Vec<_> = releases.iter()
+            .map(|(app, release)| serde_json::json!({ "app": app, "release": release }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&releases)?);
+        return Ok(());
+    }
+    
+    if releases.is_empty() {
+        println!("No releases found");
+        return Ok(());
+    }
+    
+    println!("{:<16} {:<8} {:<12} {:<10} {:<20} SOURCE", "APP", "RELEASE", "STATUS", "INSTANCES", "CREATED");
+    for (app, release) in &releases {
+        let source = release.spec.image.as_ref()
+            .or(release.spec.template.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        println!("{:<16} {:<8} {:<12} {:<10} {:<20} {}", 
+                app, release.number, release.status.to_string(), release.instances.len(), 
+                release.created_at.format("%Y-%m-%d %H:%M:%S"), source);
+        if let Some(error) = &release.error {
+            println!("    {}", error);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_refresh_command(provider: Option<&str>, fix: bool, json: bool) -> AppResult<()> {
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::refresh::{DriftKind, RefreshService};
+    
+    let refresh_service = RefreshService::new(ProviderService::new()?)?;
+    let report = refresh_service.detect(provider).await?;
+    let changed = refresh_service.apply(&report, fix).await?;
+    
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if report.drifts.is_empty() {
+        println!("No drift found");
+    } else {
+        println!("{:<16} {:<10} {:<24} {:<36} DRIFT", "PROVIDER", "KIND", "NAME", "PROVIDER ID");
+        for drift in &report.drifts {
+            let kind = match &drift.kind {
+                DriftKind::Status { from, to } => format!("status {} -> {}", from, to),
+                DriftKind::Missing if fix => "missing, record removed".to_string(),
+                DriftKind::Missing => "missing".to_string(),
+                DriftKind::Unmanaged => "unmanaged".to_string(),
+            };
+            println!("{:<16} {:<10} {:<24} {:<36} {}", 
+                    drift.provider, drift.resource.to_string(), drift.name, drift.provider_id, kind);
+        }
+        println!("\nUpdated {} records", changed);
+    }
+    
+    for (provider, error) in &report.errors {
+        eprintln!("❌ {}: {}", provider, error);
+    }
+    if !report.errors.is_empty() {
+        return Err(format!("{} providers could not be refreshed", report.errors.len()).into());
+    }
+    
+    Ok(())
+}
+
+async fn handle_import_command(
+    action: Option<&ImportCommands>,
+    provider: Option<&str>,
+    all: bool,
+    tag_filter: &[String],
+    adopt_dhcp: bool,
+    options: &ImportOptions,
+) -> AppResult<()> {
+    use bbctl::config::settings::Settings;
+    use bbctl::services::import::{ImportOutcome, ImportService};
+    use bbctl::services::provider::ProviderService;
+    
+    let import_service = ImportService::new(ProviderService::new()?)?;
+    let options = match action {
+        Some(ImportCommands::Instance { options, .. }) | Some(ImportCommands::Network { options, .. }) => options,
+        None => options,
+    };
+    let region = options.region.clone().or(Settings::load()?.default_region)
+        .unwrap_or_else(|| "default".to_string());
+    
+    let results = match action {
+        Some(ImportCommands::Instance { provider, vmid, .. }) => {
+            vec![import_service.import_proxmox_vm(provider, *vmid, &region).await?]
+        }
+        Some(ImportCommands::Network { provider, name, adopt_dhcp, .. }) => {
+            import_service.import_vyos_networks(provider, name.as_deref(), &region, *adopt_dhcp).await?
+        }
+        None => {
+            let provider = provider.ok_or("--provider is required")?;
+            if !all {
+                return Err("Pass --all to import every resource of the provider, or use `import instance` or `import network`".into());
+            }
+            import_service.import_all(provider, tag_filter, &region, adopt_dhcp).await?
+        }
+    };
+    
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else if results.is_empty() {
+        println!("Nothing to import");
+    } else {
+        println!("{:<10} {:<24} {:<20} RESULT", "KIND", "NAME", "PROVIDER ID");
+        for result in &results {
+            let outcome = match &result.outcome {
+                ImportOutcome::Imported { id } => format!("imported as {}", id),
+                ImportOutcome::AlreadyManaged { id } => format!("already managed as {}", id),
+                ImportOutcome::Failed { error } => format!("failed: {}", error),
+            };
+            println!("{:<10} {:<24} {:<20} {}", 
+                    result.resource.to_string(), result.name, result.provider_id, outcome);
+        }
+    }
+    
+    let failed = results.iter().filter(|r| matches!(r.outcome, ImportOutcome::Failed { .. })).count();
+    if failed > 0 {
+        return Err(format!("{} resources could not be imported", failed).into());
+    }
+    
+    Ok(())
+}
+
+async fn handle_manifest_command(file: &str, adopt: bool, apply: bool, json: bool, yes: bool) -> AppResult<()> {
+    use bbctl::services::manifest::{load_manifest, Change, ManifestService};
+    use bbctl::services::provider::ProviderService;
+    
+    let manifest = load_manifest(std::path::Path::new(file))?;
+    let manifest_service = ManifestService::new(ProviderService::new()?)?;
+    let plan = manifest_service.plan(&manifest, adopt).await?;
+    
+    for warning in &plan.warnings {
+        eprintln!("⚠️  Planned from stored state only: {}", warning);
+    }
+    
+    if json {
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
+    
+    if plan.is_empty() {
+        println!("No changes. Manifest '{}' is up to date.", manifest.name);
+        return Ok(());
+    }
+    
+    for step in &plan.steps {
+        println!("{}", step);
+    }
+    println!("\n{} to create, {} to update, {} to delete",
+            plan.count(Change::Create), plan.count(Change::Update), plan.count(Change::Delete));
+    
+    if !apply {
+        return Ok(());
+    }
+    
+    if !yes && !confirm(&format!("Apply manifest '{}'?", manifest.name))? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    
+    let results = manifest_service.apply(&manifest, &plan).await;
+    for result in &results {
+        match &result.error {
+            None => println!("✅ {}", result.step),
+            Some(error) => eprintln!("❌ {}: {}", result.step, error),
+        }
+    }
+    
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    if failed > 0 {
+        return Err(format!("{} steps failed, {} of {} were not run",
+                failed, plan.steps.len() - results.len(), plan.steps.len()).into());
+    }
+    println!("\n✅ Manifest '{}' applied", manifest.name);
+    
+    Ok(())
+}
+
+fn handle_export_command(name: Option<&str>, tenant: Option<&str>, region: Option<&str>) -> AppResult<()> {
+    use bbctl::services::manifest::ManifestService;
+    use bbctl::services::provider::ProviderService;
+    
+    let name = name.or(tenant).unwrap_or("infra");
+    let manifest = ManifestService::new(ProviderService::new()?)?.export(name, tenant, region)?;
+    print!("{}", toml::to_string_pretty(&manifest)?);
+    
+    Ok(())
+}
+
+async fn handle_test_vyos_command(
+    host: &str,
+    port: u16,
+    username: &str,
+    password: &Option<String>,
+    key_path: &Option<String>,
+    api_key: &Option<String>,
+) -> AppResult<()> {
+    println!("Testing connection to VyOS router at {}:{}...", host, port);
+    
+    // Create a VyOS client using our API
+    use bbctl::api::vyos::{VyOSClient, VyOSConfig};
+    use bbctl::api::Provider;
+    
+    let config = VyOSConfig {
+        host: host.to_string(),
+        ssh_port: port,
+        api_port: 443, // Default API port
+        username: username.to_string(),
+        password: password.clone(),
+        key_path: key_path.clone(),
+        api_key: api_key.clone(),
+        timeout: 30,
+    };
+    
+    let client = VyOSClient::new(config);
+    
+    // First try the synchronous connection test
+    match client.connect() {
+        Ok(_) => {
+            println!("\n✅ SSH connection successful!");
+            
+            // If API key is provided, also test the API
+            if api_key.is_some() {
+                println!("\nTesting VyOS HTTP API...");
                 
-                // First try the synchronous connection test
-                match client.connect() {
-                    Ok(_) => {
-                        println!("\n✅ SSH connection successful!");
-                        
-                        // If API key is provided, also test the API
-                        if let Some(api_key) = &api_key {
-                            println!("\nTesting VyOS HTTP API...");
-                            
-                            let mut client_mut = client;
-                            match client_mut.get_system_info().await {
-                                Ok(info) => {
-                                    println!("\n✅ API connection successful!");
-                                    println!("\nVyOS system information:");
-                                    println!("{}", serde_json::to_string_pretty(&info).unwrap_or_else(|_| info.to_string()));
-                                },
-                                Err(e) => {
-                                    println!("\n❌ API connection failed: {}", e);
-                                }
-                            }
-                        }
-                        
-                        return Ok(());
+                let mut client_mut = client;
+                match client_mut.get_system_info().await {
+                    Ok(info) => {
+                        println!("\n✅ API connection successful!");
+                        println!("\nVyOS system information:");
+                        println!("{}", serde_json::to_string_pretty(&info).unwrap_or_else(|_| info.to_string()));
                     },
                     Err(e) => {
-                        // Fallback to manual SSH connection if the client connect fails
-                        println!("VyOS client connection failed: {}", e);
-                        println!("Falling back to direct SSH connection...");
-                        
-                        // Let's try connecting interactively - we'll just verify the connection first
-                        let ssh_command = format!("ssh -o StrictHostKeyChecking=no -p {} {}@{}", 
-                                                port, username, host);
-                        println!("Running: {}", ssh_command);
-                        
-                        let output = tokio::process::Command::new("sh")
-                            .arg("-c")
-                            .arg(ssh_command)
-                            .output()
-                            .await
-                            .map_err(|e| format!("Failed to execute SSH command: {}", e))?;
-                        
-                        if output.status.success() || output.status.code() == Some(255) {
-                            // If we got output, even with a non-zero exit code, that likely means
-                            // we connected successfully but then got disconnected properly after the welcome message
-                            let stdout = String::from_utf8_lossy(&output.stdout);
-                            if stdout.contains("VyOS") {
-                                println!("\n✅ Connection successful!");
-                                println!("\nVyOS system information:");
-                                // Extract just the version information
-                                if let Some(idx) = stdout.find("VyOS") {
-                                    let version_info = &stdout[idx..];
-                                    println!("{}", version_info);
-                                } else {
-                                    println!("{}", stdout);
-                                }
-                                return Ok(());
-                            } else {
-                                return Err(format!("Connected but did not receive VyOS welcome message").into());
-                            }
-                        } else {
-                            let error = String::from_utf8_lossy(&output.stderr);
-                            return Err(format!("Connection failed: {}", error).into());
+                        return Err(format!("API connection failed: {}", e).into());
+                    }
+                }
+            }
+            
+            Ok(())
+        },
+        Err(e) => {
+            // Fallback to manual SSH connection if the client connect fails
+            println!("VyOS client connection failed: {}", e);
+            println!("Falling back to direct SSH connection...");
+            
+            // Let's try connecting interactively - we'll just verify the connection first
+            let ssh_command = format!("ssh -o StrictHostKeyChecking=no -p {} {}@{}", 
+                                    port, username, host);
+            println!("Running: {}", ssh_command);
+            
+            let output = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(ssh_command)
+                .output()
+                .await
+                .map_err(|e| format!("Failed to execute SSH command: {}", e))?;
+            
+            if output.status.success() || output.status.code() == Some(255) {
+                // If we got output, even with a non-zero exit code, that likely means
+                // we connected successfully but then got disconnected properly after the welcome message
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                if stdout.contains("VyOS") {
+                    println!("\n✅ Connection successful!");
+                    println!("\nVyOS system information:");
+                    // Extract just the version information
+                    if let Some(idx) = stdout.find("VyOS") {
+                        let version_info = &stdout[idx..];
+                        println!("{}", version_info);
+                    } else {
+                        println!("{}", stdout);
+                    }
+                    Ok(())
+                } else {
+                    Err("Connected but did not receive VyOS welcome message".into())
+                }
+            } else {
+                let error = String::from_utf8_lossy(&output.stderr);
+                Err(format!("Connection failed: {}", error).into())
+            }
+        }
+    }
+}
+
+async fn handle_providers_command(action: &ProvidersCommands) -> AppResult<()> {
+    use bbctl::api::vyos::VyOSVersion;
+    use bbctl::services::provider::{local_prefix_towards, ProviderService};
+    
+    let mut provider_service = ProviderService::new()?;
+    
+    match action {
+        ProvidersCommands::List => {
+            let mut providers: Vec<_> = provider_service.get_providers().values().collect();
+            providers.sort_by(|a, b| a.name.cmp(&b.name));
+            
+            println!("{:<20} {:<10} HOST", "NAME", "TYPE");
+            for provider in providers {
+                println!("{:<20} {:<10} {}", provider.name, provider.provider_type.to_string(), provider.host);
+            }
+        }
+        ProvidersCommands::Add { name, provider: args } => {
+            if provider_service.get_provider(name).is_some() {
+                return Err(format!("Provider '{}' already exists", name).into());
+            }
+            
+            match args.provider_type.to_lowercase().as_str() {
+                "vyos" => {
+                    use std::io::IsTerminal;
+                    
+                    let version: VyOSVersion = args.vyos_version.parse()?;
+                    let username = args.username.as_deref().unwrap_or("vyos");
+                    let interactive = io::stdin().is_terminal();
+                    
+                    // Secrets left off the command line are asked for, keeping them out of shell history.
+                    // Empty answers leave SSH to its keys and the router without an API key.
+                    let password = match &args.password {
+                        Some(password) => Some(password.clone()),
+                        None if args.key_path.is_none() && interactive => {
+                            Some(prompt_secret(&format!("SSH password for {}@{} (empty to use SSH keys)", username, args.host))?)
+                                .filter(|password| !password.is_empty())
                         }
+                        None => None,
+                    };
+                    
+                    // Settle everything that can fail before the provider is stored
+                    let bootstrap = args.api_key.is_none() && !args.skip_api_bootstrap
+                        && (args.yes || confirm(&format!("Enable the HTTP API on '{}' over SSH?", name))?);
+                    let api_key = match &args.api_key {
+                        Some(api_key) => Some(api_key.clone()),
+                        None if !bootstrap && interactive => {
+                            Some(prompt_secret(&format!("HTTP API key of '{}' (empty for none)", name))?)
+                                .filter(|api_key| !api_key.is_empty())
+                        }
+                        None => None,
+                    };
+                    let allowed = if !bootstrap {
+                        Vec::new()
+                    } else if args.api_allow.is_empty() {
+                        vec![local_prefix_towards(&args.host, args.port.unwrap_or(443))?]
+                    } else {
+                        args.api_allow.clone()
+                    };
+                    
+                    provider_service.add_vyos_provider(
+                        name,
+                        &args.host,
+                        username,
+                        password,
+                        args.key_path.clone(),
+                        api_key.clone(),
+                        args.ssh_port,
+                        args.port,
+                    )?;
+                    
+                    if api_key.is_some() || args.skip_api_bootstrap {
+                        println!("Added VyOS provider '{}'", name);
+                        return Ok(());
+                    }
+                    if !bootstrap {
+                        println!("Added VyOS provider '{}' without an HTTP API key", name);
+                        return Ok(());
                     }
+                    println!("Enabling the HTTP API on '{}' for {}...", name, allowed.join(", "));
+                    
+                    // Leave nothing behind so the command can simply be run again
+                    if let Err(e) = provider_service.bootstrap_vyos_api(name, &allowed, version).await {
+                        provider_service.remove_provider(name)?;
+                        return Err(format!("{:#}; provider '{}' was not added", e, name).into());
+                    }
+                    println!("✅ Added VyOS provider '{}' with its HTTP API enabled", name);
+                }
+                "proxmox" => {
+                    // Secrets left off the command line are asked for, keeping them out of shell history
+                    match (&args.token_id, &args.username) {
+                        (Some(token_id), _) => {
+                            let token_secret = match &args.token_secret {
+                                Some(secret) => secret.clone(),
+                                None => prompt_secret(&format!("Token secret for {}", token_id))?,
+                            };
+                            provider_service.add_proxmox_provider_with_token(
+                                name, &args.host, token_id, &token_secret, args.port, args.verify_ssl,
+                            )?;
+                        }
+                        (None, Some(username)) => {
+                            let password = match &args.password {
+                                Some(password) => password.clone(),
+                                None => prompt_secret(&format!("Password for {}@{}", username, args.realm))?,
+                            };
+                            provider_service.add_proxmox_provider_with_user_pass(
+                                name, &args.host, username, &password, &args.realm, args.port, args.verify_ssl,
+                            )?;
+                        }
+                        (None, None) => return Err("Proxmox providers need --token-id or --username".into()),
+                    }
+                    println!("Added Proxmox provider '{}'", name);
+                }
+                other => return Err(format!("Unknown provider type '{}', expected vyos or proxmox", other).into()),
+            }
+        }
+        ProvidersCommands::Remove { name } => {
+            provider_service.remove_provider(name)?;
+            println!("Removed provider '{}'", name);
+        }
+        ProvidersCommands::Test { name, verbose } => {
+            if *verbose {
+                if let Some(provider) = provider_service.get_provider(name) {
+                    println!("Testing {} provider '{}' at {}", provider.provider_type, name, provider.host);
                 }
-            },
-            _ => {
-                // For other commands, use the synchronous handler
-                cli_handler(cli)?;
             }
+            
+            if provider_service.test_connection(name).await? {
+                println!("✅ Connected to '{}'", name);
+            } else {
+                return Err(format!("Could not connect to '{}'", name).into());
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_tenants_command(action: &TenantsCommands) -> AppResult<()> {
+    use bbctl::services::network::NetworkService;
+    use bbctl::services::provider::ProviderService;
+    
+    let mut network_service = NetworkService::new(ProviderService::new()?)?;
+    
+    match action {
+        TenantsCommands::SetBandwidth { tenant, egress, ingress } => {
+            let results = network_service.set_tenant_bandwidth(tenant, egress.as_deref(), ingress.as_deref()).await?;
+            println!("Tenant '{}': egress {}, ingress {}", tenant,
+                    egress.as_deref().unwrap_or("unlimited"), ingress.as_deref().unwrap_or("unlimited"));
+            print_sync_results(results);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_fabric_command(action: &FabricCommands) -> AppResult<()> {
+    use bbctl::services::fabric::{load_fabric, FabricService};
+    use bbctl::services::provider::ProviderService;
+    
+    let fabric_service = FabricService::new(ProviderService::new()?);
+    
+    match action {
+        FabricCommands::Apply { file, dry_run } => {
+            let fabric = load_fabric(std::path::Path::new(file))?;
+            println!("Applying fabric '{}' (AS {}) to {} routers...", 
+                    fabric.name, fabric.asn, fabric.routers.len());
+            
+            let results = fabric_service.apply(&fabric, *dry_run).await?;
+            let mut failed = 0;
+            
+            for result in &results {
+                if *dry_run {
+                    println!("\n# {}", result.provider);
+                    for command in &result.commands {
+                        println!("{}", command);
+                    }
+                    continue;
+                }
+                
+                match &result.error {
+                    None => println!("✅ {}: {} commands applied", result.provider, result.commands.len()),
+                    Some(e) => {
+                        failed += 1;
+                        println!("❌ {}: {}", result.provider, e);
+                    }
+                }
+            }
+            
+            if failed > 0 {
+                return Err(format!("Fabric apply failed on {} of {} routers", failed, results.len()).into());
+            }
+        }
+        FabricCommands::Status { file } => {
+            let fabric = load_fabric(std::path::Path::new(file))?;
+            let statuses = fabric_service.status(&fabric).await?;
+            
+            for status in &statuses {
+                println!("\nRouter: {}", status.provider);
+                
+                if let Some(e) = &status.error {
+                    println!("  ❌ Failed to query EVPN state: {}", e);
+                    continue;
+                }
+                
+                println!("  NEIGHBOR\t\tAS\tUP/DOWN\t\tSTATE\t\tPREFIXES");
+                for neighbor in &status.neighbors {
+                    println!("  {}\t\t{}\t{}\t{}\t{}", 
+                            neighbor.neighbor, neighbor.remote_as, neighbor.up_down, neighbor.state,
+                            neighbor.prefixes_received.map_or("-".to_string(), |p| p.to_string()));
+                }
+                
+                for peer in status.missing_peers() {
+                    println!("  ⚠️  Expected peer {} is not established", peer);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_site_command(action: &SiteCommands) -> AppResult<()> {
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::site::{load_site, SiteService};
+    
+    let site_service = SiteService::new(ProviderService::new()?);
+    
+    let (file, router, apply, yes) = match action {
+        SiteCommands::Plan { file, router } => (file, router, false, false),
+        SiteCommands::Apply { file, router, yes } => (file, router, true, *yes),
+    };
+    
+    let site = load_site(std::path::Path::new(file))?;
+    let routers: Vec<String> = match router {
+        Some(name) if site.get_router(name).is_none() => {
+            return Err(format!("Router '{}' is not part of site '{}'", name, site.name).into());
+        }
+        Some(name) => vec![name.clone()],
+        None => site.routers.iter().map(|r| r.provider.clone()).collect(),
+    };
+    
+    let mut plans = Vec::new();
+    for router in &routers {
+        let diff = site_service.plan(&site, router).await?;
+        println!("\n# {}", router);
+        print_config_diff(&diff);
+        plans.push((router, diff));
+    }
+    
+    if !apply || plans.iter().all(|(_, diff)| diff.is_empty()) {
+        return Ok(());
+    }
+    
+    if !yes && !confirm(&format!("Apply these changes to site '{}'?", site.name))? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    
+    for (router, diff) in &plans {
+        site_service.apply(router, diff).await?;
+        println!("✅ OSPF underlay applied to '{}'", router);
+    }
+    
+    Ok(())
+}
+
+async fn handle_firewall_command(action: &FirewallCommands) -> AppResult<()> {
+    use bbctl::api::vyos::VyOSVersion;
+    use bbctl::services::firewall::{compile_policy, load_policy, FirewallService};
+    use bbctl::services::provider::ProviderService;
+    
+    match action {
+        FirewallCommands::Compile { file, vyos_version } => {
+            let policy = load_policy(std::path::Path::new(file))?;
+            let version: VyOSVersion = vyos_version.parse()?;
+            
+            for command in compile_policy(&policy, version)? {
+                println!("{}", command);
+            }
+        }
+        FirewallCommands::Plan { file, provider, vyos_version } => {
+            let policy = load_policy(std::path::Path::new(file))?;
+            let version: VyOSVersion = vyos_version.parse()?;
+            let firewall_service = FirewallService::new(ProviderService::new()?);
+            
+            let diff = firewall_service.plan(provider, &policy, version).await?;
+            print_config_diff(&diff);
+        }
+        FirewallCommands::Apply { file, provider, vyos_version, yes } => {
+            let policy = load_policy(std::path::Path::new(file))?;
+            let version: VyOSVersion = vyos_version.parse()?;
+            let firewall_service = FirewallService::new(ProviderService::new()?);
+            
+            let diff = firewall_service.plan(provider, &policy, version).await?;
+            print_config_diff(&diff);
+            
+            if diff.is_empty() {
+                return Ok(());
+            }
+            
+            if !*yes && !confirm(&format!("Apply these changes to '{}'?", provider))? {
+                println!("Aborted.");
+                return Ok(());
+            }
+            
+            firewall_service.apply(provider, &diff).await?;
+            println!("✅ Firewall policy applied to '{}'", provider);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_security_groups_command(action: &SecurityGroupsCommands) -> AppResult<()> {
+    use bbctl::models::security_group::{RuleDirection, RulePeer, SecurityGroupRule};
+    use bbctl::services::instance::InstanceService;
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::security_group::{EnforcementResult, SecurityGroupService};
+    
+    let mut sg_service = SecurityGroupService::new(ProviderService::new()?)?;
+    let instance_service = InstanceService::new(ProviderService::new()?)?;
+    let instances: Vec<_> = instance_service.list_instances().into_iter().cloned().collect();
+    
+    let print_results = |results: Vec<EnforcementResult>| {
+        for result in results {
+            match result.error {
+                None => println!("✅ {}", result.target),
+                Some(e) => println!("❌ {}: {}", result.target, e),
+            }
+        }
+    };
+    
+    match action {
+        SecurityGroupsCommands::List => {
+            println!("NAME\t\tINGRESS\tEGRESS\tMEMBERS\tDESCRIPTION");
+            for group in sg_service.list_groups() {
+                let members = instances.iter().filter(|i| group.applies_to(i)).count();
+                println!("{}\t\t{}\t{}\t{}\t{}", 
+                        group.name, group.ingress.len(), group.egress.len(), members,
+                        group.description.as_deref().unwrap_or(""));
+            }
+        }
+        SecurityGroupsCommands::Show { name } => {
+            let group = sg_service.get_group(name)
+                .ok_or_else(|| format!("Security group not found: {}", name))?;
+            
+            println!("Name: {}", group.name);
+            println!("ID: {}", group.id);
+            println!("Description: {}", group.description.as_deref().unwrap_or("-"));
+            
+            for direction in [RuleDirection::Ingress, RuleDirection::Egress] {
+                println!("\n{} rules:", direction);
+                println!("  #\tPROTOCOL\tPORTS\tPEER\tDESCRIPTION");
+                for (idx, rule) in group.rules(direction).iter().enumerate() {
+                    println!("  {}\t{}\t\t{}\t{}\t{}", 
+                            idx, rule.protocol, rule.port_range("-").unwrap_or_else(|| "all".to_string()),
+                            rule.peer, rule.description.as_deref().unwrap_or(""));
+                }
+            }
+            
+            println!("\nMembers:");
+            for instance in instances.iter().filter(|i| group.applies_to(i)) {
+                println!("  {} ({})", instance.id, instance.name);
+            }
+            for id in group.instances.iter().filter(|id| !instances.iter().any(|i| &i.id == *id)) {
+                println!("  {} (unknown instance)", id);
+            }
+        }
+        SecurityGroupsCommands::Create { name, description } => {
+            let id = sg_service.create_group(name, description.clone())?;
+            println!("Created security group '{}' ({})", name, id);
+        }
+        SecurityGroupsCommands::Delete { name } => {
+            let group = sg_service.delete_group(name)?;
+            let former: Vec<_> = instances.iter().filter(|i| group.applies_to(i)).map(|i| i.id).collect();
+            
+            println!("Deleted security group '{}'", name);
+            print_results(sg_service.sync_group(name, &instances, &former).await?);
+        }
+        SecurityGroupsCommands::AddRule { group, direction, protocol, port, cidr, peer_group, description } => {
+            let (port_from, port_to) = match port {
+                Some(port) => {
+                    let (from, to) = SecurityGroupRule::parse_port_range(port)?;
+                    (Some(from), Some(to))
+                },
+                None => (None, None),
+            };
+            
+            let peer = match (cidr, peer_group) {
+                (_, Some(peer)) => RulePeer::Group(peer.clone()),
+                (Some(cidr), None) => RulePeer::Cidr(cidr.clone()),
+                (None, None) => RulePeer::Cidr("0.0.0.0/0".to_string()),
+            };
+            
+            let rule = SecurityGroupRule {
+                protocol: protocol.to_lowercase(),
+                port_from,
+                port_to,
+                peer,
+                description: description.clone(),
+            };
+            
+            sg_service.add_rule(group, direction.parse::<RuleDirection>()?, rule)?;
+            println!("Added {} rule to '{}'", direction, group);
+            print_results(sg_service.sync_group(group, &instances, &[]).await?);
+        }
+        SecurityGroupsCommands::RemoveRule { group, direction, index } => {
+            sg_service.remove_rule(group, direction.parse::<RuleDirection>()?, *index)?;
+            println!("Removed {} rule {} from '{}'", direction, index, group);
+            print_results(sg_service.sync_group(group, &instances, &[]).await?);
+        }
+        SecurityGroupsCommands::Attach { group, instance } => {
+            let instance_id = uuid::Uuid::parse_str(instance)?;
+            sg_service.attach_instance(group, instance_id)?;
+            println!("Attached instance '{}' to '{}'", instance, group);
+            print_results(sg_service.sync_group(group, &instances, &[]).await?);
+        }
+        SecurityGroupsCommands::Detach { group, instance } => {
+            let instance_id = uuid::Uuid::parse_str(instance)?;
+            sg_service.detach_instance(group, &instance_id)?;
+            println!("Detached instance '{}' from '{}'", instance, group);
+            print_results(sg_service.sync_group(group, &instances, &[instance_id]).await?);
+        }
+        SecurityGroupsCommands::Sync { group } => {
+            match group {
+                Some(group) => print_results(sg_service.sync_group(group, &instances, &[]).await?),
+                None => {
+                    let targets: Vec<_> = instances.iter().collect();
+                    print_results(sg_service.enforce(&targets, &instances).await?);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Resolve the private address of an instance from a flag or its first network address
+fn resolve_private_ip(instance_id: &uuid::Uuid, private_ip: Option<&str>) -> AppResult<std::net::Ipv4Addr> {
+    use bbctl::services::instance::InstanceService;
+    use bbctl::services::provider::ProviderService;
+    
+    if let Some(ip) = private_ip {
+        return Ok(ip.parse()?);
+    }
+    
+    let instance_service = InstanceService::new(ProviderService::new()?)?;
+    let instance = instance_service.get_instance(instance_id)
+        .ok_or_else(|| format!("Instance not found: {} (pass --private-ip)", instance_id))?;
+    
+    instance.networks.iter()
+        .filter_map(|n| n.ip.as_deref())
+        .find_map(|ip| ip.parse().ok())
+        .ok_or_else(|| format!("Instance {} has no IPv4 address (pass --private-ip)", instance_id).into())
+}
+
+/// Print the outcome of pushing configuration to routers
+fn print_sync_results(results: Vec<(String, anyhow::Result<usize>)>) {
+    for (router, result) in results {
+        match result {
+            Ok(0) => println!("✅ {}: up to date", router),
+            Ok(changes) => println!("✅ {}: {} changes applied", router, changes),
+            Err(e) => println!("❌ {}: {}", router, e),
+        }
+    }
+}
+
+async fn handle_expose_command(
+    instance: &str,
+    port: &str,
+    protocol: &str,
+    public_ip: &str,
+    pool: Option<&str>,
+    private_ip: Option<&str>,
+) -> AppResult<()> {
+    use bbctl::services::floating_ip::{parse_port_forward, FloatingIpService};
+    use bbctl::services::provider::ProviderService;
+    
+    let instance_id = uuid::Uuid::parse_str(instance)?;
+    let private_ip = resolve_private_ip(&instance_id, private_ip)?;
+    let forward = parse_port_forward(port, protocol)?;
+    let public_ip = match public_ip {
+        "auto" => None,
+        ip => Some(ip.parse()?),
+    };
+    
+    let mut fip_service = FloatingIpService::new(ProviderService::new()?)?;
+    let ip = fip_service.expose(instance_id, private_ip, forward.clone(), public_ip, pool)?;
+    println!("Exposed {}:{}/{} on {}:{}", 
+            private_ip, forward.private_port, forward.protocol, ip, forward.public_port);
+    
+    let pool = fip_service.get_address(&ip).map(|a| a.pool.clone()).unwrap_or_default();
+    let changes = fip_service.sync_address(&ip, &pool).await?;
+    println!("Applied {} NAT changes", changes);
+    
+    Ok(())
+}
+
+async fn handle_floating_ips_command(action: &FloatingIpsCommands) -> AppResult<()> {
+    use bbctl::models::floating_ip::FloatingIpPool;
+    use bbctl::services::floating_ip::FloatingIpService;
+    use bbctl::services::provider::ProviderService;
+    
+    let mut fip_service = FloatingIpService::new(ProviderService::new()?)?;
+    
+    match action {
+        FloatingIpsCommands::List => {
+            println!("IP		POOL		INSTANCE				PRIVATE IP	NAT");
+            for address in fip_service.list_addresses() {
+                let nat = if address.is_one_to_one() {
+                    "1:1".to_string()
+                } else {
+                    address.port_forwards.iter()
+                        .map(|f| format!("{}->{}/{}", f.public_port, f.private_port, f.protocol))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                };
+                println!("{}	{}		{}	{}	{}", 
+                        address.ip, address.pool,
+                        address.instance_id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string()),
+                        address.private_ip.map(|ip| ip.to_string()).unwrap_or_else(|| "-".to_string()),
+                        nat);
+            }
+        }
+        FloatingIpsCommands::Allocate { pool, ip } => {
+            let ip = match ip {
+                Some(ip) => Some(ip.parse()?),
+                None => None,
+            };
+            let ip = fip_service.allocate(pool.as_deref(), ip)?;
+            println!("Allocated floating IP {}", ip);
+        }
+        FloatingIpsCommands::Associate { ip, instance, private_ip } => {
+            let ip: std::net::Ipv4Addr = ip.parse()?;
+            let instance_id = uuid::Uuid::parse_str(instance)?;
+            let private_ip = resolve_private_ip(&instance_id, private_ip.as_deref())?;
+            
+            fip_service.associate(&ip, instance_id, private_ip)?;
+            println!("Associated {} with {} ({})", ip, instance_id, private_ip);
+            
+            let pool = fip_service.get_address(&ip).map(|a| a.pool.clone()).unwrap_or_default();
+            let changes = fip_service.sync_address(&ip, &pool).await?;
+            println!("Applied {} NAT changes", changes);
+        }
+        FloatingIpsCommands::Disassociate { ip } => {
+            let ip: std::net::Ipv4Addr = ip.parse()?;
+            fip_service.disassociate(&ip)?;
+            println!("Disassociated {}", ip);
+            
+            let pool = fip_service.get_address(&ip).map(|a| a.pool.clone()).unwrap_or_default();
+            let changes = fip_service.sync_address(&ip, &pool).await?;
+            println!("Applied {} NAT changes", changes);
+        }
+        FloatingIpsCommands::Release { ip } => {
+            let ip: std::net::Ipv4Addr = ip.parse()?;
+            let address = fip_service.release(&ip)?;
+            println!("Released {}", ip);
+            
+            if address.is_associated() {
+                let changes = fip_service.sync_address(&ip, &address.pool).await?;
+                println!("Applied {} NAT changes", changes);
+            }
+        }
+        FloatingIpsCommands::Pools => {
+            println!("NAME		CIDR			ROUTER		INTERFACE	ALLOCATED");
+            let addresses = fip_service.list_addresses();
+            for pool in fip_service.list_pools() {
+                let allocated = addresses.iter().filter(|a| a.pool == pool.name).count();
+                println!("{}		{}		{}		{}		{}", 
+                        pool.name, pool.cidr, pool.router, pool.interface, allocated);
+            }
+        }
+        FloatingIpsCommands::CreatePool { name, cidr, router, interface, exclude } => {
+            let exclude = exclude.iter()
+                .map(|ip| ip.trim().parse())
+                .collect::<Result<Vec<_>, _>>()?;
+            
+            fip_service.create_pool(FloatingIpPool {
+                name: name.clone(),
+                cidr: cidr.clone(),
+                router: router.clone(),
+                interface: interface.clone(),
+                exclude,
+            })?;
+            println!("Created floating IP pool '{}' ({})", name, cidr);
+        }
+        FloatingIpsCommands::DeletePool { name } => {
+            fip_service.delete_pool(name)?;
+            println!("Deleted floating IP pool '{}'", name);
+        }
+        FloatingIpsCommands::Sync => {
+            print_sync_results(fip_service.sync_all().await);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_instances_command(action: &InstancesCommands) -> AppResult<()> {
+    use bbctl::config::settings::Settings;
+    use bbctl::models::container::{ContainerPort, ContainerSpec, ContainerVolume, CONTAINER_IMAGE_TAG};
+    use bbctl::models::instance::InstanceSize;
+    use bbctl::models::provider::ProviderType;
+    use bbctl::models::instance::{NODE_TAG, PROVIDER_TAG};
+    use bbctl::models::template::InstanceTemplate;
+    use bbctl::services::instance::InstanceService;
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::template::{check_template, TemplateService};
+    use bbctl::state::{FileStateStore, StateStore};
+    use std::collections::BTreeMap;
+    
+    let provider_service = ProviderService::new()?;
+    
+    match action {
+        InstancesCommands::Create { name, template, provider, region, cpu, memory, disk, container } => {
+            let ContainerArgs { image, network, network_prefix, address, env, volumes, ports } = container.as_ref();
+            let settings = Settings::load()?;
+            let base = match template {
+                Some(template) => settings.get_template(template)?.clone(),
+                None => InstanceTemplate::default(),
+            };
+            let overrides = InstanceTemplate {
+                provider: provider.clone(),
+                region: region.clone(),
+                cpu: *cpu,
+                memory_gb: *memory,
+                disk_gb: *disk,
+                image: image.clone(),
+                ..Default::default()
+            };
+            let spec = base.overridden_by(&overrides);
+            
+            let provider = spec.provider.clone().or_else(|| settings.default_provider.clone())
+                .ok_or("--provider is required when no default provider is configured")?;
+            let region = spec.region.clone().or_else(|| settings.default_region.clone())
+                .ok_or("--region is required when no default region is configured")?;
+            let provider_type = provider_service.get_providers().get(&provider)
+                .map(|p| p.provider_type)
+                .ok_or_else(|| format!("Provider '{}' not found", provider))?;
+            let mut spec = InstanceTemplate { provider: Some(provider.clone()), region: Some(region.clone()), ..spec };
+            
+            // Proxmox VMs get their address on --network from its IPAM, like a template network
+            if let (ProviderType::Proxmox, Some(network)) = (provider_type, network) {
+                if !spec.networks.contains(network) {
+                    spec.networks.insert(0, network.clone());
+                }
+            }
+            let problems = check_template(&spec, &provider_service, &FileStateStore::open()?.load()?);
+            if !problems.is_empty() {
+                return Err(problems.join("\n").into());
+            }
+            let size = InstanceSize {
+                cpu: spec.cpu.unwrap_or(settings.default_cpu),
+                memory_gb: spec.memory_gb.unwrap_or(settings.default_memory_gb.into()),
+                disk_gb: spec.disk_gb.unwrap_or(settings.default_disk_gb.into()),
+            };
+            
+            println!("Creating instance '{}' with provider '{}' in region '{}'", 
+                    name, provider, region);
+            println!("Resources: CPU: {}, Memory: {} GB, Disk: {} GB", 
+                    size.cpu, size.memory_gb, size.disk_gb);
+            
+            let mut instance_service = InstanceService::new(provider_service.clone())?;
+            let id = match provider_type {
+                ProviderType::VyOS => {
+                    let image = spec.image.as_deref().ok_or("--image is required for VyOS providers")?;
+                    let mut spec = ContainerSpec::new(image);
+                    spec.network = network.clone();
+                    spec.network_prefix = network_prefix.clone();
+                    spec.address = *address;
+                    for var in env {
+                        spec.add_environment(var).map_err(|e| format!("Invalid --env '{}': {}", var, e))?;
+                    }
+                    for volume in volumes {
+                        spec.volumes.push(ContainerVolume::parse(volume)
+                            .map_err(|e| format!("Invalid --volume '{}': {}", volume, e))?);
+                    }
+                    for port in ports {
+                        spec.ports.push(ContainerPort::parse(port)
+                            .map_err(|e| format!("Invalid --port '{}': {}", port, e))?);
+                    }
+                    
+                    instance_service.create_vyos_instance(name, &provider, &region, size, spec).await?
+                }
+                ProviderType::Proxmox => match &spec.image {
+                    Some(vm_template) => instance_service.clone_proxmox_instance(name, &provider, &region, size, vm_template).await?,
+                    None => instance_service.create_proxmox_instance(name, &provider, &region, size).await?,
+                },
+            };
+            
+            if template.is_some() || !spec.networks.is_empty() || !spec.volumes.is_empty() {
+                TemplateService::new(provider_service)?
+                    .provision(&id, template.as_deref(), &spec).await
+                    .map_err(|e| format!("Created instance {} but not its template resources: {}", id, e))?;
+                for network in &spec.networks {
+                    println!("Connected to network '{}'", network);
+                }
+                for volume in &spec.volumes {
+                    println!("Attached volume '{}' ({} GB)", InstanceTemplate::volume_name(name, volume), volume.size_gb);
+                }
+            }
+            
+            println!("✅ Created instance {}", id);
+        }
+        InstancesCommands::List => {
+            let instance_service = InstanceService::new(provider_service)?;
+            let mut instances = instance_service.list_instances();
+            instances.sort_by(|a, b| a.name.cmp(&b.name));
+            
+            println!("{:<36} {:<20} {:<12} {:<12} PROVIDER", "ID", "NAME", "STATUS", "REGION");
+            for instance in instances {
+                println!("{:<36} {:<20} {:<12} {:<12} {}", 
+                        instance.id, instance.name, instance.status.to_string(), instance.region,
+                        instance.tags.get(PROVIDER_TAG).cloned().unwrap_or_else(|| instance.provider.to_string()));
+            }
+        }
+        InstancesCommands::Delete { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let mut instance_service = InstanceService::new(provider_service)?;
+            instance_service.delete_instance(&id).await?;
+            println!("Deleted instance {}", id);
+        }
+        InstancesCommands::Start { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let mut instance_service = InstanceService::new(provider_service)?;
+            instance_service.start_instance(&id).await?;
+            println!("Started instance {}", id);
+        }
+        InstancesCommands::Stop { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let mut instance_service = InstanceService::new(provider_service)?;
+            instance_service.stop_instance(&id).await?;
+            println!("Stopped instance {}", id);
+        }
+        InstancesCommands::Show { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let instance_service = InstanceService::new(provider_service)?;
+            let instance = instance_service.get_instance(&id)
+                .ok_or_else(|| format!("Instance not found: {}", id))?;
+            
+            println!("ID: {}", instance.id);
+            println!("Name: {}", instance.name);
+            println!("Status: {}", instance.status);
+            println!("Provider: {}", instance.tags.get(PROVIDER_TAG).cloned().unwrap_or_else(|| instance.provider.to_string()));
+            println!("Provider ID: {}", if instance.provider_id.is_empty() { "-" } else { instance.provider_id.as_str() });
+            println!("Region: {}", instance.region);
+            println!("CPU: {}", instance.size.cpu);
+            println!("Memory: {} GB", instance.size.memory_gb);
+            println!("Disk: {} GB", instance.size.disk_gb);
+            println!("Created: {}", instance.created_at.format("%Y-%m-%d %H:%M:%S UTC"));
+            
+            if !instance.networks.is_empty() {
+                println!("\nNetworks:");
+                for network in &instance.networks {
+                    println!("  {}\t{}\t{}", network.network_id,
+                            network.ip.as_deref().unwrap_or("-"), network.interface.as_deref().unwrap_or("-"));
+                }
+            }
+            
+            if !instance.tags.is_empty() {
+                let tags: BTreeMap<_, _> = instance.tags.iter().collect();
+                println!("\nTags:");
+                for (key, value) in tags {
+                    println!("  {}={}", key, value);
+                }
+            }
+        }
+        InstancesCommands::Tag { id, tags } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let mut parsed = BTreeMap::new();
+            for tag in tags {
+                let (key, value) = tag.split_once('=')
+                    .filter(|(key, _)| !key.trim().is_empty())
+                    .ok_or_else(|| format!("Invalid tag '{}', expected key=value", tag))?;
+                // bbctl records these when it creates the instance
+                if [PROVIDER_TAG, NODE_TAG, CONTAINER_IMAGE_TAG].contains(&key.trim()) {
+                    return Err(format!("The '{}' tag is managed by bbctl", key.trim()).into());
+                }
+                parsed.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            
+            let mut instance_service = InstanceService::new(provider_service)?;
+            instance_service.set_tags(&id, &parsed).await?;
+            println!("Tagged instance {}", id);
+        }
+    }
+    
+    Ok(())
+}
+
+fn handle_templates_command(action: &TemplatesCommands) -> AppResult<()> {
+    use bbctl::config::settings::Settings;
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::template::check_template;
+    use bbctl::state::{FileStateStore, StateStore};
+    
+    let settings = Settings::load()?;
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    
+    match action {
+        TemplatesCommands::List => {
+            if settings.templates.is_empty() {
+                println!("No templates defined in settings.toml");
+                return Ok(());
+            }
+            
+            println!("{:<20} {:<5} {:<8} {:<8} {:<24} {:<9} VOLUMES", "NAME", "CPU", "MEMORY", "DISK", "IMAGE", "NETWORKS");
+            for (name, template) in &settings.templates {
+                println!("{:<20} {:<5} {:<8} {:<8} {:<24} {:<9} {}", 
+                        name, or_dash(template.cpu.map(|c| c.to_string())), 
+                        or_dash(template.memory_gb.map(|m| format!("{} GB", m))), 
+                        or_dash(template.disk_gb.map(|d| format!("{} GB", d))), 
+                        or_dash(template.image.clone()), template.networks.len(), template.volumes.len());
+            }
+        }
+        TemplatesCommands::Show { name } => {
+            let template = settings.get_template(name)?;
+            
+            println!("Name: {}", name);
+            println!("Description: {}", or_dash(template.description.clone()));
+            println!("Provider: {}", or_dash(template.provider.clone()));
+            println!("Region: {}", or_dash(template.region.clone()));
+            println!("CPU: {}", template.cpu.unwrap_or(settings.default_cpu));
+            println!("Memory: {} GB", template.memory_gb.unwrap_or(settings.default_memory_gb.into()));
+            println!("Disk: {} GB", template.disk_gb.unwrap_or(settings.default_disk_gb.into()));
+            println!("Image: {}", or_dash(template.image.clone()));
+            
+            if !template.networks.is_empty() {
+                println!("\nNetworks:");
+                for network in &template.networks {
+                    println!("  {}", network);
+                }
+            }
+            if !template.volumes.is_empty() {
+                println!("\nVolumes:");
+                for volume in &template.volumes {
+                    println!("  {}\t{} GB\t{}\t{}", volume.name, volume.size_gb,
+                            volume.volume_type.as_deref().unwrap_or("standard"), volume.mount_path.as_deref().unwrap_or("-"));
+                }
+            }
+        }
+        TemplatesCommands::Validate { name } => {
+            let templates: Vec<_> = match name {
+                Some(name) => vec![(name, settings.get_template(name)?)],
+                None => settings.templates.iter().collect(),
+            };
+            if templates.is_empty() {
+                println!("No templates defined in settings.toml");
+                return Ok(());
+            }
+            
+            let provider_service = ProviderService::new()?;
+            let state = FileStateStore::open()?.load()?;
+            let mut invalid = 0;
+            for (name, template) in templates {
+                let problems = check_template(template, &provider_service, &state);
+                if problems.is_empty() {
+                    println!("✅ {}", name);
+                } else {
+                    invalid += 1;
+                    println!("❌ {}", name);
+                    for problem in problems {
+                        println!("    {}", problem);
+                    }
+                }
+            }
+            
+            if invalid > 0 {
+                return Err(format!("{} templates are invalid", invalid).into());
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+fn handle_regions_command(action: &RegionsCommands) -> AppResult<()> {
+    use bbctl::models::provider::{ProviderType, ResourceLimits};
+    use bbctl::services::provider::ProviderService;
+    
+    let mut provider_service = ProviderService::new()?;
+    
+    match action {
+        RegionsCommands::List => {
+            let mut regions: Vec<_> = provider_service.get_regions().values().collect();
+            regions.sort_by(|a, b| a.id.cmp(&b.id));
+            
+            println!("{:<12} {:<20} {:<10} {:<20} AVAILABLE", "ID", "NAME", "TYPE", "LOCATION");
+            for region in regions {
+                println!("{:<12} {:<20} {:<10} {:<20} {}", 
+                        region.id, region.name, region.provider.to_string(), region.location,
+                        if region.available { "yes" } else { "no" });
+            }
+        }
+        RegionsCommands::Add { id, provider_type, name, location, unavailable, limits } => {
+            let provider_type = match provider_type.to_lowercase().as_str() {
+                "vyos" => ProviderType::VyOS,
+                "proxmox" => ProviderType::Proxmox,
+                other => return Err(format!("Unknown provider type '{}', expected vyos or proxmox", other).into()),
+            };
+            let limits = ResourceLimits {
+                max_instances: limits.max_instances,
+                max_volumes: limits.max_volumes,
+                max_networks: limits.max_networks,
+                max_cpu_per_instance: limits.max_cpu,
+                max_memory_per_instance: limits.max_memory,
+                max_disk_per_instance: limits.max_disk,
+            };
+            
+            provider_service.add_region(
+                id, name.as_deref().unwrap_or(id), provider_type, location, !unavailable, Some(limits),
+            )?;
+            println!("Added region '{}'", id);
+        }
+        RegionsCommands::Remove { id } => {
+            provider_service.remove_region(id)?;
+            println!("Removed region '{}'", id);
+        }
+        RegionsCommands::Show { id } => {
+            let region = provider_service.get_regions().get(id)
+                .ok_or_else(|| format!("Region not found: {}", id))?;
+            let limit = |value: Option<String>| value.unwrap_or_else(|| "unlimited".to_string());
+            
+            println!("ID: {}", region.id);
+            println!("Name: {}", region.name);
+            println!("Type: {}", region.provider);
+            println!("Location: {}", if region.location.is_empty() { "-" } else { region.location.as_str() });
+            println!("Available: {}", if region.available { "yes" } else { "no" });
+            println!("\nLimits:");
+            println!("  Instances: {}", limit(region.limits.max_instances.map(|v| v.to_string())));
+            println!("  Volumes: {}", limit(region.limits.max_volumes.map(|v| v.to_string())));
+            println!("  Networks: {}", limit(region.limits.max_networks.map(|v| v.to_string())));
+            println!("  CPU per instance: {}", limit(region.limits.max_cpu_per_instance.map(|v| v.to_string())));
+            println!("  Memory per instance: {}", limit(region.limits.max_memory_per_instance.map(|v| format!("{} GB", v))));
+            println!("  Disk per instance: {}", limit(region.limits.max_disk_per_instance.map(|v| format!("{} GB", v))));
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_volumes_command(action: &VolumesCommands) -> AppResult<()> {
+    use bbctl::config::settings::Settings;
+    use bbctl::models::instance::PROVIDER_TAG;
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::volume::VolumeService;
+    
+    let mut volume_service = VolumeService::new(ProviderService::new()?)?;
+    
+    match action {
+        VolumesCommands::List => {
+            let mut volumes = volume_service.list_volumes();
+            volumes.sort_by(|a, b| a.name.cmp(&b.name));
+            
+            println!("{:<36} {:<20} {:<8} {:<12} {:<10} ATTACHED TO", "ID", "NAME", "SIZE", "REGION", "STATUS");
+            for volume in volumes {
+                let attached = volume_service.attached_instance(volume)
+                    .map(|i| i.name.clone())
+                    .or_else(|| volume.attached_to.map(|id| id.to_string()))
+                    .unwrap_or_else(|| "-".to_string());
+                println!("{:<36} {:<20} {:<8} {:<12} {:<10} {}", 
+                        volume.id, volume.name, format!("{} GB", volume.size_gb), volume.region,
+                        volume.status.to_string(), attached);
+            }
+        }
+        VolumesCommands::Create { name, size, region, provider, storage } => {
+            let settings = Settings::load()?;
+            let provider = provider.clone().or(settings.default_provider)
+                .ok_or("--provider is required when no default provider is configured")?;
+            let region = region.clone().or(settings.default_region)
+                .unwrap_or_else(|| "default".to_string());
+            
+            let id = volume_service.create_volume(name, &provider, &region, *size, storage.as_deref()).await?;
+            println!("Created volume '{}' ({}) with {} GB on '{}'", name, id, size, provider);
+        }
+        VolumesCommands::Delete { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            volume_service.delete_volume(&id).await?;
+            println!("Deleted volume {}", id);
+        }
+        VolumesCommands::Attach { id, instance, path } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let instance_id = uuid::Uuid::parse_str(instance)?;
+            let device = volume_service.attach_volume(&id, &instance_id, path.as_deref()).await?;
+            println!("Attached volume {} to instance {} as {}", id, instance_id, device);
+        }
+        VolumesCommands::Detach { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            volume_service.detach_volume(&id).await?;
+            println!("Detached volume {}", id);
+        }
+        VolumesCommands::Show { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let volume = volume_service.get_volume(&id)
+                .ok_or_else(|| format!("Volume not found: {}", id))?;
+            
+            println!("ID: {}", volume.id);
+            println!("Name: {}", volume.name);
+            println!("Status: {}", volume.status);
+            println!("Size: {} GB", volume.size_gb);
+            println!("Type: {}", volume.volume_type);
+            println!("Provider: {}", volume.tags.get(PROVIDER_TAG).cloned().unwrap_or_else(|| volume.provider.to_string()));
+            println!("Provider ID: {}", if volume.provider_id.is_empty() { "-" } else { volume.provider_id.as_str() });
+            println!("Region: {}", volume.region);
+            match (volume.attached_to, volume_service.attached_instance(volume)) {
+                (Some(_), Some(instance)) => println!("Attached to: {} ({}) as {}", 
+                        instance.id, instance.name, volume.device.as_deref().unwrap_or("-")),
+                (Some(id), None) => println!("Attached to: {} (instance no longer exists)", id),
+                (None, _) => println!("Attached to: -"),
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_networks_command(action: &NetworksCommands) -> AppResult<()> {
+    use bbctl::models::network::NetworkType;
+    use bbctl::services::network::{HaGateway, NetworkService};
+    use bbctl::services::provider::ProviderService;
+    
+    let mut network_service = NetworkService::new(ProviderService::new()?)?;
+    
+    match action {
+        NetworksCommands::Create { name, cidr, provider, region, network_type, ha_routers, interface, bridge, tenant, vrid, no_preempt } => {
+            let network_type = NetworkType::from(network_type.as_str());
+            
+            let id = if ha_routers.is_empty() {
+                let provider = provider.as_deref().ok_or("--provider is required")?;
+                let id = network_service.create_network(name, provider, region, cidr, network_type)?;
+                if let Some(interface) = interface {
+                    network_service.set_router_interface(&id, interface)?;
+                }
+                id
+            } else {
+                let ha = HaGateway {
+                    routers: ha_routers.clone(),
+                    interface: interface.clone().ok_or("--interface is required with --ha-routers")?,
+                    vrid: *vrid,
+                    preempt: !no_preempt,
+                };
+                network_service.create_ha_network(name, region, cidr, network_type, &ha).await?
+            };
+            
+            if let Some(tenant) = tenant {
+                network_service.set_tenant(&id, tenant)?;
+            }
+            if let Some(bridge) = bridge {
+                network_service.set_bridge(&id, bridge)?;
+            }
+            
+            let network = network_service.get_network(&id).ok_or("Network disappeared after creation")?;
+            println!("Created network '{}' ({})", name, id);
+            println!("CIDR: {}", network.cidr);
+            if let Some(gateway) = network.gateway {
+                println!("Gateway: {}", gateway);
+            }
+            if let Some(vrid) = network.vrrp_vrid() {
+                println!("VRRP: VRID {} on {}", vrid, network.ha_routers().join(", "));
+            }
+        }
+        NetworksCommands::Show { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let network = network_service.get_network(&id)
+                .ok_or_else(|| format!("Network not found: {}", id))?;
+            
+            println!("ID: {}", network.id);
+            println!("Name: {}", network.name);
+            println!("Status: {}", network.status);
+            println!("Type: {}", network.network_type);
+            println!("CIDR: {}", network.cidr);
+            println!("Gateway: {}", network.gateway.map(|g| g.to_string()).unwrap_or_else(|| "-".to_string()));
+            println!("Instances: {}", network.instances.len());
+            println!("Tenant: {}", network.tenant());
+            println!("Bridge: {}", network.bridge());
+            
+            if let Some(limits) = network_service.tenant_limits(network.tenant())? {
+                println!("\nTenant bandwidth limits{}:", 
+                        network.router_interface().map(|i| format!(" (on {})", i)).unwrap_or_default());
+                println!("  Egress: {}", limits.egress_bandwidth.as_deref().unwrap_or("unlimited"));
+                println!("  Ingress: {}", limits.ingress_bandwidth.as_deref().unwrap_or("unlimited"));
+            }
+            
+            if !network.ha_routers().is_empty() {
+                println!("\nVRRP (VRID {}):", network.vrrp_vrid().map(|v| v.to_string()).unwrap_or_else(|| "-".to_string()));
+                for (router, result) in network_service.vrrp_status(&id).await? {
+                    match result {
+                        Ok(Some(group)) => println!("  {}\t{}\tpriority {}{}", 
+                                router, group.state, group.priority,
+                                if group.is_master() { "\t(master)" } else { "" }),
+                        Ok(None) => println!("  {}\tgroup not configured", router),
+                        Err(e) => println!("  {}\tunreachable: {}", router, e),
+                    }
+                }
+            }
+        }
+        NetworksCommands::List => {
+            let mut networks = network_service.list_networks();
+            networks.sort_by(|a, b| a.name.cmp(&b.name));
+            
+            println!("{:<36} {:<20} {:<18} {:<10} INSTANCES", "ID", "NAME", "CIDR", "TENANT");
+            for network in networks {
+                println!("{:<36} {:<20} {:<18} {:<10} {}", 
+                        network.id, network.name, network.cidr, network.tenant(), network.instances.len());
+            }
+        }
+        NetworksCommands::Delete { id } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            network_service.delete_network(&id).await?;
+            println!("Deleted network {}", id);
+        }
+        NetworksCommands::Connect { id, instance, ip } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let instance_id = uuid::Uuid::parse_str(instance)?;
+            let ip = network_service.connect_instance(&id, &instance_id, *ip).await?;
+            println!("Connected instance {} to network {} with {}", instance_id, id, ip);
+        }
+        NetworksCommands::Disconnect { id, instance } => {
+            let id = uuid::Uuid::parse_str(id)?;
+            let instance_id = uuid::Uuid::parse_str(instance)?;
+            network_service.disconnect_instance(&id, &instance_id).await?;
+            println!("Disconnected instance {} from network {}", instance_id, id);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_routers_command(action: &RoutersCommands) -> AppResult<()> {
+    use bbctl::api::vyos_op::ConntrackStats;
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::router::RouterService;
+    use bbctl::services::site::{adjacency_health, load_site};
+    
+    let router_service = RouterService::new(ProviderService::new()?);
+    
+    match action {
+        RoutersCommands::Status { provider, vrf, site, json } => {
+            let mut status = router_service.status(provider, vrf.as_deref()).await?;
+            
+            if let Some(file) = site {
+                let site = load_site(std::path::Path::new(file))?;
+                if site.get_router(provider).is_none() {
+                    return Err(format!("Router '{}' is not part of site '{}'", provider, site.name).into());
+                }
+                status.ospf_adjacencies = adjacency_health(&site, provider, &status.ospf_neighbors);
+            }
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+                return Ok(());
+            }
+            
+            println!("Router: {}", status.router);
+            
+            println!("\nInterfaces:");
+            println!("  NAME\t\tSTATE\tADDRESSES\t\tDESCRIPTION");
+            for interface in &status.interfaces {
+                println!("  {}\t\t{}/{}\t{}\t\t{}", 
+                        interface.name, interface.admin_state, interface.link_state,
+                        if interface.addresses.is_empty() { "-".to_string() } else { interface.addresses.join(",") },
+                        interface.description.as_deref().unwrap_or(""));
+            }
+            
+            println!("\nRoutes{}:", status.vrf.as_ref().map(|v| format!(" (vrf {})", v)).unwrap_or_default());
+            println!("  PREFIX\t\t\tPROTOCOL\tNEXT HOP\t\tINTERFACE");
+            for route in status.routes.iter().filter(|r| r.selected) {
+                println!("  {}\t\t{}\t{}\t\t{}", 
+                        route.prefix, route.protocol_name(), route.next_hops.join(","), route.interfaces.join(","));
+            }
+            
+            println!("\nBGP neighbors:");
+            println!("  NEIGHBOR\t\tAS\tSTATE\t\tUP/DOWN\tPREFIXES");
+            for neighbor in &status.bgp_neighbors {
+                println!("  {}\t\t{}\t{}\t{}\t{}", 
+                        neighbor.neighbor, neighbor.remote_as, neighbor.state, neighbor.up_down,
+                        neighbor.prefixes_received.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string()));
+            }
+            
+            println!("\nOSPF neighbors:");
+            println!("  NEIGHBOR ID\t\tSTATE\t\tADDRESS\t\tINTERFACE");
+            for neighbor in &status.ospf_neighbors {
+                println!("  {}\t\t{}\t\t{}\t{}", 
+                        neighbor.neighbor_id, neighbor.state, neighbor.address, neighbor.interface);
+            }
+            
+            if site.is_some() {
+                println!("\nOSPF adjacencies:");
+                for adjacency in &status.ospf_adjacencies {
+                    match &adjacency.state {
+                        Some(state) if adjacency.is_healthy() => 
+                            println!("  ✅ {} via {}: {}", adjacency.neighbor_id, adjacency.interface, state),
+                        Some(state) => 
+                            println!("  ⚠️  {} via {}: {}", adjacency.neighbor_id, adjacency.interface, state),
+                        None => 
+                            println!("  ❌ {} via {}: no neighbor", adjacency.neighbor_id, adjacency.interface),
+                    }
+                }
+            }
+            
+            println!("\nARP:");
+            println!("  ADDRESS\t\tMAC\t\t\tINTERFACE\tSTATE");
+            for entry in &status.arp {
+                println!("  {}\t\t{}\t{}\t\t{}", 
+                        entry.address, entry.mac.as_deref().unwrap_or("(incomplete)"), entry.interface, entry.state);
+            }
+            
+            let conntrack = ConntrackStats::total(&status.conntrack);
+            println!("\nConntrack: {} inserted, {} invalid, {} insert failures, {} dropped, {} early drops", 
+                    conntrack.insert, conntrack.invalid, conntrack.insert_failed, conntrack.drop, conntrack.early_drop);
+            
+            if !status.errors.is_empty() {
+                println!("\nUnavailable:");
+                for error in &status.errors {
+                    println!("  {}", error);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_collector_command(action: &CollectorCommands) -> AppResult<()> {
+    use bbctl::services::network::NetworkService;
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::usage::{Collector, TenantMap, UsageService, UsageStore};
+    
+    match action {
+        CollectorCommands::Run { listen, flush_interval, cidrs } => {
+            let listen: std::net::SocketAddr = listen.parse()
+                .map_err(|_| format!("Invalid listen address '{}'", listen))?;
+            
+            // Tenant networks known to bbctl, plus any given on the command line
+            let network_service = NetworkService::new(ProviderService::new()?)?;
+            let mut tenants = TenantMap::from_networks(&network_service.list_networks());
+            for cidr in cidrs {
+                tenants.add_spec(cidr)?;
+            }
+            if tenants.is_empty() {
+                return Err("No tenant networks to account, pass them with --cidr <tenant>=<cidr>".into());
+            }
+            
+            let store = UsageStore::open()?;
+            let mut collector = Collector::bind(listen, tenants.clone(), store.clone()).await?;
+            println!("Collecting flows on {} for {} tenant networks, writing to {}", 
+                    collector.local_addr()?, tenants.len(), store.dir().display());
+            println!("Press Ctrl+C to stop");
+            
+            collector.run(std::time::Duration::from_secs((*flush_interval).max(1))).await?;
+        }
+        CollectorCommands::Configure { router, address, port, interfaces } => {
+            let address: std::net::Ipv4Addr = address.parse()
+                .map_err(|_| format!("Invalid collector address '{}'", address))?;
+            
+            let usage_service = UsageService::new(ProviderService::new()?);
+            let changes = usage_service.configure_router(router, address, *port, interfaces).await?;
+            println!("✅ {}: exporting flows of {} to {}:{} ({} changes)", 
+                    router, interfaces.join(", "), address, port, changes);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_usage_command(action: &UsageCommands) -> AppResult<()> {
+    use bbctl::models::usage::{format_bytes, parse_duration};
+    use bbctl::services::usage::UsageStore;
+    
+    match action {
+        UsageCommands::Traffic { tenant, since, buckets, json } => {
+            let duration = parse_duration(since)
+                .map_err(|e| format!("Invalid duration '{}': {}", since, e))?;
+            let since = chrono::Utc::now() - duration;
+            
+            let store = UsageStore::open()?;
+            let samples = if *buckets {
+                store.query(tenant.as_deref(), since)?
+            } else {
+                store.totals(tenant.as_deref(), since)?
+            };
+            
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&samples)?);
+                return Ok(());
+            }
+            
+            if samples.is_empty() {
+                println!("No traffic recorded since {}", since.format("%Y-%m-%d %H:%M"));
+                return Ok(());
+            }
+            
+            if *buckets {
+                println!("{:<17} {:<16} {:>12} {:>12} {:>8}", "BUCKET", "TENANT", "IN", "OUT", "FLOWS");
+            } else {
+                println!("{:<16} {:>12} {:>12} {:>14} {:>14} {:>8}", 
+                        "TENANT", "IN", "OUT", "PACKETS IN", "PACKETS OUT", "FLOWS");
+            }
+            for sample in &samples {
+                if *buckets {
+                    println!("{:<17} {:<16} {:>12} {:>12} {:>8}", 
+                            sample.bucket.format("%Y-%m-%d %H:%M"), sample.tenant, 
+                            format_bytes(sample.bytes_in), format_bytes(sample.bytes_out), sample.flows);
+                } else {
+                    println!("{:<16} {:>12} {:>12} {:>14} {:>14} {:>8}", 
+                            sample.tenant, format_bytes(sample.bytes_in), format_bytes(sample.bytes_out), 
+                            sample.packets_in, sample.packets_out, sample.flows);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Print a log entry on one line
+fn print_log_entry(entry: &bbctl::models::logs::LogEntry) {
+    println!("{} {:<12} {:<7} {}: {}", 
+            entry.timestamp.unwrap_or(entry.received_at).format("%Y-%m-%d %H:%M:%S"),
+            entry.sender(), entry.severity, entry.app_name.as_deref().unwrap_or("-"), entry.message);
+}
+
+async fn handle_logs_command(action: &LogsCommands) -> AppResult<()> {
+    use bbctl::models::logs::Severity;
+    use bbctl::services::logs::{LogFilter, LogService, LogStore, SyslogReceiver};
+    use bbctl::services::provider::ProviderService;
+    
+    match action {
+        LogsCommands::Collect { listen, protocol, quiet } => {
+            let listen: std::net::SocketAddr = listen.parse()
+                .map_err(|_| format!("Invalid listen address '{}'", listen))?;
+            let (udp, tcp) = match protocol.as_str() {
+                "udp" => (Some(listen), None),
+                "tcp" => (None, Some(listen)),
+                "both" => (Some(listen), Some(listen)),
+                _ => return Err(format!("Protocol must be udp, tcp or both, got '{}'", protocol).into()),
+            };
+            
+            let log_service = LogService::new(ProviderService::new()?);
+            let store = LogStore::open()?;
+            let mut receiver = SyslogReceiver::bind(udp, tcp, log_service.resolver(), store.clone()).await?;
+            
+            let listeners: Vec<String> = [("udp", receiver.udp_addr()), ("tcp", receiver.tcp_addr())].iter()
+                .filter_map(|(protocol, address)| address.map(|a| format!("{}/{}", a, protocol)))
+                .collect();
+            println!("Collecting logs on {}, writing to {}", listeners.join(", "), store.dir().display());
+            println!("Press Ctrl+C to stop");
+            
+            receiver.run(|entry| if !*quiet { print_log_entry(entry) }).await?;
+        }
+        LogsCommands::Configure { router, address, port, protocol, level } => {
+            let address: std::net::IpAddr = address.parse()
+                .map_err(|_| format!("Invalid collector address '{}'", address))?;
+            let level = Severity::parse(level)?;
+            
+            let log_service = LogService::new(ProviderService::new()?);
+            let changes = log_service.configure_router(router, address, *port, protocol, level).await?;
+            println!("✅ {}: sending {} and above to {}:{}/{} ({} changes)", 
+                    router, level, address, port, protocol, changes);
+        }
+        LogsCommands::Tail { provider, grep, severity, lines, follow } => {
+            let filter = LogFilter {
+                provider: provider.clone(),
+                grep: grep.clone(),
+                severity: severity.as_deref().map(Severity::parse).transpose()?,
+            };
+            
+            let store = LogStore::open()?;
+            let mut cursor = store.end_cursor()?;
+            for entry in store.tail(&filter, *lines)? {
+                print_log_entry(&entry);
+            }
+            
+            if !*follow {
+                return Ok(());
+            }
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                for entry in store.read_new(&mut cursor, &filter)? {
+                    print_log_entry(&entry);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_load_balancers_command(action: &LoadBalancersCommands) -> AppResult<()> {
+    use bbctl::services::instance::InstanceService;
+    use bbctl::services::load_balancer::{backend_servers, load_load_balancer, LoadBalancerService};
+    use bbctl::services::provider::ProviderService;
+    
+    let mut lb_service = LoadBalancerService::new(ProviderService::new()?)?;
+    let instance_service = InstanceService::new(ProviderService::new()?)?;
+    let instances: Vec<_> = instance_service.list_instances().into_iter().cloned().collect();
+    
+    match action {
+        LoadBalancersCommands::List => {
+            println!("NAME\t\tROUTER\t\tFRONTENDS\tBACKENDS");
+            for lb in lb_service.list_load_balancers() {
+                let frontends: Vec<String> = lb.frontends.iter()
+                    .map(|f| format!("{}:{}", f.name, f.port))
+                    .collect();
+                let backends: Vec<&str> = lb.backends.iter().map(|b| b.name.as_str()).collect();
+                println!("{}\t\t{}\t\t{}\t{}", lb.name, lb.router, frontends.join(","), backends.join(","));
+            }
+        }
+        LoadBalancersCommands::Show { name } => {
+            let lb = lb_service.get_load_balancer(name)
+                .ok_or_else(|| format!("Load balancer '{}' not found", name))?;
+            
+            println!("Load balancer: {}", lb.name);
+            println!("Router: {}", lb.router);
+            
+            println!("\nFrontends:");
+            for frontend in &lb.frontends {
+                println!("  {} ({}) {}:{} -> {}{}", 
+                        frontend.name, frontend.mode, 
+                        frontend.listen_address.as_deref().unwrap_or("*"), frontend.port, frontend.backend,
+                        frontend.certificate.as_ref().map(|c| format!(" [tls: {}]", c)).unwrap_or_default());
+            }
+            
+            println!("\nBackends:");
+            for backend in &lb.backends {
+                let mut selector: Vec<String> = backend.selector.iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect();
+                selector.sort();
+                println!("  {} ({}, {}) port {} selector {}", 
+                        backend.name, backend.mode, backend.balance, backend.port, selector.join(","));
+                
+                if let Some(check) = &backend.health_check {
+                    println!("    health check: {}{}", 
+                            check.path.as_deref().unwrap_or("tcp connect"),
+                            check.expect_status.map(|s| format!(" expect {}", s)).unwrap_or_default());
+                }
+                
+                let servers = backend_servers(backend, &instances);
+                if servers.is_empty() {
+                    println!("    (no members)");
+                }
+                for (server, address) in servers {
+                    println!("    {}\t{}:{}", server, address, backend.port);
+                }
+            }
+        }
+        LoadBalancersCommands::Apply { file } => {
+            let lb = load_load_balancer(std::path::Path::new(file))?;
+            let (name, router) = (lb.name.clone(), lb.router.clone());
+            let previous = lb_service.apply_load_balancer(lb)?;
+            println!("Stored load balancer '{}'", name);
+            
+            let mut routers = vec![router];
+            if let Some(previous) = previous {
+                if !routers.contains(&previous.router) {
+                    routers.push(previous.router);
+                }
+            }
+            for router in routers {
+                let result = lb_service.sync_router(&router, &instances).await;
+                print_sync_results(vec![(router, result)]);
+            }
+        }
+        LoadBalancersCommands::Delete { name } => {
+            let lb = lb_service.delete_load_balancer(name)?;
+            println!("Deleted load balancer '{}'", lb.name);
+            
+            let result = lb_service.sync_router(&lb.router, &instances).await;
+            print_sync_results(vec![(lb.router, result)]);
+        }
+        LoadBalancersCommands::Sync => {
+            print_sync_results(lb_service.sync_all(&instances).await);
+        }
+    }
+    
+    Ok(())
+}
+
+async fn handle_dns_command(action: &DnsCommands) -> AppResult<()> {
+    use bbctl::services::dns::DnsService;
+    use bbctl::services::network::NetworkService;
+    use bbctl::services::provider::ProviderService;
+    
+    let mut dns_service = DnsService::new(ProviderService::new()?)?;
+    let network_service = NetworkService::new(ProviderService::new()?)?;
+    
+    match action {
+        DnsCommands::Records { action: DnsRecordsCommands::List } => {
+            println!("NAME				TYPE	VALUE");
+            for record in dns_service.list_records() {
+                println!("{}		{}	{}", record.name, record.record_type, record.value);
+            }
+            return Ok(());
+        }
+        DnsCommands::Records { action: DnsRecordsCommands::Add { name, record_type, value } } => {
+            let record = dns_service.add_record(name, record_type.parse()?, value)?;
+            println!("Added {} record {} -> {}", record.record_type, record.name, record.value);
+        }
+        DnsCommands::Records { action: DnsRecordsCommands::Delete { name } } => {
+            let record = dns_service.delete_record(name)?;
+            println!("Deleted {} record {}", record.record_type, record.name);
+        }
+        DnsCommands::Sync => {}
+    }
+    
+    print_sync_results(dns_service.sync(&network_service.list_networks()).await);
+    Ok(())
+}
+
+/// Print a configuration diff as `-`/`+` prefixed VyOS commands
+fn print_config_diff(diff: &bbctl::api::vyos::ConfigDiff) {
+    if diff.is_empty() {
+        println!("No changes.");
+        return;
+    }
+    
+    for command in &diff.removed {
+        println!("- {}", command);
+    }
+    for command in &diff.added {
+        println!("+ {}", command);
+    }
+    println!("\n{} to remove, {} to add", diff.removed.len(), diff.added.len());
+}
+
+/// Ask the user for a yes/no confirmation on stdin
+fn confirm(prompt: &str) -> AppResult<bool> {
+    use std::io::Write;
+    
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Ask for a line on stdin, falling back to a default
+fn prompt_line(prompt: &str, default: &str) -> AppResult<String> {
+    use std::io::Write;
+    
+    print!("{} [{}]: ", prompt, default);
+    io::stdout().flush()?;
+    
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// Ask the user to pick one of several options by number
+fn prompt_choice(prompt: &str, options: &[String], default: usize) -> AppResult<usize> {
+    for (i, option) in options.iter().enumerate() {
+        println!("  {}) {}", i + 1, option);
+    }
+    loop {
+        let answer = prompt_line(prompt, &(default + 1).to_string())?;
+        match answer.parse::<usize>() {
+            Ok(choice) if (1..=options.len()).contains(&choice) => return Ok(choice - 1),
+            _ => println!("Pick a number between 1 and {}", options.len()),
+        }
+    }
+}
+
+/// Ask for a secret on the terminal without echoing it
+fn prompt_secret(prompt: &str) -> AppResult<String> {
+    use crossterm::event::{read, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+    use std::io::{IsTerminal, Write};
+    
+    if !io::stdin().is_terminal() {
+        return Err(format!("{} is required when not running interactively", prompt).into());
+    }
+    
+    print!("{}: ", prompt);
+    io::stdout().flush()?;
+    
+    enable_raw_mode()?;
+    let mut secret = String::new();
+    let result = loop {
+        match read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Err("Cancelled".into()),
+                KeyCode::Char(c) => secret.push(c),
+                KeyCode::Backspace => { secret.pop(); },
+                KeyCode::Esc => break Err("Cancelled".into()),
+                _ => {},
+            },
+            Ok(_) => {},
+            Err(e) => break Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    };
+    disable_raw_mode()?;
+    println!();
+    
+    result.map(|_| secret)
+}
+
+/// Read the status of every VyOS router for the TUI network view
+async fn load_router_status() -> Vec<(String, Result<bbctl::services::router::RouterStatus, String>)> {
+    use bbctl::services::provider::ProviderService;
+    use bbctl::services::router::RouterService;
+    
+    let provider_service = match ProviderService::new() {
+        Ok(provider_service) => provider_service,
+        Err(e) => return vec![("providers".to_string(), Err(e.to_string()))],
+    };
+    
+    RouterService::new(provider_service).status_all().await
+        .into_iter()
+        .map(|(router, result)| (router, result.map_err(|e| e.to_string())))
+        .collect()
+}
+
+/// Number of log messages kept in the TUI log pane
+const TUI_LOG_LINES: usize = 500;
+
+/// Load the latest log messages into the TUI, returning a cursor to follow new ones
+fn load_logs(app: &mut App) -> Option<bbctl::services::logs::LogCursor> {
+    use bbctl::services::logs::{LogFilter, LogStore};
+    
+    let store = LogStore::open().ok()?;
+    let cursor = store.end_cursor().ok()?;
+    app.logs = store.tail(&LogFilter::default(), TUI_LOG_LINES).unwrap_or_default();
+    app.selected_index = app.logs.len().saturating_sub(1);
+    Some(cursor)
+}
+
+/// Append messages stored since the last read, keeping the newest in view
+fn follow_logs(app: &mut App, cursor: &mut bbctl::services::logs::LogCursor) {
+    use bbctl::services::logs::{LogFilter, LogStore};
+    
+    let Ok(store) = LogStore::open() else { return };
+    let entries = match store.read_new(cursor, &LogFilter::default()) {
+        Ok(entries) if !entries.is_empty() => entries,
+        _ => return,
+    };
+    
+    let following = app.selected_index + 1 >= app.logs.len();
+    app.logs.extend(entries);
+    let excess = app.logs.len().saturating_sub(TUI_LOG_LINES);
+    app.logs.drain(..excess);
+    app.selected_index = if following {
+        app.logs.len() - 1
+    } else {
+        app.selected_index.saturating_sub(excess)
+    };
+}
+
+async fn run_tui() -> AppResult<()> {
+    // Create an application.
+    let mut app = App::new();
+
+    // Initialize the terminal user interface.
+    let backend = CrosstermBackend::new(io::stdout());
+    let terminal = Terminal::new(backend)?;
+    let events = EventHandler::new(250);
+    let mut tui = Tui::new(terminal, events);
+    tui.init()?;
+    let mut log_cursor = None;
+    let mut router_task = None;
+
+    // Start the main loop.
+    while app.running {
+        // Render the user interface.
+        tui.draw(&mut app)?;
+        // Handle events.
+        match tui.events.next().await? {
+            Event::Tick => app.tick(),
+            Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
+            Event::Mouse(_) => {}
+            Event::Resize(_, _) => {}
+        }
+        
+        // Routers can take seconds to answer, so read them off the event loop
+        if app.refresh_routers {
+            app.refresh_routers = false;
+            if router_task.is_none() {
+                app.loading_routers = true;
+                router_task = Some(tokio::spawn(load_router_status()));
+            }
+        }
+        if let Some(task) = router_task.take_if(|task| task.is_finished()) {
+            app.routers = task.await
+                .unwrap_or_else(|e| vec![("routers".to_string(), Err(e.to_string()))]);
+            app.loading_routers = false;
+        }
+        
+        if app.refresh_logs {
+            app.refresh_logs = false;
+            log_cursor = load_logs(&mut app);
+        } else if let (AppMode::Logs, Some(cursor)) = (app.mode, log_cursor.as_mut()) {
+            follow_logs(&mut app, cursor);
+        }
+    }
+
+    // Exit the user interface.
+    tui.exit()?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> AppResult<()> {
+    // Setup logging
+    env_logger::init();
+    
+    // Initialize configuration
+    if let Err(e) = bbctl::config::init_config() {
+        eprintln!("Warning: Failed to initialize configuration: {}", e);
+        eprintln!("Some functionality may be limited.");
+    }
+    
+    // Parse command line arguments
+    let cli = Cli::parse();
+    
+    // If we have command-line arguments, handle them
+    if env::args().len() > 1 {
+        // Errors exit with a non-zero status
+        if let Err(e) = cli_handler(cli).await {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
         }
         
         Ok(())
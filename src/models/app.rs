@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Tag recording the application an instance runs
+pub const APP_TAG: &str = "app";
+
+/// Tag recording the release an instance was deployed by
+pub const RELEASE_TAG: &str = "release";
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_cpu() -> u8 {
+    1
+}
+
+fn default_memory_gb() -> u16 {
+    1
+}
+
+fn default_disk_gb() -> u16 {
+    10
+}
+
+fn default_batch_size() -> u32 {
+    1
+}
+
+fn default_timeout_secs() -> u64 {
+    120
+}
+
+fn default_interval_secs() -> u64 {
+    5
+}
+
+/// Instances of an application in one region
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppRegion {
+    /// Region name
+    pub region: String,
+    /// Provider hosting the instances
+    pub provider: String,
+    /// Number of instances
+    #[serde(default = "default_count")]
+    pub count: u32,
+}
+
+/// Health check gating a rollout
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppHealthCheck {
+    /// Port to check
+    pub port: u16,
+    /// HTTP path to request (TCP connect check when absent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// HTTP status the instance must answer with (any 2xx or 3xx when absent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_status: Option<u16>,
+    /// Seconds a new instance has to become healthy
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Seconds between checks
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+/// Application described by a `bbctl.toml` project file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppSpec {
+    /// Application name, recorded on its instances
+    pub name: String,
+    /// OCI image, deployed as containers on VyOS providers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Proxmox VM template (name or VMID), cloned on Proxmox providers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// CPU cores per instance
+    #[serde(default = "default_cpu")]
+    pub cpu: u8,
+    /// Memory per instance in GB
+    #[serde(default = "default_memory_gb")]
+    pub memory_gb: u16,
+    /// Disk per instance in GB
+    #[serde(default = "default_disk_gb")]
+    pub disk_gb: u16,
+    /// Environment variables of the containers
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Ports published from the router to the containers, such as `8080:80`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<String>,
+    /// Container network on VyOS, or bbctl network the VMs connect to on Proxmox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Prefix of the container network, containers get consecutive addresses from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_prefix: Option<String>,
+    /// Instances to run per region
+    #[serde(default)]
+    pub regions: Vec<AppRegion>,
+    /// Health check new instances must pass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<AppHealthCheck>,
+    /// Instances replaced at a time
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u32,
+}
+
+impl AppSpec {
+    /// Whether instances deployed from both specs run the same thing
+    ///
+    /// Regions, counts, health checks and batch sizes only change how many
+    /// instances run and how they are rolled out, not the instances themselves.
+    pub fn same_instances(&self, other: &AppSpec) -> bool {
+        self.image == other.image
+            && self.template == other.template
+            && (self.cpu, self.memory_gb, self.disk_gb) == (other.cpu, other.memory_gb, other.disk_gb)
+            && self.env == other.env
+            && self.ports == other.ports
+            && self.network == other.network
+            && self.network_prefix == other.network_prefix
+    }
+}
+
+/// Outcome of a release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReleaseStatus {
+    /// Being rolled out
+    InProgress,
+    /// Every instance runs the release
+    Succeeded,
+    /// The rollout failed and was undone
+    RolledBack,
+    /// The rollout failed and could not be fully undone
+    Failed,
+}
+
+impl std::fmt::Display for ReleaseStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReleaseStatus::InProgress => write!(f, "in-progress"),
+            ReleaseStatus::Succeeded => write!(f, "succeeded"),
+            ReleaseStatus::RolledBack => write!(f, "rolled-back"),
+            ReleaseStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Numbered deploy of an application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    /// Release number, counting up from 1 per application
+    pub number: u32,
+    /// Application spec the release deployed
+    pub spec: AppSpec,
+    /// Outcome
+    pub status: ReleaseStatus,
+    /// Instances running the application once the release finished
+    #[serde(default)]
+    pub instances: Vec<Uuid>,
+    /// Error that failed the rollout
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Start time
+    pub created_at: DateTime<Utc>,
+    /// End time
+    #[serde(default)]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl Release {
+    /// Create a release in progress
+    pub fn new(number: u32, spec: AppSpec) -> Self {
+        Self {
+            number,
+            spec,
+            status: ReleaseStatus::InProgress,
+            instances: Vec::new(),
+            error: None,
+            created_at: Utc::now(),
+            finished_at: None,
+        }
+    }
+
+    /// Record the outcome of the rollout
+    pub fn finish(&mut self, status: ReleaseStatus, error: Option<String>) {
+        self.status = status;
+        self.error = error;
+        self.finished_at = Some(Utc::now());
+    }
+}
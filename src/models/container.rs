@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+/// Instance tag recording the image of a VyOS container instance
+pub const CONTAINER_IMAGE_TAG: &str = "image";
+
+/// Volume mounted into a container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerVolume {
+    /// Path on the router
+    pub source: String,
+    /// Path inside the container
+    pub destination: String,
+    /// Mount read-only
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl ContainerVolume {
+    /// Parse a volume such as `/config/dns:/etc/coredns` or `/data:/data:ro`
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let (source, destination, read_only) = match parts.as_slice() {
+            [source, destination] => (*source, *destination, false),
+            [source, destination, "ro"] => (*source, *destination, true),
+            [source, destination, "rw"] => (*source, *destination, false),
+            _ => return Err("Volume must be <source>:<destination>[:ro|rw]"),
+        };
+
+        if !source.starts_with('/') || !destination.starts_with('/') {
+            return Err("Volume paths must be absolute");
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            read_only,
+        })
+    }
+}
+
+/// Port published from the router to a container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerPort {
+    /// Port on the router
+    pub source: u16,
+    /// Port inside the container
+    pub destination: u16,
+    /// Protocol (tcp or udp)
+    pub protocol: String,
+}
+
+impl ContainerPort {
+    /// Parse a port mapping such as `8053:53/udp` or `9100` (same port, tcp)
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let (ports, protocol) = match s.split_once('/') {
+            Some((ports, protocol)) => (ports, protocol.to_lowercase()),
+            None => (s, "tcp".to_string()),
+        };
+        if protocol != "tcp" && protocol != "udp" {
+            return Err("Protocol must be tcp or udp");
+        }
+
+        let (source, destination) = ports.split_once(':').unwrap_or((ports, ports));
+        let source = source.trim().parse::<u16>().map_err(|_| "Invalid port")?;
+        let destination = destination.trim().parse::<u16>().map_err(|_| "Invalid port")?;
+
+        Ok(Self {
+            source,
+            destination,
+            protocol,
+        })
+    }
+}
+
+/// Specification of a container running on a VyOS router
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerSpec {
+    /// OCI image reference
+    pub image: String,
+    /// Container network on the router (host networking when absent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    /// Prefix of the container network, creates the network when given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_prefix: Option<String>,
+    /// Static address on the container network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<Ipv4Addr>,
+    /// Environment variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+    /// Mounted volumes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<ContainerVolume>,
+    /// Published ports
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<ContainerPort>,
+}
+
+impl ContainerSpec {
+    /// Create a spec for an image
+    pub fn new(image: &str) -> Self {
+        Self {
+            image: image.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Add an environment variable from `KEY=value`
+    pub fn add_environment(&mut self, s: &str) -> Result<(), &'static str> {
+        let (key, value) = s.split_once('=').ok_or("Environment must be KEY=value")?;
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err("Invalid environment variable name");
+        }
+
+        self.environment.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
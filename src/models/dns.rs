@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+/// Domain bbctl is authoritative for on the routers
+pub const INTERNAL_DOMAIN: &str = "internal";
+
+/// DNS record type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsRecordType {
+    A,
+    Cname,
+}
+
+impl std::fmt::Display for DnsRecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsRecordType::A => write!(f, "a"),
+            DnsRecordType::Cname => write!(f, "cname"),
+        }
+    }
+}
+
+impl std::str::FromStr for DnsRecordType {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "a" => Ok(DnsRecordType::A),
+            "cname" => Ok(DnsRecordType::Cname),
+            _ => Err("Record type must be a or cname"),
+        }
+    }
+}
+
+/// DNS record in the internal domain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsRecord {
+    /// Fully qualified name, e.g. `web.app.acme.internal`
+    pub name: String,
+    /// Record type
+    pub record_type: DnsRecordType,
+    /// Address (A) or target name (CNAME)
+    pub value: String,
+    /// Created at timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+impl DnsRecord {
+    /// Create a new record, normalizing the name into the internal domain
+    pub fn new(name: &str, record_type: DnsRecordType, value: &str) -> Result<Self, &'static str> {
+        let name = qualify_name(name)?;
+        let value = match record_type {
+            DnsRecordType::A => {
+                value.parse::<std::net::Ipv4Addr>().map_err(|_| "A records need an IPv4 address")?;
+                value.to_string()
+            },
+            DnsRecordType::Cname => {
+                let target = value.trim_end_matches('.').to_lowercase();
+                if target.is_empty() {
+                    return Err("CNAME records need a target name");
+                }
+                target
+            },
+        };
+
+        Ok(Self {
+            name,
+            record_type,
+            value,
+            created_at: Utc::now(),
+        })
+    }
+
+    /// Name relative to the internal domain, e.g. `web.app.acme`
+    pub fn relative_name(&self) -> &str {
+        self.name.strip_suffix(INTERNAL_DOMAIN)
+            .and_then(|relative| relative.strip_suffix('.'))
+            .unwrap_or(&self.name)
+    }
+}
+
+/// Turn a name into a valid DNS label (lowercase letters, digits and hyphens)
+pub fn sanitize_label(label: &str) -> String {
+    let label: String = label.to_lowercase().chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    label.trim_matches('-').to_string()
+}
+
+/// Build the name of an instance on a network, `<name>.<network>.<tenant>.internal`
+pub fn instance_fqdn(instance: &str, network: &str, tenant: &str) -> String {
+    format!(
+        "{}.{}.{}.{}",
+        sanitize_label(instance), sanitize_label(network), sanitize_label(tenant), INTERNAL_DOMAIN,
+    )
+}
+
+/// Normalize a record name into the internal domain
+///
+/// `web.app.acme` and `web.app.acme.internal.` both become `web.app.acme.internal`.
+pub fn qualify_name(name: &str) -> Result<String, &'static str> {
+    let name = name.trim_end_matches('.').to_lowercase();
+    let relative = name.strip_suffix(INTERNAL_DOMAIN)
+        .and_then(|relative| relative.strip_suffix('.'))
+        .unwrap_or(&name);
+
+    if relative.is_empty() || relative == INTERNAL_DOMAIN {
+        return Err("Record name is empty");
+    }
+
+    let valid = relative.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    if !valid {
+        return Err("Record name must be dot-separated labels of letters, digits and hyphens");
+    }
+
+    Ok(format!("{}.{}", relative, INTERNAL_DOMAIN))
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Default UDP port for VXLAN encapsulation
+pub const DEFAULT_VXLAN_PORT: u16 = 4789;
+
+/// Default interface carrying the router loopback (VTEP) address
+pub const DEFAULT_LOOPBACK_INTERFACE: &str = "dum0";
+
+/// Default MTU for VXLAN interfaces
+pub const DEFAULT_VXLAN_MTU: u16 = 1450;
+
+fn default_vxlan_port() -> u16 {
+    DEFAULT_VXLAN_PORT
+}
+
+fn default_loopback_interface() -> String {
+    DEFAULT_LOOPBACK_INTERFACE.to_string()
+}
+
+fn default_vxlan_mtu() -> u16 {
+    DEFAULT_VXLAN_MTU
+}
+
+/// Router participating in the EVPN fabric
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FabricRouter {
+    /// Name of the VyOS provider for this router
+    pub provider: String,
+    /// Loopback address used as router ID and VTEP source address
+    pub loopback: IpAddr,
+    /// Interface carrying the loopback address
+    #[serde(default = "default_loopback_interface")]
+    pub loopback_interface: String,
+    /// Explicit EVPN peers (defaults to a full mesh of all other loopbacks)
+    #[serde(default)]
+    pub underlay_peers: Vec<IpAddr>,
+}
+
+/// VXLAN segment stretched across the fabric
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FabricVni {
+    /// VXLAN network identifier
+    pub vni: u32,
+    /// Tenant network this segment carries (informational)
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Local interfaces bridged with the VXLAN interface on every router
+    #[serde(default)]
+    pub bridge_members: Vec<String>,
+    /// MTU of the VXLAN interface
+    #[serde(default = "default_vxlan_mtu")]
+    pub mtu: u16,
+}
+
+impl FabricVni {
+    /// Name of the VXLAN interface for this segment
+    pub fn vxlan_interface(&self) -> String {
+        format!("vxlan{}", self.vni)
+    }
+
+    /// Name of the bridge interface for this segment
+    pub fn bridge_interface(&self) -> String {
+        format!("br{}", self.vni)
+    }
+}
+
+/// BGP EVPN / VXLAN fabric description
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fabric {
+    /// Fabric name
+    pub name: String,
+    /// Autonomous system number shared by all routers (iBGP)
+    pub asn: u32,
+    /// UDP port used for VXLAN
+    #[serde(default = "default_vxlan_port")]
+    pub vxlan_port: u16,
+    /// Routers in the fabric
+    #[serde(default)]
+    pub routers: Vec<FabricRouter>,
+    /// VXLAN segments
+    #[serde(default)]
+    pub vnis: Vec<FabricVni>,
+}
+
+impl Fabric {
+    /// Get a router by provider name
+    pub fn get_router(&self, provider: &str) -> Option<&FabricRouter> {
+        self.routers.iter().find(|r| r.provider == provider)
+    }
+
+    /// Get the EVPN peers of a router
+    pub fn peers_for(&self, router: &FabricRouter) -> Vec<IpAddr> {
+        if !router.underlay_peers.is_empty() {
+            return router.underlay_peers.clone();
+        }
+
+        // Full mesh between all router loopbacks
+        self.routers.iter()
+            .filter(|r| r.provider != router.provider)
+            .map(|r| r.loopback)
+            .collect()
+    }
+}
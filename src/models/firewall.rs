@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of firewall zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ZoneKind {
+    /// Tenant VRF or network
+    Tenant,
+    /// Management network
+    Management,
+    /// Public / upstream network
+    Public,
+}
+
+impl std::fmt::Display for ZoneKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZoneKind::Tenant => write!(f, "tenant"),
+            ZoneKind::Management => write!(f, "management"),
+            ZoneKind::Public => write!(f, "public"),
+        }
+    }
+}
+
+/// Action taken by a firewall rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Accept,
+    Drop,
+    Reject,
+}
+
+impl std::fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleAction::Accept => write!(f, "accept"),
+            RuleAction::Drop => write!(f, "drop"),
+            RuleAction::Reject => write!(f, "reject"),
+        }
+    }
+}
+
+fn default_action() -> RuleAction {
+    RuleAction::Drop
+}
+
+/// Firewall zone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Zone {
+    /// Zone name
+    pub name: String,
+    /// Zone kind
+    pub kind: ZoneKind,
+    /// Interfaces that belong to the zone
+    #[serde(default)]
+    pub interfaces: Vec<String>,
+    /// VRFs that belong to the zone (VyOS 1.5 and later)
+    #[serde(default)]
+    pub vrfs: Vec<String>,
+    /// Whether this zone represents the router itself
+    #[serde(default)]
+    pub local: bool,
+}
+
+/// Explicit rule allowing (or denying) traffic between two zones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirewallRule {
+    /// Source zone
+    pub from: String,
+    /// Destination zone
+    pub to: String,
+    /// Rule action
+    #[serde(default = "default_rule_action")]
+    pub action: RuleAction,
+    /// Protocol (tcp, udp, tcp_udp, icmp, ...)
+    #[serde(default)]
+    pub protocol: Option<String>,
+    /// Destination port(s), e.g. `443`, `80,443` or `8000-8080`
+    #[serde(default)]
+    pub destination_port: Option<String>,
+    /// Source address or CIDR
+    #[serde(default)]
+    pub source_address: Option<String>,
+    /// Destination address or CIDR
+    #[serde(default)]
+    pub destination_address: Option<String>,
+    /// Rule description
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+fn default_rule_action() -> RuleAction {
+    RuleAction::Accept
+}
+
+/// Zone-based firewall policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirewallPolicy {
+    /// Action for traffic between zones that no rule matches
+    #[serde(default = "default_action")]
+    pub default_action: RuleAction,
+    /// Zones
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+    /// Explicit rules
+    #[serde(default)]
+    pub rules: Vec<FirewallRule>,
+}
+
+impl FirewallPolicy {
+    /// Get a zone by name
+    pub fn get_zone(&self, name: &str) -> Option<&Zone> {
+        self.zones.iter().find(|z| z.name == name)
+    }
+
+    /// Get the rules for traffic from one zone to another, in policy order
+    pub fn rules_between(&self, from: &str, to: &str) -> Vec<&FirewallRule> {
+        self.rules.iter()
+            .filter(|r| r.from == from && r.to == to)
+            .collect()
+    }
+}
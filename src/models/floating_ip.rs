@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use std::net::Ipv4Addr;
+
+/// Pool of public addresses routed to a VyOS router
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FloatingIpPool {
+    /// Pool name
+    pub name: String,
+    /// Public CIDR block (e.g. 5.254.54.0/26)
+    pub cidr: String,
+    /// VyOS provider that routes the block and performs NAT
+    pub router: String,
+    /// Public-facing interface on the router
+    pub interface: String,
+    /// Addresses in the block that must never be handed out (gateway, router, ...)
+    #[serde(default)]
+    pub exclude: Vec<Ipv4Addr>,
+}
+
+/// Port forward from a public address to an instance
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortForward {
+    /// Protocol (tcp or udp)
+    pub protocol: String,
+    /// Port on the public address
+    pub public_port: u16,
+    /// Port on the instance
+    pub private_port: u16,
+}
+
+/// Public address allocated from a pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FloatingIp {
+    /// Public address
+    pub ip: Ipv4Addr,
+    /// Pool the address was allocated from
+    pub pool: String,
+    /// Associated instance ID (if any)
+    pub instance_id: Option<Uuid>,
+    /// Private address of the associated instance
+    pub private_ip: Option<Ipv4Addr>,
+    /// Port forwards; empty means a 1:1 NAT when associated
+    #[serde(default)]
+    pub port_forwards: Vec<PortForward>,
+    /// Whether the address was allocated implicitly by `expose`
+    #[serde(default)]
+    pub auto_allocated: bool,
+    /// Allocated at timestamp
+    pub allocated_at: DateTime<Utc>,
+    /// Updated at timestamp
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FloatingIp {
+    /// Create a new unassociated floating IP
+    pub fn new(ip: Ipv4Addr, pool: String) -> Self {
+        let now = Utc::now();
+        Self {
+            ip,
+            pool,
+            instance_id: None,
+            private_ip: None,
+            port_forwards: Vec::new(),
+            auto_allocated: false,
+            allocated_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Check whether the address is associated with an instance
+    pub fn is_associated(&self) -> bool {
+        self.instance_id.is_some() && self.private_ip.is_some()
+    }
+
+    /// Check whether the address is a 1:1 NAT rather than a set of port forwards
+    pub fn is_one_to_one(&self) -> bool {
+        self.is_associated() && self.port_forwards.is_empty()
+    }
+
+    /// Associate the address with an instance
+    pub fn associate(&mut self, instance_id: Uuid, private_ip: Ipv4Addr) {
+        self.instance_id = Some(instance_id);
+        self.private_ip = Some(private_ip);
+        self.updated_at = Utc::now();
+    }
+
+    /// Remove the association and all port forwards
+    pub fn disassociate(&mut self) {
+        self.instance_id = None;
+        self.private_ip = None;
+        self.port_forwards.clear();
+        self.updated_at = Utc::now();
+    }
+
+    /// Add a port forward
+    pub fn add_port_forward(&mut self, forward: PortForward) -> Result<(), &'static str> {
+        if self.port_forwards.iter().any(|f| f.protocol == forward.protocol && f.public_port == forward.public_port) {
+            return Err("Public port is already forwarded");
+        }
+
+        self.port_forwards.push(forward);
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+}
@@ -3,8 +3,15 @@ use uuid::Uuid;
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 
+use crate::models::container::ContainerSpec;
 use crate::models::provider::ProviderType;
 
+/// Tag recording the name of the provider an instance was created on
+pub const PROVIDER_TAG: &str = "provider";
+
+/// Tag recording the Proxmox node hosting an instance
+pub const NODE_TAG: &str = "node";
+
 /// Instance status
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InstanceStatus {
@@ -14,6 +21,7 @@ pub enum InstanceStatus {
     Creating,
     Restarting,
     Deleting,
+    Missing,
     Unknown,
 }
 
@@ -26,6 +34,7 @@ impl std::fmt::Display for InstanceStatus {
             InstanceStatus::Creating => write!(f, "creating"),
             InstanceStatus::Restarting => write!(f, "restarting"),
             InstanceStatus::Deleting => write!(f, "deleting"),
+            InstanceStatus::Missing => write!(f, "missing"),
             InstanceStatus::Unknown => write!(f, "unknown"),
         }
     }
@@ -40,6 +49,7 @@ impl From<&str> for InstanceStatus {
             "creating" => InstanceStatus::Creating,
             "restarting" => InstanceStatus::Restarting,
             "deleting" => InstanceStatus::Deleting,
+            "missing" => InstanceStatus::Missing,
             _ => InstanceStatus::Unknown,
         }
     }
@@ -94,6 +104,9 @@ pub struct Instance {
     pub updated_at: DateTime<Utc>,
     /// Tags
     pub tags: HashMap<String, String>,
+    /// Container a VyOS instance was created from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<ContainerSpec>,
 }
 
 impl Instance {
@@ -112,6 +125,7 @@ impl Instance {
             created_at: now,
             updated_at: now,
             tags: HashMap::new(),
+            container: None,
         }
     }
     
@@ -132,6 +146,17 @@ impl Instance {
         self.updated_at = Utc::now();
     }
     
+    /// Remove a network from the instance
+    pub fn remove_network(&mut self, network_id: &str) -> bool {
+        let before = self.networks.len();
+        self.networks.retain(|network| network.network_id != network_id);
+        let removed = self.networks.len() != before;
+        if removed {
+            self.updated_at = Utc::now();
+        }
+        removed
+    }
+    
     /// Update instance status
     pub fn update_status(&mut self, status: InstanceStatus) {
         self.status = status;
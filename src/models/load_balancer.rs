@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::models::instance::Instance;
+
+/// Default backend balancing algorithm
+pub const DEFAULT_BALANCE: &str = "round-robin";
+
+/// Balancing algorithms supported by the VyOS reverse proxy
+pub const BALANCE_ALGORITHMS: &[&str] = &["round-robin", "least-connection", "source-address"];
+
+fn default_balance() -> String {
+    DEFAULT_BALANCE.to_string()
+}
+
+/// Proxy mode of a frontend or backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyMode {
+    /// L7 HTTP proxying
+    #[default]
+    Http,
+    /// L4 TCP proxying
+    Tcp,
+}
+
+impl std::fmt::Display for ProxyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyMode::Http => write!(f, "http"),
+            ProxyMode::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// Listener accepting client connections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frontend {
+    /// Frontend name, unique within the load balancer
+    pub name: String,
+    /// Listening port
+    pub port: u16,
+    /// Listening address (all addresses when absent)
+    #[serde(default)]
+    pub listen_address: Option<String>,
+    /// Proxy mode
+    #[serde(default)]
+    pub mode: ProxyMode,
+    /// Backend receiving the traffic
+    pub backend: String,
+    /// PKI certificate name for TLS termination
+    #[serde(default)]
+    pub certificate: Option<String>,
+}
+
+/// Health check run against every backend server
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealthCheck {
+    /// HTTP path to request (TCP connect check when absent)
+    #[serde(default)]
+    pub path: Option<String>,
+    /// HTTP status the server must answer with
+    #[serde(default)]
+    pub expect_status: Option<u16>,
+}
+
+/// Pool of instances selected by tags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backend {
+    /// Backend name, unique within the load balancer
+    pub name: String,
+    /// Instance tags a member must carry, all of them must match
+    pub selector: HashMap<String, String>,
+    /// Port on the instances
+    pub port: u16,
+    /// Proxy mode
+    #[serde(default)]
+    pub mode: ProxyMode,
+    /// Balancing algorithm
+    #[serde(default = "default_balance")]
+    pub balance: String,
+    /// Health check (servers are not checked when absent)
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>,
+}
+
+impl Backend {
+    /// Check whether an instance is selected by this backend
+    pub fn selects(&self, instance: &Instance) -> bool {
+        !self.selector.is_empty()
+            && self.selector.iter().all(|(key, value)| instance.tags.get(key) == Some(value))
+    }
+}
+
+/// Reverse-proxy load balancer on a VyOS router
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadBalancer {
+    /// Load balancer name
+    pub name: String,
+    /// VyOS provider running the reverse proxy
+    pub router: String,
+    /// Listeners
+    #[serde(default)]
+    pub frontends: Vec<Frontend>,
+    /// Instance pools
+    #[serde(default)]
+    pub backends: Vec<Backend>,
+    /// Created at timestamp
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// Updated at timestamp
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl LoadBalancer {
+    /// Get a backend by name
+    pub fn get_backend(&self, name: &str) -> Option<&Backend> {
+        self.backends.iter().find(|b| b.name == name)
+    }
+
+    /// Check whether an instance is a member of any backend
+    pub fn selects(&self, instance: &Instance) -> bool {
+        self.backends.iter().any(|b| b.selects(instance))
+    }
+}
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+
+/// Syslog severity, most severe first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Emergency,
+    Alert,
+    Critical,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+impl Severity {
+    /// Severity of a syslog severity code
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0 => Severity::Emergency,
+            1 => Severity::Alert,
+            2 => Severity::Critical,
+            3 => Severity::Error,
+            4 => Severity::Warning,
+            5 => Severity::Notice,
+            6 => Severity::Info,
+            _ => Severity::Debug,
+        }
+    }
+
+    /// Parse a severity name such as `err` or `warning`
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        match s.to_lowercase().as_str() {
+            "emerg" | "emergency" => Ok(Severity::Emergency),
+            "alert" => Ok(Severity::Alert),
+            "crit" | "critical" => Ok(Severity::Critical),
+            "err" | "error" => Ok(Severity::Error),
+            "warn" | "warning" => Ok(Severity::Warning),
+            "notice" => Ok(Severity::Notice),
+            "info" => Ok(Severity::Info),
+            "debug" => Ok(Severity::Debug),
+            _ => Err("Severity must be emerg, alert, crit, err, warning, notice, info or debug"),
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Emergency => write!(f, "emerg"),
+            Severity::Alert => write!(f, "alert"),
+            Severity::Critical => write!(f, "crit"),
+            Severity::Error => write!(f, "err"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Notice => write!(f, "notice"),
+            Severity::Info => write!(f, "info"),
+            Severity::Debug => write!(f, "debug"),
+        }
+    }
+}
+
+/// Log message received from a router or hypervisor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Time the collector received the message
+    pub received_at: DateTime<Utc>,
+    /// Time the sender generated the message, when it included one
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Provider the message came from, when the sender is a known provider
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Address the message was received from
+    pub source: IpAddr,
+    /// Host name reported by the sender
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// Facility name
+    pub facility: String,
+    /// Severity
+    pub severity: Severity,
+    /// Application or tag that logged the message
+    #[serde(default)]
+    pub app_name: Option<String>,
+    /// Message text
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Name of the sender: the provider, else the host name, else the address
+    pub fn sender(&self) -> String {
+        self.provider.clone()
+            .or_else(|| self.hostname.clone())
+            .unwrap_or_else(|| self.source.to_string())
+    }
+}
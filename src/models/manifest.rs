@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::models::container::ContainerSpec;
+use crate::models::security_group::{RuleDirection, RulePeer, SecurityGroupRule};
+
+/// Tag recording the manifest that owns a resource
+pub const MANIFEST_TAG: &str = "manifest";
+
+fn default_cpu() -> u8 {
+    1
+}
+
+fn default_memory_gb() -> u16 {
+    2
+}
+
+fn default_disk_gb() -> u16 {
+    10
+}
+
+fn default_running() -> bool {
+    true
+}
+
+/// Network described by a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestNetwork {
+    /// Network name
+    pub name: String,
+    /// CIDR block
+    pub cidr: String,
+    /// Provider (defaults to the manifest provider)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Region (defaults to the manifest region)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Network type (bridged, routed, isolated, vxlan or vpn)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_type: Option<String>,
+    /// Gateway (defaults to the first host address)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<IpAddr>,
+    /// DNS servers handed out by DHCP
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_servers: Vec<IpAddr>,
+    /// Tenant owning the network (defaults to the manifest tenant)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+/// Connection of a manifest instance to a network
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestInstanceNetwork {
+    /// Network name, from the manifest or an existing network
+    pub network: String,
+    /// Fixed address (allocated from the network when absent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpAddr>,
+}
+
+/// Instance described by a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestInstance {
+    /// Instance name
+    pub name: String,
+    /// Provider (defaults to the manifest provider)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Region (defaults to the manifest region)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// CPU cores
+    #[serde(default = "default_cpu")]
+    pub cpu: u8,
+    /// Memory in GB
+    #[serde(default = "default_memory_gb")]
+    pub memory_gb: u16,
+    /// Disk in GB
+    #[serde(default = "default_disk_gb")]
+    pub disk_gb: u16,
+    /// Whether the instance should be running
+    #[serde(default = "default_running")]
+    pub running: bool,
+    /// Container to run, required on VyOS providers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<ContainerSpec>,
+    /// VM template (name or VMID) cloned on Proxmox providers, required to create the instance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Networks the instance is connected to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub networks: Vec<ManifestInstanceNetwork>,
+    /// Security groups attached to the instance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub security_groups: Vec<String>,
+    /// Tags set on the instance
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Volume described by a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestVolume {
+    /// Volume name
+    pub name: String,
+    /// Size in GB
+    pub size_gb: u16,
+    /// Provider (defaults to the manifest provider)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Region (defaults to the manifest region)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Proxmox storage the volume is allocated on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<String>,
+    /// Instance the volume is attached to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Mount path inside a VyOS container
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_path: Option<String>,
+}
+
+/// Security group rule described by a manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestRule {
+    /// Protocol (tcp, udp, icmp or all)
+    pub protocol: String,
+    /// Port or port range such as `443` or `8000-8080`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<String>,
+    /// Remote address or CIDR block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cidr: Option<String>,
+    /// Remote security group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Rule description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl ManifestRule {
+    /// Convert to a security group rule
+    pub fn to_rule(&self) -> Result<SecurityGroupRule, &'static str> {
+        let peer = match (&self.cidr, &self.group) {
+            (Some(cidr), None) => RulePeer::Cidr(cidr.clone()),
+            (None, Some(group)) => RulePeer::Group(group.clone()),
+            (None, None) => RulePeer::Cidr("0.0.0.0/0".to_string()),
+            (Some(_), Some(_)) => return Err("A rule takes either a CIDR or a group, not both"),
+        };
+        let (port_from, port_to) = match &self.ports {
+            Some(ports) => {
+                let (from, to) = SecurityGroupRule::parse_port_range(ports)?;
+                (Some(from), Some(to))
+            },
+            None => (None, None),
+        };
+
+        Ok(SecurityGroupRule {
+            protocol: self.protocol.to_lowercase(),
+            port_from,
+            port_to,
+            peer,
+            description: self.description.clone(),
+        })
+    }
+}
+
+/// Security group described by a manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestSecurityGroup {
+    /// Security group name
+    pub name: String,
+    /// Description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Ingress rules
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ingress: Vec<ManifestRule>,
+    /// Egress rules
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub egress: Vec<ManifestRule>,
+}
+
+impl ManifestSecurityGroup {
+    /// Convert the rules for a direction
+    pub fn rules(&self, direction: RuleDirection) -> Result<Vec<SecurityGroupRule>, &'static str> {
+        let rules = match direction {
+            RuleDirection::Ingress => &self.ingress,
+            RuleDirection::Egress => &self.egress,
+        };
+        rules.iter().map(ManifestRule::to_rule).collect()
+    }
+}
+
+/// Declarative description of a tenant's infrastructure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Manifest name, recorded on every resource it owns
+    pub name: String,
+    /// Default provider of the resources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Default region of the resources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Default tenant of the networks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Networks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub networks: Vec<ManifestNetwork>,
+    /// Security groups
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub security_groups: Vec<ManifestSecurityGroup>,
+    /// Instances
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<ManifestInstance>,
+    /// Volumes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<ManifestVolume>,
+}
+
+impl Manifest {
+    /// Get a network by name
+    pub fn get_network(&self, name: &str) -> Option<&ManifestNetwork> {
+        self.networks.iter().find(|n| n.name == name)
+    }
+
+    /// Get an instance by name
+    pub fn get_instance(&self, name: &str) -> Option<&ManifestInstance> {
+        self.instances.iter().find(|i| i.name == name)
+    }
+
+    /// Get a security group by name
+    pub fn get_security_group(&self, name: &str) -> Option<&ManifestSecurityGroup> {
+        self.security_groups.iter().find(|g| g.name == name)
+    }
+
+    /// Resolve a resource's provider, falling back to the manifest default
+    pub fn provider_for<'a>(&'a self, provider: &'a Option<String>) -> Option<&'a str> {
+        provider.as_deref().or(self.provider.as_deref())
+    }
+
+    /// Resolve a resource's region, falling back to the manifest default
+    pub fn region_for<'a>(&'a self, region: &'a Option<String>) -> &'a str {
+        region.as_deref().or(self.region.as_deref()).unwrap_or("default")
+    }
+}
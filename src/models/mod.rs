@@ -1,4 +1,17 @@
 pub mod instance;
 pub mod volume;
 pub mod network;
-pub mod provider;
\ No newline at end of file
+pub mod provider;
+pub mod fabric;
+pub mod firewall;
+pub mod security_group;
+pub mod floating_ip;
+pub mod dns;
+pub mod site;
+pub mod load_balancer;
+pub mod container;
+pub mod usage;
+pub mod logs;
+pub mod manifest;
+pub mod app;
+pub mod template;pub mod tenant;
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use std::collections::{HashMap, HashSet};
 use chrono::{DateTime, Utc};
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr};
 
 use crate::models::provider::ProviderType;
 
@@ -13,6 +13,7 @@ pub enum NetworkStatus {
     Creating,
     Deleting,
     Error,
+    Missing,
     Unknown,
 }
 
@@ -22,6 +23,7 @@ impl std::fmt::Display for NetworkStatus {
             NetworkStatus::Available => write!(f, "available"),
             NetworkStatus::Creating => write!(f, "creating"),
             NetworkStatus::Deleting => write!(f, "deleting"),
+            NetworkStatus::Missing => write!(f, "missing"),
             NetworkStatus::Error => write!(f, "error"),
             NetworkStatus::Unknown => write!(f, "unknown"),
         }
@@ -34,6 +36,7 @@ impl From<&str> for NetworkStatus {
             "available" => NetworkStatus::Available,
             "creating" => NetworkStatus::Creating,
             "deleting" => NetworkStatus::Deleting,
+            "missing" => NetworkStatus::Missing,
             "error" => NetworkStatus::Error,
             _ => NetworkStatus::Unknown,
         }
@@ -75,6 +78,82 @@ impl From<&str> for NetworkType {
     }
 }
 
+/// IPv4 CIDR block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ipv4Cidr {
+    /// Network address
+    pub network: Ipv4Addr,
+    /// Prefix length
+    pub prefix_len: u8,
+}
+
+impl Ipv4Cidr {
+    /// Parse a CIDR block such as `192.168.1.0/24`
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let (addr, prefix) = s.split_once('/').ok_or("CIDR block is missing a prefix length")?;
+        let addr: Ipv4Addr = addr.trim().parse().map_err(|_| "Invalid IPv4 address in CIDR block")?;
+        let prefix_len: u8 = prefix.trim().parse().map_err(|_| "Invalid prefix length in CIDR block")?;
+
+        if prefix_len > 32 {
+            return Err("Prefix length must be 32 or less");
+        }
+
+        let mask = Self::mask_bits(prefix_len);
+        Ok(Self {
+            network: Ipv4Addr::from(u32::from(addr) & mask),
+            prefix_len,
+        })
+    }
+
+    fn mask_bits(prefix_len: u8) -> u32 {
+        if prefix_len == 0 { 0 } else { u32::MAX << (32 - prefix_len) }
+    }
+
+    /// Netmask of the block
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(Self::mask_bits(self.prefix_len))
+    }
+
+    /// Broadcast address of the block
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network) | !Self::mask_bits(self.prefix_len))
+    }
+
+    /// Check whether an address falls inside the block
+    pub fn contains(&self, ip: &Ipv4Addr) -> bool {
+        u32::from(*ip) & Self::mask_bits(self.prefix_len) == u32::from(self.network)
+    }
+
+    /// First usable host address
+    pub fn first_host(&self) -> Ipv4Addr {
+        if self.prefix_len >= 31 {
+            self.network
+        } else {
+            Ipv4Addr::from(u32::from(self.network) + 1)
+        }
+    }
+
+    /// Last usable host address
+    pub fn last_host(&self) -> Ipv4Addr {
+        if self.prefix_len >= 31 {
+            self.broadcast()
+        } else {
+            Ipv4Addr::from(u32::from(self.broadcast()) - 1)
+        }
+    }
+
+    /// Iterate over the usable host addresses
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        (u32::from(self.first_host())..=u32::from(self.last_host())).map(Ipv4Addr::from)
+    }
+}
+
+impl std::fmt::Display for Ipv4Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
 /// IP allocation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpAllocation {
@@ -84,6 +163,65 @@ pub struct IpAllocation {
     pub instance_id: Option<Uuid>,
     /// Assigned at timestamp
     pub assigned_at: Option<DateTime<Utc>>,
+    /// MAC address of the instance interface (if known)
+    #[serde(default)]
+    pub mac: Option<String>,
+    /// Host name of the instance, used for internal DNS
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// Whether the address was asked for rather than picked by bbctl
+    #[serde(default)]
+    pub fixed: bool,
+}
+
+/// Network tag naming the tenant that owns the network
+pub const TENANT_TAG: &str = "tenant";
+
+/// Tenant assumed for networks without a tenant tag
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Network config key listing the VyOS routers sharing the gateway (comma-separated)
+pub const HA_ROUTERS_CONFIG: &str = "ha-routers";
+
+/// Network config key holding the router interface that carries the network
+pub const ROUTER_INTERFACE_CONFIG: &str = "router-interface";
+
+/// Network config key holding the Proxmox bridge VMs plug into (`vmbr1`, or `vmbr0.<vlan>` for a tagged NIC)
+pub const BRIDGE_CONFIG: &str = "bridge";
+
+/// Proxmox bridge assumed for networks without a bridge, the one blank VMs get
+pub const DEFAULT_BRIDGE: &str = "vmbr0";
+
+/// Network config key holding the VRRP virtual router ID
+pub const VRRP_VRID_CONFIG: &str = "vrrp-vrid";
+
+/// Network config key disabling VRRP preemption (`true`)
+pub const VRRP_NO_PREEMPT_CONFIG: &str = "vrrp-no-preempt";
+
+/// Network config key disabling DHCP on a VyOS network (`false`)
+pub const DHCP_CONFIG: &str = "dhcp";
+
+/// Network config key holding the dynamic DHCP range (`start-stop`)
+pub const DHCP_RANGE_CONFIG: &str = "dhcp-range";
+
+/// Network config key holding the DHCP subnet ID assigned on the router
+pub const DHCP_SUBNET_ID_CONFIG: &str = "dhcp-subnet-id";
+
+/// Normalize a bandwidth such as `200mbit`, `1Gbit` or `500000` (bit/s)
+pub fn parse_bandwidth(s: &str) -> Result<String, &'static str> {
+    let s = s.trim().to_lowercase();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(digits);
+
+    let value = value.parse::<u64>().map_err(|_| "Bandwidth must start with a number")?;
+    if value == 0 {
+        return Err("Bandwidth must be greater than zero");
+    }
+
+    match unit {
+        "" | "bit" | "kbit" | "mbit" | "gbit" | "tbit" => Ok(format!("{}{}", value, unit)),
+        _ => Err("Bandwidth unit must be bit, kbit, mbit, gbit or tbit"),
+    }
 }
 
 /// Network resource
@@ -147,6 +285,11 @@ impl Network {
         }
     }
     
+    /// Parse the CIDR block of an IPv4 network
+    pub fn ipv4_cidr(&self) -> Result<Ipv4Cidr, &'static str> {
+        Ipv4Cidr::parse(&self.cidr)
+    }
+    
     /// Update network status
     pub fn update_status(&mut self, status: NetworkStatus) {
         self.status = status;
@@ -195,8 +338,76 @@ impl Network {
         result
     }
     
+    /// Get the tenant that owns the network
+    pub fn tenant(&self) -> &str {
+        self.tags.get(TENANT_TAG).map(String::as_str).unwrap_or(DEFAULT_TENANT)
+    }
+    
+    /// Get the VyOS routers sharing the gateway through VRRP
+    pub fn ha_routers(&self) -> Vec<&str> {
+        self.get_config(HA_ROUTERS_CONFIG)
+            .map(|routers| routers.split(',').map(str::trim).filter(|r| !r.is_empty()).collect())
+            .unwrap_or_default()
+    }
+    
+    /// Get the router interface carrying the network
+    pub fn router_interface(&self) -> Option<&str> {
+        self.get_config(ROUTER_INTERFACE_CONFIG).map(String::as_str)
+    }
+    
+    /// Get the Proxmox bridge VMs plug into to reach the network
+    pub fn bridge(&self) -> &str {
+        self.get_config(BRIDGE_CONFIG).map(String::as_str).unwrap_or(DEFAULT_BRIDGE)
+    }
+    
+    /// Get the VRRP virtual router ID
+    pub fn vrrp_vrid(&self) -> Option<u8> {
+        self.get_config(VRRP_VRID_CONFIG).and_then(|vrid| vrid.parse().ok())
+    }
+    
+    /// Reserve an address for infrastructure (e.g. a router) rather than an instance
+    pub fn reserve_ip(&mut self, ip: IpAddr, hostname: String) -> Result<(), &'static str> {
+        if self.ip_allocations.iter().any(|alloc| alloc.ip == ip) {
+            return Err("IP address already allocated");
+        }
+        
+        self.ip_allocations.push(IpAllocation {
+            ip,
+            instance_id: None,
+            assigned_at: Some(Utc::now()),
+            mac: None,
+            hostname: Some(hostname),
+            fixed: true,
+        });
+        
+        self.updated_at = Utc::now();
+        Ok(())
+    }
+    
+    /// Check whether bbctl should serve DHCP for the network
+    pub fn dhcp_enabled(&self) -> bool {
+        self.provider == ProviderType::VyOS
+            && self.get_config(DHCP_CONFIG).map(|v| v != "false").unwrap_or(true)
+    }
+    
+    /// Find the first IPv4 host address that is neither the gateway nor allocated
+    pub fn next_free_ipv4(&self) -> Option<Ipv4Addr> {
+        let cidr = self.ipv4_cidr().ok()?;
+        cidr.hosts().find(|ip| {
+            let ip = IpAddr::V4(*ip);
+            self.gateway != Some(ip) && !self.ip_allocations.iter().any(|alloc| alloc.ip == ip)
+        })
+    }
+    
     /// Allocate an IP address to an instance
-    pub fn allocate_ip(&mut self, ip: IpAddr, instance_id: Uuid) -> Result<(), &'static str> {
+    pub fn allocate_ip(
+        &mut self,
+        ip: IpAddr,
+        instance_id: Uuid,
+        mac: Option<String>,
+        hostname: Option<String>,
+        fixed: bool,
+    ) -> Result<(), &'static str> {
         // Check if IP is already allocated
         if self.ip_allocations.iter().any(|alloc| alloc.ip == ip) {
             return Err("IP address already allocated");
@@ -212,12 +423,28 @@ impl Network {
             ip,
             instance_id: Some(instance_id),
             assigned_at: Some(Utc::now()),
+            mac,
+            hostname,
+            fixed,
         });
         
         self.updated_at = Utc::now();
         Ok(())
     }
     
+    /// Record the MAC address behind the allocations of an instance
+    pub fn set_instance_mac(&mut self, instance_id: &Uuid, mac: String) -> bool {
+        let mut updated = false;
+        for alloc in self.ip_allocations.iter_mut().filter(|a| a.instance_id.as_ref() == Some(instance_id)) {
+            alloc.mac = Some(mac.clone());
+            updated = true;
+        }
+        if updated {
+            self.updated_at = Utc::now();
+        }
+        updated
+    }
+    
     /// Release an IP address
     pub fn release_ip(&mut self, ip: &IpAddr) -> Result<(), &'static str> {
         if let Some(idx) = self.ip_allocations.iter().position(|alloc| &alloc.ip == ip) {
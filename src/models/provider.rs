@@ -58,7 +58,7 @@ pub struct Region {
 }
 
 /// Resource limits for a region
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ResourceLimits {
     /// Maximum number of instances that can be created
     pub max_instances: Option<u32>,
@@ -72,17 +72,4 @@ pub struct ResourceLimits {
     pub max_memory_per_instance: Option<u16>,
     /// Maximum disk (GB) per instance
     pub max_disk_per_instance: Option<u16>,
-}
-
-impl Default for ResourceLimits {
-    fn default() -> Self {
-        Self {
-            max_instances: None,
-            max_volumes: None,
-            max_networks: None,
-            max_cpu_per_instance: None,
-            max_memory_per_instance: None,
-            max_disk_per_instance: None,
-        }
-    }
 }
\ No newline at end of file
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+
+use crate::models::instance::Instance;
+
+/// Instance tag listing security groups by name (comma-separated)
+pub const SECURITY_GROUPS_TAG: &str = "security-groups";
+
+/// Direction of a security group rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleDirection {
+    Ingress,
+    Egress,
+}
+
+impl std::fmt::Display for RuleDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleDirection::Ingress => write!(f, "ingress"),
+            RuleDirection::Egress => write!(f, "egress"),
+        }
+    }
+}
+
+impl std::str::FromStr for RuleDirection {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ingress" | "in" => Ok(RuleDirection::Ingress),
+            "egress" | "out" => Ok(RuleDirection::Egress),
+            _ => Err("Direction must be ingress or egress"),
+        }
+    }
+}
+
+/// Remote side of a security group rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RulePeer {
+    /// Address or CIDR block
+    Cidr(String),
+    /// All members of another security group
+    Group(String),
+}
+
+impl std::fmt::Display for RulePeer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RulePeer::Cidr(cidr) => write!(f, "{}", cidr),
+            RulePeer::Group(group) => write!(f, "sg:{}", group),
+        }
+    }
+}
+
+/// Security group rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityGroupRule {
+    /// Protocol (tcp, udp, icmp or all)
+    pub protocol: String,
+    /// First port of the range (tcp/udp only)
+    pub port_from: Option<u16>,
+    /// Last port of the range (tcp/udp only)
+    pub port_to: Option<u16>,
+    /// Remote side of the rule
+    pub peer: RulePeer,
+    /// Rule description
+    pub description: Option<String>,
+}
+
+impl SecurityGroupRule {
+    /// Parse a port or port range such as `443` or `8000-8080`
+    pub fn parse_port_range(s: &str) -> Result<(u16, u16), &'static str> {
+        let (from, to) = match s.split_once('-') {
+            Some((from, to)) => (from, to),
+            None => (s, s),
+        };
+
+        let from = from.trim().parse::<u16>().map_err(|_| "Invalid port")?;
+        let to = to.trim().parse::<u16>().map_err(|_| "Invalid port")?;
+        if from > to {
+            return Err("Port range start is after its end");
+        }
+
+        Ok((from, to))
+    }
+
+    /// Format the port range using the given separator (`-` for VyOS, `:` for Proxmox)
+    pub fn port_range(&self, separator: &str) -> Option<String> {
+        match (self.port_from, self.port_to) {
+            (Some(from), Some(to)) if from != to => Some(format!("{}{}{}", from, separator, to)),
+            (Some(port), _) | (None, Some(port)) => Some(port.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Security group resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityGroup {
+    /// Security group ID (UUID)
+    pub id: Uuid,
+    /// Security group name
+    pub name: String,
+    /// Description
+    pub description: Option<String>,
+    /// Ingress rules
+    pub ingress: Vec<SecurityGroupRule>,
+    /// Egress rules
+    pub egress: Vec<SecurityGroupRule>,
+    /// Explicitly attached instances
+    pub instances: HashSet<Uuid>,
+    /// Created at timestamp
+    pub created_at: DateTime<Utc>,
+    /// Updated at timestamp
+    pub updated_at: DateTime<Utc>,
+    /// Tags
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+impl SecurityGroup {
+    /// Create a new security group
+    pub fn new(name: String, description: Option<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            description,
+            ingress: Vec::new(),
+            egress: Vec::new(),
+            instances: HashSet::new(),
+            created_at: now,
+            updated_at: now,
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Add a tag
+    pub fn add_tag(&mut self, key: String, value: String) {
+        self.tags.insert(key, value);
+        self.updated_at = Utc::now();
+    }
+
+    /// Get the rules for a direction
+    pub fn rules(&self, direction: RuleDirection) -> &[SecurityGroupRule] {
+        match direction {
+            RuleDirection::Ingress => &self.ingress,
+            RuleDirection::Egress => &self.egress,
+        }
+    }
+
+    /// Add a rule
+    pub fn add_rule(&mut self, direction: RuleDirection, rule: SecurityGroupRule) {
+        match direction {
+            RuleDirection::Ingress => self.ingress.push(rule),
+            RuleDirection::Egress => self.egress.push(rule),
+        }
+        self.updated_at = Utc::now();
+    }
+
+    /// Remove a rule by its position
+    pub fn remove_rule(&mut self, direction: RuleDirection, index: usize) -> Result<SecurityGroupRule, &'static str> {
+        let rules = match direction {
+            RuleDirection::Ingress => &mut self.ingress,
+            RuleDirection::Egress => &mut self.egress,
+        };
+
+        if index >= rules.len() {
+            return Err("Rule index out of range");
+        }
+
+        let rule = rules.remove(index);
+        self.updated_at = Utc::now();
+        Ok(rule)
+    }
+
+    /// Attach an instance explicitly
+    pub fn attach_instance(&mut self, instance_id: Uuid) -> bool {
+        let result = self.instances.insert(instance_id);
+        if result {
+            self.updated_at = Utc::now();
+        }
+        result
+    }
+
+    /// Detach an explicitly attached instance
+    pub fn detach_instance(&mut self, instance_id: &Uuid) -> bool {
+        let result = self.instances.remove(instance_id);
+        if result {
+            self.updated_at = Utc::now();
+        }
+        result
+    }
+
+    /// Check whether the group applies to an instance, either explicitly or by tag
+    pub fn applies_to(&self, instance: &Instance) -> bool {
+        if self.instances.contains(&instance.id) {
+            return true;
+        }
+
+        instance.tags.get(SECURITY_GROUPS_TAG)
+            .map(|groups| groups.split(',').any(|g| g.trim() == self.name))
+            .unwrap_or(false)
+    }
+}
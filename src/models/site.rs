@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+
+/// Backbone area every site has
+pub const BACKBONE_AREA: &str = "0";
+
+/// Default interface carrying the router loopback address
+pub const DEFAULT_LOOPBACK_INTERFACE: &str = "dum0";
+
+fn default_loopback_interface() -> String {
+    DEFAULT_LOOPBACK_INTERFACE.to_string()
+}
+
+fn default_area() -> String {
+    BACKBONE_AREA.to_string()
+}
+
+fn default_point_to_point() -> bool {
+    true
+}
+
+/// OSPF authentication of an area
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OspfAuthentication {
+    /// Cleartext password on every interface of the area
+    Plaintext {
+        password: String,
+    },
+    /// MD5 message digest on every interface of the area
+    Md5 {
+        key_id: u8,
+        key: String,
+    },
+}
+
+/// OSPF area of the site underlay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OspfArea {
+    /// Area ID, either a number or dotted quad
+    pub id: String,
+    /// Authentication used on the interfaces of this area
+    #[serde(default)]
+    pub authentication: Option<OspfAuthentication>,
+}
+
+/// Router participating in the site underlay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteRouter {
+    /// Name of the VyOS provider for this router
+    pub provider: String,
+    /// OSPF router ID, also advertised as a /32 on the loopback interface
+    pub router_id: Ipv4Addr,
+    /// Interface carrying the router ID
+    #[serde(default = "default_loopback_interface")]
+    pub loopback_interface: String,
+    /// Interfaces advertised into OSPF without forming adjacencies
+    #[serde(default)]
+    pub passive_interfaces: Vec<String>,
+    /// Area of the loopback and passive interfaces
+    #[serde(default = "default_area")]
+    pub area: String,
+}
+
+/// One side of a link between two routers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkEndpoint {
+    /// Router provider name
+    pub router: String,
+    /// Interface on that router
+    pub interface: String,
+}
+
+/// Point-to-point link between two routers of the site
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteLink {
+    /// The two ends of the link
+    pub endpoints: [LinkEndpoint; 2],
+    /// OSPF area of the link
+    #[serde(default = "default_area")]
+    pub area: String,
+    /// OSPF cost of both interfaces
+    #[serde(default)]
+    pub cost: Option<u32>,
+    /// Run the interfaces as OSPF point-to-point networks
+    #[serde(default = "default_point_to_point")]
+    pub point_to_point: bool,
+}
+
+impl SiteLink {
+    /// Get the endpoint on a router and the endpoint on its peer
+    pub fn endpoints_for(&self, router: &str) -> Option<(&LinkEndpoint, &LinkEndpoint)> {
+        let [a, b] = &self.endpoints;
+        if a.router == router {
+            Some((a, b))
+        } else if b.router == router {
+            Some((b, a))
+        } else {
+            None
+        }
+    }
+}
+
+/// Site topology describing the OSPF underlay between VyOS routers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Site {
+    /// Site name
+    pub name: String,
+    /// OSPF areas (the backbone is implied when not listed)
+    #[serde(default)]
+    pub areas: Vec<OspfArea>,
+    /// Routers in the site
+    #[serde(default)]
+    pub routers: Vec<SiteRouter>,
+    /// Links between routers
+    #[serde(default)]
+    pub links: Vec<SiteLink>,
+}
+
+impl Site {
+    /// Get a router by provider name
+    pub fn get_router(&self, provider: &str) -> Option<&SiteRouter> {
+        self.routers.iter().find(|r| r.provider == provider)
+    }
+
+    /// Get an area by ID
+    pub fn get_area(&self, id: &str) -> Option<&OspfArea> {
+        self.areas.iter().find(|a| a.id == id)
+    }
+
+    /// Router IDs a router is expected to form adjacencies with
+    pub fn expected_neighbors(&self, provider: &str) -> Vec<(Ipv4Addr, String)> {
+        self.links.iter()
+            .filter_map(|link| link.endpoints_for(provider))
+            .filter_map(|(local, peer)| {
+                self.get_router(&peer.router).map(|r| (r.router_id, local.interface.clone()))
+            })
+            .collect()
+    }
+}
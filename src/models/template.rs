@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::models::dns::sanitize_label;
+
+/// Tag recording the template an instance or volume was created from
+pub const TEMPLATE_TAG: &str = "template";
+
+/// Volume types a template volume can ask for
+const VOLUME_TYPES: [&str; 5] = ["standard", "ssd", "nvme", "hdd", "network"];
+
+/// Volume created and attached with every instance of a template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateVolume {
+    /// Volume name, prefixed with the instance name
+    pub name: String,
+    /// Size in GB
+    pub size_gb: u16,
+    /// Volume type (standard, ssd, nvme, hdd or network)
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub volume_type: Option<String>,
+    /// Proxmox storage the volume is allocated on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<String>,
+    /// Mount path inside a VyOS container
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_path: Option<String>,
+}
+
+/// Instance template defined under `[templates.<name>]` in the settings
+///
+/// Unset values fall back to the command line and then to the settings'
+/// defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceTemplate {
+    /// What the template is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Region
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// CPU cores
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<u8>,
+    /// Memory in GB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_gb: Option<u16>,
+    /// Disk in GB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_gb: Option<u16>,
+    /// Container image on VyOS, VM template (name or VMID) to clone on Proxmox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// bbctl networks the instance is connected to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub networks: Vec<String>,
+    /// Volumes created and attached with the instance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<TemplateVolume>,
+}
+
+impl InstanceTemplate {
+    /// Apply values given on the command line over the template's
+    pub fn overridden_by(&self, overrides: &InstanceTemplate) -> InstanceTemplate {
+        InstanceTemplate {
+            description: overrides.description.clone().or_else(|| self.description.clone()),
+            provider: overrides.provider.clone().or_else(|| self.provider.clone()),
+            region: overrides.region.clone().or_else(|| self.region.clone()),
+            cpu: overrides.cpu.or(self.cpu),
+            memory_gb: overrides.memory_gb.or(self.memory_gb),
+            disk_gb: overrides.disk_gb.or(self.disk_gb),
+            image: overrides.image.clone().or_else(|| self.image.clone()),
+            networks: if overrides.networks.is_empty() { self.networks.clone() } else { overrides.networks.clone() },
+            volumes: if overrides.volumes.is_empty() { self.volumes.clone() } else { overrides.volumes.clone() },
+        }
+    }
+
+    /// Check the values that do not depend on providers or state
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.cpu == Some(0) || self.memory_gb == Some(0) || self.disk_gb == Some(0) {
+            return Err("CPU, memory and disk must be at least 1");
+        }
+        if self.image.as_deref().is_some_and(|image| image.trim().is_empty()) {
+            return Err("The image is empty");
+        }
+
+        let mut networks = HashSet::new();
+        if !self.networks.iter().all(|network| networks.insert(network)) {
+            return Err("A network is listed more than once");
+        }
+
+        let mut volumes = HashSet::new();
+        for volume in &self.volumes {
+            if sanitize_label(&volume.name).is_empty() {
+                return Err("Volume names must contain letters or digits");
+            }
+            if !volumes.insert(&volume.name) {
+                return Err("A volume name is used more than once");
+            }
+            if volume.size_gb == 0 {
+                return Err("Volumes must be at least 1 GB");
+            }
+            if volume.volume_type.as_deref().is_some_and(|t| !VOLUME_TYPES.contains(&t.to_lowercase().as_str())) {
+                return Err("Volume types are standard, ssd, nvme, hdd or network");
+            }
+            if volume.mount_path.as_deref().is_some_and(|path| !path.starts_with('/')) {
+                return Err("Mount paths must be absolute");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Name of a template volume created for an instance
+    pub fn volume_name(instance: &str, volume: &TemplateVolume) -> String {
+        format!("{}-{}", instance, volume.name)
+    }
+}
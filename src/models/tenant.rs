@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Bandwidth limits of a tenant
+///
+/// The limits are enforced on every router interface carrying one of the
+/// tenant's networks, each interface on its own.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantLimits {
+    /// Limit on traffic sent by the tenant (e.g. `200mbit`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub egress_bandwidth: Option<String>,
+    /// Limit on traffic received by the tenant (e.g. `500mbit`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingress_bandwidth: Option<String>,
+}
+
+impl TenantLimits {
+    /// Check whether neither direction is limited
+    pub fn is_unlimited(&self) -> bool {
+        self.egress_bandwidth.is_none() && self.ingress_bandwidth.is_none()
+    }
+}
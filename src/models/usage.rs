@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+
+/// Traffic of one tenant during one time bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficSample {
+    /// Start of the bucket
+    pub bucket: DateTime<Utc>,
+    /// Tenant the traffic is accounted to
+    pub tenant: String,
+    /// Bytes sent by the tenant
+    pub bytes_out: u64,
+    /// Bytes received by the tenant
+    pub bytes_in: u64,
+    /// Packets sent by the tenant
+    pub packets_out: u64,
+    /// Packets received by the tenant
+    pub packets_in: u64,
+    /// Flow records accounted
+    pub flows: u64,
+}
+
+impl TrafficSample {
+    /// Create an empty sample
+    pub fn new(bucket: DateTime<Utc>, tenant: &str) -> Self {
+        Self {
+            bucket,
+            tenant: tenant.to_string(),
+            bytes_out: 0,
+            bytes_in: 0,
+            packets_out: 0,
+            packets_in: 0,
+            flows: 0,
+        }
+    }
+
+    /// Add the counters of another sample
+    pub fn add(&mut self, other: &TrafficSample) {
+        self.bytes_out += other.bytes_out;
+        self.bytes_in += other.bytes_in;
+        self.packets_out += other.packets_out;
+        self.packets_in += other.packets_in;
+        self.flows += other.flows;
+    }
+}
+
+/// Parse a duration such as `30m`, `12h`, `7d` or `2w`
+pub fn parse_duration(s: &str) -> Result<Duration, &'static str> {
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(digits);
+
+    let value = value.parse::<i64>().map_err(|_| "Duration must start with a number")?;
+    match unit {
+        "s" => Ok(Duration::seconds(value)),
+        "m" => Ok(Duration::minutes(value)),
+        "h" => Ok(Duration::hours(value)),
+        "d" => Ok(Duration::days(value)),
+        "w" => Ok(Duration::weeks(value)),
+        _ => Err("Duration unit must be s, m, h, d or w"),
+    }
+}
+
+/// Format a byte count with a binary unit
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -13,6 +13,7 @@ pub enum VolumeStatus {
     Creating,
     Deleting,
     Error,
+    Missing,
     Unknown,
 }
 
@@ -23,6 +24,7 @@ impl std::fmt::Display for VolumeStatus {
             VolumeStatus::InUse => write!(f, "in-use"),
             VolumeStatus::Creating => write!(f, "creating"),
             VolumeStatus::Deleting => write!(f, "deleting"),
+            VolumeStatus::Missing => write!(f, "missing"),
             VolumeStatus::Error => write!(f, "error"),
             VolumeStatus::Unknown => write!(f, "unknown"),
         }
@@ -36,6 +38,7 @@ impl From<&str> for VolumeStatus {
             "in-use" | "inuse" | "in_use" => VolumeStatus::InUse,
             "creating" => VolumeStatus::Creating,
             "deleting" => VolumeStatus::Deleting,
+            "missing" => VolumeStatus::Missing,
             "error" => VolumeStatus::Error,
             _ => VolumeStatus::Unknown,
         }
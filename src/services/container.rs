@@ -0,0 +1,111 @@
+use anyhow::{Result, anyhow};
+use log::debug;
+
+use crate::api::vyos::{ConfigCommand, VyOSClient};
+use crate::models::container::ContainerSpec;
+use crate::models::dns::sanitize_label;
+use crate::models::instance::InstanceSize;
+
+/// Prefix of containers created by bbctl
+pub const CONTAINER_PREFIX: &str = "bb-";
+
+/// Name of the VyOS container backing an instance
+pub fn container_name(instance_name: &str) -> Result<String> {
+    let label = sanitize_label(instance_name);
+    if label.is_empty() {
+        return Err(anyhow!("Instance name '{}' cannot be used as a container name", instance_name));
+    }
+
+    Ok(format!("{}{}", CONTAINER_PREFIX, label))
+}
+
+/// Validate a container spec before it is rendered
+pub fn validate_container_spec(spec: &ContainerSpec) -> Result<()> {
+    if spec.image.trim().is_empty() {
+        return Err(anyhow!("Container image is required"));
+    }
+    if spec.network.is_none() && (spec.address.is_some() || spec.network_prefix.is_some()) {
+        return Err(anyhow!("A container address or network prefix requires a network"));
+    }
+
+    Ok(())
+}
+
+/// Render the VyOS configuration of a container
+pub fn render_container_config(
+    name: &str,
+    spec: &ContainerSpec,
+    size: &InstanceSize,
+    description: &str,
+) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+    let base = ["container", "name", name];
+
+    if let (Some(network), Some(prefix)) = (&spec.network, &spec.network_prefix) {
+        commands.push(ConfigCommand::set(["container", "network", network.as_str(), "prefix", prefix.as_str()]));
+    }
+
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["image", spec.image.as_str()])));
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["description", description])));
+
+    // Instance sizes map onto the container resource limits
+    let memory = (u32::from(size.memory_gb) * 1024).to_string();
+    let cpu = size.cpu.to_string();
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["memory", memory.as_str()])));
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["cpu-quota", cpu.as_str()])));
+
+    match &spec.network {
+        Some(network) => {
+            let network_base: Vec<&str> = base.iter().copied().chain(["network", network.as_str()]).collect();
+            match spec.address {
+                Some(address) => {
+                    let address = address.to_string();
+                    commands.push(ConfigCommand::set(network_base.iter().copied().chain(["address", address.as_str()])));
+                },
+                None => commands.push(ConfigCommand::set(network_base)),
+            }
+        },
+        None => commands.push(ConfigCommand::set(base.iter().copied().chain(["allow-host-networks"]))),
+    }
+
+    for (key, value) in &spec.environment {
+        commands.push(ConfigCommand::set(
+            base.iter().copied().chain(["environment", key.as_str(), "value", value.as_str()]),
+        ));
+    }
+
+    for (idx, volume) in spec.volumes.iter().enumerate() {
+        let volume_name = format!("vol{}", idx);
+        let volume_base: Vec<&str> = base.iter().copied().chain(["volume", volume_name.as_str()]).collect();
+        commands.push(ConfigCommand::set(volume_base.iter().copied().chain(["source", volume.source.as_str()])));
+        commands.push(ConfigCommand::set(volume_base.iter().copied().chain(["destination", volume.destination.as_str()])));
+        commands.push(ConfigCommand::set(
+            volume_base.iter().copied().chain(["mode", if volume.read_only { "ro" } else { "rw" }]),
+        ));
+    }
+
+    for port in &spec.ports {
+        let port_name = format!("{}-{}", port.protocol, port.source);
+        let source = port.source.to_string();
+        let destination = port.destination.to_string();
+        let port_base: Vec<&str> = base.iter().copied().chain(["port", port_name.as_str()]).collect();
+        commands.push(ConfigCommand::set(port_base.iter().copied().chain(["source", source.as_str()])));
+        commands.push(ConfigCommand::set(port_base.iter().copied().chain(["destination", destination.as_str()])));
+        commands.push(ConfigCommand::set(port_base.iter().copied().chain(["protocol", port.protocol.as_str()])));
+    }
+
+    commands
+}
+
+/// Pull a container image, falling back to the op-mode command over SSH
+///
+/// VyOS refuses to commit a container whose image is not present locally.
+pub async fn pull_image(client: &mut VyOSClient, image: &str) -> Result<()> {
+    if let Err(e) = client.add_container_image(image).await {
+        debug!("API image pull of {} failed, falling back to SSH: {}", image, e);
+        client.execute_op_command(&format!("add container image {}", image)).await
+            .map_err(|e| anyhow!("Failed to pull image '{}': {}", image, e))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,690 @@
+use anyhow::{Result, Context, anyhow};
+use futures::future::join_all;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::models::app::{AppHealthCheck, AppRegion, AppSpec, Release, ReleaseStatus, APP_TAG, RELEASE_TAG};
+use crate::models::container::{ContainerPort, ContainerSpec};
+use crate::models::dns::sanitize_label;
+use crate::models::instance::{Instance, InstanceSize, InstanceStatus, PROVIDER_TAG};
+use crate::models::network::Ipv4Cidr;
+use crate::models::provider::ProviderType;
+use crate::services::instance::InstanceService;
+use crate::services::network::NetworkService;
+use crate::services::provider::ProviderService;
+use crate::state::{FileStateStore, State, StateStore};
+
+/// Project file describing the application in the current directory
+pub const APP_FILE: &str = "bbctl.toml";
+
+/// Time a single health probe may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Load an application spec from a TOML file
+pub fn load_app_spec(path: &Path) -> Result<AppSpec> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read project file: {}", path.display()))?;
+
+    let spec: AppSpec = toml::from_str(&content)
+        .context("Failed to parse project TOML")?;
+
+    validate_app_spec(&spec)?;
+    Ok(spec)
+}
+
+/// Validate an application spec on its own, without looking at the providers
+pub fn validate_app_spec(spec: &AppSpec) -> Result<()> {
+    if sanitize_label(&spec.name).is_empty() {
+        return Err(anyhow!("Application name '{}' is not usable in instance names", spec.name));
+    }
+    match (&spec.image, &spec.template) {
+        (Some(_), Some(_)) => return Err(anyhow!("Set either an image or a template, not both")),
+        (None, None) => return Err(anyhow!("Set an image (VyOS containers) or a template (Proxmox VMs)")),
+        (Some(image), None) if image.trim().is_empty() => return Err(anyhow!("The image is empty")),
+        _ => {},
+    }
+    if spec.template.is_some() && (!spec.env.is_empty() || !spec.ports.is_empty() || spec.network_prefix.is_some()) {
+        return Err(anyhow!("Environment variables, ports and network prefixes only apply to container images"));
+    }
+    if spec.cpu == 0 || spec.memory_gb == 0 {
+        return Err(anyhow!("Instances need at least 1 CPU and 1 GB of memory"));
+    }
+    if spec.batch_size == 0 {
+        return Err(anyhow!("The batch size must be at least 1"));
+    }
+
+    for port in &spec.ports {
+        ContainerPort::parse(port).map_err(|e| anyhow!("Invalid port '{}': {}", port, e))?;
+    }
+    if let Some(prefix) = &spec.network_prefix {
+        if spec.network.is_none() {
+            return Err(anyhow!("A network prefix requires a network"));
+        }
+        Ipv4Cidr::parse(prefix).map_err(|e| anyhow!("Invalid network prefix '{}': {}", prefix, e))?;
+    }
+
+    if spec.regions.is_empty() {
+        return Err(anyhow!("No regions to deploy to"));
+    }
+    let mut seen = HashSet::new();
+    for region in &spec.regions {
+        if !seen.insert((&region.region, &region.provider)) {
+            return Err(anyhow!("Region '{}' on '{}' is listed more than once", region.region, region.provider));
+        }
+    }
+
+    // Containers on one router cannot publish the same router port
+    if !spec.ports.is_empty() {
+        let mut per_provider: BTreeMap<&str, u32> = BTreeMap::new();
+        for region in &spec.regions {
+            *per_provider.entry(region.provider.as_str()).or_insert(0) += region.count;
+        }
+        if let Some((provider, _)) = per_provider.iter().find(|(_, count)| **count > 1) {
+            return Err(anyhow!("Published ports allow a single instance per provider, '{}' would run more", provider));
+        }
+    }
+
+    if let Some(check) = &spec.health_check {
+        if check.interval_secs == 0 || check.timeout_secs == 0 {
+            return Err(anyhow!("Health check interval and timeout must be at least 1 second"));
+        }
+        if let Some(path) = &check.path {
+            if !path.starts_with('/') || path.chars().any(|c| c.is_whitespace() || "'\"`$\\".contains(c)) {
+                return Err(anyhow!("Invalid health check path '{}'", path));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Changes to an application's instances in one region
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionRollout {
+    /// Region and provider
+    pub region: AppRegion,
+    /// Instances already running the spec
+    pub keep: Vec<Uuid>,
+    /// Instances replaced by new ones
+    pub replace: Vec<Uuid>,
+    /// New instances added
+    pub create: u32,
+    /// Instances removed
+    pub remove: Vec<Uuid>,
+}
+
+/// Changes a deploy makes to an application's instances
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Rollout {
+    /// Changes per region
+    pub regions: Vec<RegionRollout>,
+    /// Instances in regions the spec no longer lists
+    pub retire: Vec<Uuid>,
+}
+
+impl Rollout {
+    /// Whether the deploy leaves every instance alone
+    pub fn is_empty(&self) -> bool {
+        self.retire.is_empty()
+            && self.regions.iter().all(|r| r.replace.is_empty() && r.create == 0 && r.remove.is_empty())
+    }
+}
+
+/// Instances of an application, by name
+fn app_instances<'a>(state: &'a State, app: &str) -> Vec<&'a Instance> {
+    let mut instances: Vec<&Instance> = state.instances.values()
+        .filter(|i| i.tags.get(APP_TAG).map(String::as_str) == Some(app))
+        .collect();
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+    instances
+}
+
+/// Release an instance was deployed by
+fn instance_release<'a>(state: &'a State, app: &str, instance: &Instance) -> Option<&'a Release> {
+    let number = instance.tags.get(RELEASE_TAG)?.parse::<u32>().ok()?;
+    state.releases.get(app)?.iter().find(|r| r.number == number)
+}
+
+/// Work out which instances a spec keeps, replaces, creates and removes
+///
+/// An instance is kept when the release it was deployed by ran the same
+/// instances as the spec and it is not broken. Outdated instances are
+/// replaced up to the wanted count, any beyond it are removed.
+pub fn plan_rollout(spec: &AppSpec, state: &State) -> Rollout {
+    let instances = app_instances(state, &spec.name);
+    let mut rollout = Rollout::default();
+
+    for region in &spec.regions {
+        let (current, outdated): (Vec<&Instance>, Vec<&Instance>) = instances.iter()
+            .filter(|i| i.region == region.region && i.tags.get(PROVIDER_TAG) == Some(&region.provider))
+            .partition(|i| {
+                !matches!(i.status, InstanceStatus::Failed | InstanceStatus::Missing)
+                    && instance_release(state, &spec.name, i).is_some_and(|r| r.spec.same_instances(spec))
+            });
+
+        let count = region.count as usize;
+        let keep: Vec<Uuid> = current.iter().take(count).map(|i| i.id).collect();
+        let replace: Vec<Uuid> = outdated.iter().take(count - keep.len()).map(|i| i.id).collect();
+        let remove = current.iter().skip(count)
+            .chain(outdated.iter().skip(replace.len()))
+            .map(|i| i.id)
+            .collect();
+
+        rollout.regions.push(RegionRollout {
+            region: region.clone(),
+            create: (count - keep.len() - replace.len()) as u32,
+            keep,
+            replace,
+            remove,
+        });
+    }
+
+    rollout.retire = instances.iter()
+        .filter(|i| !spec.regions.iter().any(|r| i.region == r.region && i.tags.get(PROVIDER_TAG) == Some(&r.provider)))
+        .map(|i| i.id)
+        .collect();
+    rollout
+}
+
+/// Name for a new instance of an application, the lowest free index in the region
+fn next_instance_name(state: &State, app: &str, region: &str) -> String {
+    let taken: HashSet<&str> = state.instances.values().map(|i| i.name.as_str()).collect();
+    (1..)
+        .map(|index| format!("{}-{}-{}", sanitize_label(app), sanitize_label(region), index))
+        .find(|name| !taken.contains(name.as_str()))
+        .unwrap_or_default()
+}
+
+/// Next free container address on a router's container network
+///
+/// The first host is the router's own address on the network.
+fn next_container_address(state: &State, provider: &str, network: &str, prefix: &str) -> Result<Ipv4Addr> {
+    let used: HashSet<String> = state.instances.values()
+        .filter(|i| i.tags.get(PROVIDER_TAG).map(String::as_str) == Some(provider))
+        .flat_map(|i| i.networks.iter())
+        .filter(|n| n.network_id == network)
+        .filter_map(|n| n.ip.clone())
+        .collect();
+
+    let cidr = Ipv4Cidr::parse(prefix).map_err(|e| anyhow!("Invalid network prefix '{}': {}", prefix, e))?;
+    cidr.hosts().skip(1)
+        .find(|ip| !used.contains(&ip.to_string()))
+        .ok_or_else(|| anyhow!("Container network '{}' on '{}' has no free addresses", network, provider))
+}
+
+/// Whether an HTTP status passes a health check
+fn status_passes(check: &AppHealthCheck, status: u16) -> bool {
+    match check.expect_status {
+        Some(expected) => status == expected,
+        None => (200..400).contains(&status),
+    }
+}
+
+/// Changes made by a rollout, undone when it fails
+#[derive(Debug, Default)]
+struct Journal {
+    /// Instances created
+    created: Vec<Uuid>,
+    /// Records of the instances removed
+    removed: Vec<Instance>,
+}
+
+/// Deploy service rolling out applications
+pub struct DeployService {
+    store: Arc<dyn StateStore>,
+    provider_service: ProviderService,
+}
+
+impl DeployService {
+    /// Create a new deploy service backed by the state store
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Ok(Self::with_store(provider_service, Arc::new(FileStateStore::open()?)))
+    }
+
+    /// Create a new deploy service backed by a specific state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Self {
+        Self { store, provider_service }
+    }
+
+    /// Releases of an application, oldest first
+    pub fn list_releases(&self, app: &str) -> Result<Vec<Release>> {
+        Ok(self.store.load()?.releases.remove(app).unwrap_or_default())
+    }
+
+    /// Names of the applications with releases
+    pub fn list_apps(&self) -> Result<Vec<String>> {
+        Ok(self.store.load()?.releases.into_keys().collect())
+    }
+
+    /// Work out the changes a deploy of a spec would make
+    pub fn plan(&self, spec: &AppSpec) -> Result<Rollout> {
+        validate_app_spec(spec)?;
+        for region in &spec.regions {
+            let provider = self.provider_service.get_provider(&region.provider)
+                .ok_or_else(|| anyhow!("Provider not found: {}", region.provider))?;
+            match (provider.provider_type, &spec.image) {
+                (ProviderType::VyOS, None) => return Err(anyhow!("Provider '{}' runs containers and needs an image", region.provider)),
+                (ProviderType::Proxmox, Some(_)) => return Err(anyhow!("Provider '{}' runs VMs and needs a template", region.provider)),
+                _ => {},
+            }
+        }
+        Ok(plan_rollout(spec, &self.store.load()?))
+    }
+
+    /// Deploy a spec as a new release
+    ///
+    /// New instances are created before outdated ones are removed, a batch at
+    /// a time, and each batch must pass the health check before the rollout
+    /// goes on. When anything fails, the instances created are removed and
+    /// the ones removed are recreated from their own releases. The release
+    /// records the outcome either way.
+    pub async fn deploy(&self, spec: &AppSpec) -> Result<Release> {
+        let rollout = self.plan(spec)?;
+
+        let mut number = 0;
+        self.store.transaction(&mut |state| {
+            let releases = state.releases.entry(spec.name.clone()).or_default();
+            number = releases.last().map(|r| r.number + 1).unwrap_or(1);
+            releases.push(Release::new(number, spec.clone()));
+            Ok(())
+        })?;
+        info!("Deploying release {} of '{}'", number, spec.name);
+
+        let mut journal = Journal::default();
+        let (status, error) = match self.roll_out(spec, number, &rollout, &mut journal).await {
+            Ok(()) => (ReleaseStatus::Succeeded, None),
+            Err(e) => {
+                warn!("Release {} of '{}' failed, rolling back: {:#}", number, spec.name, e);
+                let failures = self.roll_back(&journal).await;
+                if failures.is_empty() {
+                    (ReleaseStatus::RolledBack, Some(format!("{:#}", e)))
+                } else {
+                    (ReleaseStatus::Failed, Some(format!("{:#}; rollback failed: {}", e, failures.join("; "))))
+                }
+            },
+        };
+
+        let state = self.store.transaction(&mut |state| {
+            let instances = app_instances(state, &spec.name).iter().map(|i| i.id).collect();
+            let release = state.releases.get_mut(&spec.name)
+                .and_then(|releases| releases.iter_mut().find(|r| r.number == number))
+                .ok_or_else(|| anyhow!("Release {} of '{}' not found", number, spec.name))?;
+            release.instances = instances;
+            release.finish(status, error.clone());
+            Ok(())
+        })?;
+
+        state.releases.get(&spec.name)
+            .and_then(|releases| releases.iter().find(|r| r.number == number))
+            .cloned()
+            .ok_or_else(|| anyhow!("Release {} of '{}' not found", number, spec.name))
+    }
+
+    /// Run the changes of a rollout
+    async fn roll_out(&self, spec: &AppSpec, number: u32, rollout: &Rollout, journal: &mut Journal) -> Result<()> {
+        // Published router ports cannot be shared, so old containers make way first
+        let remove_first = !spec.ports.is_empty();
+        if remove_first && rollout.regions.iter().any(|r| !r.replace.is_empty()) {
+            warn!("'{}' publishes ports, its replaced containers are down until their replacements are healthy", spec.name);
+        }
+
+        for region in &rollout.regions {
+            let work: Vec<Option<Uuid>> = region.replace.iter().copied().map(Some)
+                .chain((0..region.create).map(|_| None))
+                .collect();
+
+            for batch in work.chunks(spec.batch_size as usize) {
+                let old: Vec<Uuid> = batch.iter().flatten().copied().collect();
+                if remove_first {
+                    for id in &old {
+                        self.remove_instance(id, journal).await?;
+                    }
+                }
+
+                let mut created = Vec::new();
+                for _ in batch {
+                    created.push(self.create_instance(spec, number, &region.region, journal).await?);
+                }
+                if let Some(check) = &spec.health_check {
+                    let results = join_all(created.iter().map(|id| self.wait_healthy(check, id))).await;
+                    results.into_iter().collect::<Result<Vec<_>>>()?;
+                }
+
+                if !remove_first {
+                    for id in &old {
+                        self.remove_instance(id, journal).await?;
+                    }
+                }
+            }
+
+            for id in &region.remove {
+                self.remove_instance(id, journal).await?;
+            }
+        }
+
+        for id in &rollout.retire {
+            self.remove_instance(id, journal).await?;
+        }
+        Ok(())
+    }
+
+    /// Undo a failed rollout, returning what could not be undone
+    async fn roll_back(&self, journal: &Journal) -> Vec<String> {
+        let mut failures = Vec::new();
+        let mut scratch = Journal::default();
+
+        for id in journal.created.iter().rev() {
+            if let Err(e) = self.remove_instance(id, &mut scratch).await {
+                failures.push(format!("removing {}: {:#}", id, e));
+            }
+        }
+
+        for instance in journal.removed.iter().rev() {
+            let state = match self.store.load() {
+                Ok(state) => state,
+                Err(e) => {
+                    failures.push(format!("recreating '{}': {:#}", instance.name, e));
+                    continue;
+                },
+            };
+            let app = instance.tags.get(APP_TAG).cloned().unwrap_or_default();
+            let release = match instance_release(&state, &app, instance) {
+                Some(release) => release.clone(),
+                None => {
+                    failures.push(format!("recreating '{}': its release is unknown", instance.name));
+                    continue;
+                },
+            };
+            let region = AppRegion {
+                region: instance.region.clone(),
+                provider: instance.tags.get(PROVIDER_TAG).cloned().unwrap_or_default(),
+                count: 1,
+            };
+            if let Err(e) = self.create_instance(&release.spec, release.number, &region, &mut scratch).await {
+                failures.push(format!("recreating '{}': {:#}", instance.name, e));
+            }
+        }
+
+        failures
+    }
+
+    /// Create an instance of a release and record it in the journal
+    async fn create_instance(&self, spec: &AppSpec, number: u32, region: &AppRegion, journal: &mut Journal) -> Result<Uuid> {
+        let state = self.store.load()?;
+        let name = next_instance_name(&state, &spec.name, &region.region);
+        let size = InstanceSize { cpu: spec.cpu, memory_gb: spec.memory_gb, disk_gb: spec.disk_gb };
+        let mut instances = InstanceService::with_store(self.provider_service.clone(), self.store.clone())?;
+
+        let id = match (&spec.image, &spec.template) {
+            (Some(image), _) => {
+                let address = match (&spec.network, &spec.network_prefix) {
+                    (Some(network), Some(prefix)) => Some(next_container_address(&state, &region.provider, network, prefix)?),
+                    _ => None,
+                };
+                let container = ContainerSpec {
+                    image: image.clone(),
+                    network: spec.network.clone(),
+                    network_prefix: spec.network_prefix.clone(),
+                    address,
+                    environment: spec.env.clone(),
+                    volumes: Vec::new(),
+                    ports: spec.ports.iter()
+                        .map(|p| ContainerPort::parse(p).map_err(|e| anyhow!("Invalid port '{}': {}", p, e)))
+                        .collect::<Result<_>>()?,
+                };
+                instances.create_vyos_instance(&name, &region.provider, &region.region, size, container).await?
+            },
+            (None, Some(template)) => {
+                instances.clone_proxmox_instance(&name, &region.provider, &region.region, size, template).await?
+            },
+            (None, None) => return Err(anyhow!("Set an image or a template")),
+        };
+        journal.created.push(id);
+        info!("Created instance '{}' for release {} of '{}'", name, number, spec.name);
+
+        let tags = BTreeMap::from([
+            (APP_TAG.to_string(), spec.name.clone()),
+            (RELEASE_TAG.to_string(), number.to_string()),
+        ]);
+        instances.set_tags(&id, &tags).await?;
+
+        // VMs connect to their bbctl network, containers are addressed on the router
+        if let (Some(network), Some(_)) = (&spec.network, &spec.template) {
+            let network_id = state.networks.values()
+                .find(|n| n.name == *network)
+                .map(|n| n.id)
+                .ok_or_else(|| anyhow!("Network not found: {}", network))?;
+            NetworkService::with_store(self.provider_service.clone(), self.store.clone())?
+                .connect_instance(&network_id, &id, None).await?;
+        }
+
+        Ok(id)
+    }
+
+    /// Remove an instance and record it in the journal
+    async fn remove_instance(&self, id: &Uuid, journal: &mut Journal) -> Result<()> {
+        let state = self.store.load()?;
+        let instance = state.instances.get(id).cloned()
+            .ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+
+        InstanceService::with_store(self.provider_service.clone(), self.store.clone())?
+            .delete_instance(id).await?;
+
+        info!("Removed instance '{}'", instance.name);
+        journal.removed.push(instance);
+        Ok(())
+    }
+
+    /// Wait for an instance to pass its health check
+    async fn wait_healthy(&self, check: &AppHealthCheck, id: &Uuid) -> Result<()> {
+        let instance = self.store.load()?.instances.remove(id)
+            .ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(check.timeout_secs);
+
+        loop {
+            let error = match self.probe(check, &instance).await {
+                Ok(()) => {
+                    info!("Instance '{}' is healthy", instance.name);
+                    return Ok(());
+                },
+                Err(e) => e,
+            };
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow!("Instance '{}' is not healthy after {} seconds: {:#}", instance.name, check.timeout_secs, error));
+            }
+            tokio::time::sleep(Duration::from_secs(check.interval_secs)).await;
+        }
+    }
+
+    /// Check the health of an instance once
+    ///
+    /// Containers are checked from their router, which can reach the
+    /// container networks, and through the router's own address when they use
+    /// host networking. VMs are checked directly on their network address.
+    async fn probe(&self, check: &AppHealthCheck, instance: &Instance) -> Result<()> {
+        let address = instance.networks.iter()
+            .find_map(|n| n.ip.as_deref().and_then(|ip| ip.parse::<IpAddr>().ok()));
+        let provider = self.provider_service.find_provider_name(instance)?;
+
+        match instance.provider {
+            ProviderType::VyOS => {
+                let address = address.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+                let client = self.provider_service.get_vyos_client(&provider)?;
+                let timeout = PROBE_TIMEOUT.as_secs();
+                match &check.path {
+                    Some(path) => {
+                        let command = format!(
+                            "curl -s -o /dev/null -m {} -w \"%{{http_code}}\" http://{}:{}{}",
+                            timeout, address, check.port, path
+                        );
+                        let output = client.execute_ssh_command(&command).await?;
+                        let status = output.trim().parse::<u16>()
+                            .map_err(|_| anyhow!("No HTTP response from {}:{}", address, check.port))?;
+                        if !status_passes(check, status) {
+                            return Err(anyhow!("HTTP status {}", status));
+                        }
+                    },
+                    None => {
+                        let command = format!("timeout {} bash -c \"</dev/tcp/{}/{}\"", timeout, address, check.port);
+                        client.execute_ssh_command(&command).await
+                            .map_err(|_| anyhow!("Port {} on {} is closed", check.port, address))?;
+                    },
+                }
+            },
+            ProviderType::Proxmox => {
+                let address = address
+                    .ok_or_else(|| anyhow!("Instance '{}' has no address to check", instance.name))?;
+                match &check.path {
+                    Some(path) => {
+                        let client = reqwest::Client::builder().timeout(PROBE_TIMEOUT).build()?;
+                        let url = format!("http://{}:{}{}", address, check.port, path);
+                        let status = client.get(&url).send().await?.status().as_u16();
+                        if !status_passes(check, status) {
+                            return Err(anyhow!("HTTP status {}", status));
+                        }
+                    },
+                    None => {
+                        tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect((address, check.port))).await
+                            .map_err(|_| anyhow!("Connecting to {}:{} timed out", address, check.port))??;
+                    },
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOP: &str = r#"
+        name = "shop"
+        image = "acme/shop:1"
+
+        [[regions]]
+        region = "eu"
+        provider = "edge"
+        count = 1
+    "#;
+
+    fn spec(toml: &str) -> AppSpec {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// State running `count` instances deployed by release 1 of a spec
+    fn deployed(spec: &AppSpec, region: &str, count: usize) -> State {
+        let mut state = State::default();
+        state.releases.insert(spec.name.clone(), vec![Release::new(1, spec.clone())]);
+        for index in 1..=count {
+            add_instance(&mut state, spec, region, index);
+        }
+        state
+    }
+
+    fn add_instance(state: &mut State, spec: &AppSpec, region: &str, index: usize) -> Uuid {
+        let size = InstanceSize { cpu: spec.cpu, memory_gb: spec.memory_gb, disk_gb: spec.disk_gb };
+        let mut instance = Instance::new(format!("shop-{}-{}", region, index), ProviderType::VyOS, region.to_string(), size);
+        instance.status = InstanceStatus::Running;
+        instance.add_tag(APP_TAG.to_string(), spec.name.clone());
+        instance.add_tag(RELEASE_TAG.to_string(), "1".to_string());
+        instance.add_tag(PROVIDER_TAG.to_string(), "edge".to_string());
+        let id = instance.id;
+        state.instances.insert(id, instance);
+        id
+    }
+
+    /// Instance IDs of the application, in name order
+    fn ids(state: &State) -> Vec<Uuid> {
+        app_instances(state, "shop").iter().map(|i| i.id).collect()
+    }
+
+    #[test]
+    fn scaling_up_creates_the_missing_instances() {
+        let state = deployed(&spec(SHOP), "eu", 1);
+        let rollout = plan_rollout(&spec(&SHOP.replace("count = 1", "count = 3")), &state);
+
+        let region = &rollout.regions[0];
+        assert_eq!(region.keep, ids(&state));
+        assert_eq!(region.create, 2);
+        assert!(region.replace.is_empty() && region.remove.is_empty() && rollout.retire.is_empty());
+    }
+
+    #[test]
+    fn scaling_down_removes_the_extra_instances() {
+        let spec = spec(SHOP);
+        let state = deployed(&spec, "eu", 3);
+        let rollout = plan_rollout(&spec, &state);
+
+        let region = &rollout.regions[0];
+        assert_eq!(region.keep, ids(&state)[..1]);
+        assert_eq!(region.remove, ids(&state)[1..]);
+        assert_eq!(region.create, 0);
+        assert!(plan_rollout(&spec, &deployed(&spec, "eu", 1)).is_empty());
+    }
+
+    #[test]
+    fn outdated_instances_are_replaced_up_to_the_count() {
+        let old = spec(&SHOP.replace("count = 1", "count = 3"));
+        let state = deployed(&old, "eu", 3);
+        let new = spec(&SHOP.replace("acme/shop:1", "acme/shop:2").replace("count = 1", "count = 2"));
+        let rollout = plan_rollout(&new, &state);
+
+        let region = &rollout.regions[0];
+        assert!(region.keep.is_empty());
+        assert_eq!(region.replace, ids(&state)[..2]);
+        assert_eq!(region.remove, ids(&state)[2..]);
+        assert_eq!(region.create, 0);
+    }
+
+    #[test]
+    fn failed_instances_are_replaced() {
+        let spec = spec(&SHOP.replace("count = 1", "count = 2"));
+        let mut state = deployed(&spec, "eu", 2);
+        let failed = ids(&state)[0];
+        state.instances.get_mut(&failed).unwrap().status = InstanceStatus::Failed;
+        let rollout = plan_rollout(&spec, &state);
+
+        let region = &rollout.regions[0];
+        assert_eq!(region.keep, ids(&state)[1..]);
+        assert_eq!(region.replace, [failed]);
+        assert_eq!(region.create, 0);
+    }
+
+    #[test]
+    fn instances_in_unlisted_regions_are_retired() {
+        let spec = spec(SHOP);
+        let mut state = deployed(&spec, "eu", 1);
+        let retired = add_instance(&mut state, &spec, "us", 1);
+        let rollout = plan_rollout(&spec, &state);
+
+        assert_eq!(rollout.retire, [retired]);
+        assert!(rollout.regions[0].replace.is_empty() && rollout.regions[0].create == 0);
+    }
+
+    #[test]
+    fn app_specs_are_validated() {
+        assert!(validate_app_spec(&spec(SHOP)).is_ok());
+
+        let invalid = [
+            SHOP.replace("image = \"acme/shop:1\"", "image = \"acme/shop:1\"\ntemplate = \"debian\""),
+            SHOP.replace("image = \"acme/shop:1\"", ""),
+            SHOP.replace("image = \"acme/shop:1\"", "image = \"acme/shop:1\"\nbatch_size = 0"),
+            SHOP.replace("image = \"acme/shop:1\"", "image = \"acme/shop:1\"\nnetwork_prefix = \"172.20.0.0/24\""),
+            SHOP.replace("image = \"acme/shop:1\"", "image = \"acme/shop:1\"\nports = [\"8080:80\"]").replace("count = 1", "count = 2"),
+            SHOP.replace("image = \"acme/shop:1\"", "template = \"debian\"\nenv = { MODE = \"production\" }"),
+            format!("{}\n[[regions]]\nregion = \"eu\"\nprovider = \"edge\"", SHOP),
+        ];
+        for toml in &invalid {
+            assert!(validate_app_spec(&spec(toml)).is_err(), "accepted {}", toml);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::config::dns_records::DnsRecords;
+use crate::models::dns::{instance_fqdn, DnsRecord, DnsRecordType, INTERNAL_DOMAIN};
+use crate::models::network::Network;
+use crate::models::provider::ProviderType;
+use crate::services::provider::ProviderService;
+
+/// Check whether a VyOS config path is owned by internal DNS
+pub fn is_managed_dns_path(path: &[String]) -> bool {
+    match path {
+        [service, dns, forwarding, authoritative, domain, ..]
+            if service == "service" && dns == "dns" && forwarding == "forwarding" && authoritative == "authoritative-domain" => {
+            domain == INTERNAL_DOMAIN
+        },
+        _ => false,
+    }
+}
+
+/// Build the A records of every instance address on a set of networks
+pub fn instance_records(networks: &[&Network]) -> Vec<DnsRecord> {
+    let mut records = Vec::new();
+
+    for network in networks {
+        for alloc in &network.ip_allocations {
+            let (Some(hostname), std::net::IpAddr::V4(ip)) = (&alloc.hostname, alloc.ip) else {
+                continue;
+            };
+
+            let name = instance_fqdn(hostname, &network.name, network.tenant());
+            match DnsRecord::new(&name, DnsRecordType::A, &ip.to_string()) {
+                Ok(record) => records.push(record),
+                Err(e) => warn!("Skipping DNS record for '{}' on '{}': {}", hostname, network.name, e),
+            }
+        }
+    }
+
+    records
+}
+
+/// Render the authoritative zone for the internal domain
+///
+/// User records take precedence over generated instance records of the same name.
+pub fn render_dns_config(generated: &[DnsRecord], user: &[&DnsRecord]) -> Vec<ConfigCommand> {
+    let mut records: BTreeMap<&str, &DnsRecord> = BTreeMap::new();
+    for record in generated {
+        if records.insert(record.name.as_str(), record).is_some() {
+            warn!("Duplicate instance DNS name: {}", record.name);
+        }
+    }
+    for record in user {
+        records.insert(record.name.as_str(), record);
+    }
+
+    let base = ["service", "dns", "forwarding", "authoritative-domain", INTERNAL_DOMAIN, "records"];
+    records.values()
+        .map(|record| {
+            let kind = record.record_type.to_string();
+            let field = match record.record_type {
+                DnsRecordType::A => "address",
+                DnsRecordType::Cname => "target",
+            };
+            ConfigCommand::set(
+                base.iter().copied().chain([kind.as_str(), record.relative_name(), field, record.value.as_str()]),
+            )
+        })
+        .collect()
+}
+
+/// DNS service for publishing internal names on VyOS routers
+pub struct DnsService {
+    records: DnsRecords,
+    provider_service: ProviderService,
+}
+
+impl DnsService {
+    /// Create a new DNS service
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        let records = DnsRecords::load()?;
+
+        Ok(Self {
+            records,
+            provider_service,
+        })
+    }
+
+    /// List user-managed records
+    pub fn list_records(&self) -> Vec<&DnsRecord> {
+        let mut records: Vec<&DnsRecord> = self.records.get_all_records().values().collect();
+        records.sort_by(|a, b| a.name.cmp(&b.name));
+        records
+    }
+
+    /// Add a user-managed record
+    pub fn add_record(&mut self, name: &str, record_type: DnsRecordType, value: &str) -> Result<DnsRecord> {
+        let record = DnsRecord::new(name, record_type, value)
+            .map_err(|e| anyhow!("Invalid DNS record '{}': {}", name, e))?;
+
+        self.records.add_record(record.clone())?;
+        self.records.save()?;
+
+        info!("Added {} record {} -> {}", record.record_type, record.name, record.value);
+        Ok(record)
+    }
+
+    /// Delete a user-managed record
+    pub fn delete_record(&mut self, name: &str) -> Result<DnsRecord> {
+        let name = crate::models::dns::qualify_name(name)
+            .map_err(|e| anyhow!("Invalid DNS name '{}': {}", name, e))?;
+
+        let record = self.records.remove_record(&name)?;
+        self.records.save()?;
+
+        info!("Deleted DNS record: {}", name);
+        Ok(record)
+    }
+
+    /// Get the VyOS routers that answer for the internal domain
+    pub fn routers(&self) -> Vec<String> {
+        let mut routers: Vec<String> = self.provider_service.get_providers().iter()
+            .filter(|(_, p)| p.provider_type == ProviderType::VyOS)
+            .map(|(name, _)| name.clone())
+            .collect();
+        routers.sort();
+        routers
+    }
+
+    /// Render the internal zone for a set of networks
+    pub fn render(&self, networks: &[&Network]) -> Vec<ConfigCommand> {
+        let generated = instance_records(networks);
+        render_dns_config(&generated, &self.list_records())
+    }
+
+    /// Push the internal zone to one router
+    pub async fn sync_router(&self, router: &str, desired: &[ConfigCommand]) -> Result<usize> {
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["service", "dns", "forwarding"]).await
+            .context(format!("Failed to read DNS configuration from '{}'", router))?
+            .into_iter()
+            .filter(|c| is_managed_dns_path(c.path()))
+            .collect();
+
+        debug!("Router '{}' has {} managed DNS paths", router, current.len());
+        let diff = ConfigDiff::between(&current, desired);
+        if diff.is_empty() {
+            info!("DNS on '{}' is already up to date", router);
+            return Ok(0);
+        }
+
+        let commands = diff.commands();
+        client.configure(&commands).await
+            .context(format!("Failed to configure DNS on '{}'", router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+
+        info!("Applied {} DNS changes to '{}'", commands.len(), router);
+        Ok(commands.len())
+    }
+
+    /// Push the internal zone to every VyOS router
+    pub async fn sync(&self, networks: &[&Network]) -> Vec<(String, Result<usize>)> {
+        let desired = self.render(networks);
+
+        let mut results = Vec::new();
+        for router in self.routers() {
+            let result = self.sync_router(&router, &desired).await;
+            results.push((router, result));
+        }
+        results
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info, error};
+use std::collections::HashSet;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::api::vyos::ConfigCommand;
+use crate::api::vyos_op::{parse_bgp_summary, BgpNeighbor};
+use crate::models::fabric::{Fabric, FabricRouter};
+use crate::services::provider::ProviderService;
+
+/// Highest VNI representable in the 24-bit VXLAN header
+const MAX_VNI: u32 = 16_777_215;
+
+/// Outcome of pushing fabric configuration to one router
+#[derive(Debug, Clone)]
+pub struct FabricApplyResult {
+    /// Router provider name
+    pub provider: String,
+    /// Commands generated for the router
+    pub commands: Vec<ConfigCommand>,
+    /// Error, if the push failed
+    pub error: Option<String>,
+}
+
+/// EVPN control-plane state of one router
+#[derive(Debug, Clone)]
+pub struct RouterEvpnStatus {
+    /// Router provider name
+    pub provider: String,
+    /// Peers the fabric description expects
+    pub expected_peers: Vec<IpAddr>,
+    /// Neighbors reported by the router
+    pub neighbors: Vec<BgpNeighbor>,
+    /// Error, if the router could not be queried
+    pub error: Option<String>,
+}
+
+impl RouterEvpnStatus {
+    /// Expected peers that have no established session
+    pub fn missing_peers(&self) -> Vec<IpAddr> {
+        self.expected_peers.iter()
+            .filter(|peer| {
+                !self.neighbors.iter()
+                    .any(|n| n.is_established() && n.neighbor == peer.to_string())
+            })
+            .copied()
+            .collect()
+    }
+}
+
+/// Load a fabric description from a TOML file
+pub fn load_fabric(path: &Path) -> Result<Fabric> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read fabric file: {}", path.display()))?;
+
+    let fabric: Fabric = toml::from_str(&content)
+        .context("Failed to parse fabric TOML")?;
+
+    validate_fabric(&fabric)?;
+    Ok(fabric)
+}
+
+/// Validate a fabric description
+pub fn validate_fabric(fabric: &Fabric) -> Result<()> {
+    if fabric.routers.is_empty() {
+        return Err(anyhow!("Fabric '{}' has no routers", fabric.name));
+    }
+
+    let mut providers = HashSet::new();
+    let mut loopbacks = HashSet::new();
+    for router in &fabric.routers {
+        if !providers.insert(&router.provider) {
+            return Err(anyhow!("Router '{}' is listed more than once", router.provider));
+        }
+        if !loopbacks.insert(router.loopback) {
+            return Err(anyhow!("Loopback {} is used by more than one router", router.loopback));
+        }
+    }
+
+    let mut vnis = HashSet::new();
+    for vni in &fabric.vnis {
+        if vni.vni == 0 || vni.vni > MAX_VNI {
+            return Err(anyhow!("VNI {} is out of range (1-{})", vni.vni, MAX_VNI));
+        }
+        if !vnis.insert(vni.vni) {
+            return Err(anyhow!("VNI {} is defined more than once", vni.vni));
+        }
+    }
+
+    Ok(())
+}
+
+/// Render the VyOS configuration for one router of the fabric
+pub fn render_router_config(fabric: &Fabric, router: &FabricRouter) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+    let asn = fabric.asn.to_string();
+    let loopback = router.loopback.to_string();
+    let host_prefix = if router.loopback.is_ipv4() { "32" } else { "128" };
+
+    // Loopback carrying the router ID and VTEP address
+    commands.push(ConfigCommand::set([
+        "interfaces", "dummy", &router.loopback_interface,
+        "address", &format!("{}/{}", loopback, host_prefix),
+    ]));
+
+    // BGP instance with the EVPN address family
+    commands.push(ConfigCommand::set(["protocols", "bgp", "system-as", &asn]));
+    commands.push(ConfigCommand::set(["protocols", "bgp", "parameters", "router-id", &loopback]));
+    commands.push(ConfigCommand::set([
+        "protocols", "bgp", "address-family", "l2vpn-evpn", "advertise-all-vni",
+    ]));
+
+    for peer in fabric.peers_for(router) {
+        let peer = peer.to_string();
+        let neighbor = ["protocols", "bgp", "neighbor", peer.as_str()];
+
+        commands.push(ConfigCommand::set(neighbor.iter().copied().chain(["remote-as", "internal"])));
+        commands.push(ConfigCommand::set(
+            neighbor.iter().copied().chain(["update-source", router.loopback_interface.as_str()]),
+        ));
+        commands.push(ConfigCommand::set(
+            neighbor.iter().copied().chain(["address-family", "l2vpn-evpn"]),
+        ));
+    }
+
+    // One VXLAN interface per segment, bridged with its local members
+    let port = fabric.vxlan_port.to_string();
+    for vni in &fabric.vnis {
+        let vxlan = vni.vxlan_interface();
+        let bridge = vni.bridge_interface();
+        let vni_id = vni.vni.to_string();
+        let mtu = vni.mtu.to_string();
+
+        commands.push(ConfigCommand::set(["interfaces", "vxlan", &vxlan, "vni", &vni_id]));
+        commands.push(ConfigCommand::set(["interfaces", "vxlan", &vxlan, "source-address", &loopback]));
+        commands.push(ConfigCommand::set(["interfaces", "vxlan", &vxlan, "port", &port]));
+        commands.push(ConfigCommand::set(["interfaces", "vxlan", &vxlan, "mtu", &mtu]));
+        commands.push(ConfigCommand::set(["interfaces", "vxlan", &vxlan, "parameters", "nolearning"]));
+        if let Some(network) = &vni.network {
+            commands.push(ConfigCommand::set(["interfaces", "vxlan", &vxlan, "description", network]));
+        }
+
+        commands.push(ConfigCommand::set(["interfaces", "bridge", &bridge, "member", "interface", &vxlan]));
+        for member in &vni.bridge_members {
+            commands.push(ConfigCommand::set(["interfaces", "bridge", &bridge, "member", "interface", member]));
+        }
+    }
+
+    commands
+}
+
+/// Fabric service for managing the EVPN/VXLAN overlay between VyOS routers
+pub struct FabricService {
+    provider_service: ProviderService,
+}
+
+impl FabricService {
+    /// Create a new fabric service
+    pub fn new(provider_service: ProviderService) -> Self {
+        Self { provider_service }
+    }
+
+    /// Push the fabric configuration to every router
+    ///
+    /// Routers are configured independently, so a failure on one router is
+    /// reported in its result without stopping the others.
+    pub async fn apply(&self, fabric: &Fabric, dry_run: bool) -> Result<Vec<FabricApplyResult>> {
+        validate_fabric(fabric)?;
+
+        let mut results = Vec::new();
+        for router in &fabric.routers {
+            let commands = render_router_config(fabric, router);
+
+            if dry_run {
+                results.push(FabricApplyResult {
+                    provider: router.provider.clone(),
+                    commands,
+                    error: None,
+                });
+                continue;
+            }
+
+            info!("Applying fabric '{}' to router '{}'", fabric.name, router.provider);
+            let error = match self.push_config(&router.provider, &commands).await {
+                Ok(_) => None,
+                Err(e) => {
+                    error!("Failed to apply fabric to router '{}': {}", router.provider, e);
+                    Some(e.to_string())
+                }
+            };
+
+            results.push(FabricApplyResult {
+                provider: router.provider.clone(),
+                commands,
+                error,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Query EVPN neighbor state on every router
+    pub async fn status(&self, fabric: &Fabric) -> Result<Vec<RouterEvpnStatus>> {
+        let mut statuses = Vec::new();
+
+        for router in &fabric.routers {
+            let expected_peers = fabric.peers_for(router);
+
+            let result = async {
+                let mut client = self.provider_service.get_vyos_client(&router.provider)?;
+                client.show(&["bgp", "l2vpn", "evpn", "summary"]).await
+            }.await;
+
+            let status = match result {
+                Ok(output) => {
+                    debug!("EVPN summary from '{}': {}", router.provider, output);
+                    RouterEvpnStatus {
+                        provider: router.provider.clone(),
+                        expected_peers,
+                        neighbors: parse_bgp_summary(&output),
+                        error: None,
+                    }
+                },
+                Err(e) => RouterEvpnStatus {
+                    provider: router.provider.clone(),
+                    expected_peers,
+                    neighbors: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            };
+
+            statuses.push(status);
+        }
+
+        Ok(statuses)
+    }
+
+    /// Send configuration to a router and persist it
+    async fn push_config(&self, provider_name: &str, commands: &[ConfigCommand]) -> Result<()> {
+        let mut client = self.provider_service.get_vyos_client(provider_name)?;
+
+        client.configure(commands).await
+            .context(format!("Failed to configure router '{}'", provider_name))?;
+        client.save().await
+            .context(format!("Failed to save configuration on router '{}'", provider_name))?;
+
+        Ok(())
+    }
+}
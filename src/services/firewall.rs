@@ -0,0 +1,339 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::api::vyos::{ConfigCommand, ConfigDiff, VyOSVersion};
+use crate::models::firewall::{FirewallPolicy, RuleAction};
+use crate::services::provider::ProviderService;
+
+/// Prefix of the rulesets bbctl owns on a router
+pub const RULESET_PREFIX: &str = "bb-";
+
+/// Rule number of the implicit established/related rule in every ruleset
+const ESTABLISHED_RULE: u32 = 1;
+
+/// Spacing between generated rule numbers
+const RULE_STEP: u32 = 10;
+
+/// Name of the ruleset applied to traffic from one zone to another
+pub fn ruleset_name(from: &str, to: &str) -> String {
+    format!("{}{}-{}", RULESET_PREFIX, from, to)
+}
+
+/// Check whether a config path is owned by the firewall policy
+///
+/// The whole `firewall zone` subtree is generated from the policy, while
+/// only rulesets carrying [`RULESET_PREFIX`] are considered ours.
+pub fn is_managed_path(path: &[String]) -> bool {
+    match path {
+        [firewall, zone, ..] if firewall == "firewall" && zone == "zone" => true,
+        [firewall, family, name, ruleset, ..] if firewall == "firewall" && family == "ipv4" && name == "name" => {
+            ruleset.starts_with(RULESET_PREFIX)
+        },
+        _ => false,
+    }
+}
+
+/// Load a firewall policy from a TOML file
+pub fn load_policy(path: &Path) -> Result<FirewallPolicy> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read firewall policy: {}", path.display()))?;
+
+    toml::from_str(&content).context("Failed to parse firewall policy TOML")
+}
+
+/// Validate a firewall policy for a VyOS version
+pub fn validate_policy(policy: &FirewallPolicy, version: VyOSVersion) -> Result<()> {
+    if policy.default_action == RuleAction::Accept {
+        return Err(anyhow!("Zone default action must be drop or reject"));
+    }
+
+    let mut names = HashSet::new();
+    let mut local_zones = 0;
+    for zone in &policy.zones {
+        if !names.insert(zone.name.as_str()) {
+            return Err(anyhow!("Zone '{}' is defined more than once", zone.name));
+        }
+
+        if zone.local {
+            local_zones += 1;
+            if !zone.interfaces.is_empty() || !zone.vrfs.is_empty() {
+                return Err(anyhow!("Local zone '{}' cannot have members", zone.name));
+            }
+        } else if zone.interfaces.is_empty() && zone.vrfs.is_empty() {
+            return Err(anyhow!("Zone '{}' has no interfaces or VRFs", zone.name));
+        }
+
+        if !zone.vrfs.is_empty() && version == VyOSVersion::V1_4 {
+            return Err(anyhow!("Zone '{}' uses VRF members, which require VyOS 1.5", zone.name));
+        }
+    }
+
+    if local_zones > 1 {
+        return Err(anyhow!("Only one local zone can be defined"));
+    }
+
+    for rule in &policy.rules {
+        for zone in [&rule.from, &rule.to] {
+            if policy.get_zone(zone).is_none() {
+                return Err(anyhow!("Rule references unknown zone '{}'", zone));
+            }
+        }
+
+        if rule.from == rule.to {
+            return Err(anyhow!("Rule from '{}' to itself is not supported", rule.from));
+        }
+
+        if rule.destination_port.is_some() {
+            match rule.protocol.as_deref() {
+                Some("tcp") | Some("udp") | Some("tcp_udp") => {},
+                _ => return Err(anyhow!(
+                    "Rule from '{}' to '{}' sets a port without a tcp/udp protocol", rule.from, rule.to
+                )),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compile a firewall policy into VyOS configuration commands
+pub fn compile_policy(policy: &FirewallPolicy, version: VyOSVersion) -> Result<Vec<ConfigCommand>> {
+    validate_policy(policy, version)?;
+
+    let mut commands = Vec::new();
+    let default_action = policy.default_action.to_string();
+
+    for zone in &policy.zones {
+        let base = ["firewall", "zone", zone.name.as_str()];
+
+        if zone.local {
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["local-zone"])));
+        }
+
+        for interface in &zone.interfaces {
+            let member: &[&str] = match version {
+                VyOSVersion::V1_4 => &["interface"],
+                VyOSVersion::V1_5 => &["member", "interface"],
+            };
+            commands.push(ConfigCommand::set(
+                base.iter().chain(member).copied().chain([interface.as_str()]),
+            ));
+        }
+
+        for vrf in &zone.vrfs {
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["member", "vrf", vrf.as_str()])));
+        }
+
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["default-action", default_action.as_str()])));
+
+        // Every other zone gets its own ruleset towards this zone
+        for from in policy.zones.iter().filter(|z| z.name != zone.name) {
+            let ruleset = ruleset_name(&from.name, &zone.name);
+            commands.push(ConfigCommand::set(
+                base.iter().copied().chain(["from", from.name.as_str(), "firewall", "name", ruleset.as_str()]),
+            ));
+        }
+    }
+
+    for to in &policy.zones {
+        for from in policy.zones.iter().filter(|z| z.name != to.name) {
+            let ruleset = ruleset_name(&from.name, &to.name);
+            let base = ["firewall", "ipv4", "name", ruleset.as_str()];
+
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["default-action", default_action.as_str()])));
+
+            // Return traffic is always allowed
+            let established = ESTABLISHED_RULE.to_string();
+            let established_rule = base.iter().copied().chain(["rule", established.as_str()]);
+            commands.push(ConfigCommand::set(established_rule.clone().chain(["action", "accept"])));
+            commands.push(ConfigCommand::set(established_rule.clone().chain(["state", "established"])));
+            commands.push(ConfigCommand::set(established_rule.chain(["state", "related"])));
+
+            for (idx, rule) in policy.rules_between(&from.name, &to.name).into_iter().enumerate() {
+                let number = ((idx as u32) + 1) * RULE_STEP;
+                let number = number.to_string();
+                let prefix: Vec<&str> = base.iter().copied().chain(["rule", number.as_str()]).collect();
+                let action = rule.action.to_string();
+
+                commands.push(ConfigCommand::set(prefix.iter().copied().chain(["action", action.as_str()])));
+
+                if let Some(protocol) = &rule.protocol {
+                    commands.push(ConfigCommand::set(prefix.iter().copied().chain(["protocol", protocol.as_str()])));
+                }
+                if let Some(port) = &rule.destination_port {
+                    commands.push(ConfigCommand::set(prefix.iter().copied().chain(["destination", "port", port.as_str()])));
+                }
+                if let Some(source) = &rule.source_address {
+                    commands.push(ConfigCommand::set(prefix.iter().copied().chain(["source", "address", source.as_str()])));
+                }
+                if let Some(destination) = &rule.destination_address {
+                    commands.push(ConfigCommand::set(
+                        prefix.iter().copied().chain(["destination", "address", destination.as_str()]),
+                    ));
+                }
+                if let Some(description) = &rule.description {
+                    commands.push(ConfigCommand::set(prefix.iter().copied().chain(["description", description.as_str()])));
+                }
+            }
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Firewall service for programming tenant isolation on VyOS routers
+pub struct FirewallService {
+    provider_service: ProviderService,
+}
+
+impl FirewallService {
+    /// Create a new firewall service
+    pub fn new(provider_service: ProviderService) -> Self {
+        Self { provider_service }
+    }
+
+    /// Compute the changes needed to bring a router in line with a policy
+    pub async fn plan(&self, provider_name: &str, policy: &FirewallPolicy, version: VyOSVersion) -> Result<ConfigDiff> {
+        let desired = compile_policy(policy, version)?;
+
+        let mut client = self.provider_service.get_vyos_client(provider_name)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["firewall"]).await
+            .context(format!("Failed to read firewall configuration from '{}'", provider_name))?
+            .into_iter()
+            .filter(|c| is_managed_path(c.path()))
+            .collect();
+
+        debug!("Router '{}' has {} managed firewall paths", provider_name, current.len());
+        Ok(ConfigDiff::between(&current, &desired))
+    }
+
+    /// Apply a previously computed plan to a router
+    pub async fn apply(&self, provider_name: &str, diff: &ConfigDiff) -> Result<()> {
+        if diff.is_empty() {
+            info!("Firewall on '{}' is already up to date", provider_name);
+            return Ok(());
+        }
+
+        let mut client = self.provider_service.get_vyos_client(provider_name)?;
+        client.configure(&diff.commands()).await
+            .context(format!("Failed to configure firewall on '{}'", provider_name))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", provider_name))?;
+
+        info!("Applied {} firewall changes to '{}'", diff.commands().len(), provider_name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+        [[zones]]
+        name = "acme"
+        kind = "tenant"
+        interfaces = ["eth1"]
+
+        [[zones]]
+        name = "globex"
+        kind = "tenant"
+        interfaces = ["eth2"]
+
+        [[zones]]
+        name = "public"
+        kind = "public"
+        interfaces = ["eth0"]
+    "#;
+
+    fn policy(rules: &str) -> FirewallPolicy {
+        toml::from_str(&format!("{}\n{}", POLICY, rules)).unwrap()
+    }
+
+    fn compile(policy: &FirewallPolicy, version: VyOSVersion) -> Vec<String> {
+        compile_policy(policy, version).unwrap().iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn tenant_zones_deny_each_other_by_default() {
+        let commands = compile(&policy(""), VyOSVersion::V1_4);
+
+        assert!(commands.contains(&"set firewall zone acme default-action drop".to_string()));
+        assert!(commands.contains(&"set firewall zone globex from acme firewall name bb-acme-globex".to_string()));
+        assert!(commands.contains(&"set firewall ipv4 name bb-acme-globex default-action drop".to_string()));
+        assert!(commands.contains(&"set firewall ipv4 name bb-acme-globex rule 1 state established".to_string()));
+        assert!(!commands.iter().any(|c| c.starts_with("set firewall ipv4 name bb-acme-globex rule 10")));
+    }
+
+    #[test]
+    fn explicit_rules_allow_only_their_direction() {
+        let policy = policy(r#"
+            [[rules]]
+            from = "public"
+            to = "acme"
+            protocol = "tcp"
+            destination_port = "443"
+            description = "HTTPS in"
+        "#);
+        let commands = compile(&policy, VyOSVersion::V1_5);
+
+        let rule: Vec<_> = commands.iter()
+            .filter(|c| c.starts_with("set firewall ipv4 name bb-public-acme rule 10 "))
+            .cloned()
+            .collect();
+        assert_eq!(rule, [
+            "set firewall ipv4 name bb-public-acme rule 10 action accept",
+            "set firewall ipv4 name bb-public-acme rule 10 protocol tcp",
+            "set firewall ipv4 name bb-public-acme rule 10 destination port 443",
+            "set firewall ipv4 name bb-public-acme rule 10 description 'HTTPS in'",
+        ]);
+        assert!(!commands.iter().any(|c| c.starts_with("set firewall ipv4 name bb-acme-public rule 10")));
+    }
+
+    #[test]
+    fn zone_members_follow_the_version_syntax() {
+        let policy = policy("");
+
+        let v14 = compile(&policy, VyOSVersion::V1_4);
+        assert!(v14.contains(&"set firewall zone acme interface eth1".to_string()));
+        assert!(!v14.iter().any(|c| c.contains(" member ")));
+
+        let v15 = compile(&policy, VyOSVersion::V1_5);
+        assert!(v15.contains(&"set firewall zone acme member interface eth1".to_string()));
+        assert!(!v15.contains(&"set firewall zone acme interface eth1".to_string()));
+    }
+
+    #[test]
+    fn vrf_members_require_1_5() {
+        let mut policy = policy("");
+        policy.zones[0].vrfs.push("acme".to_string());
+
+        assert!(compile_policy(&policy, VyOSVersion::V1_4).is_err());
+        assert!(compile(&policy, VyOSVersion::V1_5).contains(&"set firewall zone acme member vrf acme".to_string()));
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        let mut accept_by_default = policy("");
+        accept_by_default.default_action = RuleAction::Accept;
+        assert!(compile_policy(&accept_by_default, VyOSVersion::V1_5).is_err());
+
+        let port_without_protocol = policy(r#"
+            [[rules]]
+            from = "public"
+            to = "acme"
+            destination_port = "443"
+        "#);
+        assert!(compile_policy(&port_without_protocol, VyOSVersion::V1_5).is_err());
+
+        let unknown_zone = policy(r#"
+            [[rules]]
+            from = "public"
+            to = "initech"
+        "#);
+        assert!(compile_policy(&unknown_zone, VyOSVersion::V1_5).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info};
+use std::collections::{BTreeMap, HashSet};
+use std::net::Ipv4Addr;
+use uuid::Uuid;
+
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::config::floating_ips::FloatingIps;
+use crate::models::floating_ip::{FloatingIp, FloatingIpPool, PortForward};
+use crate::models::network::Ipv4Cidr;
+use crate::models::provider::ProviderType;
+use crate::services::provider::ProviderService;
+
+/// First NAT rule number owned by floating IPs on VyOS
+const NAT_RULE_BASE: u32 = 1000;
+
+/// Last NAT rule number owned by floating IPs on VyOS
+const NAT_RULE_MAX: u32 = 1999;
+
+/// Check whether a VyOS config path is owned by floating IPs
+pub fn is_managed_nat_path(path: &[String]) -> bool {
+    match path {
+        [nat, kind, rule, number, ..] if nat == "nat" && (kind == "destination" || kind == "source") && rule == "rule" => {
+            number.parse::<u32>()
+                .map(|n| (NAT_RULE_BASE..=NAT_RULE_MAX).contains(&n))
+                .unwrap_or(false)
+        },
+        _ => false,
+    }
+}
+
+/// Parse a port forward spec such as `443` or `443:8443` (public:private)
+pub fn parse_port_forward(spec: &str, protocol: &str) -> Result<PortForward> {
+    let (public, private) = match spec.split_once(':') {
+        Some((public, private)) => (public, private),
+        None => (spec, spec),
+    };
+
+    let public_port = public.trim().parse::<u16>()
+        .map_err(|_| anyhow!("Invalid public port: {}", public))?;
+    let private_port = private.trim().parse::<u16>()
+        .map_err(|_| anyhow!("Invalid private port: {}", private))?;
+
+    let protocol = protocol.to_lowercase();
+    if protocol != "tcp" && protocol != "udp" {
+        return Err(anyhow!("Port forwards support tcp or udp, got '{}'", protocol));
+    }
+
+    Ok(PortForward { protocol, public_port, private_port })
+}
+
+/// Render the NAT rules for the floating IPs of one router
+///
+/// Rules are numbered from [`NAT_RULE_BASE`] in address order, so the same set of
+/// addresses always renders to the same configuration.
+pub fn render_nat_config(addresses: &[&FloatingIp], pools: &BTreeMap<String, FloatingIpPool>) -> Result<Vec<ConfigCommand>> {
+    let mut addresses: Vec<&FloatingIp> = addresses.iter()
+        .copied()
+        .filter(|a| a.is_associated())
+        .collect();
+    addresses.sort_by_key(|a| a.ip);
+
+    let mut commands = Vec::new();
+    let mut number = NAT_RULE_BASE;
+
+    for address in addresses {
+        let pool = pools.get(&address.pool)
+            .ok_or_else(|| anyhow!("Pool '{}' of {} does not exist", address.pool, address.ip))?;
+        let public_ip = address.ip.to_string();
+        let private_ip = address.private_ip.map(|ip| ip.to_string()).unwrap_or_default();
+        let description = match address.instance_id {
+            Some(id) => format!("bbctl floating IP for {}", id),
+            None => "bbctl floating IP".to_string(),
+        };
+
+        // A 1:1 NAT is a single destination rule plus the matching source rule,
+        // port forwards are one destination rule each
+        let forwards: Vec<Option<&PortForward>> = if address.is_one_to_one() {
+            vec![None]
+        } else {
+            address.port_forwards.iter().map(Some).collect()
+        };
+
+        for forward in forwards {
+            if number > NAT_RULE_MAX {
+                return Err(anyhow!("Too many floating IP NAT rules (maximum {})", NAT_RULE_MAX - NAT_RULE_BASE + 1));
+            }
+
+            let rule = number.to_string();
+            let base = ["nat", "destination", "rule", rule.as_str()];
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["description", description.as_str()])));
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["inbound-interface", "name", pool.interface.as_str()])));
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["destination", "address", public_ip.as_str()])));
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["translation", "address", private_ip.as_str()])));
+
+            match forward {
+                Some(forward) => {
+                    let public_port = forward.public_port.to_string();
+                    let private_port = forward.private_port.to_string();
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["protocol", forward.protocol.as_str()])));
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["destination", "port", public_port.as_str()])));
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["translation", "port", private_port.as_str()])));
+                },
+                None => {
+                    let base = ["nat", "source", "rule", rule.as_str()];
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["description", description.as_str()])));
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["outbound-interface", "name", pool.interface.as_str()])));
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["source", "address", private_ip.as_str()])));
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["translation", "address", public_ip.as_str()])));
+                },
+            }
+
+            number += 1;
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Floating IP service for exposing instances through VyOS NAT
+pub struct FloatingIpService {
+    floating_ips: FloatingIps,
+    provider_service: ProviderService,
+}
+
+impl FloatingIpService {
+    /// Create a new floating IP service
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        let floating_ips = FloatingIps::load()?;
+
+        Ok(Self {
+            floating_ips,
+            provider_service,
+        })
+    }
+
+    /// List all pools
+    pub fn list_pools(&self) -> Vec<&FloatingIpPool> {
+        let mut pools: Vec<&FloatingIpPool> = self.floating_ips.pools.values().collect();
+        pools.sort_by(|a, b| a.name.cmp(&b.name));
+        pools
+    }
+
+    /// List all allocated addresses
+    pub fn list_addresses(&self) -> Vec<&FloatingIp> {
+        let mut addresses: Vec<&FloatingIp> = self.floating_ips.addresses.values().collect();
+        addresses.sort_by_key(|a| a.ip);
+        addresses
+    }
+
+    /// Get an allocated address
+    pub fn get_address(&self, ip: &Ipv4Addr) -> Option<&FloatingIp> {
+        self.floating_ips.get_address(ip)
+    }
+
+    /// Create a new pool
+    pub fn create_pool(&mut self, pool: FloatingIpPool) -> Result<()> {
+        Ipv4Cidr::parse(&pool.cidr)
+            .map_err(|e| anyhow!("Invalid pool CIDR '{}': {}", pool.cidr, e))?;
+
+        match self.provider_service.get_provider(&pool.router) {
+            Some(provider) if provider.provider_type == ProviderType::VyOS => {},
+            Some(_) => return Err(anyhow!("Provider '{}' is not a VyOS router", pool.router)),
+            None => return Err(anyhow!("Provider '{}' does not exist", pool.router)),
+        }
+
+        let name = pool.name.clone();
+        self.floating_ips.add_pool(pool)?;
+        self.floating_ips.save()?;
+
+        info!("Created floating IP pool: {}", name);
+        Ok(())
+    }
+
+    /// Delete a pool without allocated addresses
+    pub fn delete_pool(&mut self, name: &str) -> Result<()> {
+        self.floating_ips.remove_pool(name)?;
+        self.floating_ips.save()?;
+
+        info!("Deleted floating IP pool: {}", name);
+        Ok(())
+    }
+
+    /// Allocate an address, optionally from a specific pool or a specific address
+    pub fn allocate(&mut self, pool: Option<&str>, ip: Option<Ipv4Addr>) -> Result<Ipv4Addr> {
+        let ip = self.reserve(pool, ip, false)?;
+        self.floating_ips.save()?;
+
+        info!("Allocated floating IP: {}", ip);
+        Ok(ip)
+    }
+
+    /// Associate an allocated address with an instance as a 1:1 NAT
+    pub fn associate(&mut self, ip: &Ipv4Addr, instance_id: Uuid, private_ip: Ipv4Addr) -> Result<()> {
+        let address = self.floating_ips.get_address_mut(ip)
+            .ok_or_else(|| anyhow!("Address {} is not allocated", ip))?;
+
+        if address.is_associated() && address.instance_id != Some(instance_id) {
+            return Err(anyhow!("Address {} is already associated with another instance", ip));
+        }
+
+        address.port_forwards.clear();
+        address.associate(instance_id, private_ip);
+        self.floating_ips.save()?;
+
+        info!("Associated floating IP {} with instance {}", ip, instance_id);
+        Ok(())
+    }
+
+    /// Remove the association of an address
+    pub fn disassociate(&mut self, ip: &Ipv4Addr) -> Result<()> {
+        let address = self.floating_ips.get_address_mut(ip)
+            .ok_or_else(|| anyhow!("Address {} is not allocated", ip))?;
+
+        address.disassociate();
+        self.floating_ips.save()?;
+
+        info!("Disassociated floating IP: {}", ip);
+        Ok(())
+    }
+
+    /// Release an address back to its pool
+    pub fn release(&mut self, ip: &Ipv4Addr) -> Result<FloatingIp> {
+        let address = self.floating_ips.remove_address(ip)?;
+        self.floating_ips.save()?;
+
+        info!("Released floating IP: {}", ip);
+        Ok(address)
+    }
+
+    /// Forward a public port to an instance
+    ///
+    /// Without a public IP, an address already forwarding ports to the instance is
+    /// reused, otherwise a new one is allocated and released again with the instance.
+    pub fn expose(
+        &mut self,
+        instance_id: Uuid,
+        private_ip: Ipv4Addr,
+        forward: PortForward,
+        public_ip: Option<Ipv4Addr>,
+        pool: Option<&str>,
+    ) -> Result<Ipv4Addr> {
+        let ip = match public_ip {
+            Some(ip) => {
+                if self.floating_ips.get_address(&ip).is_none() {
+                    self.reserve(pool, Some(ip), false)?;
+                }
+                ip
+            },
+            None => {
+                let existing = self.floating_ips.get_addresses_by_instance(&instance_id)
+                    .into_iter()
+                    .filter(|a| !a.port_forwards.is_empty())
+                    .filter(|a| pool.is_none_or(|p| a.pool == p))
+                    .map(|a| a.ip)
+                    .min();
+
+                match existing {
+                    Some(ip) => ip,
+                    None => self.reserve(pool, None, true)?,
+                }
+            },
+        };
+
+        let address = self.floating_ips.get_address_mut(&ip)
+            .ok_or_else(|| anyhow!("Address {} is not allocated", ip))?;
+
+        if address.is_associated() {
+            if address.instance_id != Some(instance_id) {
+                return Err(anyhow!("Address {} is already associated with another instance", ip));
+            }
+            if address.is_one_to_one() {
+                return Err(anyhow!("Address {} is a 1:1 NAT; disassociate it before forwarding ports", ip));
+            }
+        }
+
+        address.associate(instance_id, private_ip);
+        address.add_port_forward(forward).map_err(|e| anyhow!("{}: {}", ip, e))?;
+        self.floating_ips.save()?;
+
+        info!("Exposed instance {} on {}", instance_id, ip);
+        Ok(ip)
+    }
+
+    /// Clean up the addresses of a deleted instance
+    ///
+    /// Addresses allocated by `expose` are released, explicitly allocated ones are
+    /// only disassociated. Returns the addresses that changed.
+    pub fn release_instance(&mut self, instance_id: &Uuid) -> Result<Vec<Ipv4Addr>> {
+        let addresses: Vec<(Ipv4Addr, bool)> = self.floating_ips.get_addresses_by_instance(instance_id)
+            .into_iter()
+            .map(|a| (a.ip, a.auto_allocated))
+            .collect();
+
+        for (ip, auto_allocated) in &addresses {
+            if *auto_allocated {
+                self.floating_ips.remove_address(ip)?;
+            } else if let Some(address) = self.floating_ips.get_address_mut(ip) {
+                address.disassociate();
+            }
+        }
+
+        if !addresses.is_empty() {
+            self.floating_ips.save()?;
+            info!("Cleaned up {} floating IPs of instance {}", addresses.len(), instance_id);
+        }
+
+        Ok(addresses.into_iter().map(|(ip, _)| ip).collect())
+    }
+
+    /// Get the routers that carry at least one pool
+    pub fn routers(&self) -> Vec<String> {
+        let routers: HashSet<&str> = self.floating_ips.pools.values()
+            .map(|p| p.router.as_str())
+            .collect();
+        let mut routers: Vec<String> = routers.into_iter().map(String::from).collect();
+        routers.sort();
+        routers
+    }
+
+    /// Render the NAT configuration for a router
+    pub fn render_router(&self, router: &str) -> Result<Vec<ConfigCommand>> {
+        let pools: BTreeMap<String, FloatingIpPool> = self.floating_ips.pools.iter()
+            .filter(|(_, p)| p.router == router)
+            .map(|(name, p)| (name.clone(), p.clone()))
+            .collect();
+
+        let addresses: Vec<&FloatingIp> = self.floating_ips.addresses.values()
+            .filter(|a| pools.contains_key(&a.pool))
+            .collect();
+
+        render_nat_config(&addresses, &pools)
+    }
+
+    /// Compute the NAT changes needed on a router
+    pub async fn plan(&self, router: &str) -> Result<ConfigDiff> {
+        let desired = self.render_router(router)?;
+
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["nat"]).await
+            .context(format!("Failed to read NAT configuration from '{}'", router))?
+            .into_iter()
+            .filter(|c| is_managed_nat_path(c.path()))
+            .collect();
+
+        debug!("Router '{}' has {} managed NAT paths", router, current.len());
+        Ok(ConfigDiff::between(&current, &desired))
+    }
+
+    /// Push the NAT configuration to a router
+    pub async fn sync_router(&self, router: &str) -> Result<usize> {
+        let diff = self.plan(router).await?;
+        if diff.is_empty() {
+            info!("NAT on '{}' is already up to date", router);
+            return Ok(0);
+        }
+
+        let commands = diff.commands();
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        client.configure(&commands).await
+            .context(format!("Failed to configure NAT on '{}'", router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+
+        info!("Applied {} NAT changes to '{}'", commands.len(), router);
+        Ok(commands.len())
+    }
+
+    /// Push the NAT configuration to every router carrying a pool
+    pub async fn sync_all(&self) -> Vec<(String, Result<usize>)> {
+        let mut results = Vec::new();
+        for router in self.routers() {
+            let result = self.sync_router(&router).await;
+            results.push((router, result));
+        }
+        results
+    }
+
+    /// Push the NAT configuration to the router carrying an address
+    pub async fn sync_address(&self, ip: &Ipv4Addr, pool: &str) -> Result<usize> {
+        let router = self.floating_ips.get_pool(pool)
+            .map(|p| p.router.clone())
+            .ok_or_else(|| anyhow!("Pool '{}' of {} does not exist", pool, ip))?;
+
+        self.sync_router(&router).await
+    }
+
+    /// Reserve an address without saving
+    fn reserve(&mut self, pool: Option<&str>, ip: Option<Ipv4Addr>, auto_allocated: bool) -> Result<Ipv4Addr> {
+        // Pick the pool: explicit, the one containing the address, or the only one
+        let pool = match (pool, ip) {
+            (Some(name), _) => self.floating_ips.get_pool(name)
+                .ok_or_else(|| anyhow!("Pool '{}' does not exist", name))?,
+            (None, Some(ip)) => self.floating_ips.pools.values()
+                .find(|p| Ipv4Cidr::parse(&p.cidr).map(|c| c.contains(&ip)).unwrap_or(false))
+                .ok_or_else(|| anyhow!("No pool contains {}", ip))?,
+            (None, None) => {
+                let mut pools = self.floating_ips.pools.values();
+                match (pools.next(), pools.next()) {
+                    (Some(pool), None) => pool,
+                    (None, _) => return Err(anyhow!("No floating IP pools are defined")),
+                    (Some(_), Some(_)) => return Err(anyhow!("Several pools are defined; pick one with --pool")),
+                }
+            },
+        };
+
+        let cidr = Ipv4Cidr::parse(&pool.cidr)
+            .map_err(|e| anyhow!("Invalid pool CIDR '{}': {}", pool.cidr, e))?;
+        let is_free = |candidate: &Ipv4Addr| {
+            !pool.exclude.contains(candidate) && self.floating_ips.get_address(candidate).is_none()
+        };
+
+        let ip = match ip {
+            Some(ip) => {
+                if !cidr.contains(&ip) || ip < cidr.first_host() || ip > cidr.last_host() {
+                    return Err(anyhow!("{} is not a usable address in pool '{}'", ip, pool.name));
+                }
+                if !is_free(&ip) {
+                    return Err(anyhow!("{} is excluded or already allocated", ip));
+                }
+                ip
+            },
+            None => cidr.hosts().find(is_free)
+                .ok_or_else(|| anyhow!("Pool '{}' is exhausted", pool.name))?,
+        };
+
+        let mut address = FloatingIp::new(ip, pool.name.clone());
+        address.auto_allocated = auto_allocated;
+        self.floating_ips.add_address(address)?;
+        Ok(ip)
+    }
+}
@@ -0,0 +1,770 @@
+use anyhow::{Result, Context, anyhow};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::vyos::ConfigCommand;
+use crate::models::instance::{Instance, InstanceSize, InstanceStatus, NODE_TAG, PROVIDER_TAG};
+use crate::models::network::{IpAllocation, Network, NetworkStatus, NetworkType, DHCP_CONFIG, DHCP_RANGE_CONFIG, DHCP_SUBNET_ID_CONFIG};
+use crate::models::provider::ProviderType;
+use crate::models::volume::{Volume, VolumeStatus, VolumeType};
+use crate::services::network::{render_dhcp_config, DHCP_PREFIX};
+use crate::services::provider::ProviderService;
+use crate::services::refresh::{proxmox_status, ResourceKind};
+use crate::services::volume::{disk_volume_id, OWNER_TAG, STORAGE_TAG};
+use crate::state::{FileStateStore, State, StateStore};
+
+/// Value recorded for instance tags copied from Proxmox, which have no value
+pub const PROXMOX_TAG_VALUE: &str = "true";
+
+/// Disk buses a Proxmox VM configuration can attach disks on
+const DISK_BUSES: [&str; 4] = ["scsi", "virtio", "sata", "ide"];
+
+/// Disk attached to a Proxmox VM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxmoxDisk {
+    /// Configuration key, such as `scsi0`
+    pub slot: String,
+    /// Proxmox volume ID, such as `local-lvm:vm-100-disk-0`
+    pub volume_id: String,
+    /// Storage holding the disk
+    pub storage: String,
+    /// Size in GB, rounded up
+    pub size_gb: u16,
+}
+
+/// Network interface of a Proxmox VM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxmoxNic {
+    /// Configuration key, such as `net0`
+    pub interface: String,
+    /// MAC address, in lowercase
+    pub mac: Option<String>,
+    /// Bridge the interface is plugged into
+    pub bridge: Option<String>,
+    /// VLAN tag on the bridge
+    pub vlan: Option<u16>,
+}
+
+impl ProxmoxNic {
+    /// Network the interface is recorded on until it is linked to a bbctl network
+    pub fn network_id(&self) -> String {
+        match (&self.bridge, self.vlan) {
+            (Some(bridge), Some(vlan)) => format!("{}.{}", bridge, vlan),
+            (Some(bridge), None) => bridge.clone(),
+            (None, _) => self.interface.clone(),
+        }
+    }
+}
+
+/// Static mapping of a VyOS DHCP subnet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DhcpMapping {
+    /// Mapping name
+    pub name: String,
+    /// Fixed address
+    pub ip: Option<IpAddr>,
+    /// MAC address, in lowercase
+    pub mac: Option<String>,
+}
+
+/// Subnet served by a VyOS DHCP shared network
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DhcpSubnet {
+    /// Shared network the subnet belongs to
+    pub shared_network: String,
+    /// Subnet CIDR
+    pub cidr: String,
+    /// Subnet ID (VyOS 1.4 and later)
+    pub subnet_id: Option<u32>,
+    /// Default router handed out
+    pub gateway: Option<IpAddr>,
+    /// Name servers handed out
+    pub dns_servers: Vec<IpAddr>,
+    /// First dynamic range (`start`, `stop`)
+    pub range: Option<(String, String)>,
+    /// Static mappings by name
+    pub mappings: BTreeMap<String, DhcpMapping>,
+}
+
+/// What happened to a resource during an import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ImportOutcome {
+    /// A record was created
+    Imported { id: Uuid },
+    /// A record already describes the resource
+    AlreadyManaged { id: Uuid },
+    /// The resource could not be imported
+    Failed { error: String },
+}
+
+/// Result of importing one resource
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    /// Kind of resource
+    pub resource: ResourceKind,
+    /// Resource name
+    pub name: String,
+    /// Provider-specific ID, such as a VMID or subnet CIDR
+    pub provider_id: String,
+    /// What happened
+    #[serde(flatten)]
+    pub outcome: ImportOutcome,
+}
+
+/// Read a Proxmox configuration value that may be a number or a numeric string
+fn config_u64(value: &serde_json::Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// Parse a Proxmox size such as `32G`, `512M` or `1T` into whole GB, rounded up
+pub fn parse_size_gb(size: &str) -> Option<u16> {
+    let size = size.trim();
+    let (number, unit) = match size.char_indices().find(|(_, c)| !c.is_ascii_digit() && *c != '.') {
+        Some((index, _)) => size.split_at(index),
+        None => (size, ""),
+    };
+    let number: f64 = number.parse().ok()?;
+    let gb = match unit.to_ascii_uppercase().as_str() {
+        "" => number / (1024.0 * 1024.0 * 1024.0),
+        "K" => number / (1024.0 * 1024.0),
+        "M" => number / 1024.0,
+        "G" => number,
+        "T" => number * 1024.0,
+        _ => return None,
+    };
+
+    let gb = gb.ceil();
+    (gb <= f64::from(u16::MAX)).then_some(gb as u16)
+}
+
+/// Split a Proxmox `key=value,...` option into its parts
+fn option_parts(option: &str) -> impl Iterator<Item = (&str, &str)> {
+    option.split(',').map(|part| part.split_once('=').unwrap_or((part, "")))
+}
+
+/// Whether a configuration key names a disk slot, such as `scsi0` or `virtio1`
+fn is_disk_slot(key: &str) -> bool {
+    DISK_BUSES.iter().any(|bus| {
+        key.strip_prefix(bus).is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+/// Sort key keeping disks in bus order, then slot number
+fn disk_order(slot: &str) -> (usize, u32) {
+    let bus = DISK_BUSES.iter().position(|bus| slot.starts_with(bus)).unwrap_or(DISK_BUSES.len());
+    let number = slot.trim_start_matches(|c: char| c.is_ascii_alphabetic()).parse().unwrap_or(0);
+    (bus, number)
+}
+
+/// Boot disk slot of a Proxmox VM configuration, from `boot: order=...` or `bootdisk`
+fn boot_slot(config: &serde_json::Value) -> Option<String> {
+    if let Some(boot) = config["boot"].as_str() {
+        let order = option_parts(boot).find(|(key, _)| *key == "order").map(|(_, value)| value);
+        if let Some(slot) = order.and_then(|order| order.split(';').find(|slot| is_disk_slot(slot))) {
+            return Some(slot.to_string());
+        }
+    }
+    config["bootdisk"].as_str().map(str::to_string)
+}
+
+/// Disks of a Proxmox VM configuration, boot disk first
+///
+/// CD-ROM drives, including cloud-init drives, and empty slots are skipped.
+pub fn proxmox_disks(config: &serde_json::Value) -> Vec<ProxmoxDisk> {
+    let Some(options) = config.as_object() else {
+        return Vec::new();
+    };
+
+    let mut disks: Vec<ProxmoxDisk> = options.iter()
+        .filter(|(key, _)| is_disk_slot(key))
+        .filter_map(|(key, value)| {
+            let option = value.as_str()?;
+            let volume_id = disk_volume_id(option);
+            if volume_id == "none" || option_parts(option).any(|part| part == ("media", "cdrom")) {
+                return None;
+            }
+            let size_gb = option_parts(option)
+                .find(|(key, _)| *key == "size")
+                .and_then(|(_, size)| parse_size_gb(size))
+                .unwrap_or(0);
+            Some(ProxmoxDisk {
+                slot: key.clone(),
+                volume_id: volume_id.to_string(),
+                storage: volume_id.split(':').next().unwrap_or_default().to_string(),
+                size_gb,
+            })
+        })
+        .collect();
+
+    let boot = boot_slot(config);
+    disks.sort_by_key(|disk| (Some(&disk.slot) != boot.as_ref(), disk_order(&disk.slot)));
+    disks
+}
+
+/// Network interfaces of a Proxmox VM configuration, in slot order
+pub fn proxmox_nics(config: &serde_json::Value) -> Vec<ProxmoxNic> {
+    let Some(options) = config.as_object() else {
+        return Vec::new();
+    };
+
+    let mut nics: Vec<ProxmoxNic> = options.iter()
+        .filter(|(key, _)| key.strip_prefix("net").is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())))
+        .filter_map(|(key, value)| {
+            let option = value.as_str()?;
+            let mut nic = ProxmoxNic { interface: key.clone(), mac: None, bridge: None, vlan: None };
+            for (name, value) in option_parts(option) {
+                match name {
+                    "bridge" => nic.bridge = Some(value.to_string()),
+                    "tag" => nic.vlan = value.parse().ok(),
+                    "macaddr" => nic.mac = Some(value.to_lowercase()),
+                    // The model option carries the MAC address, as in `virtio=BC:24:11:00:00:01`
+                    _ if value.matches(':').count() == 5 => nic.mac = Some(value.to_lowercase()),
+                    _ => {},
+                }
+            }
+            Some(nic)
+        })
+        .collect();
+
+    nics.sort_by_key(|nic| nic.interface.trim_start_matches("net").parse::<u32>().unwrap_or(0));
+    nics
+}
+
+/// Split a Proxmox tag list, which may be separated by `;`, `,` or spaces
+pub fn proxmox_tags(tags: &str) -> Vec<String> {
+    tags.split([';', ',', ' '])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Build the records for a Proxmox VM from its configuration
+///
+/// The boot disk becomes the instance's disk size; every other disk becomes
+/// a volume attached to the instance and owned by the VM. Proxmox tags are
+/// copied as instance tags with the value [`PROXMOX_TAG_VALUE`].
+pub fn instance_from_vm_config(
+    provider_name: &str,
+    node: &str,
+    vmid: u64,
+    status: InstanceStatus,
+    config: &serde_json::Value,
+    region: &str,
+) -> (Instance, Vec<Volume>) {
+    let name = config["name"].as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("vm-{}", vmid));
+    let cores = config_u64(&config["cores"]).unwrap_or(1) * config_u64(&config["sockets"]).unwrap_or(1);
+    let memory_mb = config_u64(&config["memory"]).unwrap_or(512);
+    let disks = proxmox_disks(config);
+
+    let size = InstanceSize {
+        cpu: cores.clamp(1, u64::from(u8::MAX)) as u8,
+        memory_gb: memory_mb.div_ceil(1024).clamp(1, u64::from(u16::MAX)) as u16,
+        disk_gb: disks.first().map(|disk| disk.size_gb).unwrap_or(0),
+    };
+
+    let mut instance = Instance::new(name, ProviderType::Proxmox, region.to_string(), size);
+    instance.provider_id = vmid.to_string();
+    for tag in proxmox_tags(config["tags"].as_str().unwrap_or_default()) {
+        instance.add_tag(tag, PROXMOX_TAG_VALUE.to_string());
+    }
+    instance.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+    instance.add_tag(NODE_TAG.to_string(), node.to_string());
+    for nic in proxmox_nics(config) {
+        instance.add_network(nic.network_id(), None, Some(nic.interface.clone()), nic.mac);
+    }
+    instance.update_status(status);
+
+    let volumes = disks.iter().skip(1).map(|disk| {
+        let mut volume = Volume::new(
+            format!("{}-{}", instance.name, disk.slot),
+            ProviderType::Proxmox,
+            region.to_string(),
+            disk.size_gb,
+            VolumeType::Standard,
+        );
+        volume.provider_id = disk.volume_id.clone();
+        volume.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+        volume.add_tag(STORAGE_TAG.to_string(), disk.storage.clone());
+        volume.add_tag(OWNER_TAG.to_string(), instance.provider_id.clone());
+        volume.add_tag(NODE_TAG.to_string(), node.to_string());
+        volume.attach(instance.id, Some(disk.slot.clone()));
+        volume.update_status(VolumeStatus::InUse);
+        volume
+    }).collect();
+
+    (instance, volumes)
+}
+
+/// Parse the subnets of the DHCP shared networks in a VyOS configuration
+///
+/// Both the VyOS 1.3 (`default-router`, `dns-server`, `mac-address`) and
+/// 1.4 (`option default-router`, `option name-server`, `mac`) layouts are read.
+pub fn parse_dhcp_subnets(commands: &[ConfigCommand]) -> Vec<DhcpSubnet> {
+    let mut subnets: BTreeMap<(String, String), DhcpSubnet> = BTreeMap::new();
+
+    for command in commands {
+        let ConfigCommand::Set(path) = command else {
+            continue;
+        };
+        let [service, dhcp, shared, shared_network, subnet, cidr, rest @ ..] = path.as_slice() else {
+            continue;
+        };
+        if service != "service" || dhcp != "dhcp-server" || shared != "shared-network-name" || subnet != "subnet" {
+            continue;
+        }
+
+        let entry = subnets.entry((shared_network.clone(), cidr.clone())).or_insert_with(|| DhcpSubnet {
+            shared_network: shared_network.clone(),
+            cidr: cidr.clone(),
+            ..Default::default()
+        });
+        let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+        match rest.as_slice() {
+            ["subnet-id", id] => entry.subnet_id = id.parse().ok(),
+            ["default-router", ip] | ["option", "default-router", ip] => entry.gateway = ip.parse().ok(),
+            ["dns-server", ip] | ["name-server", ip] | ["option", "name-server", ip] => {
+                if let Ok(ip) = ip.parse() {
+                    entry.dns_servers.push(ip);
+                }
+            },
+            ["range", _, "start", ip] => {
+                let range = entry.range.get_or_insert_with(Default::default);
+                range.0 = ip.to_string();
+            },
+            ["range", _, "stop", ip] => {
+                let range = entry.range.get_or_insert_with(Default::default);
+                range.1 = ip.to_string();
+            },
+            ["static-mapping", name, key, value] => {
+                let mapping = entry.mappings.entry(name.to_string()).or_insert_with(|| DhcpMapping {
+                    name: name.to_string(),
+                    ip: None,
+                    mac: None,
+                });
+                match *key {
+                    "ip-address" => mapping.ip = value.parse().ok(),
+                    "mac" | "mac-address" => mapping.mac = Some(value.to_lowercase()),
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+    }
+
+    subnets.into_values().collect()
+}
+
+/// Build the network record for a DHCP subnet
+///
+/// Static mappings become allocations with their MAC address. Mappings
+/// named after their address, as bbctl names them, get no host name.
+pub fn network_from_dhcp_subnet(provider_name: &str, region: &str, name: &str, subnet: &DhcpSubnet) -> Result<Network> {
+    let mut network = Network::new(
+        name.to_string(),
+        ProviderType::VyOS,
+        region.to_string(),
+        subnet.cidr.clone(),
+        NetworkType::Routed,
+    );
+    network.ipv4_cidr()
+        .map_err(|e| anyhow!("Subnet '{}' of '{}' is not a valid IPv4 block: {}", subnet.cidr, subnet.shared_network, e))?;
+
+    if let Some(gateway) = subnet.gateway {
+        network.set_gateway(gateway);
+    }
+    for dns_server in &subnet.dns_servers {
+        network.add_dns_server(*dns_server);
+    }
+    if let Some((start, stop)) = subnet.range.as_ref().filter(|(start, stop)| !start.is_empty() && !stop.is_empty()) {
+        network.set_config(DHCP_RANGE_CONFIG.to_string(), format!("{}-{}", start, stop));
+    }
+    if let Some(subnet_id) = subnet.subnet_id {
+        network.set_config(DHCP_SUBNET_ID_CONFIG.to_string(), subnet_id.to_string());
+    }
+
+    for mapping in subnet.mappings.values() {
+        let Some(ip) = mapping.ip else {
+            warn!("Skipping static mapping '{}' of '{}' without an address", mapping.name, subnet.shared_network);
+            continue;
+        };
+        if network.ip_allocations.iter().any(|a| a.ip == ip) {
+            warn!("Skipping static mapping '{}' of '{}': {} is mapped twice", mapping.name, subnet.shared_network, ip);
+            continue;
+        }
+        let generated = format!("host-{}", ip.to_string().replace('.', "-"));
+        network.ip_allocations.push(IpAllocation {
+            ip,
+            instance_id: None,
+            assigned_at: Some(chrono::Utc::now()),
+            mac: mapping.mac.clone(),
+            hostname: (mapping.name != generated).then(|| mapping.name.clone()),
+            fixed: true,
+        });
+    }
+
+    network.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+    network.update_status(NetworkStatus::Available);
+    Ok(network)
+}
+
+/// Link unassigned allocations to the instances whose interfaces have their MAC address
+///
+/// The instance interface moves onto the network and takes the allocated
+/// address. Returns the number of allocations linked.
+pub fn link_allocations(state: &mut State) -> usize {
+    let mut linked = 0;
+    let mut connected = Vec::new();
+
+    for network in state.networks.values_mut() {
+        let network_id = network.id.to_string();
+        for allocation in network.ip_allocations.iter_mut().filter(|a| a.instance_id.is_none()) {
+            let Some(mac) = allocation.mac.as_deref() else {
+                continue;
+            };
+            let found = state.instances.values_mut().find_map(|instance| {
+                let interface = instance.networks.iter_mut()
+                    .find(|n| n.mac.as_deref().is_some_and(|m| m.eq_ignore_ascii_case(mac)))?;
+                interface.network_id = network_id.clone();
+                interface.ip = Some(allocation.ip.to_string());
+                Some(instance.id)
+            });
+            if let Some(instance_id) = found {
+                allocation.instance_id = Some(instance_id);
+                connected.push(instance_id);
+            }
+        }
+
+        linked += connected.len();
+        for instance_id in connected.drain(..) {
+            network.connect_instance(instance_id);
+        }
+    }
+
+    linked
+}
+
+/// Name of the network record imported for a DHCP shared network
+fn imported_network_name(shared_network: &str) -> String {
+    shared_network.strip_prefix(DHCP_PREFIX).unwrap_or(shared_network).to_string()
+}
+
+/// Import service adopting existing provider resources as bbctl records
+pub struct ImportService {
+    store: Arc<dyn StateStore>,
+    provider_service: ProviderService,
+}
+
+impl ImportService {
+    /// Create a new import service backed by the state store
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Ok(Self::with_store(provider_service, Arc::new(FileStateStore::open()?)))
+    }
+
+    /// Create a new import service backed by a specific state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Self {
+        Self { store, provider_service }
+    }
+
+    /// Check that a provider exists and has the expected type
+    fn require_provider(&self, provider_name: &str, provider_type: ProviderType) -> Result<()> {
+        let provider = self.provider_service.get_provider(provider_name)
+            .ok_or_else(|| anyhow!("Provider not found: {}", provider_name))?;
+        if provider.provider_type != provider_type {
+            return Err(anyhow!("Provider '{}' is not a {} provider", provider_name, provider_type));
+        }
+        Ok(())
+    }
+
+    /// Import every resource of a provider
+    ///
+    /// On Proxmox every VM carrying all of `tag_filter` is imported; on VyOS
+    /// every DHCP subnet. A resource that fails is reported and the rest
+    /// are still imported.
+    pub async fn import_all(&self, provider_name: &str, tag_filter: &[String], region: &str, adopt_dhcp: bool) -> Result<Vec<ImportResult>> {
+        let provider = self.provider_service.get_provider(provider_name)
+            .ok_or_else(|| anyhow!("Provider not found: {}", provider_name))?;
+
+        match provider.provider_type {
+            ProviderType::Proxmox => self.import_proxmox_vms(provider_name, tag_filter, region).await,
+            ProviderType::VyOS if !tag_filter.is_empty() => {
+                Err(anyhow!("--tag-filter only applies to Proxmox providers"))
+            },
+            ProviderType::VyOS => self.import_vyos_networks(provider_name, None, region, adopt_dhcp).await,
+        }
+    }
+
+    /// Import one Proxmox VM
+    pub async fn import_proxmox_vm(&self, provider_name: &str, vmid: u64, region: &str) -> Result<ImportResult> {
+        self.require_provider(provider_name, ProviderType::Proxmox)?;
+        let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+        client.login().await?;
+
+        let vms = client.get_resources(Some("vm")).await?;
+        let vm = vms.as_array().into_iter().flatten()
+            .find(|vm| vm["type"] == "qemu" && vm["vmid"].as_u64() == Some(vmid))
+            .ok_or_else(|| anyhow!("VM {} not found on '{}'", vmid, provider_name))?
+            .clone();
+
+        self.import_vm(&mut client, provider_name, &vm, region).await
+    }
+
+    /// Import every Proxmox VM carrying all of the given tags
+    pub async fn import_proxmox_vms(&self, provider_name: &str, tag_filter: &[String], region: &str) -> Result<Vec<ImportResult>> {
+        self.require_provider(provider_name, ProviderType::Proxmox)?;
+        let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+        client.login().await?;
+
+        let vms = client.get_resources(Some("vm")).await?;
+        let mut vms: Vec<serde_json::Value> = vms.as_array().into_iter().flatten()
+            .filter(|vm| vm["type"] == "qemu" && vm["template"].as_u64() != Some(1))
+            .filter(|vm| {
+                let tags = proxmox_tags(vm["tags"].as_str().unwrap_or_default());
+                tag_filter.iter().all(|wanted| tags.contains(wanted))
+            })
+            .cloned()
+            .collect();
+        vms.sort_by_key(|vm| vm["vmid"].as_u64());
+
+        let mut results = Vec::new();
+        for vm in &vms {
+            let result = match self.import_vm(&mut client, provider_name, vm, region).await {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!("Failed to import VM {}: {:#}", vm["vmid"], e);
+                    ImportResult {
+                        resource: ResourceKind::Instance,
+                        name: vm["name"].as_str().unwrap_or_default().to_string(),
+                        provider_id: vm["vmid"].to_string(),
+                        outcome: ImportOutcome::Failed { error: format!("{:#}", e) },
+                    }
+                },
+            };
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    /// Import a VM listed in the cluster resources
+    async fn import_vm(
+        &self,
+        client: &mut crate::api::proxmox::ProxmoxClient,
+        provider_name: &str,
+        vm: &serde_json::Value,
+        region: &str,
+    ) -> Result<ImportResult> {
+        let vmid = vm["vmid"].as_u64().ok_or_else(|| anyhow!("VM without a VMID"))?;
+        let node = vm["node"].as_str().ok_or_else(|| anyhow!("VM {} has no node", vmid))?;
+        let name = vm["name"].as_str().unwrap_or_default().to_string();
+        let provider_id = vmid.to_string();
+
+        let state = self.store.load()?;
+        let existing = state.instances.values().find(|i| {
+            i.provider == ProviderType::Proxmox
+                && i.provider_id == provider_id
+                && self.provider_service.find_provider_name(i).is_ok_and(|p| p == provider_name)
+        });
+        if let Some(existing) = existing {
+            return Ok(ImportResult {
+                resource: ResourceKind::Instance,
+                name: existing.name.clone(),
+                provider_id,
+                outcome: ImportOutcome::AlreadyManaged { id: existing.id },
+            });
+        }
+
+        let config = client.get_vm_config(node, vmid).await
+            .context(format!("Failed to read the configuration of VM {}", vmid))?;
+        let status = proxmox_status(vm["status"].as_str().unwrap_or_default());
+        let (instance, volumes) = instance_from_vm_config(provider_name, node, vmid, status, &config, region);
+        let id = instance.id;
+        let name = if name.is_empty() { instance.name.clone() } else { name };
+
+        self.store.transaction(&mut |state| {
+            if state.instances.values().any(|i| i.provider == ProviderType::Proxmox && i.provider_id == provider_id && i.tags.get(PROVIDER_TAG).map(String::as_str) == Some(provider_name)) {
+                return Err(anyhow!("VM {} was imported concurrently", vmid));
+            }
+            state.instances.insert(id, instance.clone());
+            for volume in &volumes {
+                if !state.volumes.values().any(|v| v.provider_id == volume.provider_id) {
+                    state.volumes.insert(volume.id, volume.clone());
+                }
+            }
+            link_allocations(state);
+            Ok(())
+        })?;
+
+        info!("Imported VM {} as instance '{}' ({}) with {} volumes", vmid, name, id, volumes.len());
+        Ok(ImportResult {
+            resource: ResourceKind::Instance,
+            name,
+            provider_id,
+            outcome: ImportOutcome::Imported { id },
+        })
+    }
+
+    /// Import the DHCP subnets of a VyOS router as networks
+    ///
+    /// Only the named shared network is imported when one is given. Shared
+    /// networks bbctl does not own keep being served by the router as they
+    /// are and get DHCP disabled in bbctl; with `adopt_dhcp` they are
+    /// replaced by bbctl-owned ones so bbctl IPAM manages them from then on.
+    /// A shared network is only adopted when all of its subnets are imported
+    /// by this run.
+    pub async fn import_vyos_networks(
+        &self,
+        provider_name: &str,
+        shared_network: Option<&str>,
+        region: &str,
+        adopt_dhcp: bool,
+    ) -> Result<Vec<ImportResult>> {
+        self.require_provider(provider_name, ProviderType::VyOS)?;
+        let mut client = self.provider_service.get_vyos_client(provider_name)?;
+
+        let commands = client.get_config_commands(&["service", "dhcp-server", "shared-network-name"]).await
+            .context(format!("Failed to read DHCP configuration from '{}'", provider_name))?;
+        let mut subnets = parse_dhcp_subnets(&commands);
+        if let Some(shared_network) = shared_network {
+            subnets.retain(|subnet| subnet.shared_network == shared_network);
+            if subnets.is_empty() {
+                return Err(anyhow!("Shared network '{}' not found on '{}'", shared_network, provider_name));
+            }
+        }
+
+        let state = self.store.load()?;
+        let on_provider: Vec<&Network> = state.networks.values()
+            .filter(|n| n.ha_routers().contains(&provider_name)
+                || self.provider_service.find_network_provider_name(n).is_ok_and(|p| p == provider_name))
+            .collect();
+
+        let mut results = Vec::new();
+        let mut adopted: BTreeMap<String, Vec<Network>> = BTreeMap::new();
+        let mut used_subnet_ids: BTreeSet<u32> = subnets.iter().filter_map(|s| s.subnet_id).collect();
+        used_subnet_ids.extend(on_provider.iter()
+            .filter_map(|n| n.get_config(DHCP_SUBNET_ID_CONFIG).and_then(|id| id.parse::<u32>().ok())));
+
+        let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+        for subnet in &subnets {
+            let count = names.entry(subnet.shared_network.as_str()).or_insert(0);
+            *count += 1;
+            let mut name = imported_network_name(&subnet.shared_network);
+            if *count > 1 {
+                name = format!("{}-{}", name, count);
+            }
+
+            if let Some(existing) = on_provider.iter().find(|n| n.cidr == subnet.cidr) {
+                results.push(ImportResult {
+                    resource: ResourceKind::Network,
+                    name: existing.name.clone(),
+                    provider_id: subnet.cidr.clone(),
+                    outcome: ImportOutcome::AlreadyManaged { id: existing.id },
+                });
+                continue;
+            }
+
+            let mut network = match network_from_dhcp_subnet(provider_name, region, &name, subnet) {
+                Ok(network) => network,
+                Err(e) => {
+                    results.push(ImportResult {
+                        resource: ResourceKind::Network,
+                        name,
+                        provider_id: subnet.cidr.clone(),
+                        outcome: ImportOutcome::Failed { error: e.to_string() },
+                    });
+                    continue;
+                },
+            };
+
+            // Until the shared network is owned by bbctl, bbctl must not serve the subnet too
+            let prefixed = subnet.shared_network.starts_with(DHCP_PREFIX);
+            if !prefixed || *count > 1 {
+                network.set_config(DHCP_CONFIG.to_string(), "false".to_string());
+                if adopt_dhcp && !prefixed {
+                    if network.get_config(DHCP_SUBNET_ID_CONFIG).is_none() {
+                        let subnet_id = (1..).find(|n| !used_subnet_ids.contains(n)).unwrap_or(1);
+                        used_subnet_ids.insert(subnet_id);
+                        network.set_config(DHCP_SUBNET_ID_CONFIG.to_string(), subnet_id.to_string());
+                    }
+                    adopted.entry(subnet.shared_network.clone()).or_default().push(network.clone());
+                }
+            }
+
+            results.push(ImportResult {
+                resource: ResourceKind::Network,
+                name,
+                provider_id: subnet.cidr.clone(),
+                outcome: ImportOutcome::Imported { id: network.id },
+            });
+
+            self.store.transaction(&mut |state| {
+                state.networks.insert(network.id, network.clone());
+                link_allocations(state);
+                Ok(())
+            })?;
+        }
+
+        // Deleting the shared network drops every subnet in it, so only adopt whole ones
+        for (shared_network, networks) in adopted {
+            let total = subnets.iter().filter(|s| s.shared_network == shared_network).count();
+            let adoption = if networks.len() < total {
+                Err(anyhow!("{} of its {} subnets were not imported", total - networks.len(), total))
+            } else {
+                self.adopt_dhcp(&mut client, provider_name, &shared_network, &networks).await
+            };
+            if let Err(e) = adoption {
+                warn!("Failed to adopt DHCP of '{}': {:#}", shared_network, e);
+                for result in results.iter_mut().filter(|r| networks.iter().any(|n| r.outcome == ImportOutcome::Imported { id: n.id })) {
+                    result.outcome = ImportOutcome::Failed {
+                        error: format!("Imported with DHCP left to the router, adoption failed: {:#}", e),
+                    };
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Replace a foreign shared network by bbctl-owned ones in a single commit
+    async fn adopt_dhcp(
+        &self,
+        client: &mut crate::api::vyos::VyOSClient,
+        provider_name: &str,
+        shared_network: &str,
+        networks: &[Network],
+    ) -> Result<()> {
+        let mut commands = vec![ConfigCommand::delete(["service", "dhcp-server", "shared-network-name", shared_network])];
+        for network in networks {
+            let mut network = network.clone();
+            network.remove_config(DHCP_CONFIG);
+            let subnet_id = network.get_config(DHCP_SUBNET_ID_CONFIG)
+                .and_then(|id| id.parse::<u32>().ok())
+                .ok_or_else(|| anyhow!("Network '{}' has no DHCP subnet ID", network.name))?;
+            commands.extend(render_dhcp_config(&network, subnet_id, provider_name)?);
+        }
+
+        client.configure(&commands).await
+            .context(format!("Failed to configure DHCP on '{}'", provider_name))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", provider_name))?;
+
+        self.store.transaction(&mut |state| {
+            for network in networks {
+                if let Some(network) = state.networks.get_mut(&network.id) {
+                    network.remove_config(DHCP_CONFIG);
+                }
+            }
+            Ok(())
+        })?;
+
+        info!("Adopted DHCP shared network '{}' on '{}'", shared_network, provider_name);
+        Ok(())
+    }
+}
@@ -1,31 +1,58 @@
 use anyhow::{Result, Context, anyhow};
-use log::{debug, info, error};
-use std::collections::HashMap;
+use log::{debug, info, warn, error};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 use serde_json::json;
-use chrono::Utc;
 
-use crate::models::instance::{Instance, InstanceStatus, InstanceSize, InstanceNetwork};
+use crate::api::proxmox::ProxmoxClient;
+use crate::api::vyos::ConfigCommand;
+use crate::models::container::{ContainerSpec, CONTAINER_IMAGE_TAG};
+use crate::models::instance::{Instance, InstanceStatus, InstanceSize, NODE_TAG, PROVIDER_TAG};
+use crate::models::network::DEFAULT_BRIDGE;
 use crate::models::provider::ProviderType;
+use crate::services::container::{container_name, pull_image, render_container_config, validate_container_spec};
+use crate::services::floating_ip::FloatingIpService;
+use crate::services::import::proxmox_nics;
+use crate::services::load_balancer::LoadBalancerService;
+use crate::services::network::NetworkService;
 use crate::services::provider::ProviderService;
+use crate::services::security_group::SecurityGroupService;
+use crate::services::volume::DEFAULT_PROXMOX_STORAGE;
+use crate::state::{FileStateStore, StateStore};
 
-/// Storage for instance data
+/// Storage for instance data, persisted in the state store
+///
+/// Reads are served from the state as of the last load or write, every
+/// change is written through to the store in its own transaction.
 #[derive(Debug)]
 pub struct InstanceStorage {
-    instances: HashMap<Uuid, Instance>,
+    store: Arc<dyn StateStore>,
+    instances: BTreeMap<Uuid, Instance>,
 }
 
 impl InstanceStorage {
-    /// Create a new instance storage
-    pub fn new() -> Self {
-        Self {
-            instances: HashMap::new(),
-        }
+    /// Create an instance storage backed by a state store
+    pub fn new(store: Arc<dyn StateStore>) -> Result<Self> {
+        let instances = store.load()?.instances;
+        Ok(Self { store, instances })
+    }
+    
+    /// Reload the instances from the store
+    pub fn reload(&mut self) -> Result<()> {
+        self.instances = self.store.load()?.instances;
+        Ok(())
     }
     
     /// Add an instance
-    pub fn add_instance(&mut self, instance: Instance) {
-        self.instances.insert(instance.id, instance);
+    pub fn add_instance(&mut self, instance: Instance) -> Result<()> {
+        let state = self.store.transaction(&mut |state| {
+            state.instances.insert(instance.id, instance.clone());
+            Ok(())
+        })?;
+        self.instances = state.instances;
+        Ok(())
     }
     
     /// Get an instance by ID
@@ -33,14 +60,34 @@ impl InstanceStorage {
         self.instances.get(id)
     }
     
-    /// Get a mutable reference to an instance
-    pub fn get_instance_mut(&mut self, id: &Uuid) -> Option<&mut Instance> {
-        self.instances.get_mut(id)
+    /// Change a stored instance and write it back
+    pub fn update_instance<T>(&mut self, id: &Uuid, change: impl FnOnce(&mut Instance) -> Result<T>) -> Result<T> {
+        let mut change = Some(change);
+        let mut output = None;
+        let state = self.store.transaction(&mut |state| {
+            let instance = state.instances.get_mut(id)
+                .ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+            let change = change.take().ok_or_else(|| anyhow!("Instance update applied twice"))?;
+            output = Some(change(instance)?);
+            Ok(())
+        })?;
+        self.instances = state.instances;
+        output.ok_or_else(|| anyhow!("Instance update was not applied"))
     }
     
     /// Remove an instance
-    pub fn remove_instance(&mut self, id: &Uuid) -> Option<Instance> {
-        self.instances.remove(id)
+    pub fn remove_instance(&mut self, id: &Uuid) -> Result<Option<Instance>> {
+        let mut removed = None;
+        let state = self.store.transaction(&mut |state| {
+            removed = state.instances.remove(id);
+            // Volumes of the removed instance are free to be attached again
+            for volume in state.volumes.values_mut().filter(|v| v.attached_to == Some(*id)) {
+                volume.detach();
+            }
+            Ok(())
+        })?;
+        self.instances = state.instances;
+        Ok(removed)
     }
     
     /// Get all instances
@@ -63,6 +110,27 @@ impl InstanceStorage {
     }
 }
 
+/// Time a Proxmox task such as a full clone may take
+const PROXMOX_TASK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Wait for a Proxmox task to finish and check that it succeeded
+async fn wait_for_task(client: &mut ProxmoxClient, node: &str, upid: &str) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + PROXMOX_TASK_TIMEOUT;
+    loop {
+        let status = client.get_task_status(node, upid).await?;
+        if status["status"].as_str() == Some("stopped") {
+            return match status["exitstatus"].as_str() {
+                Some("OK") => Ok(()),
+                other => Err(anyhow!("Task {} failed: {}", upid, other.unwrap_or("unknown error"))),
+            };
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(anyhow!("Task {} did not finish within {} seconds", upid, PROXMOX_TASK_TIMEOUT.as_secs()));
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
 /// Instance service for managing VMs
 pub struct InstanceService {
     storage: InstanceStorage,
@@ -70,12 +138,17 @@ pub struct InstanceService {
 }
 
 impl InstanceService {
-    /// Create a new instance service
-    pub fn new(provider_service: ProviderService) -> Self {
-        Self {
-            storage: InstanceStorage::new(),
+    /// Create a new instance service backed by the state store
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Self::with_store(provider_service, Arc::new(FileStateStore::open()?))
+    }
+    
+    /// Create a new instance service backed by a specific state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Result<Self> {
+        Ok(Self {
+            storage: InstanceStorage::new(store)?,
             provider_service,
-        }
+        })
     }
     
     /// List all instances
@@ -88,17 +161,37 @@ impl InstanceService {
         self.storage.get_instance(id)
     }
     
-    /// Create a new instance on a VyOS provider
+    /// Create a new instance as a container on a VyOS provider
+    ///
+    /// The image is pulled first, since VyOS refuses to commit a container
+    /// whose image is not present on the router. Names that map to a container
+    /// already on the router, such as `Web` and `web` or `web_1` and `web-1`,
+    /// are refused rather than overwriting it.
     pub async fn create_vyos_instance(
         &mut self,
         name: &str,
         provider_name: &str,
         region: &str,
         size: InstanceSize,
-        network_id: Option<String>,
+        spec: ContainerSpec,
     ) -> Result<Uuid> {
+        validate_container_spec(&spec)?;
+        let container = container_name(name)?;
+        
+        let existing = self.storage.get_all_instances().into_iter().find(|i| {
+            i.provider == ProviderType::VyOS
+                && i.provider_id == container
+                && self.find_provider_name(i).is_ok_and(|p| p == provider_name)
+        });
+        if let Some(existing) = existing {
+            return Err(anyhow!("Container '{}' is already used by instance '{}'", container, existing.name));
+        }
+        
         // Get VyOS client
         let mut client = self.provider_service.get_vyos_client(provider_name)?;
+        if client.config_exists(&["container", "name", container.as_str()]).await? {
+            return Err(anyhow!("Container '{}' already exists on '{}'", container, provider_name));
+        }
         
         // Create a new instance object
         let mut instance = Instance::new(
@@ -108,33 +201,38 @@ impl InstanceService {
             size.clone(),
         );
         
-        info!("Creating VyOS instance '{}' in region '{}'", name, region);
+        info!("Creating VyOS container '{}' for instance '{}' in region '{}'", container, name, region);
+        
+        pull_image(&mut client, &spec.image).await?;
         
-        // Use VyOS API to create the VM
-        // This is a simplified example - in a real implementation, you would call the VyOS API
-        // to create a VM and get the provider-specific ID
+        let description = format!("bbctl instance {}", instance.id);
+        let commands = render_container_config(&container, &spec, &size, &description);
+        debug!("Container configuration for '{}': {} commands", container, commands.len());
         
-        // Example: Use the VyOS API to get information about the router
-        let result = client.get_system_info().await;
+        let result = async {
+            client.configure(&commands).await?;
+            client.save().await
+        }.await;
         
         match result {
-            Ok(info) => {
-                debug!("VyOS system info: {:?}", info);
+            Ok(_) => {
+                instance.provider_id = container;
+                instance.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+                instance.add_tag(CONTAINER_IMAGE_TAG.to_string(), spec.image.clone());
                 
-                // In a real implementation, you would parse the response and set the provider ID
-                instance.provider_id = format!("vyos-{}", Uuid::new_v4());
-                
-                // Add network if specified
-                if let Some(net_id) = network_id {
-                    instance.add_network(net_id, Some("192.168.1.100".to_string()), Some("eth0".to_string()), None);
+                // Containers on a network are reachable on their static address
+                if let Some(network) = &spec.network {
+                    instance.add_network(network.clone(), spec.address.map(|a| a.to_string()), Some("eth0".to_string()), None);
                 }
+                instance.container = Some(spec);
                 
                 // Set status to running
                 instance.update_status(InstanceStatus::Running);
                 
                 // Store the instance
                 let id = instance.id;
-                self.storage.add_instance(instance);
+                self.storage.add_instance(instance.clone())?;
+                self.sync_load_balancers(&instance).await;
                 
                 info!("Successfully created VyOS instance: {}", id);
                 Ok(id)
@@ -146,88 +244,151 @@ impl InstanceService {
         }
     }
     
-    /// Create a new instance on a Proxmox provider
+    /// Create a new, empty VM on a Proxmox provider
+    ///
+    /// The VM gets a blank disk of the instance's size on the default storage
+    /// and one NIC on `vmbr0`, and is started once the create task has
+    /// finished. It has no OS, so use [`Self::clone_proxmox_instance`] for VMs
+    /// that should boot one. Addresses come from connecting the instance to
+    /// bbctl networks afterwards.
     pub async fn create_proxmox_instance(
         &mut self,
         name: &str,
         provider_name: &str,
         region: &str,
         size: InstanceSize,
-        network_id: Option<String>,
     ) -> Result<Uuid> {
-        // Get Proxmox client
         let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+        client.login().await?;
+        
+        let nodes = client.get_nodes().await?;
+        let node = nodes.as_array()
+            .and_then(|nodes| nodes.first())
+            .and_then(|node| node["node"].as_str())
+            .ok_or_else(|| anyhow!("No nodes found in Proxmox cluster"))?
+            .to_string();
+        
+        let vmid = client.next_vmid().await?;
+        info!("Creating VM {} for instance '{}' on node '{}' in region '{}'", vmid, name, node, region);
+        
+        let params = json!({
+            "vmid": vmid,
+            "name": name,
+            "cores": size.cpu,
+            "memory": u32::from(size.memory_gb) * 1024,
+            "scsihw": "virtio-scsi-pci",
+            "scsi0": format!("{}:{}", DEFAULT_PROXMOX_STORAGE, size.disk_gb),
+            "net0": format!("virtio,bridge={}", DEFAULT_BRIDGE),
+        });
+        let upid = client.create_vm(&node, params).await
+            .context(format!("Failed to create VM {}", vmid))?;
+        let upid = upid.as_str()
+            .ok_or_else(|| anyhow!("Invalid create task: {}", upid))?;
+        wait_for_task(&mut client, &node, upid).await
+            .context(format!("Failed to create VM {}", vmid))?;
+        client.start_vm(&node, vmid).await
+            .context(format!("Failed to start VM {}", vmid))?;
         
-        // Create a new instance object
         let mut instance = Instance::new(
             name.to_string(),
             ProviderType::Proxmox,
             region.to_string(),
-            size.clone(),
+            size,
         );
+        instance.provider_id = vmid.to_string();
+        instance.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+        instance.add_tag(NODE_TAG.to_string(), node.clone());
+        
+        // Proxmox picks the MAC address when the VM is created
+        let config = client.get_vm_config(&node, vmid).await?;
+        for nic in proxmox_nics(&config) {
+            instance.add_network(nic.network_id(), None, Some(nic.interface.clone()), nic.mac.clone());
+        }
+        instance.update_status(InstanceStatus::Running);
         
-        info!("Creating Proxmox instance '{}' in region '{}'", name, region);
+        let id = instance.id;
+        self.storage.add_instance(instance.clone())?;
+        self.sync_load_balancers(&instance).await;
         
-        // Ensure client is connected
+        info!("Successfully created Proxmox instance {} as VM {}", id, vmid);
+        Ok(id)
+    }
+    
+    /// Create a new instance on a Proxmox provider by cloning a VM template
+    ///
+    /// The template is found by name or VMID and fully cloned on its node.
+    /// The clone gets the instance's CPU and memory, keeps the template's
+    /// disks and NICs, and is started once the clone task has finished.
+    pub async fn clone_proxmox_instance(
+        &mut self,
+        name: &str,
+        provider_name: &str,
+        region: &str,
+        size: InstanceSize,
+        template: &str,
+    ) -> Result<Uuid> {
+        let mut client = self.provider_service.get_proxmox_client(provider_name)?;
         client.login().await?;
         
-        // Get first node in the cluster
-        let nodes = client.get_nodes().await?;
+        let resources = client.get_resources(Some("vm")).await?;
+        let source = resources.as_array().into_iter().flatten()
+            .filter(|vm| vm["template"].as_u64() == Some(1))
+            .find(|vm| vm["name"].as_str() == Some(template) || vm["vmid"].as_u64().map(|id| id.to_string()).as_deref() == Some(template))
+            .ok_or_else(|| anyhow!("Template '{}' not found on '{}'", template, provider_name))?;
+        let node = source["node"].as_str()
+            .ok_or_else(|| anyhow!("Template '{}' has no node", template))?
+            .to_string();
+        let template_id = source["vmid"].as_u64()
+            .ok_or_else(|| anyhow!("Template '{}' has no VMID", template))?;
         
-        if let Some(nodes_array) = nodes.as_array() {
-            if let Some(first_node) = nodes_array.first() {
-                if let Some(node_name) = first_node["node"].as_str() {
-                    // Parameters for VM creation
-                    let vm_params = json!({
-                        "vmid": 1000,  // This would be dynamically generated in a real implementation
-                        "name": name,
-                        "cores": size.cpu,
-                        "memory": size.memory_gb * 1024,
-                        "disk": format!("{}G", size.disk_gb),
-                        "net0": "virtio,bridge=vmbr0",
-                    });
-                    
-                    // Create the VM
-                    let result = client.create_vm(node_name, vm_params).await;
-                    
-                    match result {
-                        Ok(response) => {
-                            debug!("Proxmox VM creation response: {:?}", response);
-                            
-                            // Set provider ID to the VMID
-                            if let Some(vmid) = response["vmid"].as_u64() {
-                                instance.provider_id = vmid.to_string();
-                                
-                                // Add network if specified
-                                if let Some(net_id) = network_id {
-                                    instance.add_network(net_id, Some("192.168.1.100".to_string()), Some("eth0".to_string()), None);
-                                }
-                                
-                                // Set status to running
-                                instance.update_status(InstanceStatus::Running);
-                                
-                                // Store the instance
-                                let id = instance.id;
-                                self.storage.add_instance(instance);
-                                
-                                info!("Successfully created Proxmox instance: {}", id);
-                                return Ok(id);
-                            } else {
-                                return Err(anyhow!("Failed to get VMID from Proxmox response"));
-                            }
-                        },
-                        Err(e) => {
-                            error!("Failed to create Proxmox instance: {}", e);
-                            return Err(anyhow!("Failed to create Proxmox instance: {}", e));
-                        }
-                    }
-                }
-            }
-            
-            Err(anyhow!("No nodes found in Proxmox cluster"))
-        } else {
-            Err(anyhow!("Invalid response from Proxmox API"))
+        let vmid = client.next_vmid().await?;
+        info!("Cloning template {} into VM {} for instance '{}' on node '{}'", template_id, vmid, name, node);
+        
+        let upid = client.clone_vm(&node, template_id, json!({ "newid": vmid, "name": name, "full": 1 })).await
+            .context(format!("Failed to clone template '{}'", template))?;
+        wait_for_task(&mut client, &node, &upid).await
+            .context(format!("Failed to clone template '{}'", template))?;
+        
+        client.update_vm_config(&node, vmid, json!({ "cores": size.cpu, "memory": u32::from(size.memory_gb) * 1024 })).await
+            .context(format!("Failed to size VM {}", vmid))?;
+        client.start_vm(&node, vmid).await
+            .context(format!("Failed to start VM {}", vmid))?;
+        
+        let mut instance = Instance::new(
+            name.to_string(),
+            ProviderType::Proxmox,
+            region.to_string(),
+            size,
+        );
+        instance.provider_id = vmid.to_string();
+        instance.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+        instance.add_tag(NODE_TAG.to_string(), node.clone());
+        
+        // The clone's NICs keep the template's bridges but get new MAC addresses
+        let config = client.get_vm_config(&node, vmid).await?;
+        for nic in proxmox_nics(&config) {
+            instance.add_network(nic.network_id(), None, Some(nic.interface.clone()), nic.mac.clone());
         }
+        instance.update_status(InstanceStatus::Running);
+        
+        let id = instance.id;
+        self.storage.add_instance(instance.clone())?;
+        self.sync_load_balancers(&instance).await;
+        
+        info!("Successfully created Proxmox instance {} from template '{}'", id, template);
+        Ok(id)
+    }
+    
+    /// Set tags on an instance and update the load balancers selecting it
+    pub async fn set_tags(&mut self, id: &Uuid, tags: &BTreeMap<String, String>) -> Result<()> {
+        let instance = self.storage.update_instance(id, |instance| {
+            for (key, value) in tags {
+                instance.add_tag(key.clone(), value.clone());
+            }
+            Ok(instance.clone())
+        })?;
+        self.sync_load_balancers(&instance).await;
+        Ok(())
     }
     
     /// Start an instance
@@ -240,24 +401,29 @@ impl InstanceService {
         let provider = instance.provider;
         let provider_id = instance.provider_id.clone();
         
-        // Find the provider name
+        // Find the provider name and, for Proxmox, the hosting node
         let provider_name = self.find_provider_name(instance)?;
+        let node_name = self.provider_service.proxmox_node(&provider_name, instance);
         
         match provider {
             ProviderType::VyOS => {
                 // Get VyOS client
                 let mut client = self.provider_service.get_vyos_client(&provider_name)?;
                 
-                // Use VyOS API to start the VM
-                // Example: Send commands over SSH
-                let result = client.execute_ssh_command(&format!("start vm {}", provider_id)).await;
+                // Re-enable the container and restart it so it comes up immediately
+                let result = async {
+                    client.configure(&[ConfigCommand::delete(["container", "name", provider_id.as_str(), "disable"])]).await?;
+                    client.save().await?;
+                    client.execute_op_command(&format!("restart container {}", provider_id)).await
+                }.await;
                 
                 match result {
                     Ok(_) => {
                         // Update instance status
-                        if let Some(instance) = self.storage.get_instance_mut(id) {
+                        self.storage.update_instance(id, |instance| {
                             instance.update_status(InstanceStatus::Running);
-                        }
+                            Ok(())
+                        })?;
                         
                         info!("Successfully started VyOS instance: {}", id);
                         Ok(())
@@ -275,22 +441,19 @@ impl InstanceService {
                 // Ensure client is connected
                 client.login().await?;
                 
-                // Get the node name from the provider ID
-                // In a real implementation, you would store or lookup the node name
-                let node_name = "pve";  // Placeholder
-                
                 // Start the VM
                 let vmid = provider_id.parse::<u64>()
                     .context("Invalid VMID in provider_id")?;
                 
-                let result = client.start_vm(node_name, vmid).await;
+                let result = client.start_vm(&node_name, vmid).await;
                 
                 match result {
                     Ok(_) => {
                         // Update instance status
-                        if let Some(instance) = self.storage.get_instance_mut(id) {
+                        self.storage.update_instance(id, |instance| {
                             instance.update_status(InstanceStatus::Running);
-                        }
+                            Ok(())
+                        })?;
                         
                         info!("Successfully started Proxmox instance: {}", id);
                         Ok(())
@@ -314,24 +477,28 @@ impl InstanceService {
         let provider = instance.provider;
         let provider_id = instance.provider_id.clone();
         
-        // Find the provider name
+        // Find the provider name and, for Proxmox, the hosting node
         let provider_name = self.find_provider_name(instance)?;
+        let node_name = self.provider_service.proxmox_node(&provider_name, instance);
         
         match provider {
             ProviderType::VyOS => {
                 // Get VyOS client
                 let mut client = self.provider_service.get_vyos_client(&provider_name)?;
                 
-                // Use VyOS API to stop the VM
-                // Example: Send commands over SSH
-                let result = client.execute_ssh_command(&format!("stop vm {}", provider_id)).await;
+                // Disabling the container stops it and keeps it stopped across reboots
+                let result = async {
+                    client.configure(&[ConfigCommand::set(["container", "name", provider_id.as_str(), "disable"])]).await?;
+                    client.save().await
+                }.await;
                 
                 match result {
                     Ok(_) => {
                         // Update instance status
-                        if let Some(instance) = self.storage.get_instance_mut(id) {
+                        self.storage.update_instance(id, |instance| {
                             instance.update_status(InstanceStatus::Stopped);
-                        }
+                            Ok(())
+                        })?;
                         
                         info!("Successfully stopped VyOS instance: {}", id);
                         Ok(())
@@ -349,22 +516,19 @@ impl InstanceService {
                 // Ensure client is connected
                 client.login().await?;
                 
-                // Get the node name from the provider ID
-                // In a real implementation, you would store or lookup the node name
-                let node_name = "pve";  // Placeholder
-                
                 // Stop the VM
                 let vmid = provider_id.parse::<u64>()
                     .context("Invalid VMID in provider_id")?;
                 
-                let result = client.stop_vm(node_name, vmid).await;
+                let result = client.stop_vm(&node_name, vmid).await;
                 
                 match result {
                     Ok(_) => {
                         // Update instance status
-                        if let Some(instance) = self.storage.get_instance_mut(id) {
+                        self.storage.update_instance(id, |instance| {
                             instance.update_status(InstanceStatus::Stopped);
-                        }
+                            Ok(())
+                        })?;
                         
                         info!("Successfully stopped Proxmox instance: {}", id);
                         Ok(())
@@ -378,85 +542,193 @@ impl InstanceService {
         }
     }
     
-    /// Delete an instance
+    /// Delete an instance and release what it held
+    ///
+    /// A running Proxmox VM is stopped first, and the record is only dropped
+    /// once the delete task has finished. The instance is then disconnected
+    /// from its networks, which releases its addresses, DHCP static mappings
+    /// and DNS names, and detached from its security groups.
     pub async fn delete_instance(&mut self, id: &Uuid) -> Result<()> {
         // Get the instance
-        let instance = self.storage.get_instance(id)
+        let instance = self.storage.get_instance(id).cloned()
             .ok_or_else(|| anyhow!("Instance not found: {}", id))?;
         
-        // Clone necessary values for the match block
-        let provider = instance.provider;
-        let provider_id = instance.provider_id.clone();
-        
         // Find the provider name
-        let provider_name = self.find_provider_name(instance)?;
+        let provider_name = self.find_provider_name(&instance)?;
         
-        match provider {
+        match instance.provider {
             ProviderType::VyOS => {
                 // Get VyOS client
                 let mut client = self.provider_service.get_vyos_client(&provider_name)?;
                 
-                // Use VyOS API to delete the VM
-                // Example: Send commands over SSH
-                let result = client.execute_ssh_command(&format!("delete vm {}", provider_id)).await;
+                // Removing the container configuration stops and removes the container
+                let result = async {
+                    client.configure(&[ConfigCommand::delete(["container", "name", instance.provider_id.as_str()])]).await?;
+                    client.save().await
+                }.await;
                 
-                match result {
-                    Ok(_) => {
-                        // Remove the instance from storage
-                        self.storage.remove_instance(id);
-                        
-                        info!("Successfully deleted VyOS instance: {}", id);
-                        Ok(())
-                    },
-                    Err(e) => {
-                        error!("Failed to delete VyOS instance: {}", e);
-                        Err(anyhow!("Failed to delete VyOS instance: {}", e))
-                    }
+                if let Err(e) = result {
+                    error!("Failed to delete VyOS instance: {}", e);
+                    return Err(anyhow!("Failed to delete VyOS instance: {}", e));
                 }
             },
             ProviderType::Proxmox => {
                 // Get Proxmox client
+                let node_name = self.provider_service.proxmox_node(&provider_name, &instance);
                 let mut client = self.provider_service.get_proxmox_client(&provider_name)?;
                 
                 // Ensure client is connected
                 client.login().await?;
                 
-                // Get the node name from the provider ID
-                // In a real implementation, you would store or lookup the node name
-                let node_name = "pve";  // Placeholder
-                
-                // Delete the VM
-                let vmid = provider_id.parse::<u64>()
+                let vmid = instance.provider_id.parse::<u64>()
                     .context("Invalid VMID in provider_id")?;
                 
-                let result = client.delete_vm(node_name, vmid).await;
+                // Proxmox refuses to delete a running VM
+                let status = client.get_vm_status(&node_name, vmid).await
+                    .context(format!("Failed to read the status of VM {}", vmid))?;
+                if status["status"].as_str() == Some("running") {
+                    let upid = client.stop_vm(&node_name, vmid).await
+                        .context(format!("Failed to stop VM {}", vmid))?;
+                    let upid = upid.as_str()
+                        .ok_or_else(|| anyhow!("Invalid stop task: {}", upid))?;
+                    wait_for_task(&mut client, &node_name, upid).await
+                        .context(format!("Failed to stop VM {}", vmid))?;
+                }
                 
-                match result {
-                    Ok(_) => {
-                        // Remove the instance from storage
-                        self.storage.remove_instance(id);
-                        
-                        info!("Successfully deleted Proxmox instance: {}", id);
-                        Ok(())
-                    },
-                    Err(e) => {
-                        error!("Failed to delete Proxmox instance: {}", e);
-                        Err(anyhow!("Failed to delete Proxmox instance: {}", e))
-                    }
+                let upid = client.delete_vm(&node_name, vmid).await
+                    .context(format!("Failed to delete VM {}", vmid))?;
+                let upid = upid.as_str()
+                    .ok_or_else(|| anyhow!("Invalid delete task: {}", upid))?;
+                if let Err(e) = wait_for_task(&mut client, &node_name, upid).await {
+                    error!("Failed to delete Proxmox instance: {}", e);
+                    return Err(anyhow!("Failed to delete VM {}: {}", vmid, e));
                 }
+            },
+        }
+        
+        self.forget_instance(id).await?;
+        
+        info!("Successfully deleted {} instance: {}", instance.provider, id);
+        Ok(())
+    }
+    
+    /// Drop the record of an instance whose VM or container is already gone
+    ///
+    /// Everything a delete releases is released too: network addresses with
+    /// their DHCP static mappings and DNS records, volume attachments,
+    /// security groups, floating IPs and load balancer backends.
+    pub async fn forget_instance(&mut self, id: &Uuid) -> Result<()> {
+        let instance = self.storage.get_instance(id).cloned()
+            .ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+        
+        self.release_networks(&instance).await;
+        if let Some(instance) = self.storage.remove_instance(id)? {
+            self.sync_load_balancers(&instance).await;
+        }
+        self.release_security_groups(&instance).await;
+        self.release_floating_ips(id).await;
+        Ok(())
+    }
+    
+    /// Disconnect a deleted instance from its networks, releasing its addresses
+    ///
+    /// Failures are logged rather than returned, since the instance itself is gone.
+    async fn release_networks(&self, instance: &Instance) {
+        let mut service = match NetworkService::with_store(self.provider_service.clone(), self.storage.store.clone()) {
+            Ok(service) => service,
+            Err(e) => {
+                warn!("Failed to load networks for instance {}: {}", instance.id, e);
+                return;
+            }
+        };
+        
+        let connected: Vec<Uuid> = service.list_networks().into_iter()
+            .filter(|n| n.instances.contains(&instance.id))
+            .map(|n| n.id)
+            .collect();
+        for network_id in connected {
+            if let Err(e) = service.disconnect_instance(&network_id, &instance.id).await {
+                warn!("Failed to disconnect instance {} from network {}: {}", instance.id, network_id, e);
             }
         }
     }
     
-    /// Helper method to find provider name for an instance
-    fn find_provider_name(&self, instance: &Instance) -> Result<String> {
-        // Iterate through providers to find a matching one
-        for (name, provider) in self.provider_service.get_providers() {
-            if provider.provider_type == instance.provider {
-                return Ok(name.clone());
+    /// Detach a deleted instance from its security groups and update the remaining members
+    ///
+    /// Failures are logged rather than returned, since the instance itself is gone.
+    async fn release_security_groups(&self, instance: &Instance) {
+        let mut service = match SecurityGroupService::new(self.provider_service.clone()) {
+            Ok(service) => service,
+            Err(e) => {
+                warn!("Failed to load security groups for instance {}: {}", instance.id, e);
+                return;
+            }
+        };
+        
+        let groups: Vec<(String, bool)> = service.groups_for_instance(instance).into_iter()
+            .map(|g| (g.name.clone(), g.instances.contains(&instance.id)))
+            .collect();
+        let instances: Vec<Instance> = self.storage.get_all_instances().into_iter().cloned().collect();
+        for (name, attached) in groups {
+            if attached {
+                if let Err(e) = service.detach_instance(&name, &instance.id) {
+                    warn!("Failed to detach instance {} from security group '{}': {}", instance.id, name, e);
+                    continue;
+                }
+            }
+            if let Err(e) = service.sync_group(&name, &instances, &[]).await {
+                warn!("Failed to update security group '{}' after deleting instance {}: {}", name, instance.id, e);
+            }
+        }
+    }
+    
+    /// Release or disassociate the floating IPs of a deleted instance
+    ///
+    /// Failures are logged rather than returned, since the instance itself is gone.
+    async fn release_floating_ips(&self, id: &Uuid) {
+        let mut service = match FloatingIpService::new(self.provider_service.clone()) {
+            Ok(service) => service,
+            Err(e) => {
+                warn!("Failed to load floating IPs for instance {}: {}", id, e);
+                return;
             }
+        };
+        
+        match service.release_instance(id) {
+            Ok(addresses) if addresses.is_empty() => {},
+            Ok(_) => {
+                for (router, result) in service.sync_all().await {
+                    if let Err(e) = result {
+                        warn!("Failed to update NAT on '{}' after deleting instance {}: {}", router, id, e);
+                    }
+                }
+            },
+            Err(e) => warn!("Failed to release floating IPs of instance {}: {}", id, e),
         }
+    }
+    
+    /// Update the load balancers selecting an instance after it was created or deleted
+    ///
+    /// Failures are logged rather than returned, since the instance change itself succeeded.
+    async fn sync_load_balancers(&self, instance: &Instance) {
+        let service = match LoadBalancerService::new(self.provider_service.clone()) {
+            Ok(service) => service,
+            Err(e) => {
+                warn!("Failed to load load balancers for instance {}: {}", instance.id, e);
+                return;
+            }
+        };
         
-        Err(anyhow!("No provider found for instance: {}", instance.id))
+        let instances: Vec<Instance> = self.storage.get_all_instances().into_iter().cloned().collect();
+        for router in service.routers_for_instance(instance) {
+            if let Err(e) = service.sync_router(&router, &instances).await {
+                warn!("Failed to update load balancers on '{}' for instance {}: {}", router, instance.id, e);
+            }
+        }
+    }
+    
+    /// Helper method to find provider name for an instance
+    fn find_provider_name(&self, instance: &Instance) -> Result<String> {
+        self.provider_service.find_provider_name(instance)
     }
 }
\ No newline at end of file
@@ -0,0 +1,325 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::config::load_balancers::LoadBalancers;
+use crate::models::dns::sanitize_label;
+use crate::models::instance::Instance;
+use crate::models::load_balancer::{Backend, LoadBalancer, ProxyMode, BALANCE_ALGORITHMS};
+use crate::models::provider::ProviderType;
+use crate::services::provider::ProviderService;
+
+/// Prefix of reverse-proxy services and backends owned by bbctl
+pub const LB_PREFIX: &str = "bb-";
+
+/// Name of the VyOS object for a frontend or backend of a load balancer
+fn object_name(load_balancer: &str, name: &str) -> String {
+    format!("{}{}-{}", LB_PREFIX, load_balancer, name)
+}
+
+/// Check whether a VyOS config path is owned by load balancers
+pub fn is_managed_lb_path(path: &[String]) -> bool {
+    match path {
+        [lb, proxy, kind, name, ..] if lb == "load-balancing" && proxy == "reverse-proxy" => {
+            (kind == "service" || kind == "backend") && name.starts_with(LB_PREFIX)
+        },
+        _ => false,
+    }
+}
+
+/// Load a load balancer description from a TOML file
+pub fn load_load_balancer(path: &Path) -> Result<LoadBalancer> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read load balancer file: {}", path.display()))?;
+
+    toml::from_str(&content).context("Failed to parse load balancer TOML")
+}
+
+/// Check that a name can be embedded in VyOS object names
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Validate a load balancer against the others sharing its router
+pub fn validate_load_balancer(load_balancer: &LoadBalancer, others: &[&LoadBalancer]) -> Result<()> {
+    if !is_valid_name(&load_balancer.name) {
+        return Err(anyhow!("Invalid load balancer name '{}'", load_balancer.name));
+    }
+    if load_balancer.frontends.is_empty() {
+        return Err(anyhow!("Load balancer '{}' has no frontends", load_balancer.name));
+    }
+
+    let mut backends = HashSet::new();
+    for backend in &load_balancer.backends {
+        if !is_valid_name(&backend.name) {
+            return Err(anyhow!("Invalid backend name '{}'", backend.name));
+        }
+        if !backends.insert(backend.name.as_str()) {
+            return Err(anyhow!("Backend '{}' is defined more than once", backend.name));
+        }
+        if backend.selector.is_empty() {
+            return Err(anyhow!("Backend '{}' has no tag selector", backend.name));
+        }
+        if !BALANCE_ALGORITHMS.contains(&backend.balance.as_str()) {
+            return Err(anyhow!(
+                "Unknown balance algorithm '{}' (expected one of: {})",
+                backend.balance, BALANCE_ALGORITHMS.join(", "),
+            ));
+        }
+        if backend.mode == ProxyMode::Tcp && backend.health_check.as_ref().is_some_and(|h| h.path.is_some()) {
+            return Err(anyhow!("Backend '{}' uses an HTTP health check in tcp mode", backend.name));
+        }
+    }
+
+    // Ports already taken by other load balancers on the same router
+    let taken: HashSet<u16> = others.iter()
+        .filter(|lb| lb.router == load_balancer.router && lb.name != load_balancer.name)
+        .flat_map(|lb| lb.frontends.iter().map(|f| f.port))
+        .collect();
+
+    let mut frontends = HashSet::new();
+    let mut ports = HashSet::new();
+    for frontend in &load_balancer.frontends {
+        if !is_valid_name(&frontend.name) {
+            return Err(anyhow!("Invalid frontend name '{}'", frontend.name));
+        }
+        if !frontends.insert(frontend.name.as_str()) {
+            return Err(anyhow!("Frontend '{}' is defined more than once", frontend.name));
+        }
+        if !ports.insert(frontend.port) || taken.contains(&frontend.port) {
+            return Err(anyhow!("Port {} is already used on router '{}'", frontend.port, load_balancer.router));
+        }
+
+        let backend = load_balancer.get_backend(&frontend.backend)
+            .ok_or_else(|| anyhow!("Frontend '{}' uses unknown backend '{}'", frontend.name, frontend.backend))?;
+        if backend.mode != frontend.mode {
+            return Err(anyhow!("Frontend '{}' and backend '{}' use different modes", frontend.name, backend.name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the servers of a backend as `(name, address)` pairs
+///
+/// Every selected instance contributes its first network address; server
+/// names are derived from the instance name.
+pub fn backend_servers(backend: &Backend, instances: &[Instance]) -> Vec<(String, String)> {
+    let mut members: Vec<&Instance> = instances.iter()
+        .filter(|i| backend.selects(i))
+        .collect();
+    members.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+
+    let mut names = BTreeSet::new();
+    let mut servers = Vec::new();
+    for instance in members {
+        let Some(address) = instance.networks.iter().find_map(|n| n.ip.clone()) else {
+            continue;
+        };
+
+        let mut name = sanitize_label(&instance.name);
+        if name.is_empty() || !names.insert(name.clone()) {
+            name = format!("i-{}", &instance.id.simple().to_string()[..8]);
+            names.insert(name.clone());
+        }
+        servers.push((name, address));
+    }
+
+    servers
+}
+
+/// Render the reverse-proxy configuration of a set of load balancers
+pub fn render_lb_config(load_balancers: &[&LoadBalancer], instances: &[Instance]) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+
+    let mut sorted: Vec<&LoadBalancer> = load_balancers.to_vec();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for load_balancer in sorted {
+        for backend in &load_balancer.backends {
+            let name = object_name(&load_balancer.name, &backend.name);
+            let base = ["load-balancing", "reverse-proxy", "backend", name.as_str()];
+            let mode = backend.mode.to_string();
+            let port = backend.port.to_string();
+
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["mode", mode.as_str()])));
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["balance", backend.balance.as_str()])));
+
+            if let Some(check) = &backend.health_check {
+                if let Some(path) = &check.path {
+                    commands.push(ConfigCommand::set(base.iter().copied().chain(["http-check", "uri", path.as_str()])));
+                }
+                if let Some(status) = check.expect_status {
+                    let status = status.to_string();
+                    commands.push(ConfigCommand::set(
+                        base.iter().copied().chain(["http-check", "expect", "status", status.as_str()]),
+                    ));
+                }
+            }
+
+            for (server, address) in backend_servers(backend, instances) {
+                let server_base: Vec<&str> = base.iter().copied().chain(["server", server.as_str()]).collect();
+                commands.push(ConfigCommand::set(server_base.iter().copied().chain(["address", address.as_str()])));
+                commands.push(ConfigCommand::set(server_base.iter().copied().chain(["port", port.as_str()])));
+                if backend.health_check.is_some() {
+                    commands.push(ConfigCommand::set(server_base.iter().copied().chain(["check"])));
+                }
+            }
+        }
+
+        for frontend in &load_balancer.frontends {
+            let name = object_name(&load_balancer.name, &frontend.name);
+            let base = ["load-balancing", "reverse-proxy", "service", name.as_str()];
+            let port = frontend.port.to_string();
+            let mode = frontend.mode.to_string();
+            let backend = object_name(&load_balancer.name, &frontend.backend);
+
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["port", port.as_str()])));
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["mode", mode.as_str()])));
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["backend", backend.as_str()])));
+            if let Some(address) = &frontend.listen_address {
+                commands.push(ConfigCommand::set(base.iter().copied().chain(["listen-address", address.as_str()])));
+            }
+            if let Some(certificate) = &frontend.certificate {
+                commands.push(ConfigCommand::set(base.iter().copied().chain(["ssl", "certificate", certificate.as_str()])));
+            }
+        }
+    }
+
+    commands
+}
+
+/// Load balancer service for managing reverse proxies on VyOS routers
+pub struct LoadBalancerService {
+    load_balancers: LoadBalancers,
+    provider_service: ProviderService,
+}
+
+impl LoadBalancerService {
+    /// Create a new load balancer service
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        let load_balancers = LoadBalancers::load()?;
+
+        Ok(Self {
+            load_balancers,
+            provider_service,
+        })
+    }
+
+    /// List all load balancers
+    pub fn list_load_balancers(&self) -> Vec<&LoadBalancer> {
+        let mut load_balancers: Vec<&LoadBalancer> = self.load_balancers.get_all_load_balancers().values().collect();
+        load_balancers.sort_by(|a, b| a.name.cmp(&b.name));
+        load_balancers
+    }
+
+    /// Get a load balancer by name
+    pub fn get_load_balancer(&self, name: &str) -> Option<&LoadBalancer> {
+        self.load_balancers.get_load_balancer(name)
+    }
+
+    /// Create or replace a load balancer
+    ///
+    /// Returns the previous definition when one was replaced, so its router
+    /// can be cleaned up if the load balancer moved.
+    pub fn apply_load_balancer(&mut self, mut load_balancer: LoadBalancer) -> Result<Option<LoadBalancer>> {
+        let provider = self.provider_service.get_providers().get(&load_balancer.router)
+            .ok_or_else(|| anyhow!("Provider '{}' not found", load_balancer.router))?;
+        if provider.provider_type != ProviderType::VyOS {
+            return Err(anyhow!("Provider '{}' is not a VyOS router", load_balancer.router));
+        }
+
+        let others: Vec<&LoadBalancer> = self.load_balancers.get_all_load_balancers().values().collect();
+        validate_load_balancer(&load_balancer, &others)?;
+
+        if let Some(existing) = self.load_balancers.get_load_balancer(&load_balancer.name) {
+            load_balancer.created_at = existing.created_at;
+        }
+        load_balancer.updated_at = chrono::Utc::now();
+
+        let previous = self.load_balancers.upsert_load_balancer(load_balancer);
+        self.load_balancers.save()?;
+        Ok(previous)
+    }
+
+    /// Delete a load balancer
+    pub fn delete_load_balancer(&mut self, name: &str) -> Result<LoadBalancer> {
+        let load_balancer = self.load_balancers.remove_load_balancer(name)?;
+        self.load_balancers.save()?;
+
+        info!("Deleted load balancer: {}", name);
+        Ok(load_balancer)
+    }
+
+    /// Routers running at least one load balancer
+    pub fn routers(&self) -> Vec<String> {
+        let routers: BTreeSet<String> = self.load_balancers.get_all_load_balancers().values()
+            .map(|lb| lb.router.clone())
+            .collect();
+        routers.into_iter().collect()
+    }
+
+    /// Routers of the load balancers that select an instance
+    pub fn routers_for_instance(&self, instance: &Instance) -> Vec<String> {
+        let routers: BTreeSet<String> = self.load_balancers.get_all_load_balancers().values()
+            .filter(|lb| lb.selects(instance))
+            .map(|lb| lb.router.clone())
+            .collect();
+        routers.into_iter().collect()
+    }
+
+    /// Render the desired reverse-proxy configuration of a router
+    pub fn render_router(&self, router: &str, instances: &[Instance]) -> Vec<ConfigCommand> {
+        let load_balancers: Vec<&LoadBalancer> = self.load_balancers.get_all_load_balancers().values()
+            .filter(|lb| lb.router == router)
+            .collect();
+        render_lb_config(&load_balancers, instances)
+    }
+
+    /// Compute the reverse-proxy changes for a router
+    pub async fn plan(&self, router: &str, instances: &[Instance]) -> Result<ConfigDiff> {
+        let desired = self.render_router(router, instances);
+
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["load-balancing", "reverse-proxy"]).await
+            .context(format!("Failed to read reverse-proxy configuration from '{}'", router))?
+            .into_iter()
+            .filter(|c| is_managed_lb_path(c.path()))
+            .collect();
+
+        debug!("Router '{}' has {} managed reverse-proxy paths", router, current.len());
+        Ok(ConfigDiff::between(&current, &desired))
+    }
+
+    /// Bring the reverse proxy of a router in line with its load balancers
+    pub async fn sync_router(&self, router: &str, instances: &[Instance]) -> Result<usize> {
+        let diff = self.plan(router, instances).await?;
+        if diff.is_empty() {
+            info!("Load balancers on '{}' are already up to date", router);
+            return Ok(0);
+        }
+
+        let commands = diff.commands();
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        client.configure(&commands).await
+            .context(format!("Failed to configure load balancers on '{}'", router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+
+        info!("Applied {} load balancer changes to '{}'", commands.len(), router);
+        Ok(commands.len())
+    }
+
+    /// Sync every router running a load balancer
+    pub async fn sync_all(&self, instances: &[Instance]) -> Vec<(String, Result<usize>)> {
+        let mut results = Vec::new();
+        for router in self.routers() {
+            let result = self.sync_router(&router, instances).await;
+            results.push((router, result));
+        }
+        results
+    }
+}
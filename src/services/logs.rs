@@ -0,0 +1,466 @@
+use anyhow::{Result, Context, anyhow};
+use chrono::Utc;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+
+use crate::api::syslog::{facility_name, parse_syslog, split_frames};
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::config::get_config_dir;
+use crate::models::logs::{LogEntry, Severity};
+use crate::models::provider::ProviderConfig;
+use crate::services::provider::ProviderService;
+
+/// Directory under the config directory holding collected logs
+pub const LOGS_DIR: &str = "logs";
+
+/// Syslog transport protocols VyOS can send with
+pub const SYSLOG_PROTOCOLS: &[&str] = &["udp", "tcp"];
+
+/// Check whether a VyOS config path belongs to the remote syslog host of a collector
+pub fn is_managed_syslog_path(path: &[String], collector: &str) -> bool {
+    matches!(path, [system, syslog, host, address, ..]
+        if system == "system" && syslog == "syslog" && host == "host" && address == collector)
+}
+
+/// Render the VyOS configuration sending syslog to a collector
+pub fn render_syslog_config(collector: IpAddr, port: u16, protocol: &str, level: Severity) -> Vec<ConfigCommand> {
+    let collector = collector.to_string();
+    let port = port.to_string();
+    let level = level.to_string();
+    let base = ["system", "syslog", "host", collector.as_str()];
+
+    vec![
+        ConfigCommand::set(base.iter().copied().chain(["facility", "all", "level", level.as_str()])),
+        ConfigCommand::set(base.iter().copied().chain(["port", port.as_str()])),
+        ConfigCommand::set(base.iter().copied().chain(["protocol", protocol])),
+    ]
+}
+
+/// Maps senders to the providers they belong to
+#[derive(Debug, Clone, Default)]
+pub struct ProviderResolver {
+    by_address: HashMap<IpAddr, String>,
+    by_name: HashMap<String, String>,
+}
+
+impl ProviderResolver {
+    /// Build a resolver from the configured providers
+    ///
+    /// Provider hosts given as names are resolved once; a sender is also
+    /// recognized when the host name in its messages matches a provider.
+    pub fn from_providers(providers: &HashMap<String, ProviderConfig>) -> Self {
+        let mut resolver = Self::default();
+
+        for (name, provider) in providers {
+            resolver.by_name.insert(name.to_lowercase(), name.clone());
+            let host = provider.host.trim();
+            resolver.by_name.insert(host.to_lowercase(), name.clone());
+
+            match host.parse::<IpAddr>() {
+                Ok(address) => { resolver.by_address.insert(address, name.clone()); },
+                Err(_) => match (host, 0).to_socket_addrs() {
+                    Ok(addresses) => {
+                        for address in addresses {
+                            resolver.by_address.insert(address.ip(), name.clone());
+                        }
+                    },
+                    Err(e) => debug!("Could not resolve host '{}' of provider '{}': {}", host, name, e),
+                },
+            }
+        }
+
+        resolver
+    }
+
+    /// Find the provider a message came from
+    pub fn resolve(&self, source: &IpAddr, hostname: Option<&str>) -> Option<String> {
+        self.by_address.get(source).cloned()
+            .or_else(|| hostname.and_then(|h| self.by_name.get(&h.to_lowercase()).cloned()))
+    }
+}
+
+/// Selection of log entries
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Only entries from this provider
+    pub provider: Option<String>,
+    /// Only entries whose message, application or sender contains this text (case-insensitive)
+    pub grep: Option<String>,
+    /// Only entries at least this severe
+    pub severity: Option<Severity>,
+}
+
+impl LogFilter {
+    /// Check whether an entry is selected
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if let Some(provider) = &self.provider {
+            if entry.provider.as_deref() != Some(provider.as_str()) {
+                return false;
+            }
+        }
+        if let Some(severity) = self.severity {
+            if entry.severity > severity {
+                return false;
+            }
+        }
+        if let Some(grep) = &self.grep {
+            let grep = grep.to_lowercase();
+            let found = entry.message.to_lowercase().contains(&grep)
+                || entry.app_name.as_deref().is_some_and(|a| a.to_lowercase().contains(&grep))
+                || entry.sender().to_lowercase().contains(&grep);
+            if !found {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Position in the log store after the last entry read
+#[derive(Debug, Clone)]
+pub struct LogCursor {
+    path: Option<PathBuf>,
+    offset: u64,
+}
+
+/// Local store of collected log messages
+///
+/// Entries are appended as JSON lines to one file per day.
+#[derive(Debug, Clone)]
+pub struct LogStore {
+    dir: PathBuf,
+}
+
+impl LogStore {
+    /// Open the store in the bbctl config directory
+    pub fn open() -> Result<Self> {
+        Ok(Self::with_dir(get_config_dir()?.join(LOGS_DIR)))
+    }
+
+    /// Open a store in a specific directory
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Directory holding the logs
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append an entry to the store
+    pub fn append(&self, entry: &LogEntry) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .context(format!("Failed to create logs directory {}", self.dir.display()))?;
+
+        let path = self.dir.join(format!("{}.jsonl", entry.received_at.format("%Y-%m-%d")));
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        OpenOptions::new().create(true).append(true).open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Read the last entries selected by a filter, oldest first
+    pub fn tail(&self, filter: &LogFilter, limit: usize) -> Result<Vec<LogEntry>> {
+        let mut entries = Vec::new();
+
+        // Walk the days backwards until enough entries are found
+        for path in self.files()?.into_iter().rev() {
+            let content = fs::read_to_string(&path)
+                .context(format!("Failed to read {}", path.display()))?;
+            let mut day: Vec<LogEntry> = parse_lines(&path, &content).into_iter()
+                .filter(|e| filter.matches(e))
+                .collect();
+
+            day.append(&mut entries);
+            entries = day;
+            if entries.len() >= limit {
+                break;
+            }
+        }
+
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.split_off(skip))
+    }
+
+    /// Cursor positioned after the newest entry
+    pub fn end_cursor(&self) -> Result<LogCursor> {
+        let path = self.files()?.pop();
+        let offset = match &path {
+            Some(path) => fs::metadata(path)?.len(),
+            None => 0,
+        };
+
+        Ok(LogCursor { path, offset })
+    }
+
+    /// Read the entries appended since a cursor, advancing it
+    pub fn read_new(&self, cursor: &mut LogCursor, filter: &LogFilter) -> Result<Vec<LogEntry>> {
+        let mut entries = Vec::new();
+
+        for path in self.files()? {
+            // Skip the days before the cursor's file
+            if cursor.path.as_ref().is_some_and(|current| &path < current) {
+                continue;
+            }
+            if cursor.path.as_ref() != Some(&path) {
+                cursor.path = Some(path.clone());
+                cursor.offset = 0;
+            }
+
+            let mut file = File::open(&path).context(format!("Failed to open {}", path.display()))?;
+            file.seek(SeekFrom::Start(cursor.offset))?;
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+
+            // Leave a partially written line for the next read
+            let complete = content.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+            cursor.offset += complete as u64;
+            entries.extend(parse_lines(&path, &content[..complete]).into_iter().filter(|e| filter.matches(e)));
+        }
+
+        Ok(entries)
+    }
+
+    /// Day files in chronological order
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .context(format!("Failed to read logs directory {}", self.dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Parse the JSON lines of a day file, skipping invalid lines
+fn parse_lines(path: &Path, content: &str) -> Vec<LogEntry> {
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(idx, line)| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping invalid log entry at {}:{}: {}", path.display(), idx + 1, e);
+                None
+            },
+        })
+        .collect()
+}
+
+/// Read syslog frames from a TCP connection and hand them to the receiver
+async fn read_tcp_stream(mut stream: TcpStream, source: IpAddr, frames: mpsc::Sender<(IpAddr, String)>) {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    loop {
+        match stream.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(len) => {
+                buffer.extend_from_slice(&chunk[..len]);
+                for frame in split_frames(&mut buffer) {
+                    if frames.send((source, frame)).await.is_err() {
+                        return;
+                    }
+                }
+            },
+            Err(e) => {
+                debug!("Syslog connection from {} failed: {}", source, e);
+                break;
+            },
+        }
+    }
+
+    // A final message without a trailing newline
+    if !buffer.is_empty() {
+        let _ = frames.send((source, String::from_utf8_lossy(&buffer).into_owned())).await;
+    }
+}
+
+/// Receive a datagram, never completing without a socket
+async fn recv_datagram(socket: Option<&UdpSocket>, buffer: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+    match socket {
+        Some(socket) => socket.recv_from(buffer).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Accept a connection, never completing without a listener
+async fn accept_connection(listener: Option<&TcpListener>) -> std::io::Result<(TcpStream, SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Syslog listener storing messages tagged with their provider
+pub struct SyslogReceiver {
+    udp: Option<UdpSocket>,
+    tcp: Option<TcpListener>,
+    resolver: ProviderResolver,
+    store: LogStore,
+}
+
+impl SyslogReceiver {
+    /// Bind the UDP and/or TCP listeners
+    pub async fn bind(
+        udp: Option<SocketAddr>,
+        tcp: Option<SocketAddr>,
+        resolver: ProviderResolver,
+        store: LogStore,
+    ) -> Result<Self> {
+        if udp.is_none() && tcp.is_none() {
+            return Err(anyhow!("At least one of UDP and TCP must be enabled"));
+        }
+
+        let udp = match udp {
+            Some(address) => Some(UdpSocket::bind(address).await
+                .context(format!("Failed to bind syslog UDP listener to {}", address))?),
+            None => None,
+        };
+        let tcp = match tcp {
+            Some(address) => Some(TcpListener::bind(address).await
+                .context(format!("Failed to bind syslog TCP listener to {}", address))?),
+            None => None,
+        };
+
+        Ok(Self { udp, tcp, resolver, store })
+    }
+
+    /// Address of the UDP listener
+    pub fn udp_addr(&self) -> Option<SocketAddr> {
+        self.udp.as_ref().and_then(|s| s.local_addr().ok())
+    }
+
+    /// Address of the TCP listener
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        self.tcp.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    /// Parse a message, tag it with its provider and store it
+    pub fn handle_message(&self, source: IpAddr, line: &str) -> Result<LogEntry> {
+        let message = parse_syslog(line)?;
+
+        let entry = LogEntry {
+            received_at: Utc::now(),
+            timestamp: message.timestamp,
+            provider: self.resolver.resolve(&source, message.hostname.as_deref()),
+            source,
+            hostname: message.hostname,
+            facility: facility_name(message.facility).to_string(),
+            severity: Severity::from_code(message.severity),
+            app_name: message.app_name,
+            message: message.message,
+        };
+
+        self.store.append(&entry)?;
+        Ok(entry)
+    }
+
+    /// Receive messages until interrupted, calling `on_entry` for each stored entry
+    pub async fn run(&mut self, mut on_entry: impl FnMut(&LogEntry)) -> Result<()> {
+        let (sender, mut frames) = mpsc::channel::<(IpAddr, String)>(1024);
+        let mut buffer = vec![0u8; 65535];
+
+        loop {
+            tokio::select! {
+                result = recv_datagram(self.udp.as_ref(), &mut buffer) => {
+                    match result {
+                        Ok((len, source)) => {
+                            let line = String::from_utf8_lossy(&buffer[..len]).into_owned();
+                            let _ = sender.send((source.ip(), line)).await;
+                        },
+                        Err(e) => warn!("Failed to receive syslog datagram: {}", e),
+                    }
+                },
+                result = accept_connection(self.tcp.as_ref()) => {
+                    match result {
+                        Ok((stream, peer)) => {
+                            debug!("Syslog connection from {}", peer);
+                            tokio::spawn(read_tcp_stream(stream, peer.ip(), sender.clone()));
+                        },
+                        Err(e) => warn!("Failed to accept syslog connection: {}", e),
+                    }
+                },
+                Some((source, line)) = frames.recv() => {
+                    match self.handle_message(source, &line) {
+                        Ok(entry) => on_entry(&entry),
+                        Err(e) => debug!("Dropping syslog message from {}: {:#}", source, e),
+                    }
+                },
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Syslog receiver stopped");
+                    return Ok(());
+                },
+            }
+        }
+    }
+}
+
+/// Service configuring remote syslog on VyOS routers
+pub struct LogService {
+    provider_service: ProviderService,
+}
+
+impl LogService {
+    /// Create a new log service
+    pub fn new(provider_service: ProviderService) -> Self {
+        Self { provider_service }
+    }
+
+    /// Resolver tagging messages with the configured providers
+    pub fn resolver(&self) -> ProviderResolver {
+        ProviderResolver::from_providers(self.provider_service.get_providers())
+    }
+
+    /// Configure a router to send its logs to a collector
+    pub async fn configure_router(
+        &self,
+        router: &str,
+        collector: IpAddr,
+        port: u16,
+        protocol: &str,
+        level: Severity,
+    ) -> Result<usize> {
+        if !SYSLOG_PROTOCOLS.contains(&protocol) {
+            return Err(anyhow!("Syslog protocol must be one of: {}", SYSLOG_PROTOCOLS.join(", ")));
+        }
+        let desired = render_syslog_config(collector, port, protocol, level);
+
+        let address = collector.to_string();
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["system", "syslog"]).await
+            .context(format!("Failed to read syslog configuration from '{}'", router))?
+            .into_iter()
+            .filter(|c| is_managed_syslog_path(c.path(), &address))
+            .collect();
+
+        let diff = ConfigDiff::between(&current, &desired);
+        if diff.is_empty() {
+            info!("Syslog on '{}' already points at {}", router, address);
+            return Ok(0);
+        }
+
+        let commands = diff.commands();
+        client.configure(&commands).await
+            .context(format!("Failed to configure syslog on '{}'", router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+
+        info!("Applied {} syslog changes to '{}'", commands.len(), router);
+        Ok(commands.len())
+    }
+}
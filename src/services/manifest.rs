@@ -0,0 +1,1531 @@
+use anyhow::{Result, Context, anyhow};
+use futures::future::join_all;
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::models::container::{ContainerSpec, CONTAINER_IMAGE_TAG};
+use crate::models::instance::{Instance, InstanceSize, InstanceStatus, NODE_TAG, PROVIDER_TAG};
+use crate::models::manifest::{
+    Manifest, ManifestInstance, ManifestInstanceNetwork, ManifestNetwork, ManifestRule, ManifestSecurityGroup,
+    ManifestVolume, MANIFEST_TAG,
+};
+use crate::models::network::{Ipv4Cidr, Network, NetworkStatus, NetworkType, TENANT_TAG};
+use crate::models::provider::ProviderType;
+use crate::models::security_group::{RuleDirection, RulePeer, SecurityGroup, SecurityGroupRule};
+use crate::models::volume::{Volume, VolumeStatus};
+use crate::services::instance::InstanceService;
+use crate::services::network::NetworkService;
+use crate::services::provider::ProviderService;
+use crate::services::refresh::{Drift, DriftKind, RefreshService, ResourceKind};
+use crate::services::security_group::SecurityGroupService;
+use crate::services::volume::{VolumeService, STORAGE_TAG};
+use crate::state::{FileStateStore, State, StateStore};
+
+/// Concurrency key of the steps changing security groups, which share one file
+const SECURITY_GROUPS_KEY: &str = "security-groups";
+
+/// Load a manifest from a TOML file
+pub fn load_manifest(path: &Path) -> Result<Manifest> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read manifest file: {}", path.display()))?;
+
+    let manifest: Manifest = toml::from_str(&content)
+        .context("Failed to parse manifest TOML")?;
+
+    validate_manifest(&manifest)?;
+    Ok(manifest)
+}
+
+/// Check that names are unique within a kind of resource
+fn check_unique<'a>(kind: &str, names: impl Iterator<Item = &'a str>) -> Result<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if name.is_empty() {
+            return Err(anyhow!("A {} has an empty name", kind));
+        }
+        if !seen.insert(name) {
+            return Err(anyhow!("{} '{}' is defined more than once", kind, name));
+        }
+    }
+    Ok(())
+}
+
+/// Validate a manifest on its own, without looking at stored resources
+pub fn validate_manifest(manifest: &Manifest) -> Result<()> {
+    if manifest.name.is_empty() {
+        return Err(anyhow!("The manifest has no name"));
+    }
+    check_unique("Network", manifest.networks.iter().map(|n| n.name.as_str()))?;
+    check_unique("Security group", manifest.security_groups.iter().map(|g| g.name.as_str()))?;
+    check_unique("Instance", manifest.instances.iter().map(|i| i.name.as_str()))?;
+    check_unique("Volume", manifest.volumes.iter().map(|v| v.name.as_str()))?;
+
+    for network in &manifest.networks {
+        if manifest.provider_for(&network.provider).is_none() {
+            return Err(anyhow!("Network '{}' has no provider", network.name));
+        }
+        Ipv4Cidr::parse(&network.cidr)
+            .map_err(|e| anyhow!("Network '{}' has an invalid CIDR '{}': {}", network.name, network.cidr, e))?;
+    }
+
+    for group in &manifest.security_groups {
+        for direction in [RuleDirection::Ingress, RuleDirection::Egress] {
+            let rules = group.rules(direction)
+                .map_err(|e| anyhow!("Security group '{}' has an invalid {} rule: {}", group.name, direction, e))?;
+            if rules.iter().any(|r| r.port_from.is_some() && r.protocol != "tcp" && r.protocol != "udp") {
+                return Err(anyhow!("Security group '{}' has ports on a rule that is not tcp or udp", group.name));
+            }
+        }
+    }
+
+    for instance in &manifest.instances {
+        if manifest.provider_for(&instance.provider).is_none() {
+            return Err(anyhow!("Instance '{}' has no provider", instance.name));
+        }
+        if instance.cpu == 0 || instance.memory_gb == 0 {
+            return Err(anyhow!("Instance '{}' needs at least 1 CPU and 1 GB of memory", instance.name));
+        }
+        check_unique("Network connection", instance.networks.iter().map(|n| n.network.as_str()))
+            .map_err(|e| anyhow!("Instance '{}': {}", instance.name, e))?;
+    }
+
+    for volume in &manifest.volumes {
+        if manifest.provider_for(&volume.provider).is_none() {
+            return Err(anyhow!("Volume '{}' has no provider", volume.name));
+        }
+        if volume.size_gb == 0 {
+            return Err(anyhow!("Volume '{}' must be at least 1 GB", volume.name));
+        }
+        if let Some(instance) = &volume.instance {
+            let target = manifest.get_instance(instance)
+                .ok_or_else(|| anyhow!("Volume '{}' is attached to unknown instance '{}'", volume.name, instance))?;
+            if manifest.provider_for(&target.provider) != manifest.provider_for(&volume.provider) {
+                return Err(anyhow!("Volume '{}' is not on the provider of instance '{}'", volume.name, instance));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Kind of resource a manifest describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestResource {
+    Network,
+    SecurityGroup,
+    Instance,
+    Volume,
+}
+
+impl std::fmt::Display for ManifestResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestResource::Network => write!(f, "network"),
+            ManifestResource::SecurityGroup => write!(f, "security group"),
+            ManifestResource::Instance => write!(f, "instance"),
+            ManifestResource::Volume => write!(f, "volume"),
+        }
+    }
+}
+
+/// Stage of a plan
+///
+/// Stages run in order. Links are removed and resources deleted from the
+/// most to the least dependent before anything is created the other way round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    /// Disconnect networks, detach volumes and security groups
+    Unlink,
+    /// Delete volumes
+    RemoveVolumes,
+    /// Delete instances
+    RemoveInstances,
+    /// Delete networks and security groups
+    RemoveNetworks,
+    /// Create and update networks and security groups
+    Networks,
+    /// Create and update instances
+    Instances,
+    /// Create volumes
+    Volumes,
+    /// Connect networks, attach volumes and security groups
+    Link,
+}
+
+impl Stage {
+    /// Every stage, in execution order
+    pub const ALL: [Stage; 8] = [
+        Stage::Unlink,
+        Stage::RemoveVolumes,
+        Stage::RemoveInstances,
+        Stage::RemoveNetworks,
+        Stage::Networks,
+        Stage::Instances,
+        Stage::Volumes,
+        Stage::Link,
+    ];
+}
+
+/// Kind of change a step makes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Create,
+    Update,
+    Delete,
+}
+
+/// Operation a step performs
+///
+/// Resources created by the same plan are referred to by name and resolved
+/// when the step runs, existing ones by ID.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    CreateNetwork { name: String },
+    DeleteNetwork { id: Uuid },
+    SetTenant { id: Uuid, tenant: String },
+    CreateSecurityGroup { name: String },
+    SetSecurityGroupRules { name: String },
+    DeleteSecurityGroup { name: String },
+    CreateInstance { name: String },
+    DeleteInstance { id: Uuid },
+    StartInstance { id: Uuid },
+    StopInstance { id: Uuid },
+    SetInstanceTags { id: Uuid, tags: BTreeMap<String, String> },
+    CreateVolume { name: String },
+    DeleteVolume { id: Uuid },
+    ConnectNetwork { instance: String, network: String, ip: Option<IpAddr> },
+    DisconnectNetwork { instance_id: Uuid, network_id: Uuid },
+    AttachSecurityGroup { group: String, instance: String },
+    DetachSecurityGroup { group: String, instance_id: Uuid },
+    AttachVolume { volume: String, instance: String },
+    DetachVolume { id: Uuid },
+    /// Record an existing resource as owned by the manifest
+    Adopt { resource: ManifestResource, id: Option<Uuid>, name: String },
+    /// Drop the record of a resource that is gone from its provider
+    Forget { resource: ManifestResource, id: Uuid },
+}
+
+/// One change of a plan
+#[derive(Debug, Clone, Serialize)]
+pub struct Step {
+    /// Stage the step runs in
+    pub stage: Stage,
+    /// Steps with the same key run one after the other, usually the provider
+    pub key: String,
+    /// Kind of change
+    pub change: Change,
+    /// Kind of resource changed
+    pub resource: ManifestResource,
+    /// Name of the resource
+    pub name: String,
+    /// What the step does
+    pub summary: String,
+    /// Operation
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self.change {
+            Change::Create => "+",
+            Change::Update => "~",
+            Change::Delete => "-",
+        };
+        write!(f, "{} {} {}", symbol, self.resource, self.name)?;
+        if !self.summary.is_empty() {
+            write!(f, ": {}", self.summary)?;
+        }
+        Ok(())
+    }
+}
+
+/// Changes needed to bring stored and live resources in line with a manifest
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Plan {
+    /// Manifest name
+    pub manifest: String,
+    /// Steps in stage order
+    pub steps: Vec<Step>,
+    /// Providers that could not be read, planned from stored state only
+    pub warnings: Vec<String>,
+}
+
+impl Plan {
+    /// Whether the plan changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Count the steps making a kind of change
+    pub fn count(&self, change: Change) -> usize {
+        self.steps.iter().filter(|s| s.change == change).count()
+    }
+}
+
+/// Result of running a step
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    /// Step that ran
+    pub step: Step,
+    /// Error, if the step failed
+    pub error: Option<String>,
+}
+
+/// Builder collecting the steps of a plan
+struct Planner {
+    steps: Vec<Step>,
+}
+
+impl Planner {
+    #[allow(clippy::too_many_arguments)]
+    fn push(&mut self, stage: Stage, key: &str, change: Change, resource: ManifestResource, name: &str, summary: String, action: Action) {
+        self.steps.push(Step {
+            stage,
+            key: key.to_string(),
+            change,
+            resource,
+            name: name.to_string(),
+            summary,
+            action,
+        });
+    }
+}
+
+/// Whether a resource's tags record it as owned by a manifest
+fn owned_by(manifest: &str, tags: &HashMap<String, String>) -> bool {
+    tags.get(MANIFEST_TAG).map(String::as_str) == Some(manifest)
+}
+
+/// Find the record a manifest resource maps to
+///
+/// A record the manifest owns wins over one it does not. An unowned record
+/// with the same name is only taken over when adopting, otherwise it is an error.
+fn match_record<'a, T>(
+    records: impl Iterator<Item = &'a T>,
+    owned: impl Fn(&T) -> bool,
+    resource: ManifestResource,
+    name: &str,
+    manifest: &str,
+    adopt: bool,
+) -> Result<Option<(&'a T, bool)>> {
+    let candidates: Vec<&T> = records.collect();
+    if let Some(record) = candidates.iter().find(|r| owned(r)) {
+        return Ok(Some((record, false)));
+    }
+    match candidates.first() {
+        Some(record) if adopt => Ok(Some((record, true))),
+        Some(_) => Err(anyhow!(
+            "Existing {} '{}' is not managed by manifest '{}', pass --adopt to take it over",
+            resource, name, manifest
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Compute the changes that bring the state in line with a manifest
+///
+/// `providers` maps provider names to their types. `drifts` come from a
+/// refresh and give the live status of records; records whose resources are
+/// missing are forgotten and recreated. Resources the manifest owns but no
+/// longer describes are deleted, anything else is left alone.
+pub fn plan_manifest(
+    manifest: &Manifest,
+    state: &State,
+    groups: &[SecurityGroup],
+    providers: &BTreeMap<String, ProviderType>,
+    drifts: &[Drift],
+    adopt: bool,
+) -> Result<Plan> {
+    validate_manifest(manifest)?;
+    let mut planner = Planner { steps: Vec::new() };
+    let name = manifest.name.as_str();
+
+    let missing: HashSet<Uuid> = drifts.iter()
+        .filter(|d| d.kind == DriftKind::Missing)
+        .filter_map(|d| d.id)
+        .chain(state.instances.values().filter(|i| i.status == InstanceStatus::Missing).map(|i| i.id))
+        .chain(state.volumes.values().filter(|v| v.status == VolumeStatus::Missing).map(|v| v.id))
+        .chain(state.networks.values().filter(|n| n.status == NetworkStatus::Missing).map(|n| n.id))
+        .collect();
+    let live_status: HashMap<Uuid, InstanceStatus> = drifts.iter()
+        .filter(|d| d.resource == ResourceKind::Instance)
+        .filter_map(|d| match (&d.kind, d.id) {
+            (DriftKind::Status { to, .. }, Some(id)) => Some((id, InstanceStatus::from(to.as_str()))),
+            _ => None,
+        })
+        .collect();
+
+    let provider_type = |resource: ManifestResource, resource_name: &str, provider: &str| {
+        providers.get(provider).copied()
+            .ok_or_else(|| anyhow!("Provider '{}' of {} '{}' is not configured", provider, resource, resource_name))
+    };
+
+    // Networks
+    let mut network_ids: HashMap<&str, Uuid> = HashMap::new();
+    for spec in &manifest.networks {
+        let provider = manifest.provider_for(&spec.provider).unwrap_or_default();
+        provider_type(ManifestResource::Network, &spec.name, provider)?;
+        let record = match_record(
+            state.networks.values().filter(|n| n.name == spec.name && n.tags.get(PROVIDER_TAG).map(String::as_str) == Some(provider)),
+            |n| owned_by(name, &n.tags), ManifestResource::Network, &spec.name, name, adopt,
+        )?;
+
+        let record = match record {
+            Some((network, _)) if missing.contains(&network.id) => {
+                planner.push(Stage::Networks, provider, Change::Create, ManifestResource::Network, &spec.name,
+                        "gone from the provider, recreate".to_string(), Action::Forget { resource: ManifestResource::Network, id: network.id });
+                None
+            },
+            other => other,
+        };
+
+        let Some((network, adopted)) = record else {
+            planner.push(Stage::Networks, provider, Change::Create, ManifestResource::Network, &spec.name,
+                    format!("{} on {}", spec.cidr, provider), Action::CreateNetwork { name: spec.name.clone() });
+            continue;
+        };
+
+        network_ids.insert(spec.name.as_str(), network.id);
+        if adopted {
+            planner.push(Stage::Networks, provider, Change::Update, ManifestResource::Network, &spec.name,
+                    "adopt".to_string(), Action::Adopt { resource: ManifestResource::Network, id: Some(network.id), name: spec.name.clone() });
+        }
+
+        let network_type = NetworkType::from(spec.network_type.as_deref().unwrap_or("routed"));
+        let mut immutable = Vec::new();
+        if network.cidr != spec.cidr {
+            immutable.push(format!("CIDR {} -> {}", network.cidr, spec.cidr));
+        }
+        if network.network_type != network_type {
+            immutable.push(format!("type {} -> {}", network.network_type, network_type));
+        }
+        if spec.gateway.is_some() && network.gateway != spec.gateway {
+            immutable.push("gateway".to_string());
+        }
+        if !spec.dns_servers.is_empty() && network.dns_servers != spec.dns_servers {
+            immutable.push("DNS servers".to_string());
+        }
+        if !immutable.is_empty() {
+            return Err(anyhow!("Network '{}' cannot be changed in place ({}), rename it to replace it", spec.name, immutable.join(", ")));
+        }
+
+        if let Some(tenant) = spec.tenant.as_ref().or(manifest.tenant.as_ref()) {
+            if network.tags.get(TENANT_TAG) != Some(tenant) {
+                planner.push(Stage::Networks, provider, Change::Update, ManifestResource::Network, &spec.name,
+                        format!("tenant {}", tenant), Action::SetTenant { id: network.id, tenant: tenant.clone() });
+            }
+        }
+    }
+
+    // Resolve a network an instance connects to, from the manifest or existing records
+    let resolve_network = |network: &str| -> Result<Option<Uuid>> {
+        if manifest.get_network(network).is_some() {
+            return Ok(network_ids.get(network).copied());
+        }
+        let existing: Vec<_> = state.networks.values().filter(|n| n.name == network).collect();
+        match existing.as_slice() {
+            [record] => Ok(Some(record.id)),
+            [] => Err(anyhow!("Network '{}' is neither in the manifest nor an existing network", network)),
+            _ => Err(anyhow!("More than one network is named '{}'", network)),
+        }
+    };
+    let network_key = |id: Option<Uuid>, network: &str| -> String {
+        id.and_then(|id| state.networks.get(&id))
+            .and_then(|n| n.tags.get(PROVIDER_TAG).cloned())
+            .or_else(|| manifest.get_network(network).and_then(|n| manifest.provider_for(&n.provider)).map(str::to_string))
+            .unwrap_or_default()
+    };
+
+    // Security groups
+    let group_names: HashSet<&str> = groups.iter().map(|g| g.name.as_str())
+        .chain(manifest.security_groups.iter().map(|g| g.name.as_str()))
+        .collect();
+    let mut new_groups = Vec::new();
+    for spec in &manifest.security_groups {
+        for rule in spec.ingress.iter().chain(spec.egress.iter()) {
+            if let Some(peer) = &rule.group {
+                if !group_names.contains(peer.as_str()) {
+                    return Err(anyhow!("Security group '{}' refers to unknown group '{}'", spec.name, peer));
+                }
+            }
+        }
+
+        let record = match_record(
+            groups.iter().filter(|g| g.name == spec.name),
+            |g| owned_by(name, &g.tags), ManifestResource::SecurityGroup, &spec.name, name, adopt,
+        )?;
+        let ingress = spec.rules(RuleDirection::Ingress).map_err(|e| anyhow!(e))?;
+        let egress = spec.rules(RuleDirection::Egress).map_err(|e| anyhow!(e))?;
+
+        match record {
+            None => {
+                planner.push(Stage::Networks, SECURITY_GROUPS_KEY, Change::Create, ManifestResource::SecurityGroup, &spec.name,
+                        String::new(), Action::CreateSecurityGroup { name: spec.name.clone() });
+                new_groups.push(spec);
+            },
+            Some((group, adopted)) => {
+                if adopted {
+                    planner.push(Stage::Networks, SECURITY_GROUPS_KEY, Change::Update, ManifestResource::SecurityGroup, &spec.name,
+                            "adopt".to_string(), Action::Adopt { resource: ManifestResource::SecurityGroup, id: None, name: spec.name.clone() });
+                }
+                if group.ingress != ingress || group.egress != egress || group.description != spec.description {
+                    planner.push(Stage::Networks, SECURITY_GROUPS_KEY, Change::Update, ManifestResource::SecurityGroup, &spec.name,
+                            format!("{} ingress, {} egress rules", ingress.len(), egress.len()),
+                            Action::SetSecurityGroupRules { name: spec.name.clone() });
+                }
+            },
+        }
+    }
+    // Rules of new groups are set once every group exists, as groups may refer to each other
+    for spec in new_groups {
+        planner.push(Stage::Networks, SECURITY_GROUPS_KEY, Change::Create, ManifestResource::SecurityGroup, &spec.name,
+                format!("{} ingress, {} egress rules", spec.ingress.len(), spec.egress.len()),
+                Action::SetSecurityGroupRules { name: spec.name.clone() });
+    }
+
+    // Instances
+    let mut instance_ids: HashMap<&str, Uuid> = HashMap::new();
+    for spec in &manifest.instances {
+        let provider = manifest.provider_for(&spec.provider).unwrap_or_default();
+        let instance_provider_type = provider_type(ManifestResource::Instance, &spec.name, provider)?;
+        match (instance_provider_type, &spec.container) {
+            (ProviderType::VyOS, None) => return Err(anyhow!("Instance '{}' on VyOS provider '{}' needs a container", spec.name, provider)),
+            (ProviderType::VyOS, Some(_)) if spec.template.is_some() => return Err(anyhow!("Instance '{}' on VyOS provider '{}' cannot clone a template", spec.name, provider)),
+            (ProviderType::Proxmox, Some(_)) => return Err(anyhow!("Instance '{}' on Proxmox provider '{}' cannot run a container", spec.name, provider)),
+            _ => {},
+        }
+        for group in &spec.security_groups {
+            if !group_names.contains(group.as_str()) {
+                return Err(anyhow!("Instance '{}' uses unknown security group '{}'", spec.name, group));
+            }
+        }
+
+        let record = match_record(
+            state.instances.values().filter(|i| i.name == spec.name && i.tags.get(PROVIDER_TAG).map(String::as_str) == Some(provider)),
+            |i| owned_by(name, &i.tags), ManifestResource::Instance, &spec.name, name, adopt,
+        )?;
+        let record = match record {
+            Some((instance, _)) if missing.contains(&instance.id) => {
+                planner.push(Stage::Instances, provider, Change::Create, ManifestResource::Instance, &spec.name,
+                        "gone from the provider, recreate".to_string(), Action::Forget { resource: ManifestResource::Instance, id: instance.id });
+                None
+            },
+            other => other,
+        };
+
+        let Some((instance, adopted)) = record else {
+            if instance_provider_type == ProviderType::Proxmox && spec.template.is_none() {
+                return Err(anyhow!("Instance '{}' on Proxmox provider '{}' needs a template to be created", spec.name, provider));
+            }
+            plan_new_instance(&mut planner, spec, provider, &resolve_network, &network_key)?;
+            continue;
+        };
+
+        instance_ids.insert(spec.name.as_str(), instance.id);
+        if adopted {
+            planner.push(Stage::Instances, provider, Change::Update, ManifestResource::Instance, &spec.name,
+                    "adopt".to_string(), Action::Adopt { resource: ManifestResource::Instance, id: Some(instance.id), name: spec.name.clone() });
+        }
+        plan_instance_update(&mut planner, spec, provider, instance, state, groups, live_status.get(&instance.id).copied(), &resolve_network, &network_key)?;
+    }
+
+    // Volumes
+    for spec in &manifest.volumes {
+        let provider = manifest.provider_for(&spec.provider).unwrap_or_default();
+        provider_type(ManifestResource::Volume, &spec.name, provider)?;
+        let record = match_record(
+            state.volumes.values().filter(|v| v.name == spec.name && v.tags.get(PROVIDER_TAG).map(String::as_str) == Some(provider)),
+            |v| owned_by(name, &v.tags), ManifestResource::Volume, &spec.name, name, adopt,
+        )?;
+        let record = match record {
+            Some((volume, _)) if missing.contains(&volume.id) => {
+                planner.push(Stage::Volumes, provider, Change::Create, ManifestResource::Volume, &spec.name,
+                        "gone from the provider, recreate".to_string(), Action::Forget { resource: ManifestResource::Volume, id: volume.id });
+                None
+            },
+            other => other,
+        };
+
+        let Some((volume, adopted)) = record else {
+            planner.push(Stage::Volumes, provider, Change::Create, ManifestResource::Volume, &spec.name,
+                    format!("{} GB on {}", spec.size_gb, provider), Action::CreateVolume { name: spec.name.clone() });
+            if let Some(instance) = &spec.instance {
+                planner.push(Stage::Link, provider, Change::Create, ManifestResource::Volume, &spec.name,
+                        format!("attach to {}", instance), Action::AttachVolume { volume: spec.name.clone(), instance: instance.clone() });
+            }
+            continue;
+        };
+
+        if adopted {
+            planner.push(Stage::Volumes, provider, Change::Update, ManifestResource::Volume, &spec.name,
+                    "adopt".to_string(), Action::Adopt { resource: ManifestResource::Volume, id: Some(volume.id), name: spec.name.clone() });
+        }
+        if volume.size_gb != spec.size_gb {
+            return Err(anyhow!("Volume '{}' cannot be resized in place ({} GB -> {} GB)", spec.name, volume.size_gb, spec.size_gb));
+        }
+
+        let attached = volume.attached_to
+            .filter(|id| !missing.contains(id))
+            .and_then(|id| state.instances.get(&id));
+        let desired = spec.instance.as_ref().map(|instance| instance_ids.get(instance.as_str()).copied());
+        let unchanged = match (attached, desired) {
+            (Some(current), Some(Some(id))) => current.id == id,
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            continue;
+        }
+        if let Some(current) = attached {
+            planner.push(Stage::Unlink, provider, Change::Update, ManifestResource::Volume, &spec.name,
+                    format!("detach from {}", current.name), Action::DetachVolume { id: volume.id });
+        }
+        if let Some(instance) = &spec.instance {
+            planner.push(Stage::Link, provider, Change::Update, ManifestResource::Volume, &spec.name,
+                    format!("attach to {}", instance), Action::AttachVolume { volume: spec.name.clone(), instance: instance.clone() });
+        }
+    }
+
+    // Deletions of resources the manifest owns but no longer describes
+    for volume in state.volumes.values().filter(|v| owned_by(name, &v.tags)) {
+        if manifest.volumes.iter().any(|v| v.name == volume.name) {
+            continue;
+        }
+        let provider = volume.tags.get(PROVIDER_TAG).cloned().unwrap_or_default();
+        if volume.attached_to.is_some() {
+            planner.push(Stage::Unlink, &provider, Change::Delete, ManifestResource::Volume, &volume.name,
+                    "detach".to_string(), Action::DetachVolume { id: volume.id });
+        }
+        planner.push(Stage::RemoveVolumes, &provider, Change::Delete, ManifestResource::Volume, &volume.name,
+                String::new(), Action::DeleteVolume { id: volume.id });
+    }
+
+    for instance in state.instances.values().filter(|i| owned_by(name, &i.tags)) {
+        if manifest.instances.iter().any(|i| i.name == instance.name) {
+            continue;
+        }
+        let provider = instance.tags.get(PROVIDER_TAG).cloned().unwrap_or_default();
+        for network_id in connected_networks(instance, state) {
+            let network = &state.networks[&network_id];
+            planner.push(Stage::Unlink, &network_key(Some(network_id), &network.name), Change::Delete, ManifestResource::Instance, &instance.name,
+                    format!("disconnect from {}", network.name), Action::DisconnectNetwork { instance_id: instance.id, network_id });
+        }
+        for group in groups.iter().filter(|g| g.instances.contains(&instance.id)) {
+            planner.push(Stage::Unlink, SECURITY_GROUPS_KEY, Change::Delete, ManifestResource::Instance, &instance.name,
+                    format!("detach security group {}", group.name), Action::DetachSecurityGroup { group: group.name.clone(), instance_id: instance.id });
+        }
+        planner.push(Stage::RemoveInstances, &provider, Change::Delete, ManifestResource::Instance, &instance.name,
+                String::new(), Action::DeleteInstance { id: instance.id });
+    }
+
+    for group in groups.iter().filter(|g| owned_by(name, &g.tags)) {
+        if manifest.get_security_group(&group.name).is_none() {
+            planner.push(Stage::RemoveNetworks, SECURITY_GROUPS_KEY, Change::Delete, ManifestResource::SecurityGroup, &group.name,
+                    String::new(), Action::DeleteSecurityGroup { name: group.name.clone() });
+        }
+    }
+
+    for network in state.networks.values().filter(|n| owned_by(name, &n.tags)) {
+        if manifest.networks.iter().any(|n| n.name == network.name) {
+            continue;
+        }
+        let provider = network.tags.get(PROVIDER_TAG).cloned().unwrap_or_default();
+        planner.push(Stage::RemoveNetworks, &provider, Change::Delete, ManifestResource::Network, &network.name,
+                String::new(), Action::DeleteNetwork { id: network.id });
+    }
+
+    let mut steps = planner.steps;
+    steps.sort_by_key(|s| s.stage);
+    Ok(Plan { manifest: manifest.name.clone(), steps, warnings: Vec::new() })
+}
+
+/// Networks an instance is connected to that bbctl manages
+fn connected_networks(instance: &Instance, state: &State) -> Vec<Uuid> {
+    instance.networks.iter()
+        .filter_map(|n| Uuid::parse_str(&n.network_id).ok())
+        .filter(|id| state.networks.contains_key(id))
+        .collect()
+}
+
+/// Plan the creation of an instance and its links
+fn plan_new_instance(
+    planner: &mut Planner,
+    spec: &ManifestInstance,
+    provider: &str,
+    resolve_network: &dyn Fn(&str) -> Result<Option<Uuid>>,
+    network_key: &dyn Fn(Option<Uuid>, &str) -> String,
+) -> Result<()> {
+    let state = if spec.running { "" } else { ", stopped" };
+    let template = spec.template.as_ref().map(|t| format!(" from template {}", t)).unwrap_or_default();
+    planner.push(Stage::Instances, provider, Change::Create, ManifestResource::Instance, &spec.name,
+            format!("{} CPU, {} GB memory, {} GB disk on {}{}{}", spec.cpu, spec.memory_gb, spec.disk_gb, provider, template, state),
+            Action::CreateInstance { name: spec.name.clone() });
+
+    for connection in &spec.networks {
+        let id = resolve_network(&connection.network)?;
+        planner.push(Stage::Link, &network_key(id, &connection.network), Change::Create, ManifestResource::Instance, &spec.name,
+                connect_summary(&connection.network, connection.ip),
+                Action::ConnectNetwork { instance: spec.name.clone(), network: connection.network.clone(), ip: connection.ip });
+    }
+    for group in &spec.security_groups {
+        planner.push(Stage::Link, SECURITY_GROUPS_KEY, Change::Create, ManifestResource::Instance, &spec.name,
+                format!("attach security group {}", group),
+                Action::AttachSecurityGroup { group: group.clone(), instance: spec.name.clone() });
+    }
+    Ok(())
+}
+
+/// Summary of a network connection
+fn connect_summary(network: &str, ip: Option<IpAddr>) -> String {
+    match ip {
+        Some(ip) => format!("connect to {} with {}", network, ip),
+        None => format!("connect to {}", network),
+    }
+}
+
+/// Plan the changes to an existing instance and its links
+#[allow(clippy::too_many_arguments)]
+fn plan_instance_update(
+    planner: &mut Planner,
+    spec: &ManifestInstance,
+    provider: &str,
+    instance: &Instance,
+    state: &State,
+    groups: &[SecurityGroup],
+    live_status: Option<InstanceStatus>,
+    resolve_network: &dyn Fn(&str) -> Result<Option<Uuid>>,
+    network_key: &dyn Fn(Option<Uuid>, &str) -> String,
+) -> Result<()> {
+    let mut immutable = Vec::new();
+    if instance.size.cpu != spec.cpu || instance.size.memory_gb != spec.memory_gb || instance.size.disk_gb != spec.disk_gb {
+        immutable.push("size".to_string());
+    }
+    if let Some(container) = &spec.container {
+        // Records from before the spec was stored only know the image
+        if instance.tags.get(CONTAINER_IMAGE_TAG) != Some(&container.image) {
+            immutable.push("container image".to_string());
+        } else if instance.container.as_ref().is_some_and(|current| current != container) {
+            immutable.push("container settings".to_string());
+        }
+    }
+    if !immutable.is_empty() {
+        return Err(anyhow!("Instance '{}' cannot be changed in place ({}), rename it to replace it", spec.name, immutable.join(", ")));
+    }
+
+    let update = |summary: String, action| (Stage::Instances, provider.to_string(), Change::Update, summary, action);
+    let mut changes = Vec::new();
+
+    match (spec.running, live_status.unwrap_or(instance.status)) {
+        (true, InstanceStatus::Stopped) => changes.push(update("start".to_string(), Action::StartInstance { id: instance.id })),
+        (false, InstanceStatus::Running) => changes.push(update("stop".to_string(), Action::StopInstance { id: instance.id })),
+        _ => {},
+    }
+
+    let tags: BTreeMap<String, String> = spec.tags.iter()
+        .filter(|(key, value)| instance.tags.get(*key) != Some(value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if !tags.is_empty() {
+        let summary = format!("tags {}", tags.keys().cloned().collect::<Vec<_>>().join(", "));
+        changes.push(update(summary, Action::SetInstanceTags { id: instance.id, tags }));
+    }
+
+    // Network connections, a changed address is a reconnect
+    let current: HashMap<Uuid, Option<IpAddr>> = instance.networks.iter()
+        .filter_map(|n| Uuid::parse_str(&n.network_id).ok().map(|id| (id, n.ip.as_deref().and_then(|ip| ip.parse().ok()))))
+        .filter(|(id, _)| state.networks.contains_key(id))
+        .collect();
+    let mut wanted = HashSet::new();
+    for connection in &spec.networks {
+        let id = resolve_network(&connection.network)?;
+        let key = network_key(id, &connection.network);
+        if let Some(id) = id {
+            wanted.insert(id);
+            match current.get(&id) {
+                Some(ip) if connection.ip.is_none() || connection.ip == *ip => continue,
+                Some(_) => changes.push((Stage::Unlink, key.clone(), Change::Update, format!("disconnect from {}", connection.network),
+                        Action::DisconnectNetwork { instance_id: instance.id, network_id: id })),
+                None => {},
+            }
+        }
+        changes.push((Stage::Link, key, Change::Update, connect_summary(&connection.network, connection.ip),
+                Action::ConnectNetwork { instance: spec.name.clone(), network: connection.network.clone(), ip: connection.ip }));
+    }
+    let mut stale: Vec<&Uuid> = current.keys().filter(|id| !wanted.contains(*id)).collect();
+    stale.sort();
+    for id in stale {
+        let network = &state.networks[id];
+        changes.push((Stage::Unlink, network_key(Some(*id), &network.name), Change::Update, format!("disconnect from {}", network.name),
+                Action::DisconnectNetwork { instance_id: instance.id, network_id: *id }));
+    }
+
+    // Explicit security group attachments
+    let attached: BTreeSet<&str> = groups.iter()
+        .filter(|g| g.instances.contains(&instance.id))
+        .map(|g| g.name.as_str())
+        .collect();
+    let desired: BTreeSet<&str> = spec.security_groups.iter().map(String::as_str).collect();
+    for group in desired.difference(&attached) {
+        changes.push((Stage::Link, SECURITY_GROUPS_KEY.to_string(), Change::Update, format!("attach security group {}", group),
+                Action::AttachSecurityGroup { group: group.to_string(), instance: spec.name.clone() }));
+    }
+    for group in attached.difference(&desired) {
+        changes.push((Stage::Unlink, SECURITY_GROUPS_KEY.to_string(), Change::Update, format!("detach security group {}", group),
+                Action::DetachSecurityGroup { group: group.to_string(), instance_id: instance.id }));
+    }
+
+    for (stage, key, change, summary, action) in changes {
+        planner.push(stage, &key, change, ManifestResource::Instance, &spec.name, summary, action);
+    }
+    Ok(())
+}
+
+/// Tags bbctl sets itself, left out of exported instance tags
+const SYSTEM_TAGS: [&str; 4] = [PROVIDER_TAG, NODE_TAG, CONTAINER_IMAGE_TAG, MANIFEST_TAG];
+
+/// Value shared by every item, if any
+fn common<'a>(values: impl Iterator<Item = Option<&'a str>>) -> Option<String> {
+    let values: BTreeSet<Option<&str>> = values.collect();
+    match values.into_iter().collect::<Vec<_>>().as_slice() {
+        [Some(value)] => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Convert a security group rule to its manifest form
+fn export_rule(rule: &SecurityGroupRule) -> ManifestRule {
+    let (cidr, group) = match &rule.peer {
+        RulePeer::Cidr(cidr) if cidr == "0.0.0.0/0" => (None, None),
+        RulePeer::Cidr(cidr) => (Some(cidr.clone()), None),
+        RulePeer::Group(group) => (None, Some(group.clone())),
+    };
+    ManifestRule {
+        protocol: rule.protocol.clone(),
+        ports: rule.port_range("-"),
+        cidr,
+        group,
+        description: rule.description.clone(),
+    }
+}
+
+/// Describe stored resources as a manifest
+///
+/// With a tenant, the tenant's networks are exported along with the
+/// instances connected to them. With a region, only resources in that region
+/// are exported. Volumes follow the instances they are attached to, and
+/// security groups the instances explicitly attached to them. Containers are
+/// exported with the spec they were created from. Provider IDs, statuses and
+/// addresses bbctl picked itself are left out, so applying the export plans
+/// no changes.
+pub fn export_manifest(
+    name: &str,
+    state: &State,
+    groups: &[SecurityGroup],
+    tenant: Option<&str>,
+    region: Option<&str>,
+) -> Result<Manifest> {
+    let in_region = |r: &str| region.is_none_or(|region| region == r);
+
+    let mut networks: Vec<&Network> = state.networks.values()
+        .filter(|n| in_region(&n.region))
+        .filter(|n| tenant.is_none_or(|tenant| n.tenant() == tenant))
+        .collect();
+    networks.sort_by(|a, b| a.name.cmp(&b.name));
+    let network_ids: HashSet<Uuid> = networks.iter().map(|n| n.id).collect();
+
+    let mut instances: Vec<&Instance> = state.instances.values()
+        .filter(|i| in_region(&i.region))
+        .filter(|i| tenant.is_none() || connected_networks(i, state).iter().any(|id| network_ids.contains(id)))
+        .collect();
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+    let instance_ids: HashSet<Uuid> = instances.iter().map(|i| i.id).collect();
+
+    let mut volumes: Vec<&Volume> = state.volumes.values()
+        .filter(|v| in_region(&v.region))
+        .filter(|v| tenant.is_none() || v.attached_to.is_some_and(|id| instance_ids.contains(&id)))
+        .collect();
+    volumes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut security_groups: Vec<&SecurityGroup> = groups.iter()
+        .filter(|g| g.instances.iter().any(|id| instance_ids.contains(id)))
+        .collect();
+    security_groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+    check_unique("Network", networks.iter().map(|n| n.name.as_str()))?;
+    check_unique("Instance", instances.iter().map(|i| i.name.as_str()))?;
+    check_unique("Volume", volumes.iter().map(|v| v.name.as_str()))?;
+
+    let provider_of = |tags: &HashMap<String, String>, resource: ManifestResource, name: &str| {
+        tags.get(PROVIDER_TAG).cloned()
+            .ok_or_else(|| anyhow!("{} '{}' has no provider", resource, name))
+    };
+    let mut manifest = Manifest {
+        name: name.to_string(),
+        provider: None,
+        region: None,
+        tenant: tenant.map(str::to_string),
+        networks: Vec::new(),
+        security_groups: Vec::new(),
+        instances: Vec::new(),
+        volumes: Vec::new(),
+    };
+
+    for network in networks {
+        let default_gateway = network.ipv4_cidr().ok().map(|cidr| IpAddr::V4(cidr.first_host()));
+        manifest.networks.push(ManifestNetwork {
+            name: network.name.clone(),
+            cidr: network.cidr.clone(),
+            provider: Some(provider_of(&network.tags, ManifestResource::Network, &network.name)?),
+            region: Some(network.region.clone()),
+            network_type: Some(network.network_type.to_string()),
+            gateway: network.gateway.filter(|gateway| Some(*gateway) != default_gateway),
+            dns_servers: network.dns_servers.clone(),
+            tenant: network.tags.get(TENANT_TAG).filter(|t| Some(t.as_str()) != tenant).cloned(),
+        });
+    }
+
+    for group in security_groups {
+        manifest.security_groups.push(ManifestSecurityGroup {
+            name: group.name.clone(),
+            description: group.description.clone(),
+            ingress: group.ingress.iter().map(export_rule).collect(),
+            egress: group.egress.iter().map(export_rule).collect(),
+        });
+    }
+
+    for instance in &instances {
+        let connections = instance.networks.iter()
+            .filter_map(|n| Uuid::parse_str(&n.network_id).ok().and_then(|id| state.networks.get(&id)).map(|network| (network, n)))
+            .map(|(network, connection)| ManifestInstanceNetwork {
+                network: network.name.clone(),
+                ip: connection.ip.as_deref()
+                    .and_then(|ip| ip.parse().ok())
+                    .filter(|ip| network.ip_allocations.iter().any(|a| a.ip == *ip && a.instance_id == Some(instance.id) && a.fixed)),
+            })
+            .collect();
+        let mut groups: Vec<String> = groups.iter()
+            .filter(|g| g.instances.contains(&instance.id))
+            .map(|g| g.name.clone())
+            .collect();
+        groups.sort();
+
+        manifest.instances.push(ManifestInstance {
+            name: instance.name.clone(),
+            provider: Some(provider_of(&instance.tags, ManifestResource::Instance, &instance.name)?),
+            region: Some(instance.region.clone()),
+            cpu: instance.size.cpu,
+            memory_gb: instance.size.memory_gb,
+            disk_gb: instance.size.disk_gb,
+            running: instance.status != InstanceStatus::Stopped,
+            container: instance.container.clone()
+                .or_else(|| instance.tags.get(CONTAINER_IMAGE_TAG).map(|image| ContainerSpec::new(image))),
+            template: None,
+            networks: connections,
+            security_groups: groups,
+            tags: instance.tags.iter()
+                .filter(|(key, _)| !SYSTEM_TAGS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        });
+    }
+
+    for volume in volumes {
+        let attached = volume.attached_to.and_then(|id| state.instances.get(&id));
+        if let Some(instance) = attached.filter(|i| !instance_ids.contains(&i.id)) {
+            return Err(anyhow!("Volume '{}' is attached to instance '{}', which is not exported", volume.name, instance.name));
+        }
+        manifest.volumes.push(ManifestVolume {
+            name: volume.name.clone(),
+            size_gb: volume.size_gb,
+            provider: Some(provider_of(&volume.tags, ManifestResource::Volume, &volume.name)?),
+            region: Some(volume.region.clone()),
+            storage: volume.tags.get(STORAGE_TAG).cloned(),
+            instance: attached.map(|i| i.name.clone()),
+            mount_path: attached.filter(|_| volume.provider == ProviderType::VyOS).and(volume.device.clone()),
+        });
+    }
+
+    // Settings shared by every resource move to the top of the manifest
+    let providers = manifest.networks.iter().map(|n| n.provider.as_deref())
+        .chain(manifest.instances.iter().map(|i| i.provider.as_deref()))
+        .chain(manifest.volumes.iter().map(|v| v.provider.as_deref()));
+    manifest.provider = common(providers);
+    let regions = manifest.networks.iter().map(|n| n.region.as_deref())
+        .chain(manifest.instances.iter().map(|i| i.region.as_deref()))
+        .chain(manifest.volumes.iter().map(|v| v.region.as_deref()));
+    manifest.region = common(regions);
+
+    let (provider, region) = (manifest.provider.clone(), manifest.region.clone());
+    for network in &mut manifest.networks {
+        network.provider.take_if(|p| provider.as_ref() == Some(p));
+        network.region.take_if(|r| region.as_ref() == Some(r));
+    }
+    for instance in &mut manifest.instances {
+        instance.provider.take_if(|p| provider.as_ref() == Some(p));
+        instance.region.take_if(|r| region.as_ref() == Some(r));
+    }
+    for volume in &mut manifest.volumes {
+        volume.provider.take_if(|p| provider.as_ref() == Some(p));
+        volume.region.take_if(|r| region.as_ref() == Some(r));
+    }
+
+    validate_manifest(&manifest)?;
+    Ok(manifest)
+}
+
+/// Manifest service planning and applying manifests
+pub struct ManifestService {
+    store: Arc<dyn StateStore>,
+    provider_service: ProviderService,
+}
+
+impl ManifestService {
+    /// Create a new manifest service backed by the state store
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Ok(Self::with_store(provider_service, Arc::new(FileStateStore::open()?)))
+    }
+
+    /// Create a new manifest service backed by a specific state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Self {
+        Self { store, provider_service }
+    }
+
+    /// Plan a manifest against the stored state and the live providers
+    ///
+    /// Every provider the manifest or its resources use is refreshed first.
+    /// A provider that cannot be read is planned from stored state only and
+    /// reported as a warning.
+    pub async fn plan(&self, manifest: &Manifest, adopt: bool) -> Result<Plan> {
+        validate_manifest(manifest)?;
+        let state = self.store.load()?;
+        let providers: BTreeMap<String, ProviderType> = self.provider_service.get_providers().iter()
+            .map(|(name, provider)| (name.clone(), provider.provider_type))
+            .collect();
+
+        let mut used: BTreeSet<String> = manifest.networks.iter().filter_map(|n| manifest.provider_for(&n.provider))
+            .chain(manifest.instances.iter().filter_map(|i| manifest.provider_for(&i.provider)))
+            .chain(manifest.volumes.iter().filter_map(|v| manifest.provider_for(&v.provider)))
+            .map(str::to_string)
+            .collect();
+        let owned_tags = state.instances.values().map(|i| &i.tags)
+            .chain(state.volumes.values().map(|v| &v.tags))
+            .chain(state.networks.values().map(|n| &n.tags))
+            .filter(|tags| tags.get(MANIFEST_TAG) == Some(&manifest.name));
+        used.extend(owned_tags.filter_map(|tags| tags.get(PROVIDER_TAG).cloned()));
+        used.retain(|provider| providers.contains_key(provider));
+
+        let refresh = RefreshService::with_store(self.provider_service.clone(), self.store.clone());
+        let mut drifts = Vec::new();
+        let mut warnings = Vec::new();
+        for provider in &used {
+            match refresh.detect(Some(provider)).await {
+                Ok(report) => {
+                    drifts.extend(report.drifts);
+                    warnings.extend(report.errors.into_iter().map(|(provider, error)| format!("{}: {}", provider, error)));
+                },
+                Err(e) => warnings.push(format!("{}: {:#}", provider, e)),
+            }
+        }
+        for warning in &warnings {
+            warn!("Planning from stored state only, provider unreadable: {}", warning);
+        }
+
+        let groups: Vec<SecurityGroup> = SecurityGroupService::new(self.provider_service.clone())?
+            .list_groups().into_iter().cloned().collect();
+        let mut plan = plan_manifest(manifest, &state, &groups, &providers, &drifts, adopt)?;
+        plan.warnings = warnings;
+        Ok(plan)
+    }
+
+    /// Export stored resources as a manifest
+    pub fn export(&self, name: &str, tenant: Option<&str>, region: Option<&str>) -> Result<Manifest> {
+        let state = self.store.load()?;
+        let groups: Vec<SecurityGroup> = SecurityGroupService::new(self.provider_service.clone())?
+            .list_groups().into_iter().cloned().collect();
+        export_manifest(name, &state, &groups, tenant, region)
+    }
+
+    /// Apply a plan
+    ///
+    /// Stages run in order. Within a stage, the steps of each provider run one
+    /// after the other while different providers run concurrently. A stage
+    /// with a failed step stops the apply; steps not run are not reported.
+    pub async fn apply(&self, manifest: &Manifest, plan: &Plan) -> Vec<StepResult> {
+        let mut results = Vec::new();
+
+        for stage in Stage::ALL {
+            let mut lanes: BTreeMap<&str, Vec<&Step>> = BTreeMap::new();
+            for step in plan.steps.iter().filter(|s| s.stage == stage) {
+                lanes.entry(step.key.as_str()).or_default().push(step);
+            }
+            if lanes.is_empty() {
+                continue;
+            }
+
+            let lanes = lanes.into_values().map(|steps| async move {
+                let mut lane_results = Vec::new();
+                for step in steps {
+                    let error = self.execute(manifest, step).await.err().map(|e| format!("{:#}", e));
+                    let failed = error.is_some();
+                    lane_results.push(StepResult { step: step.clone(), error });
+                    if failed {
+                        break;
+                    }
+                }
+                lane_results
+            });
+            let stage_results: Vec<StepResult> = join_all(lanes).await.into_iter().flatten().collect();
+            let failed = stage_results.iter().any(|r| r.error.is_some());
+            results.extend(stage_results);
+
+            if failed {
+                warn!("Stopping manifest '{}' after a failed {:?} stage", manifest.name, stage);
+                break;
+            }
+        }
+
+        info!("Applied {} steps of manifest '{}'", results.iter().filter(|r| r.error.is_none()).count(), manifest.name);
+        results
+    }
+
+    /// Change the state in a transaction
+    fn update_state(&self, mut change: impl FnMut(&mut State) -> Result<()>) -> Result<()> {
+        self.store.transaction(&mut change)?;
+        Ok(())
+    }
+
+    /// Find a record the manifest owns by name
+    fn owned<'a, T>(&self, manifest: &Manifest, records: impl Iterator<Item = &'a T>, tags: impl Fn(&T) -> &HashMap<String, String>, name_of: impl Fn(&T) -> &str, resource: ManifestResource, name: &str) -> Result<&'a T> {
+        records.into_iter()
+            .find(|r| name_of(r) == name && tags(r).get(MANIFEST_TAG) == Some(&manifest.name))
+            .ok_or_else(|| anyhow!("{} '{}' of manifest '{}' not found", resource, name, manifest.name))
+    }
+
+    /// Look up an instance the manifest owns
+    fn owned_instance(&self, manifest: &Manifest, state: &State, name: &str) -> Result<Instance> {
+        self.owned(manifest, state.instances.values(), |i| &i.tags, |i| &i.name, ManifestResource::Instance, name).cloned()
+    }
+
+    /// Look up a network by name, preferring one the manifest owns
+    fn network_id(&self, manifest: &Manifest, state: &State, name: &str) -> Result<Uuid> {
+        if manifest.get_network(name).is_some() {
+            return self.owned(manifest, state.networks.values(), |n| &n.tags, |n| &n.name, ManifestResource::Network, name).map(|n| n.id);
+        }
+        state.networks.values().find(|n| n.name == name)
+            .map(|n| n.id)
+            .ok_or_else(|| anyhow!("Network not found: {}", name))
+    }
+
+    /// Enforce a security group after its rules or members changed
+    async fn sync_group(&self, service: &SecurityGroupService, name: &str, previous_members: &[Uuid]) -> Result<()> {
+        let instances: Vec<Instance> = self.store.load()?.instances.into_values().collect();
+        let failed: Vec<String> = service.sync_group(name, &instances, previous_members).await?.into_iter()
+            .filter_map(|r| r.error.map(|e| format!("{}: {}", r.target, e)))
+            .collect();
+        if !failed.is_empty() {
+            return Err(anyhow!("Failed to enforce security group '{}': {}", name, failed.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// Run one step
+    async fn execute(&self, manifest: &Manifest, step: &Step) -> Result<()> {
+        let owner = manifest.name.clone();
+        match &step.action {
+            Action::CreateNetwork { name } => {
+                let spec = manifest.get_network(name).ok_or_else(|| anyhow!("Network '{}' is not in the manifest", name))?;
+                let provider = manifest.provider_for(&spec.provider).unwrap_or_default();
+                let mut service = NetworkService::with_store(self.provider_service.clone(), self.store.clone())?;
+                let network_type = NetworkType::from(spec.network_type.as_deref().unwrap_or("routed"));
+                let id = service.create_network(name, provider, manifest.region_for(&spec.region), &spec.cidr, network_type)?;
+                self.update_state(|state| {
+                    let network = state.networks.get_mut(&id).ok_or_else(|| anyhow!("Network not found: {}", id))?;
+                    network.add_tag(MANIFEST_TAG.to_string(), owner.clone());
+                    if let Some(gateway) = spec.gateway {
+                        network.set_gateway(gateway);
+                    }
+                    for dns_server in &spec.dns_servers {
+                        network.add_dns_server(*dns_server);
+                    }
+                    Ok(())
+                })?;
+                if let Some(tenant) = spec.tenant.as_ref().or(manifest.tenant.as_ref()) {
+                    service.set_tenant(&id, tenant)?;
+                }
+            },
+            Action::DeleteNetwork { id } => {
+                NetworkService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .delete_network(id).await?;
+            },
+            Action::SetTenant { id, tenant } => {
+                NetworkService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .set_tenant(id, tenant)?;
+            },
+            Action::CreateSecurityGroup { name } => {
+                let spec = manifest.get_security_group(name).ok_or_else(|| anyhow!("Security group '{}' is not in the manifest", name))?;
+                let mut service = SecurityGroupService::new(self.provider_service.clone())?;
+                service.create_group(name, spec.description.clone())?;
+                service.set_tag(name, MANIFEST_TAG, &owner)?;
+            },
+            Action::SetSecurityGroupRules { name } => {
+                let spec = manifest.get_security_group(name).ok_or_else(|| anyhow!("Security group '{}' is not in the manifest", name))?;
+                let ingress = spec.rules(RuleDirection::Ingress).map_err(|e| anyhow!(e))?;
+                let egress = spec.rules(RuleDirection::Egress).map_err(|e| anyhow!(e))?;
+                let mut service = SecurityGroupService::new(self.provider_service.clone())?;
+                service.replace_rules(name, spec.description.clone(), ingress, egress)?;
+                self.sync_group(&service, name, &[]).await?;
+            },
+            Action::DeleteSecurityGroup { name } => {
+                let mut service = SecurityGroupService::new(self.provider_service.clone())?;
+                let instances: Vec<Instance> = self.store.load()?.instances.into_values().collect();
+                let group = service.delete_group(name)?;
+                let former: Vec<Uuid> = instances.iter().filter(|i| group.applies_to(i)).map(|i| i.id).collect();
+                self.sync_group(&service, name, &former).await?;
+            },
+            Action::CreateInstance { name } => {
+                let spec = manifest.get_instance(name).ok_or_else(|| anyhow!("Instance '{}' is not in the manifest", name))?;
+                let provider = manifest.provider_for(&spec.provider).unwrap_or_default();
+                let provider_type = self.provider_service.get_provider(provider)
+                    .map(|p| p.provider_type)
+                    .ok_or_else(|| anyhow!("Provider not found: {}", provider))?;
+                let region = manifest.region_for(&spec.region);
+                let size = InstanceSize { cpu: spec.cpu, memory_gb: spec.memory_gb, disk_gb: spec.disk_gb };
+
+                let mut service = InstanceService::with_store(self.provider_service.clone(), self.store.clone())?;
+                let id = match (provider_type, &spec.container, &spec.template) {
+                    (ProviderType::VyOS, Some(container), _) => service.create_vyos_instance(name, provider, region, size, container.clone()).await?,
+                    (ProviderType::VyOS, None, _) => return Err(anyhow!("Instance '{}' needs a container", name)),
+                    (ProviderType::Proxmox, _, Some(template)) => service.clone_proxmox_instance(name, provider, region, size, template).await?,
+                    (ProviderType::Proxmox, _, None) => return Err(anyhow!("Instance '{}' needs a template", name)),
+                };
+                self.update_state(|state| {
+                    let instance = state.instances.get_mut(&id).ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+                    instance.add_tag(MANIFEST_TAG.to_string(), owner.clone());
+                    for (key, value) in &spec.tags {
+                        instance.add_tag(key.clone(), value.clone());
+                    }
+                    Ok(())
+                })?;
+                if !spec.running {
+                    service.stop_instance(&id).await?;
+                }
+            },
+            Action::DeleteInstance { id } => {
+                InstanceService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .delete_instance(id).await?;
+            },
+            Action::StartInstance { id } => {
+                InstanceService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .start_instance(id).await?;
+            },
+            Action::StopInstance { id } => {
+                InstanceService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .stop_instance(id).await?;
+            },
+            Action::SetInstanceTags { id, tags } => {
+                self.update_state(|state| {
+                    let instance = state.instances.get_mut(id).ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+                    for (key, value) in tags {
+                        instance.add_tag(key.clone(), value.clone());
+                    }
+                    Ok(())
+                })?;
+            },
+            Action::CreateVolume { name } => {
+                let spec = manifest.volumes.iter().find(|v| v.name == *name)
+                    .ok_or_else(|| anyhow!("Volume '{}' is not in the manifest", name))?;
+                let provider = manifest.provider_for(&spec.provider).unwrap_or_default();
+                let mut service = VolumeService::with_store(self.provider_service.clone(), self.store.clone())?;
+                let id = service.create_volume(name, provider, manifest.region_for(&spec.region), spec.size_gb, spec.storage.as_deref()).await?;
+                self.update_state(|state| {
+                    let volume = state.volumes.get_mut(&id).ok_or_else(|| anyhow!("Volume not found: {}", id))?;
+                    volume.add_tag(MANIFEST_TAG.to_string(), owner.clone());
+                    Ok(())
+                })?;
+            },
+            Action::DeleteVolume { id } => {
+                VolumeService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .delete_volume(id).await?;
+            },
+            Action::ConnectNetwork { instance, network, ip } => {
+                let state = self.store.load()?;
+                let instance = self.owned_instance(manifest, &state, instance)?;
+                let network_id = self.network_id(manifest, &state, network)?;
+                NetworkService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .connect_instance(&network_id, &instance.id, *ip).await?;
+            },
+            Action::DisconnectNetwork { instance_id, network_id } => {
+                NetworkService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .disconnect_instance(network_id, instance_id).await?;
+            },
+            Action::AttachSecurityGroup { group, instance } => {
+                let instance = self.owned_instance(manifest, &self.store.load()?, instance)?;
+                let mut service = SecurityGroupService::new(self.provider_service.clone())?;
+                service.attach_instance(group, instance.id)?;
+                self.sync_group(&service, group, &[]).await?;
+            },
+            Action::DetachSecurityGroup { group, instance_id } => {
+                let mut service = SecurityGroupService::new(self.provider_service.clone())?;
+                service.detach_instance(group, instance_id)?;
+                self.sync_group(&service, group, &[*instance_id]).await?;
+            },
+            Action::AttachVolume { volume, instance } => {
+                let state = self.store.load()?;
+                let spec = manifest.volumes.iter().find(|v| v.name == *volume)
+                    .ok_or_else(|| anyhow!("Volume '{}' is not in the manifest", volume))?;
+                let volume = self.owned(manifest, state.volumes.values(), |v| &v.tags, |v| &v.name, ManifestResource::Volume, volume)?;
+                let instance = self.owned_instance(manifest, &state, instance)?;
+                VolumeService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .attach_volume(&volume.id, &instance.id, spec.mount_path.as_deref()).await?;
+            },
+            Action::DetachVolume { id } => {
+                VolumeService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .detach_volume(id).await?;
+            },
+            Action::Adopt { resource: ManifestResource::SecurityGroup, name, .. } => {
+                SecurityGroupService::new(self.provider_service.clone())?
+                    .set_tag(name, MANIFEST_TAG, &owner)?;
+            },
+            Action::Adopt { resource, id, .. } => {
+                let id = id.ok_or_else(|| anyhow!("No record to adopt"))?;
+                self.update_state(|state| {
+                    let tagged = match resource {
+                        ManifestResource::Network => state.networks.get_mut(&id).map(|n| n.add_tag(MANIFEST_TAG.to_string(), owner.clone())),
+                        ManifestResource::Instance => state.instances.get_mut(&id).map(|i| i.add_tag(MANIFEST_TAG.to_string(), owner.clone())),
+                        ManifestResource::Volume => state.volumes.get_mut(&id).map(|v| v.add_tag(MANIFEST_TAG.to_string(), owner.clone())),
+                        ManifestResource::SecurityGroup => None,
+                    };
+                    tagged.ok_or_else(|| anyhow!("{} not found: {}", resource, id))
+                })?;
+            },
+            Action::Forget { resource: ManifestResource::Instance, id } => {
+                // Addresses of the vanished instance must not block the one replacing it
+                InstanceService::with_store(self.provider_service.clone(), self.store.clone())?
+                    .forget_instance(id).await?;
+            },
+            Action::Forget { resource, id } => {
+                self.update_state(|state| {
+                    match resource {
+                        ManifestResource::Network => { state.networks.remove(id); },
+                        ManifestResource::Volume => { state.volumes.remove(id); },
+                        ManifestResource::Instance | ManifestResource::SecurityGroup => {},
+                    }
+                    Ok(())
+                })?;
+            },
+        }
+
+        info!("Manifest '{}': {}", manifest.name, step);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::volume::VolumeType;
+
+    const SHOP: &str = r#"
+        name = "shop"
+        provider = "pve"
+        region = "lab"
+
+        [[networks]]
+        name = "lan"
+        cidr = "10.0.0.0/24"
+
+        [[instances]]
+        name = "web"
+        template = "debian"
+        networks = [{ network = "lan" }]
+
+        [[volumes]]
+        name = "data"
+        size_gb = 20
+        instance = "web"
+    "#;
+
+    fn providers() -> BTreeMap<String, ProviderType> {
+        BTreeMap::from([
+            ("pve".to_string(), ProviderType::Proxmox),
+            ("edge".to_string(), ProviderType::VyOS),
+        ])
+    }
+
+    fn owned_tags(provider: &str) -> HashMap<String, String> {
+        HashMap::from([
+            (MANIFEST_TAG.to_string(), "shop".to_string()),
+            (PROVIDER_TAG.to_string(), provider.to_string()),
+        ])
+    }
+
+    /// State holding the resources of `SHOP` as if it had been applied
+    fn applied_shop() -> (State, Uuid, Uuid, Uuid) {
+        let mut network = Network::new("lan".to_string(), ProviderType::Proxmox, "lab".to_string(),
+            "10.0.0.0/24".to_string(), NetworkType::Routed);
+        network.tags = owned_tags("pve");
+        let size = InstanceSize { cpu: 1, memory_gb: 2, disk_gb: 10 };
+        let mut instance = Instance::new("web".to_string(), ProviderType::Proxmox, "lab".to_string(), size);
+        instance.tags = owned_tags("pve");
+        instance.status = InstanceStatus::Running;
+        instance.provider_id = "100".to_string();
+        network.connect_instance(instance.id);
+        network.allocate_ip("10.0.0.2".parse().unwrap(), instance.id, None, None, false).unwrap();
+        instance.add_network(network.id.to_string(), Some("10.0.0.2".to_string()), Some("net0".to_string()), None);
+        let mut volume = Volume::new("data".to_string(), ProviderType::Proxmox, "lab".to_string(), 20, VolumeType::Standard);
+        volume.tags = owned_tags("pve");
+        volume.attach(instance.id, Some("scsi1".to_string()));
+
+        let ids = (network.id, instance.id, volume.id);
+        let mut state = State::default();
+        state.networks.insert(network.id, network);
+        state.instances.insert(instance.id, instance);
+        state.volumes.insert(volume.id, volume);
+        (state, ids.0, ids.1, ids.2)
+    }
+
+    fn plan(manifest: &str, state: &State, groups: &[SecurityGroup], drifts: &[Drift]) -> Plan {
+        let manifest: Manifest = toml::from_str(manifest).unwrap();
+        plan_manifest(&manifest, state, groups, &providers(), drifts, false).unwrap()
+    }
+
+    fn actions(plan: &Plan) -> Vec<(Stage, &Action)> {
+        plan.steps.iter().map(|s| (s.stage, &s.action)).collect()
+    }
+
+    #[test]
+    fn new_resources_are_created_before_they_are_linked() {
+        let plan = plan(SHOP, &State::default(), &[], &[]);
+        let name = |s: &str| s.to_string();
+        assert_eq!(actions(&plan), [
+            (Stage::Networks, &Action::CreateNetwork { name: name("lan") }),
+            (Stage::Instances, &Action::CreateInstance { name: name("web") }),
+            (Stage::Volumes, &Action::CreateVolume { name: name("data") }),
+            (Stage::Link, &Action::ConnectNetwork { instance: name("web"), network: name("lan"), ip: None }),
+            (Stage::Link, &Action::AttachVolume { volume: name("data"), instance: name("web") }),
+        ]);
+        assert_eq!(plan.count(Change::Create), 5);
+    }
+
+    #[test]
+    fn applied_manifest_plans_nothing() {
+        let (state, ..) = applied_shop();
+        assert!(plan(SHOP, &state, &[], &[]).is_empty());
+    }
+
+    #[test]
+    fn missing_instance_is_forgotten_and_recreated() {
+        let (state, _, instance_id, _) = applied_shop();
+        let drift = Drift {
+            provider: "pve".to_string(),
+            resource: ResourceKind::Instance,
+            id: Some(instance_id),
+            name: "web".to_string(),
+            provider_id: "100".to_string(),
+            kind: DriftKind::Missing,
+        };
+
+        let plan = plan(SHOP, &state, &[], &[drift]);
+        let name = |s: &str| s.to_string();
+        assert_eq!(actions(&plan), [
+            (Stage::Instances, &Action::Forget { resource: ManifestResource::Instance, id: instance_id }),
+            (Stage::Instances, &Action::CreateInstance { name: name("web") }),
+            (Stage::Link, &Action::ConnectNetwork { instance: name("web"), network: name("lan"), ip: None }),
+            (Stage::Link, &Action::AttachVolume { volume: name("data"), instance: name("web") }),
+        ]);
+    }
+
+    #[test]
+    fn removed_resources_are_unlinked_before_they_are_deleted() {
+        let (state, network_id, instance_id, volume_id) = applied_shop();
+        let mut group = SecurityGroup::new("web".to_string(), None);
+        group.tags.insert(MANIFEST_TAG.to_string(), "shop".to_string());
+        group.instances.insert(instance_id);
+
+        let plan = plan("name = \"shop\"", &state, &[group], &[]);
+        assert_eq!(actions(&plan), [
+            (Stage::Unlink, &Action::DetachVolume { id: volume_id }),
+            (Stage::Unlink, &Action::DisconnectNetwork { instance_id, network_id }),
+            (Stage::Unlink, &Action::DetachSecurityGroup { group: "web".to_string(), instance_id }),
+            (Stage::RemoveVolumes, &Action::DeleteVolume { id: volume_id }),
+            (Stage::RemoveInstances, &Action::DeleteInstance { id: instance_id }),
+            (Stage::RemoveNetworks, &Action::DeleteSecurityGroup { name: "web".to_string() }),
+            (Stage::RemoveNetworks, &Action::DeleteNetwork { id: network_id }),
+        ]);
+        assert_eq!(plan.count(Change::Delete), 7);
+    }
+
+    #[test]
+    fn export_plans_no_changes() {
+        let (mut state, network_id, ..) = applied_shop();
+        let size = InstanceSize { cpu: 1, memory_gb: 2, disk_gb: 10 };
+        let mut proxy = Instance::new("proxy".to_string(), ProviderType::VyOS, "lab".to_string(), size);
+        proxy.tags = owned_tags("edge");
+        proxy.status = InstanceStatus::Running;
+        let mut container = ContainerSpec::new("nginx:latest");
+        container.add_environment("WORKERS=4").unwrap();
+        proxy.add_tag(CONTAINER_IMAGE_TAG.to_string(), container.image.clone());
+        proxy.container = Some(container.clone());
+        proxy.add_network(network_id.to_string(), Some("10.0.0.5".to_string()), None, None);
+        let network = state.networks.get_mut(&network_id).unwrap();
+        network.connect_instance(proxy.id);
+        network.allocate_ip("10.0.0.5".parse().unwrap(), proxy.id, None, None, true).unwrap();
+        state.instances.insert(proxy.id, proxy);
+
+        let manifest = export_manifest("shop", &state, &[], None, None).unwrap();
+        let web = manifest.get_instance("web").unwrap();
+        let proxy = manifest.get_instance("proxy").unwrap();
+        assert_eq!(web.networks[0].ip, None);
+        assert_eq!(proxy.networks[0].ip, Some("10.0.0.5".parse().unwrap()));
+        assert_eq!(proxy.container, Some(container));
+
+        let plan = plan_manifest(&manifest, &state, &[], &providers(), &[], false).unwrap();
+        assert!(plan.is_empty(), "{:?}", plan.steps);
+    }
+
+    #[test]
+    fn steps_run_in_the_lane_of_their_provider() {
+        let manifest = r#"
+            name = "shop"
+            provider = "pve"
+
+            [[networks]]
+            name = "lan"
+            cidr = "10.0.0.0/24"
+
+            [[security_groups]]
+            name = "web"
+
+            [[instances]]
+            name = "proxy"
+            provider = "edge"
+            container = { image = "nginx:latest" }
+            networks = [{ network = "lan" }]
+            security_groups = ["web"]
+        "#;
+
+        let plan = plan(manifest, &State::default(), &[], &[]);
+        let lanes: Vec<(&Action, &str)> = plan.steps.iter().map(|s| (&s.action, s.key.as_str())).collect();
+        let name = |s: &str| s.to_string();
+        assert_eq!(lanes, [
+            (&Action::CreateNetwork { name: name("lan") }, "pve"),
+            (&Action::CreateSecurityGroup { name: name("web") }, SECURITY_GROUPS_KEY),
+            (&Action::SetSecurityGroupRules { name: name("web") }, SECURITY_GROUPS_KEY),
+            (&Action::CreateInstance { name: name("proxy") }, "edge"),
+            // Connections run in the lane of the network's provider
+            (&Action::ConnectNetwork { instance: name("proxy"), network: name("lan"), ip: None }, "pve"),
+            (&Action::AttachSecurityGroup { group: name("web"), instance: name("proxy") }, SECURITY_GROUPS_KEY),
+        ]);
+    }
+}
@@ -1,4 +1,21 @@
 pub mod provider;
 pub mod instance;
 pub mod volume;
-pub mod network;
\ No newline at end of file
+pub mod network;
+pub mod fabric;
+pub mod firewall;
+pub mod security_group;
+pub mod floating_ip;
+pub mod dns;
+pub mod router;
+pub mod site;
+pub mod load_balancer;
+pub mod container;
+pub mod usage;
+pub mod logs;
+pub mod refresh;
+pub mod import;
+pub mod manifest;
+pub mod deploy;
+pub mod project;
+pub mod template;
\ No newline at end of file
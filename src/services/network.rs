@@ -0,0 +1,1079 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::api::vyos_op::{parse_vrrp_summary, VrrpGroup};
+use crate::models::dns::sanitize_label;
+use crate::models::instance::{Instance, PROVIDER_TAG};
+use crate::models::network::{
+    parse_bandwidth, Network, NetworkStatus, NetworkType, BRIDGE_CONFIG, DHCP_RANGE_CONFIG, DHCP_SUBNET_ID_CONFIG,
+    HA_ROUTERS_CONFIG, ROUTER_INTERFACE_CONFIG, TENANT_TAG, VRRP_NO_PREEMPT_CONFIG, VRRP_VRID_CONFIG,
+};
+use crate::models::provider::ProviderType;
+use crate::models::tenant::TenantLimits;
+use crate::services::dns::DnsService;
+use crate::services::provider::ProviderService;
+use crate::state::{FileStateStore, StateStore};
+
+/// Prefix of the DHCP shared networks and VRRP groups bbctl owns on a router
+pub const DHCP_PREFIX: &str = "bb-";
+
+/// VRRP sync group holding every bbctl group, used by conntrack-sync
+pub const VRRP_SYNC_GROUP: &str = "bb-ha";
+
+/// Prefix of the QoS policies bbctl owns on a router
+pub const QOS_PREFIX: &str = "bb-";
+
+/// VRRP priority of the preferred router; each following router gets 50 less
+const VRRP_BASE_PRIORITY: u8 = 200;
+
+/// Name of the router objects (DHCP shared network, VRRP group) generated for a network
+fn router_object_name(network: &Network) -> String {
+    let name: String = network.name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("{}{}", DHCP_PREFIX, name)
+}
+
+/// Name of the DHCP shared network generated for a network
+pub fn shared_network_name(network: &Network) -> String {
+    router_object_name(network)
+}
+
+/// Name of the VRRP group generated for a network
+pub fn vrrp_group_name(network: &Network) -> String {
+    router_object_name(network)
+}
+
+/// VyOS interface type for an interface name (`eth1` is `ethernet`, `br100` is `bridge`, ...)
+pub fn interface_type(interface: &str) -> &'static str {
+    let kind: String = interface.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    match kind.as_str() {
+        "br" => "bridge",
+        "bond" => "bonding",
+        "dum" => "dummy",
+        "vxlan" => "vxlan",
+        "wg" => "wireguard",
+        _ => "ethernet",
+    }
+}
+
+/// Address of the n-th HA router on a network, right after the virtual gateway
+pub fn ha_router_address(network: &Network, index: usize) -> Result<Ipv4Addr> {
+    let cidr = network.ipv4_cidr()
+        .map_err(|e| anyhow!("Network '{}' has an invalid CIDR '{}': {}", network.name, network.cidr, e))?;
+
+    cidr.hosts().nth(index + 1)
+        .ok_or_else(|| anyhow!("Network '{}' is too small for {} routers", network.name, index + 1))
+}
+
+/// Render the VRRP configuration of an HA network for one of its routers
+///
+/// Routers are prioritized in the order they are listed; the virtual address is
+/// the network gateway and every router gets its own address right after it.
+pub fn render_vrrp_config(network: &Network, router: &str) -> Result<Vec<ConfigCommand>> {
+    let routers = network.ha_routers();
+    let index = routers.iter().position(|r| *r == router)
+        .ok_or_else(|| anyhow!("Router '{}' is not an HA router of network '{}'", router, network.name))?;
+    let interface = network.get_config(ROUTER_INTERFACE_CONFIG)
+        .ok_or_else(|| anyhow!("Network '{}' has no router interface", network.name))?;
+    let vrid = network.vrrp_vrid()
+        .ok_or_else(|| anyhow!("Network '{}' has no VRRP ID", network.name))?;
+    let gateway = network.gateway
+        .ok_or_else(|| anyhow!("Network '{}' has no gateway", network.name))?;
+    let prefix_len = network.ipv4_cidr()
+        .map_err(|e| anyhow!("Network '{}' has an invalid CIDR '{}': {}", network.name, network.cidr, e))?
+        .prefix_len;
+
+    let group = vrrp_group_name(network);
+    let own_address = format!("{}/{}", ha_router_address(network, index)?, prefix_len);
+    let virtual_address = format!("{}/{}", gateway, prefix_len);
+    let vrid = vrid.to_string();
+    let priority = VRRP_BASE_PRIORITY.saturating_sub((index as u8).saturating_mul(50)).max(1).to_string();
+
+    let mut commands = vec![
+        ConfigCommand::set(["interfaces", interface_type(interface), interface.as_str(), "address", own_address.as_str()]),
+    ];
+
+    let base = ["high-availability", "vrrp", "group", group.as_str()];
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["vrid", vrid.as_str()])));
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["interface", interface.as_str()])));
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["address", virtual_address.as_str()])));
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["priority", priority.as_str()])));
+    if network.get_config(VRRP_NO_PREEMPT_CONFIG).map(|v| v == "true").unwrap_or(false) {
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["no-preempt"])));
+    }
+
+    commands.push(ConfigCommand::set(["high-availability", "vrrp", "sync-group", VRRP_SYNC_GROUP, "member", group.as_str()]));
+
+    // Keep connection state in sync so failover does not drop established flows
+    let conntrack = ["service", "conntrack-sync"];
+    commands.push(ConfigCommand::set(conntrack.iter().copied().chain(["failover-mechanism", "vrrp", "sync-group", VRRP_SYNC_GROUP])));
+    for protocol in ["tcp", "udp", "icmp"] {
+        commands.push(ConfigCommand::set(conntrack.iter().copied().chain(["accept-protocol", protocol])));
+    }
+    if routers.len() == 2 {
+        // A pair syncs over unicast, larger groups fall back to multicast
+        let peer = ha_router_address(network, 1 - index)?.to_string();
+        commands.push(ConfigCommand::set(conntrack.iter().copied().chain(["interface", interface.as_str(), "peer", peer.as_str()])));
+    } else {
+        commands.push(ConfigCommand::set(conntrack.iter().copied().chain(["interface", interface.as_str()])));
+    }
+
+    Ok(commands)
+}
+
+/// Render the commands removing an HA network from one of its routers
+pub fn render_vrrp_removal(network: &Network, router: &str) -> Result<Vec<ConfigCommand>> {
+    let index = network.ha_routers().iter().position(|r| *r == router)
+        .ok_or_else(|| anyhow!("Router '{}' is not an HA router of network '{}'", router, network.name))?;
+    let interface = network.get_config(ROUTER_INTERFACE_CONFIG)
+        .ok_or_else(|| anyhow!("Network '{}' has no router interface", network.name))?;
+    let prefix_len = network.ipv4_cidr()
+        .map_err(|e| anyhow!("Network '{}' has an invalid CIDR '{}': {}", network.name, network.cidr, e))?
+        .prefix_len;
+
+    let group = vrrp_group_name(network);
+    let own_address = format!("{}/{}", ha_router_address(network, index)?, prefix_len);
+
+    Ok(vec![
+        ConfigCommand::delete(["high-availability", "vrrp", "sync-group", VRRP_SYNC_GROUP, "member", group.as_str()]),
+        ConfigCommand::delete(["high-availability", "vrrp", "group", group.as_str()]),
+        ConfigCommand::delete(["interfaces", interface_type(interface), interface.as_str(), "address", own_address.as_str()]),
+    ])
+}
+
+/// Check whether a VyOS config path is owned by tenant bandwidth limits
+pub fn is_managed_qos_path(path: &[String]) -> bool {
+    match path {
+        [qos, policy, kind, name, ..] if qos == "qos" && policy == "policy" => {
+            (kind == "shaper" || kind == "limiter") && name.starts_with(QOS_PREFIX)
+        },
+        [qos, interface, _, _, name] if qos == "qos" && interface == "interface" => name.starts_with(QOS_PREFIX),
+        _ => false,
+    }
+}
+
+/// Names of the policies limiting a tenant, as `(egress limiter, ingress shaper)`
+pub fn tenant_policy_names(tenant: &str) -> (String, String) {
+    let tenant = sanitize_label(tenant);
+    (format!("{}{}-egress", QOS_PREFIX, tenant), format!("{}{}-ingress", QOS_PREFIX, tenant))
+}
+
+/// Render the QoS configuration limiting a tenant network on its router interface
+///
+/// Traffic the tenant sends arrives on the interface and is policed by a
+/// limiter, traffic towards the tenant leaves through it and is shaped.
+/// Every interface applies the tenant's policies on its own, so a tenant with
+/// several networks on a router can use the limit once per network.
+pub fn render_qos_config(network: &Network, limits: &TenantLimits) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+    let Some(interface) = network.router_interface() else {
+        return commands;
+    };
+    let (limiter, shaper) = tenant_policy_names(network.tenant());
+
+    if let Some(egress) = limits.egress_bandwidth.as_deref() {
+        commands.push(ConfigCommand::set(["qos", "policy", "limiter", limiter.as_str(), "default", "bandwidth", egress]));
+        commands.push(ConfigCommand::set(["qos", "interface", interface, "ingress", limiter.as_str()]));
+    }
+    if let Some(ingress) = limits.ingress_bandwidth.as_deref() {
+        let base = ["qos", "policy", "shaper", shaper.as_str()];
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["bandwidth", ingress])));
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["default", "bandwidth", "100%"])));
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["default", "queue-type", "fq-codel"])));
+        commands.push(ConfigCommand::set(["qos", "interface", interface, "egress", shaper.as_str()]));
+    }
+
+    commands
+}
+
+/// Check whether a VyOS config path is owned by bbctl IPAM
+pub fn is_managed_dhcp_path(path: &[String]) -> bool {
+    match path {
+        [service, dhcp, shared, name, ..] if service == "service" && dhcp == "dhcp-server" && shared == "shared-network-name" => {
+            name.starts_with(DHCP_PREFIX)
+        },
+        _ => false,
+    }
+}
+
+/// Render the DHCP server configuration for a network
+///
+/// Every allocation with a known MAC becomes a static mapping; the dynamic
+/// range is only served when the network sets [`DHCP_RANGE_CONFIG`]. HA
+/// networks are served by every router without DHCP failover, so each router
+/// hands out its own slice of the range and leases never overlap.
+pub fn render_dhcp_config(network: &Network, subnet_id: u32, router: &str) -> Result<Vec<ConfigCommand>> {
+    let cidr = network.ipv4_cidr()
+        .map_err(|e| anyhow!("Network '{}' has an invalid CIDR '{}': {}", network.name, network.cidr, e))?;
+
+    let shared_network = shared_network_name(network);
+    let subnet = cidr.to_string();
+    let base = ["service", "dhcp-server", "shared-network-name", shared_network.as_str(), "subnet", subnet.as_str()];
+    let mut commands = Vec::new();
+
+    let subnet_id = subnet_id.to_string();
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["subnet-id", subnet_id.as_str()])));
+
+    if let Some(gateway) = network.gateway {
+        let gateway = gateway.to_string();
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["option", "default-router", gateway.as_str()])));
+    }
+
+    for dns_server in &network.dns_servers {
+        let dns_server = dns_server.to_string();
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["option", "name-server", dns_server.as_str()])));
+    }
+
+    if let Some(range) = network.get_config(DHCP_RANGE_CONFIG) {
+        let (start, stop) = router_dhcp_range(network, range, router)?;
+        let (start, stop) = (start.to_string(), stop.to_string());
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["range", "0", "start", start.as_str()])));
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["range", "0", "stop", stop.as_str()])));
+    }
+
+    let mut allocations: Vec<_> = network.ip_allocations.iter()
+        .filter(|a| a.ip.is_ipv4())
+        .filter_map(|a| a.mac.as_ref().map(|mac| (a.ip, mac.to_lowercase())))
+        .collect();
+    allocations.sort();
+
+    for (ip, mac) in allocations {
+        let mapping = format!("host-{}", ip.to_string().replace('.', "-"));
+        let ip = ip.to_string();
+        let prefix = base.iter().copied().chain(["static-mapping", mapping.as_str()]);
+        commands.push(ConfigCommand::set(prefix.clone().chain(["ip-address", ip.as_str()])));
+        commands.push(ConfigCommand::set(prefix.chain(["mac", mac.as_str()])));
+    }
+
+    Ok(commands)
+}
+
+/// Part of a network's dynamic DHCP range a router hands out
+///
+/// The range is split evenly between HA routers in their order of
+/// preference, the last one taking what is left over.
+fn router_dhcp_range(network: &Network, range: &str, router: &str) -> Result<(Ipv4Addr, Ipv4Addr)> {
+    let invalid = || anyhow!("Network '{}' has an invalid DHCP range '{}'", network.name, range);
+    let (start, stop) = range.split_once('-').ok_or_else(invalid)?;
+    let start: Ipv4Addr = start.trim().parse().map_err(|_| invalid())?;
+    let stop: Ipv4Addr = stop.trim().parse().map_err(|_| invalid())?;
+    if start > stop {
+        return Err(invalid());
+    }
+
+    let routers = network.ha_routers();
+    if routers.is_empty() {
+        return Ok((start, stop));
+    }
+    let index = routers.iter().position(|r| *r == router)
+        .ok_or_else(|| anyhow!("Router '{}' does not serve network '{}'", router, network.name))?;
+
+    let (first, last) = (u32::from(start), u32::from(stop));
+    let share = (last - first + 1) / routers.len() as u32;
+    if share == 0 {
+        return Err(anyhow!("The DHCP range of '{}' is too small to split between {} routers", network.name, routers.len()));
+    }
+    let slice_start = first + share * index as u32;
+    let slice_stop = if index + 1 == routers.len() { last } else { slice_start + share - 1 };
+    Ok((Ipv4Addr::from(slice_start), Ipv4Addr::from(slice_stop)))
+}
+
+/// Allocate an instance an address on a network and record it on both
+///
+/// The NIC already linked to the network is used, or else the first NIC on
+/// the network's bridge, and its MAC address is recorded with the address.
+/// A requested address is recorded as fixed.
+fn link_instance(network: &mut Network, instance: &mut Instance, ip: Option<IpAddr>) -> Result<IpAddr> {
+    let fixed = ip.is_some();
+    let ip = match ip {
+        Some(ip) => ip,
+        None => network.next_free_ipv4()
+            .map(IpAddr::V4)
+            .ok_or_else(|| anyhow!("Network '{}' has no free addresses", network.name))?,
+    };
+
+    let key = network.id.to_string();
+    let nic = instance.networks.iter().position(|n| n.network_id == key)
+        .or_else(|| instance.networks.iter().position(|n| n.interface.is_some() && n.network_id == network.bridge()));
+    let mac = nic.and_then(|index| instance.networks[index].mac.clone());
+
+    network.connect_instance(instance.id);
+    network.allocate_ip(ip, instance.id, mac, Some(instance.name.clone()), fixed)
+        .map_err(|e| anyhow!("Failed to allocate {} on '{}': {}", ip, network.name, e))?;
+    match nic {
+        Some(index) => {
+            instance.networks[index].network_id = key;
+            instance.networks[index].ip = Some(ip.to_string());
+        },
+        None => instance.add_network(key, Some(ip.to_string()), None, None),
+    }
+    Ok(ip)
+}
+
+/// Forget an instance's address on a network, putting its NIC back on the bridge
+pub fn unlink_instance(network: &Network, instance: &mut Instance) {
+    let key = network.id.to_string();
+    for nic in instance.networks.iter_mut().filter(|n| n.network_id == key && n.interface.is_some()) {
+        nic.network_id = network.bridge().to_string();
+        nic.ip = None;
+    }
+    instance.remove_network(&key);
+}
+
+/// Highly available gateway shared by several VyOS routers
+#[derive(Debug, Clone)]
+pub struct HaGateway {
+    /// Routers in order of preference
+    pub routers: Vec<String>,
+    /// Router interface that carries the network
+    pub interface: String,
+    /// VRRP virtual router ID (picked automatically when not set)
+    pub vrid: Option<u8>,
+    /// Whether a recovered preferred router takes the gateway back
+    pub preempt: bool,
+}
+
+/// Storage for network data, persisted in the state store
+///
+/// IP allocations live on their network, so allocating an address is a
+/// read-modify-write of the network inside one store transaction.
+#[derive(Debug)]
+pub struct NetworkStorage {
+    store: Arc<dyn StateStore>,
+    networks: BTreeMap<Uuid, Network>,
+}
+
+impl NetworkStorage {
+    /// Create a network storage backed by a state store
+    pub fn new(store: Arc<dyn StateStore>) -> Result<Self> {
+        let networks = store.load()?.networks;
+        Ok(Self { store, networks })
+    }
+
+    /// Reload the networks from the store
+    pub fn reload(&mut self) -> Result<()> {
+        self.networks = self.store.load()?.networks;
+        Ok(())
+    }
+
+    /// Add a network
+    pub fn add_network(&mut self, network: Network) -> Result<()> {
+        let state = self.store.transaction(&mut |state| {
+            state.networks.insert(network.id, network.clone());
+            Ok(())
+        })?;
+        self.networks = state.networks;
+        Ok(())
+    }
+
+    /// Get a network by ID
+    pub fn get_network(&self, id: &Uuid) -> Option<&Network> {
+        self.networks.get(id)
+    }
+
+    /// Change a stored network and write it back
+    ///
+    /// The change is applied to the latest stored copy, so concurrent bbctl
+    /// processes cannot hand out the same address twice. Nothing is written
+    /// if the change fails.
+    pub fn update_network<T>(&mut self, id: &Uuid, change: impl FnOnce(&mut Network) -> Result<T>) -> Result<T> {
+        let mut change = Some(change);
+        let mut output = None;
+        let state = self.store.transaction(&mut |state| {
+            let network = state.networks.get_mut(id)
+                .ok_or_else(|| anyhow!("Network not found: {}", id))?;
+            let change = change.take().ok_or_else(|| anyhow!("Network update applied twice"))?;
+            output = Some(change(network)?);
+            Ok(())
+        })?;
+        self.networks = state.networks;
+        output.ok_or_else(|| anyhow!("Network update was not applied"))
+    }
+
+    /// Change a stored network and an instance's record of it together
+    ///
+    /// The instance is absent when its record is already gone.
+    pub fn update_connection<T>(
+        &mut self,
+        id: &Uuid,
+        instance_id: &Uuid,
+        change: impl FnOnce(&mut Network, Option<&mut Instance>) -> Result<T>,
+    ) -> Result<T> {
+        let mut change = Some(change);
+        let mut output = None;
+        let state = self.store.transaction(&mut |state| {
+            let network = state.networks.get_mut(id)
+                .ok_or_else(|| anyhow!("Network not found: {}", id))?;
+            let change = change.take().ok_or_else(|| anyhow!("Network update applied twice"))?;
+            output = Some(change(network, state.instances.get_mut(instance_id))?);
+            Ok(())
+        })?;
+        self.networks = state.networks;
+        output.ok_or_else(|| anyhow!("Network update was not applied"))
+    }
+
+    /// Get the bandwidth limits of every tenant that has any
+    pub fn tenant_limits(&self) -> Result<BTreeMap<String, TenantLimits>> {
+        Ok(self.store.load()?.tenants)
+    }
+
+    /// Set the bandwidth limits of a tenant, forgetting them once it is unlimited
+    pub fn set_tenant_limits(&mut self, tenant: &str, limits: &TenantLimits) -> Result<()> {
+        let state = self.store.transaction(&mut |state| {
+            if limits.is_unlimited() {
+                state.tenants.remove(tenant);
+            } else {
+                state.tenants.insert(tenant.to_string(), limits.clone());
+            }
+            Ok(())
+        })?;
+        self.networks = state.networks;
+        Ok(())
+    }
+
+    /// Remove a network
+    pub fn remove_network(&mut self, id: &Uuid) -> Result<Option<Network>> {
+        let mut removed = None;
+        let state = self.store.transaction(&mut |state| {
+            removed = state.networks.remove(id);
+            Ok(())
+        })?;
+        self.networks = state.networks;
+        Ok(removed)
+    }
+
+    /// Get all networks
+    pub fn get_all_networks(&self) -> Vec<&Network> {
+        self.networks.values().collect()
+    }
+}
+
+/// Network service for managing networks and their IP address management
+pub struct NetworkService {
+    storage: NetworkStorage,
+    provider_service: ProviderService,
+}
+
+impl NetworkService {
+    /// Create a new network service backed by the state store
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Self::with_store(provider_service, Arc::new(FileStateStore::open()?))
+    }
+
+    /// Create a new network service backed by a specific state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Result<Self> {
+        Ok(Self {
+            storage: NetworkStorage::new(store)?,
+            provider_service,
+        })
+    }
+
+    /// List all networks
+    pub fn list_networks(&self) -> Vec<&Network> {
+        self.storage.get_all_networks()
+    }
+
+    /// Get a network by ID
+    pub fn get_network(&self, id: &Uuid) -> Option<&Network> {
+        self.storage.get_network(id)
+    }
+
+    /// Create a new network
+    ///
+    /// The gateway defaults to the first host address of the block.
+    pub fn create_network(
+        &mut self,
+        name: &str,
+        provider_name: &str,
+        region: &str,
+        cidr: &str,
+        network_type: NetworkType,
+    ) -> Result<Uuid> {
+        let provider = self.provider_service.get_provider(provider_name)
+            .ok_or_else(|| anyhow!("Provider not found: {}", provider_name))?;
+
+        let mut network = Network::new(
+            name.to_string(),
+            provider.provider_type,
+            region.to_string(),
+            cidr.to_string(),
+            network_type,
+        );
+
+        let parsed = network.ipv4_cidr()
+            .map_err(|e| anyhow!("Invalid CIDR '{}': {}", cidr, e))?;
+        network.set_gateway(IpAddr::V4(parsed.first_host()));
+        network.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+        network.update_status(NetworkStatus::Available);
+
+        let id = network.id;
+        self.storage.add_network(network)?;
+
+        info!("Created network '{}' ({}) on '{}'", name, cidr, provider_name);
+        Ok(id)
+    }
+
+    /// Create a network whose gateway is shared by several VyOS routers through VRRP
+    ///
+    /// The first router is preferred. The gateway becomes the VRRP virtual address
+    /// and the routers take the addresses right after it.
+    pub async fn create_ha_network(
+        &mut self,
+        name: &str,
+        region: &str,
+        cidr: &str,
+        network_type: NetworkType,
+        ha: &HaGateway,
+    ) -> Result<Uuid> {
+        let routers = &ha.routers;
+        if routers.len() < 2 {
+            return Err(anyhow!("An HA network needs at least two routers"));
+        }
+        for router in routers {
+            match self.provider_service.get_provider(router) {
+                Some(provider) if provider.provider_type == ProviderType::VyOS => {},
+                Some(_) => return Err(anyhow!("Provider '{}' is not a VyOS router", router)),
+                None => return Err(anyhow!("Provider not found: {}", router)),
+            }
+        }
+
+        let vrid = match ha.vrid {
+            Some(vrid) => vrid,
+            None => self.next_vrid(routers)?,
+        };
+
+        let id = self.create_network(name, &routers[0], region, cidr, network_type)?;
+        let configured = self.storage.update_network(&id, |network| {
+            network.set_config(HA_ROUTERS_CONFIG.to_string(), routers.join(","));
+            network.set_config(ROUTER_INTERFACE_CONFIG.to_string(), ha.interface.clone());
+            network.set_config(VRRP_VRID_CONFIG.to_string(), vrid.to_string());
+            if !ha.preempt {
+                network.set_config(VRRP_NO_PREEMPT_CONFIG.to_string(), "true".to_string());
+            }
+
+            for (index, router) in routers.iter().enumerate() {
+                let address = ha_router_address(network, index)?;
+                network.reserve_ip(IpAddr::V4(address), router.clone())
+                    .map_err(|e| anyhow!("Failed to reserve {} for '{}': {}", address, router, e))?;
+            }
+
+            Ok(network.clone())
+        });
+        let network = match configured {
+            Ok(network) => network,
+            Err(e) => {
+                self.storage.remove_network(&id)?;
+                return Err(e);
+            },
+        };
+
+        // Push the VRRP configuration to every router, undoing it all if one fails
+        for (index, router) in routers.iter().enumerate() {
+            if let Err(e) = self.push_vrrp(&network, router, false).await {
+                for configured in routers.iter().take(index + 1) {
+                    if let Err(e) = self.push_vrrp(&network, configured, true).await {
+                        warn!("Failed to remove VRRP from '{}': {:#}", configured, e);
+                    }
+                }
+                self.storage.remove_network(&id)?;
+                return Err(e);
+            }
+        }
+
+        info!("Created HA network '{}' with VRID {} on {}", name, vrid, routers.join(", "));
+        Ok(id)
+    }
+
+    /// Configure or remove the VRRP group of an HA network on one of its routers
+    async fn push_vrrp(&self, network: &Network, router: &str, remove: bool) -> Result<()> {
+        let commands = if remove {
+            render_vrrp_removal(network, router)?
+        } else {
+            render_vrrp_config(network, router)?
+        };
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        client.configure(&commands).await
+            .context(format!("Failed to configure VRRP for '{}' on '{}'", network.name, router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+        Ok(())
+    }
+
+    /// Get the VRRP state of an HA network on each of its routers
+    pub async fn vrrp_status(&self, id: &Uuid) -> Result<Vec<(String, Result<Option<VrrpGroup>>)>> {
+        let network = self.storage.get_network(id)
+            .ok_or_else(|| anyhow!("Network not found: {}", id))?;
+        let group = vrrp_group_name(network);
+
+        let mut results = Vec::new();
+        for router in network.ha_routers() {
+            let result = async {
+                let mut client = self.provider_service.get_vyos_client(router)?;
+                let output = client.show(&["vrrp"]).await
+                    .context(format!("Failed to read VRRP state from '{}'", router))?;
+                Ok(parse_vrrp_summary(&output).into_iter().find(|g| g.name == group))
+            }.await;
+            results.push((router.to_string(), result));
+        }
+
+        Ok(results)
+    }
+
+    /// Delete a network and remove its DHCP configuration
+    ///
+    /// Networks with connected instances are refused. VRRP is removed from
+    /// the routers of an HA network first, and the record is only dropped
+    /// once every router has let go of the gateway.
+    pub async fn delete_network(&mut self, id: &Uuid) -> Result<()> {
+        let state = self.storage.store.load()?;
+        let network = state.networks.get(id).cloned()
+            .ok_or_else(|| anyhow!("Network not found: {}", id))?;
+
+        let key = id.to_string();
+        let connected: BTreeSet<Uuid> = network.instances.iter().copied()
+            .chain(network.ip_allocations.iter().filter_map(|a| a.instance_id))
+            .chain(state.instances.values().filter(|i| i.networks.iter().any(|n| n.network_id == key)).map(|i| i.id))
+            .collect();
+        if !connected.is_empty() {
+            return Err(anyhow!("Network '{}' still has {} connected instances, disconnect them first", network.name, connected.len()));
+        }
+
+        for router in network.ha_routers() {
+            self.push_vrrp(&network, router, true).await?;
+        }
+        self.storage.remove_network(id)?;
+
+        for router in self.network_routers(&network) {
+            self.sync_dhcp(&router).await?;
+        }
+        if self.tenant_limits(network.tenant())?.is_some() {
+            for router in self.network_routers(&network) {
+                self.sync_qos(&router).await?;
+            }
+        }
+        self.sync_dns().await?;
+
+        info!("Deleted network: {}", id);
+        Ok(())
+    }
+
+    /// Connect an instance to a network and allocate it an address
+    ///
+    /// The address is recorded on the instance too. A Proxmox VM's first NIC
+    /// on the network's bridge is linked to the network, and its MAC address
+    /// gets a DHCP static mapping. Returns the allocated address.
+    pub async fn connect_instance(&mut self, network_id: &Uuid, instance_id: &Uuid, ip: Option<IpAddr>) -> Result<IpAddr> {
+        let ip = self.storage.update_connection(network_id, instance_id, |network, instance| {
+            let instance = instance.ok_or_else(|| anyhow!("Instance not found: {}", instance_id))?;
+            let ip = link_instance(network, instance, ip)?;
+            info!("Connected instance {} to network '{}' with {}", instance.id, network.name, ip);
+            Ok(ip)
+        })?;
+        self.sync_network_dhcp(network_id).await?;
+        self.sync_dns().await?;
+        Ok(ip)
+    }
+
+    /// Disconnect an instance from a network and release its addresses
+    ///
+    /// A Proxmox VM's NIC goes back to being recorded on the network's bridge.
+    /// The instance's record may already be gone.
+    pub async fn disconnect_instance(&mut self, network_id: &Uuid, instance_id: &Uuid) -> Result<()> {
+        self.storage.update_connection(network_id, instance_id, |network, instance| {
+            if !network.disconnect_instance(instance_id) {
+                return Err(anyhow!("Instance {} is not connected to network '{}'", instance_id, network.name));
+            }
+            if let Some(instance) = instance {
+                unlink_instance(network, instance);
+            }
+
+            info!("Disconnected instance {} from network '{}'", instance_id, network.name);
+            Ok(())
+        })?;
+        self.sync_network_dhcp(network_id).await?;
+        self.sync_dns().await?;
+        Ok(())
+    }
+
+    /// Render the DHCP configuration of every bbctl network served by a router
+    pub fn render_router_dhcp(&self, router: &str) -> Result<Vec<ConfigCommand>> {
+        let mut networks = self.router_networks(router);
+        networks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut commands = Vec::new();
+        for network in networks {
+            let subnet_id = network.get_config(DHCP_SUBNET_ID_CONFIG)
+                .and_then(|id| id.parse::<u32>().ok())
+                .ok_or_else(|| anyhow!("Network '{}' has no DHCP subnet ID", network.name))?;
+            commands.extend(render_dhcp_config(network, subnet_id, router)?);
+        }
+
+        Ok(commands)
+    }
+
+    /// Bring the DHCP server on a router in line with bbctl IPAM
+    pub async fn sync_dhcp(&mut self, router: &str) -> Result<usize> {
+        self.assign_subnet_ids(router)?;
+        let desired = self.render_router_dhcp(router)?;
+
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["service", "dhcp-server"]).await
+            .context(format!("Failed to read DHCP configuration from '{}'", router))?
+            .into_iter()
+            .filter(|c| is_managed_dhcp_path(c.path()))
+            .collect();
+
+        debug!("Router '{}' has {} managed DHCP paths", router, current.len());
+        let diff = ConfigDiff::between(&current, &desired);
+        if diff.is_empty() {
+            info!("DHCP on '{}' is already up to date", router);
+            return Ok(0);
+        }
+
+        let commands = diff.commands();
+        client.configure(&commands).await
+            .context(format!("Failed to configure DHCP on '{}'", router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+
+        info!("Applied {} DHCP changes to '{}'", commands.len(), router);
+        Ok(commands.len())
+    }
+
+    /// Sync the DHCP servers serving a network, if bbctl manages them
+    pub async fn sync_network_dhcp(&mut self, network_id: &Uuid) -> Result<()> {
+        let network = self.storage.get_network(network_id)
+            .ok_or_else(|| anyhow!("Network not found: {}", network_id))?;
+
+        if !network.dhcp_enabled() {
+            return Ok(());
+        }
+
+        for router in self.network_routers(network) {
+            self.sync_dhcp(&router).await?;
+        }
+        Ok(())
+    }
+
+    /// Publish instance names of every network to the internal DNS zone
+    pub async fn sync_dns(&self) -> Result<()> {
+        let dns_service = DnsService::new(self.provider_service.clone())?;
+        let networks = self.storage.get_all_networks();
+
+        let failed: Vec<String> = dns_service.sync(&networks).await.into_iter()
+            .filter_map(|(router, result)| result.err().map(|e| format!("{}: {}", router, e)))
+            .collect();
+
+        if !failed.is_empty() {
+            return Err(anyhow!("Failed to sync DNS: {}", failed.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// Set the tenant owning a network
+    pub fn set_tenant(&mut self, id: &Uuid, tenant: &str) -> Result<()> {
+        self.storage.update_network(id, |network| {
+            network.add_tag(TENANT_TAG.to_string(), tenant.to_string());
+            Ok(())
+        })
+    }
+
+    /// Set the router interface carrying a network
+    pub fn set_router_interface(&mut self, id: &Uuid, interface: &str) -> Result<()> {
+        self.storage.update_network(id, |network| {
+            network.set_config(ROUTER_INTERFACE_CONFIG.to_string(), interface.to_string());
+            Ok(())
+        })
+    }
+
+    /// Set the Proxmox bridge VMs plug into to reach a network
+    pub fn set_bridge(&mut self, id: &Uuid, bridge: &str) -> Result<()> {
+        self.storage.update_network(id, |network| {
+            network.set_config(BRIDGE_CONFIG.to_string(), bridge.to_string());
+            Ok(())
+        })
+    }
+
+    /// Get the networks owned by a tenant
+    pub fn tenant_networks(&self, tenant: &str) -> Vec<&Network> {
+        self.storage.get_all_networks().into_iter()
+            .filter(|n| n.tenant() == tenant)
+            .collect()
+    }
+
+    /// Limit the bandwidth of a tenant on every router carrying its networks
+    ///
+    /// The limits are stored in the state; a direction without a limit is
+    /// left unrestricted. Returns the sync result per router.
+    pub async fn set_tenant_bandwidth(
+        &mut self,
+        tenant: &str,
+        egress: Option<&str>,
+        ingress: Option<&str>,
+    ) -> Result<Vec<(String, Result<usize>)>> {
+        let egress = egress.map(parse_bandwidth).transpose()
+            .map_err(|e| anyhow!("Invalid egress bandwidth: {}", e))?;
+        let ingress = ingress.map(parse_bandwidth).transpose()
+            .map_err(|e| anyhow!("Invalid ingress bandwidth: {}", e))?;
+
+        let networks = self.tenant_networks(tenant);
+        if networks.is_empty() {
+            return Err(anyhow!("Tenant '{}' has no networks", tenant));
+        }
+
+        let mut interfaces: BTreeMap<String, usize> = BTreeMap::new();
+        for network in networks {
+            if network.router_interface().is_none() {
+                warn!("Network '{}' has no router interface, its limits cannot be applied", network.name);
+                continue;
+            }
+            for router in self.network_routers(network) {
+                *interfaces.entry(router).or_default() += 1;
+            }
+        }
+        for (router, count) in interfaces.iter().filter(|(_, count)| **count > 1) {
+            warn!("Tenant '{}' has {} interfaces on '{}', each is limited separately", tenant, count, router);
+        }
+
+        let limits = TenantLimits { egress_bandwidth: egress.clone(), ingress_bandwidth: ingress.clone() };
+        self.storage.set_tenant_limits(tenant, &limits)?;
+
+        info!("Set bandwidth of tenant '{}': egress {}, ingress {}", tenant,
+              egress.as_deref().unwrap_or("unlimited"), ingress.as_deref().unwrap_or("unlimited"));
+
+        let mut results = Vec::new();
+        for router in interfaces.into_keys() {
+            let result = self.sync_qos(&router).await;
+            results.push((router, result));
+        }
+        Ok(results)
+    }
+
+    /// Get the bandwidth limits of a tenant, if it has any
+    pub fn tenant_limits(&self, tenant: &str) -> Result<Option<TenantLimits>> {
+        Ok(self.storage.tenant_limits()?.remove(tenant))
+    }
+
+    /// Render the tenant bandwidth limits of every network carried by a router
+    pub fn render_router_qos(&self, router: &str, tenants: &BTreeMap<String, TenantLimits>) -> Vec<ConfigCommand> {
+        let mut networks: Vec<&Network> = self.storage.get_all_networks().into_iter()
+            .filter(|n| self.network_routers(n).iter().any(|r| r == router))
+            .collect();
+        networks.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // Tenants with several networks on a router share one set of policies
+        let mut commands = Vec::new();
+        let limited = networks.into_iter()
+            .filter_map(|n| tenants.get(n.tenant()).map(|limits| render_qos_config(n, limits)));
+        for command in limited.flatten() {
+            if !commands.contains(&command) {
+                commands.push(command);
+            }
+        }
+        commands
+    }
+
+    /// Bring the QoS policies on a router in line with the tenant limits
+    pub async fn sync_qos(&self, router: &str) -> Result<usize> {
+        let desired = self.render_router_qos(router, &self.storage.tenant_limits()?);
+
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["qos"]).await
+            .context(format!("Failed to read QoS configuration from '{}'", router))?
+            .into_iter()
+            .filter(|c| is_managed_qos_path(c.path()))
+            .collect();
+
+        debug!("Router '{}' has {} managed QoS paths", router, current.len());
+        let diff = ConfigDiff::between(&current, &desired);
+        if diff.is_empty() {
+            info!("QoS on '{}' is already up to date", router);
+            return Ok(0);
+        }
+
+        let commands = diff.commands();
+        client.configure(&commands).await
+            .context(format!("Failed to configure QoS on '{}'", router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+
+        info!("Applied {} QoS changes to '{}'", commands.len(), router);
+        Ok(commands.len())
+    }
+
+    /// Get the VyOS routers carrying a network
+    fn network_routers(&self, network: &Network) -> Vec<String> {
+        let ha_routers = network.ha_routers();
+        if !ha_routers.is_empty() {
+            return ha_routers.into_iter().map(str::to_string).collect();
+        }
+
+        if network.provider != ProviderType::VyOS {
+            return Vec::new();
+        }
+        self.provider_service.find_network_provider_name(network).into_iter().collect()
+    }
+
+    /// Get the DHCP-enabled networks served by a router, HA networks included
+    fn router_networks(&self, router: &str) -> Vec<&Network> {
+        self.storage.get_all_networks().into_iter()
+            .filter(|n| n.dhcp_enabled())
+            .filter(|n| self.network_routers(n).iter().any(|name| name == router))
+            .collect()
+    }
+
+    /// Find the lowest VRRP ID not used by another HA network on the same routers
+    fn next_vrid(&self, routers: &[String]) -> Result<u8> {
+        let used: BTreeSet<u8> = self.storage.get_all_networks().into_iter()
+            .filter(|n| n.ha_routers().iter().any(|r| routers.iter().any(|router| router == r)))
+            .filter_map(|n| n.vrrp_vrid())
+            .collect();
+
+        (1..=u8::MAX).find(|vrid| !used.contains(vrid))
+            .ok_or_else(|| anyhow!("No free VRRP IDs left on {}", routers.join(", ")))
+    }
+
+    /// Give every network on a router a stable, unique DHCP subnet ID
+    fn assign_subnet_ids(&mut self, router: &str) -> Result<()> {
+        let mut used: BTreeSet<u32> = BTreeSet::new();
+        let mut missing = Vec::new();
+
+        for network in self.router_networks(router) {
+            match network.get_config(DHCP_SUBNET_ID_CONFIG).and_then(|id| id.parse::<u32>().ok()) {
+                Some(id) => { used.insert(id); },
+                None => missing.push(network.id),
+            }
+        }
+
+        for id in missing {
+            let subnet_id = (1..).find(|n| !used.contains(n)).unwrap_or(1);
+            used.insert(subnet_id);
+            self.storage.update_network(&id, |network| {
+                network.set_config(DHCP_SUBNET_ID_CONFIG.to_string(), subnet_id.to_string());
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::instance::InstanceSize;
+    use crate::state::{MemoryStateStore, State};
+
+    fn ha_network(range: &str) -> Network {
+        let mut network = Network::new("web".to_string(), ProviderType::VyOS, "lab".to_string(), "10.0.0.0/24".to_string(), NetworkType::Routed);
+        network.set_config(HA_ROUTERS_CONFIG.to_string(), "r1,r2".to_string());
+        network.set_config(DHCP_RANGE_CONFIG.to_string(), range.to_string());
+        network
+    }
+
+    fn rendered_range(network: &Network, router: &str) -> Vec<String> {
+        render_dhcp_config(network, 1, router).unwrap().iter()
+            .map(|c| c.to_string())
+            .filter(|c| c.contains(" range "))
+            .collect()
+    }
+
+    #[test]
+    fn ha_routers_serve_disjoint_ranges() {
+        let network = ha_network("10.0.0.100-10.0.0.200");
+        assert_eq!(rendered_range(&network, "r1"), [
+            "set service dhcp-server shared-network-name bb-web subnet 10.0.0.0/24 range 0 start 10.0.0.100",
+            "set service dhcp-server shared-network-name bb-web subnet 10.0.0.0/24 range 0 stop 10.0.0.149",
+        ]);
+        assert_eq!(rendered_range(&network, "r2"), [
+            "set service dhcp-server shared-network-name bb-web subnet 10.0.0.0/24 range 0 start 10.0.0.150",
+            "set service dhcp-server shared-network-name bb-web subnet 10.0.0.0/24 range 0 stop 10.0.0.200",
+        ]);
+    }
+
+    #[test]
+    fn single_router_networks_serve_the_whole_range() {
+        let mut network = ha_network("10.0.0.100-10.0.0.200");
+        network.remove_config(HA_ROUTERS_CONFIG);
+        assert_eq!(rendered_range(&network, "r1"), [
+            "set service dhcp-server shared-network-name bb-web subnet 10.0.0.0/24 range 0 start 10.0.0.100",
+            "set service dhcp-server shared-network-name bb-web subnet 10.0.0.0/24 range 0 stop 10.0.0.200",
+        ]);
+    }
+
+    #[test]
+    fn tenant_limits_apply_to_the_network_interface() {
+        let mut network = ha_network("10.0.0.100-10.0.0.200");
+        network.add_tag(TENANT_TAG.to_string(), "Acme".to_string());
+        network.set_config(ROUTER_INTERFACE_CONFIG.to_string(), "eth1".to_string());
+        let limits = TenantLimits { egress_bandwidth: Some("200mbit".to_string()), ingress_bandwidth: None };
+
+        let commands: Vec<String> = render_qos_config(&network, &limits).iter().map(|c| c.to_string()).collect();
+        assert_eq!(commands, [
+            "set qos policy limiter bb-acme-egress default bandwidth 200mbit",
+            "set qos interface eth1 ingress bb-acme-egress",
+        ]);
+        network.remove_config(ROUTER_INTERFACE_CONFIG);
+        assert!(render_qos_config(&network, &limits).is_empty());
+    }
+
+    #[tokio::test]
+    async fn tenant_bandwidth_is_stored_in_the_state() {
+        let mut network = ha_network("10.0.0.100-10.0.0.200");
+        network.add_tag(TENANT_TAG.to_string(), "acme".to_string());
+        network.set_config(ROUTER_INTERFACE_CONFIG.to_string(), "eth1".to_string());
+        let mut state = State::new();
+        state.networks.insert(network.id, network);
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::with_state(state));
+        let mut service = NetworkService::with_store(ProviderService::new().unwrap(), store.clone()).unwrap();
+
+        // The routers are not configured, so only their QoS sync fails
+        let results = service.set_tenant_bandwidth("acme", Some("200MBit"), None).await.unwrap();
+        assert_eq!(results.iter().map(|(router, _)| router.as_str()).collect::<Vec<_>>(), ["r1", "r2"]);
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+
+        let limits = TenantLimits { egress_bandwidth: Some("200mbit".to_string()), ingress_bandwidth: None };
+        assert_eq!(store.load().unwrap().tenants.get("acme"), Some(&limits));
+        assert_eq!(service.tenant_limits("acme").unwrap(), Some(limits));
+        assert_eq!(service.render_router_qos("r2", &service.storage.tenant_limits().unwrap()).len(), 2);
+
+        service.set_tenant_bandwidth("acme", None, None).await.unwrap();
+        assert!(store.load().unwrap().tenants.is_empty());
+        assert!(service.render_router_qos("r1", &BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn ha_ranges_must_be_splittable() {
+        assert!(render_dhcp_config(&ha_network("10.0.0.100-10.0.0.100"), 1, "r1").is_err());
+        assert!(render_dhcp_config(&ha_network("10.0.0.100-10.0.0.200"), 1, "r3").is_err());
+    }
+
+    #[test]
+    fn connecting_links_the_nic_on_the_bridge() {
+        let mut network = Network::new("app".to_string(), ProviderType::VyOS, "lab".to_string(), "10.0.0.0/24".to_string(), NetworkType::Routed);
+        network.set_config(BRIDGE_CONFIG.to_string(), "vmbr1".to_string());
+        network.set_gateway("10.0.0.1".parse().unwrap());
+        let size = InstanceSize { cpu: 1, memory_gb: 1, disk_gb: 10 };
+        let mut instance = Instance::new("web".to_string(), ProviderType::Proxmox, "lab".to_string(), size);
+        instance.add_network("vmbr0".to_string(), None, Some("net0".to_string()), Some("bc:24:11:00:00:01".to_string()));
+        instance.add_network("vmbr1".to_string(), None, Some("net1".to_string()), Some("bc:24:11:00:00:02".to_string()));
+
+        let ip = link_instance(&mut network, &mut instance, None).unwrap();
+        assert_eq!(ip.to_string(), "10.0.0.2");
+        assert_eq!(network.ip_allocations[0].mac.as_deref(), Some("bc:24:11:00:00:02"));
+        assert_eq!(instance.networks[1].network_id, network.id.to_string());
+        assert_eq!(instance.networks[1].ip.as_deref(), Some("10.0.0.2"));
+
+        network.disconnect_instance(&instance.id);
+        unlink_instance(&network, &mut instance);
+        assert_eq!(instance.networks.len(), 2);
+        assert_eq!(instance.networks[1].network_id, "vmbr1");
+        assert!(instance.networks[1].ip.is_none());
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::{Result, Context, anyhow};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::settings::Settings;
+use crate::models::app::{AppHealthCheck, AppRegion, AppSpec};
+use crate::models::provider::{ProviderType, Region};
+use crate::services::provider::ProviderService;
+use crate::services::deploy::{validate_app_spec, APP_FILE};
+
+/// Project files of other tools, reported so their settings can be carried over
+const OTHER_PROJECT_FILES: [&str; 4] = ["deploy.toml", "fly.toml", "docker-compose.yml", "compose.yaml"];
+
+/// Ready-made project for `bbctl init --from-template`
+#[derive(Debug, Clone, Copy)]
+pub struct ProjectPreset {
+    /// Preset name
+    pub name: &'static str,
+    /// What the preset deploys
+    pub description: &'static str,
+    /// Provider type the preset runs on
+    pub provider_type: ProviderType,
+}
+
+/// Presets, in the order they are listed
+pub const PRESETS: [ProjectPreset; 3] = [
+    ProjectPreset {
+        name: "web",
+        description: "HTTP service in a container, published on router port 8080",
+        provider_type: ProviderType::VyOS,
+    },
+    ProjectPreset {
+        name: "worker",
+        description: "Background container without published ports",
+        provider_type: ProviderType::VyOS,
+    },
+    ProjectPreset {
+        name: "vm",
+        description: "VM cloned from a Proxmox template, checked over SSH",
+        provider_type: ProviderType::Proxmox,
+    },
+];
+
+/// Get a preset by name
+pub fn get_preset(name: &str) -> Option<&'static ProjectPreset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+/// Application spec with the default size and a single region
+pub fn base_app_spec(name: &str, provider: &str, region: &str, settings: &Settings) -> AppSpec {
+    AppSpec {
+        name: name.to_string(),
+        image: None,
+        template: None,
+        cpu: settings.default_cpu,
+        memory_gb: u16::from(settings.default_memory_gb),
+        disk_gb: u16::from(settings.default_disk_gb),
+        env: BTreeMap::new(),
+        ports: Vec::new(),
+        network: None,
+        network_prefix: None,
+        regions: vec![AppRegion {
+            region: region.to_string(),
+            provider: provider.to_string(),
+            count: 1,
+        }],
+        health_check: None,
+        batch_size: 1,
+    }
+}
+
+impl ProjectPreset {
+    /// Application spec of the preset
+    pub fn spec(&self, name: &str, provider: &str, region: &str, settings: &Settings) -> AppSpec {
+        let mut spec = base_app_spec(name, provider, region, settings);
+        match self.name {
+            "web" => {
+                spec.image = Some("docker.io/library/nginx:stable".to_string());
+                spec.network = Some("bbctl-apps".to_string());
+                spec.network_prefix = Some("172.30.0.0/24".to_string());
+                spec.ports = vec!["8080:80".to_string()];
+                spec.health_check = Some(AppHealthCheck {
+                    port: 80,
+                    path: Some("/".to_string()),
+                    expect_status: None,
+                    timeout_secs: 60,
+                    interval_secs: 5,
+                });
+            },
+            "worker" => {
+                spec.image = Some(format!("{}:latest", name));
+                spec.network = Some("bbctl-apps".to_string());
+                spec.network_prefix = Some("172.30.0.0/24".to_string());
+            },
+            _ => {
+                spec.template = Some("debian-12".to_string());
+                spec.health_check = Some(AppHealthCheck {
+                    port: 22,
+                    path: None,
+                    expect_status: None,
+                    timeout_secs: 300,
+                    interval_secs: 10,
+                });
+            },
+        }
+        spec
+    }
+}
+
+/// Serve the Dockerfile's exposed port from a container spec
+pub fn use_exposed_port(spec: &mut AppSpec, port: u16) {
+    if spec.image.is_none() {
+        return;
+    }
+    spec.image = Some(format!("{}:latest", spec.name));
+    if let Some(published) = spec.ports.first_mut() {
+        let router_port = published.split(':').next().unwrap_or_default().to_string();
+        *published = format!("{}:{}", router_port, port);
+    }
+    if let Some(check) = &mut spec.health_check {
+        check.port = port;
+    }
+}
+
+/// Available regions of a provider type, sorted by ID
+pub fn regions_for(provider_service: &ProviderService, provider_type: ProviderType) -> Vec<&Region> {
+    let mut regions: Vec<_> = provider_service.get_regions_by_provider(provider_type)
+        .into_iter()
+        .filter(|r| r.available)
+        .collect();
+    regions.sort_by(|a, b| a.id.cmp(&b.id));
+    regions
+}
+
+/// Pick the provider and region of a project without asking
+///
+/// Explicit choices win, then the defaults from the settings, then the only
+/// provider of the type and the first of its regions.
+pub fn resolve_target(
+    provider_service: &ProviderService,
+    settings: &Settings,
+    provider_type: ProviderType,
+    provider: Option<&str>,
+    region: Option<&str>,
+) -> Result<(String, String)> {
+    let matches = |name: &str| provider_service.get_provider(name)
+        .is_some_and(|config| config.provider_type == provider_type);
+
+    let provider = match provider {
+        Some(name) if matches(name) => name.to_string(),
+        Some(name) => return Err(anyhow!("Provider '{}' is not a {} provider", name, provider_type)),
+        None => match settings.default_provider.as_deref().filter(|name| matches(name)) {
+            Some(name) => name.to_string(),
+            None => {
+                let mut names: Vec<_> = provider_service.get_providers().iter()
+                    .filter(|(_, config)| config.provider_type == provider_type)
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                match names.len() {
+                    1 => names.remove(0),
+                    0 => return Err(anyhow!("No {} provider configured, add one with `bbctl providers add`", provider_type)),
+                    _ => return Err(anyhow!("Several {} providers are configured, pick one with --provider", provider_type)),
+                }
+            },
+        },
+    };
+
+    let regions = regions_for(provider_service, provider_type);
+    let region = region.map(str::to_string)
+        .or_else(|| settings.default_region.clone().filter(|id| regions.iter().any(|r| &r.id == id)))
+        .or_else(|| regions.first().map(|r| r.id.clone()))
+        .unwrap_or_else(|| "default".to_string());
+
+    Ok((provider, region))
+}
+
+/// Project files found in a directory
+#[derive(Debug, Clone, Default)]
+pub struct ProjectFiles {
+    /// Existing bbctl project file
+    pub project: Option<PathBuf>,
+    /// Project files of other tools
+    pub others: Vec<PathBuf>,
+    /// First port a Dockerfile exposes
+    pub exposed_port: Option<u16>,
+}
+
+/// Look for project files in a directory
+pub fn detect_project_files(dir: &Path) -> ProjectFiles {
+    let project = Some(dir.join(APP_FILE)).filter(|p| p.exists());
+    let others = OTHER_PROJECT_FILES.iter()
+        .map(|file| dir.join(file))
+        .filter(|p| p.exists())
+        .collect();
+    let exposed_port = fs::read_to_string(dir.join("Dockerfile")).ok()
+        .and_then(|dockerfile| dockerfile_exposed_port(&dockerfile));
+
+    ProjectFiles { project, others, exposed_port }
+}
+
+/// First port of the first `EXPOSE` instruction of a Dockerfile
+pub fn dockerfile_exposed_port(dockerfile: &str) -> Option<u16> {
+    dockerfile.lines()
+        .map(str::trim)
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(instruction, _)| instruction.eq_ignore_ascii_case("EXPOSE"))
+        .and_then(|(_, ports)| ports.split_whitespace().next())
+        .and_then(|port| port.split('/').next())
+        .and_then(|port| port.parse().ok())
+}
+
+/// Write an application spec as a project file
+pub fn write_app_spec(path: &Path, spec: &AppSpec) -> Result<()> {
+    validate_app_spec(spec)?;
+    let content = toml::to_string_pretty(spec)
+        .map_err(|e| anyhow!("Failed to serialize project: {}", e))?;
+
+    fs::write(path, format!("# bbctl project, deploy it with `bbctl deploy`\n\n{}", content))
+        .context(format!("Failed to write project file: {}", path.display()))?;
+    Ok(())
+}
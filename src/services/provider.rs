@@ -1,13 +1,69 @@
 use anyhow::{Result, Context, anyhow};
-use log::{debug, info, error};
+use log::{debug, info, warn, error};
 use std::collections::HashMap;
+use std::net::{IpAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+use uuid::Uuid;
 
+use crate::models::instance::{Instance, NODE_TAG, PROVIDER_TAG};
+use crate::models::network::Network;
 use crate::models::provider::{ProviderType, ProviderConfig, Region, ResourceLimits};
 use crate::config::provider::Providers;
-use crate::config::credentials::{Credentials, ProviderCredentials};
+use crate::config::credentials::Credentials;
 use crate::api::{Provider, vyos::VyOSClient, vyos::VyOSConfig, proxmox::ProxmoxClient, proxmox::ProxmoxConfig, proxmox::ProxmoxAuth};
+use crate::api::vyos::{ConfigCommand, VyOSVersion};
+
+/// ID of the HTTP API key bbctl creates on VyOS routers
+pub const API_KEY_ID: &str = "bbctl";
+
+/// Attempts to reach the HTTP API after enabling it, one every two seconds
+const API_VERIFY_ATTEMPTS: u32 = 10;
+
+/// Generate a random HTTP API key
+pub fn generate_api_key() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Render the configuration enabling the VyOS HTTP API with a key
+///
+/// Only the given source addresses may reach the API.
+pub fn render_api_bootstrap(api_key: &str, allowed: &[String], version: VyOSVersion) -> Vec<ConfigCommand> {
+    let mut commands = vec![
+        ConfigCommand::set(["service", "https", "api", "keys", "id", API_KEY_ID, "key", api_key]),
+    ];
+
+    // 1.5 no longer serves the REST endpoints unless asked to
+    if version == VyOSVersion::V1_5 {
+        commands.push(ConfigCommand::set(["service", "https", "api", "rest"]));
+    }
+
+    for address in allowed {
+        commands.push(ConfigCommand::set(["service", "https", "allow-client", "address", address.as_str()]));
+    }
+
+    commands
+}
+
+/// Local address used to reach a host, as a host prefix
+pub fn local_prefix_towards(host: &str, port: u16) -> Result<String> {
+    let remote = (host, port).to_socket_addrs()
+        .context(format!("Failed to resolve '{}'", host))?
+        .next()
+        .ok_or_else(|| anyhow!("'{}' has no address", host))?;
+
+    // Connecting a UDP socket selects the route without sending anything
+    let bind = if remote.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(remote)?;
+
+    Ok(match socket.local_addr()?.ip() {
+        IpAddr::V4(address) => format!("{}/32", address),
+        IpAddr::V6(address) => format!("{}/128", address),
+    })
+}
 
 /// Provider service for managing infrastructure providers
+#[derive(Debug, Clone)]
 pub struct ProviderService {
     providers: Providers,
     credentials: Credentials,
@@ -40,7 +96,59 @@ impl ProviderService {
         self.providers.get_regions_by_provider(provider_type)
     }
     
+    /// Get a provider config by name
+    pub fn get_provider(&self, name: &str) -> Option<&ProviderConfig> {
+        self.providers.get_provider(name)
+    }
+    
+    /// Find the name of the provider hosting an instance
+    pub fn find_provider_name(&self, instance: &Instance) -> Result<String> {
+        self.find_tagged_provider(&instance.tags, instance.provider)
+            .ok_or_else(|| anyhow!("No provider found for instance: {}", instance.id))
+    }
+    
+    /// Find the name of the provider hosting a network
+    ///
+    /// HA networks are hosted on the first of their routers, the others are
+    /// listed by [`Network::ha_routers`].
+    pub fn find_network_provider_name(&self, network: &Network) -> Result<String> {
+        self.find_tagged_provider(&network.tags, network.provider)
+            .ok_or_else(|| anyhow!("No provider found for network: {}", network.id))
+    }
+    
+    /// Resolve a provider from a resource's provider tag or its provider type
+    fn find_tagged_provider(&self, tags: &HashMap<String, String>, provider_type: ProviderType) -> Option<String> {
+        // Prefer the provider recorded when the resource was created
+        if let Some(name) = tags.get(PROVIDER_TAG) {
+            if self.providers.get_provider(name).is_some() {
+                return Some(name.clone());
+            }
+        }
+        
+        // Fall back to the first provider of the right type
+        self.get_providers().iter()
+            .find(|(_, provider)| provider.provider_type == provider_type)
+            .map(|(name, _)| name.clone())
+    }
+    
+    /// Get the Proxmox node hosting an instance
+    pub fn proxmox_node(&self, provider_name: &str, instance: &Instance) -> String {
+        self.tagged_proxmox_node(provider_name, &instance.tags)
+            .unwrap_or_else(|| "pve".to_string())
+    }
+    
+    /// Get the Proxmox node recorded on a resource, or configured for its provider
+    pub fn tagged_proxmox_node(&self, provider_name: &str, tags: &HashMap<String, String>) -> Option<String> {
+        tags.get(NODE_TAG)
+            .or_else(|| {
+                self.providers.get_provider(provider_name)
+                    .and_then(|p| p.params.get(NODE_TAG))
+            })
+            .cloned()
+    }
+    
     /// Add a new VyOS provider
+    #[allow(clippy::too_many_arguments)]
     pub fn add_vyos_provider(
         &mut self,
         name: &str,
@@ -53,7 +161,7 @@ impl ProviderService {
         api_port: Option<u16>,
     ) -> Result<()> {
         // Create provider params
-        let mut params = HashMap::new();
+        let params = HashMap::new();
         
         // Add provider
         self.providers.add_provider(name, ProviderType::VyOS, host, params)?;
@@ -88,7 +196,7 @@ impl ProviderService {
         verify_ssl: bool,
     ) -> Result<()> {
         // Create provider params
-        let mut params = HashMap::new();
+        let params = HashMap::new();
         
         // Add provider
         self.providers.add_provider(name, ProviderType::Proxmox, host, params)?;
@@ -111,6 +219,7 @@ impl ProviderService {
     }
     
     /// Add a new Proxmox provider with username/password auth
+    #[allow(clippy::too_many_arguments)]
     pub fn add_proxmox_provider_with_user_pass(
         &mut self,
         name: &str,
@@ -122,7 +231,7 @@ impl ProviderService {
         verify_ssl: bool,
     ) -> Result<()> {
         // Create provider params
-        let mut params = HashMap::new();
+        let params = HashMap::new();
         
         // Add provider
         self.providers.add_provider(name, ProviderType::Proxmox, host, params)?;
@@ -200,6 +309,11 @@ impl ProviderService {
     
     /// Get a VyOS client for a provider
     pub fn get_vyos_client(&self, provider_name: &str) -> Result<VyOSClient> {
+        Ok(VyOSClient::new(self.vyos_config(provider_name)?))
+    }
+    
+    /// Build the client configuration of a VyOS provider
+    fn vyos_config(&self, provider_name: &str) -> Result<VyOSConfig> {
         // Get provider config
         let provider = self.providers.get_provider(provider_name)
             .ok_or_else(|| anyhow!("Provider not found: {}", provider_name))?;
@@ -224,10 +338,70 @@ impl ProviderService {
             timeout: 30,
         };
         
-        // Create client
-        let client = VyOSClient::new(config);
-        
-        Ok(client)
+        Ok(config)
+    }
+    
+    /// Enable the HTTP API of a VyOS provider over SSH and store the generated key
+    ///
+    /// The key is only stored once the API answers with it, and removed from the
+    /// router again when it never does. Returns the key.
+    pub async fn bootstrap_vyos_api(
+        &mut self,
+        provider_name: &str,
+        allowed: &[String],
+        version: VyOSVersion,
+    ) -> Result<String> {
+        let mut creds = self.credentials.get_vyos_credentials(provider_name)?.clone();
+        let api_key = generate_api_key();
+
+        // Log in over SSH and commit the API configuration
+        let ssh_client = self.get_vyos_client(provider_name)?;
+        let commands = render_api_bootstrap(&api_key, allowed, version);
+        ssh_client.configure_over_ssh(&commands).await
+            .context(format!("Failed to enable the HTTP API on '{}'", provider_name))?;
+        info!("Enabled the HTTP API on '{}'", provider_name);
+
+        // The HTTPS service restarts on commit, give it some time
+        creds.api_key = Some(api_key.clone());
+        let mut client = VyOSClient::new(VyOSConfig {
+            api_key: Some(api_key.clone()),
+            ..self.vyos_config(provider_name)?
+        });
+        let mut last_error = None;
+        for attempt in 1..=API_VERIFY_ATTEMPTS {
+            match client.show(&["version"]).await {
+                Ok(_) => {
+                    last_error = None;
+                    break;
+                },
+                Err(e) => {
+                    debug!("HTTP API on '{}' not ready (attempt {}): {}", provider_name, attempt, e);
+                    last_error = Some(e);
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                },
+            }
+        }
+        if let Some(e) = last_error {
+            let removal = [ConfigCommand::delete(["service", "https", "api", "keys", "id", API_KEY_ID])];
+            if let Err(e) = ssh_client.configure_over_ssh(&removal).await {
+                warn!("Failed to remove the unverified HTTP API key from '{}': {:#}", provider_name, e);
+            }
+            return Err(anyhow!("The HTTP API on '{}' did not answer with the new key: {}", provider_name, e));
+        }
+
+        self.credentials.add_vyos_credentials(
+            provider_name,
+            &creds.username,
+            creds.password,
+            creds.key_path,
+            creds.api_key,
+            creds.ssh_port,
+            creds.api_port,
+        )?;
+        self.credentials.save()?;
+
+        info!("Stored the HTTP API key of '{}'", provider_name);
+        Ok(api_key)
     }
     
     /// Get a Proxmox client for a provider
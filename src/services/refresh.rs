@@ -0,0 +1,562 @@
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::models::instance::{Instance, InstanceStatus, PROVIDER_TAG};
+use crate::models::network::{Network, NetworkStatus, DHCP_SUBNET_ID_CONFIG};
+use crate::models::provider::ProviderType;
+use crate::models::volume::{Volume, VolumeStatus};
+use crate::services::network::{shared_network_name, unlink_instance, NetworkService};
+use crate::services::provider::ProviderService;
+use crate::services::volume::VYOS_VOLUME_ROOT;
+use crate::state::{FileStateStore, State, StateStore};
+
+/// Kind of resource a drift concerns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResourceKind {
+    Instance,
+    Volume,
+    Network,
+}
+
+impl std::fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceKind::Instance => write!(f, "instance"),
+            ResourceKind::Volume => write!(f, "volume"),
+            ResourceKind::Network => write!(f, "network"),
+        }
+    }
+}
+
+/// Difference between a stored record and the provider
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "drift", rename_all = "lowercase")]
+pub enum DriftKind {
+    /// The provider reports a different status than the record
+    Status { from: String, to: String },
+    /// The record's backing resource is gone
+    Missing,
+    /// The provider has a resource no record describes
+    Unmanaged,
+}
+
+/// Drift found by a refresh
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    /// Provider the resource lives on
+    pub provider: String,
+    /// Kind of resource
+    pub resource: ResourceKind,
+    /// Record ID, absent for unmanaged resources
+    pub id: Option<Uuid>,
+    /// Record or resource name
+    pub name: String,
+    /// Provider-specific ID, such as a VMID or container name
+    pub provider_id: String,
+    /// What differs
+    #[serde(flatten)]
+    pub kind: DriftKind,
+}
+
+/// Instance as reported by a provider
+#[derive(Debug, Clone)]
+pub struct LiveInstance {
+    /// Name on the provider
+    pub name: String,
+    /// Status on the provider
+    pub status: InstanceStatus,
+}
+
+/// Resources a provider reports, keyed by provider-specific ID
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    /// Instances by VMID or container name
+    pub instances: BTreeMap<String, LiveInstance>,
+    /// Volumes by Proxmox volume ID or VyOS directory, with the VMID owning the disk
+    pub volumes: BTreeMap<String, Option<String>>,
+    /// DHCP shared networks, for providers whose networks have a backing resource
+    pub networks: Option<BTreeSet<String>>,
+}
+
+/// Result of refreshing every provider
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RefreshReport {
+    /// Drift found, by provider
+    pub drifts: Vec<Drift>,
+    /// Providers that could not be read, whose records were left alone
+    pub errors: Vec<(String, String)>,
+}
+
+/// Status a volume has when its resource exists
+fn present_volume_status(volume: &Volume) -> VolumeStatus {
+    if volume.attached_to.is_some() { VolumeStatus::InUse } else { VolumeStatus::Available }
+}
+
+/// Compare a provider's inventory with the records it hosts
+///
+/// Networks are only compared once bbctl has pushed their DHCP
+/// configuration, which is when they get a DHCP subnet ID. Records already
+/// flagged missing are reported again, so a later fix can drop them. Disks
+/// of known instances, such as their boot disks, are not unmanaged volumes.
+pub fn detect_drift(
+    provider: &str,
+    inventory: &Inventory,
+    instances: &[&Instance],
+    volumes: &[&Volume],
+    networks: &[&Network],
+) -> Vec<Drift> {
+    let mut drifts = Vec::new();
+    let mut drift = |resource, id, name: &str, provider_id: &str, kind| {
+        drifts.push(Drift {
+            provider: provider.to_string(),
+            resource,
+            id,
+            name: name.to_string(),
+            provider_id: provider_id.to_string(),
+            kind,
+        });
+    };
+
+    for instance in instances {
+        let kind = match inventory.instances.get(&instance.provider_id) {
+            Some(live) if live.status != instance.status => DriftKind::Status {
+                from: instance.status.to_string(),
+                to: live.status.to_string(),
+            },
+            Some(_) => continue,
+            None => DriftKind::Missing,
+        };
+        drift(ResourceKind::Instance, Some(instance.id), &instance.name, &instance.provider_id, kind);
+    }
+
+    // Proxmox volumes get their disk on first attach, until then there is nothing to find
+    for volume in volumes.iter().filter(|v| !v.provider_id.is_empty()) {
+        let present = inventory.volumes.contains_key(&volume.provider_id);
+        let kind = match (present, volume.status) {
+            (true, VolumeStatus::Missing) => DriftKind::Status {
+                from: volume.status.to_string(),
+                to: present_volume_status(volume).to_string(),
+            },
+            (true, _) => continue,
+            (false, _) => DriftKind::Missing,
+        };
+        drift(ResourceKind::Volume, Some(volume.id), &volume.name, &volume.provider_id, kind);
+    }
+
+    if let Some(live_networks) = &inventory.networks {
+        for network in networks.iter().filter(|n| n.dhcp_enabled() && n.get_config(DHCP_SUBNET_ID_CONFIG).is_some()) {
+            let shared = shared_network_name(network);
+            let kind = match (live_networks.contains(&shared), network.status) {
+                (true, NetworkStatus::Missing) => DriftKind::Status {
+                    from: network.status.to_string(),
+                    to: NetworkStatus::Available.to_string(),
+                },
+                (true, _) => continue,
+                (false, _) => DriftKind::Missing,
+            };
+            drift(ResourceKind::Network, Some(network.id), &network.name, &shared, kind);
+        }
+    }
+
+    // Anything left over on the provider is not managed by bbctl
+    let known_instances: BTreeSet<&str> = instances.iter().map(|i| i.provider_id.as_str()).collect();
+    for (provider_id, live) in &inventory.instances {
+        if !known_instances.contains(provider_id.as_str()) {
+            drift(ResourceKind::Instance, None, &live.name, provider_id, DriftKind::Unmanaged);
+        }
+    }
+
+    let known: BTreeSet<&str> = volumes.iter().map(|v| v.provider_id.as_str()).collect();
+    for (provider_id, owner) in &inventory.volumes {
+        let owned = owner.as_deref().is_some_and(|owner| known_instances.contains(owner));
+        if !known.contains(provider_id.as_str()) && !owned {
+            drift(ResourceKind::Volume, None, provider_id, provider_id, DriftKind::Unmanaged);
+        }
+    }
+
+    if let Some(live_networks) = &inventory.networks {
+        let known: BTreeSet<String> = networks.iter().map(|n| shared_network_name(n)).collect();
+        for shared in live_networks {
+            if !known.contains(shared) {
+                drift(ResourceKind::Network, None, shared, shared, DriftKind::Unmanaged);
+            }
+        }
+    }
+
+    drifts
+}
+
+/// Drop an instance record along with its addresses and volume attachments
+fn forget_instance(state: &mut State, id: &Uuid) -> bool {
+    if state.instances.remove(id).is_none() {
+        return false;
+    }
+
+    for network in state.networks.values_mut() {
+        network.disconnect_instance(id);
+        network.ip_allocations.retain(|alloc| alloc.instance_id != Some(*id));
+    }
+    for volume in state.volumes.values_mut().filter(|v| v.attached_to == Some(*id)) {
+        volume.detach();
+    }
+    true
+}
+
+/// Drop a network record, putting the NICs linked to it back on its bridge
+fn forget_network(state: &mut State, id: &Uuid) -> bool {
+    let Some(network) = state.networks.remove(id) else {
+        return false;
+    };
+
+    for instance in state.instances.values_mut() {
+        unlink_instance(&network, instance);
+    }
+    true
+}
+
+/// Networks that lose addresses when missing instances are dropped
+pub fn released_networks(state: &State, drifts: &[Drift]) -> BTreeSet<Uuid> {
+    let missing: BTreeSet<Uuid> = drifts.iter()
+        .filter(|d| d.resource == ResourceKind::Instance && d.kind == DriftKind::Missing)
+        .filter_map(|d| d.id)
+        .collect();
+
+    state.networks.values()
+        .filter(|n| n.instances.iter().any(|id| missing.contains(id))
+            || n.ip_allocations.iter().any(|a| a.instance_id.is_some_and(|id| missing.contains(&id))))
+        .map(|n| n.id)
+        .collect()
+}
+
+/// Apply refresh results to the state
+///
+/// Status drift is always recorded and missing resources are flagged with
+/// the `missing` status. With `fix`, records of missing resources are
+/// dropped instead, including ones flagged by an earlier refresh. Dropped
+/// instances release their addresses and volumes, and NICs linked to a
+/// dropped network go back to its bridge. Unmanaged resources are never
+/// touched. Returns the number of records changed.
+pub fn apply_drift(state: &mut State, drifts: &[Drift], fix: bool) -> usize {
+    let mut changed = 0;
+
+    for drift in drifts {
+        let Some(id) = drift.id else {
+            continue;
+        };
+
+        let status = match &drift.kind {
+            DriftKind::Status { to, .. } => to.as_str(),
+            DriftKind::Missing if fix => {
+                let removed = match drift.resource {
+                    ResourceKind::Instance => forget_instance(state, &id),
+                    ResourceKind::Volume => state.volumes.remove(&id).is_some(),
+                    ResourceKind::Network => forget_network(state, &id),
+                };
+                changed += usize::from(removed);
+                continue;
+            },
+            DriftKind::Missing => "missing",
+            DriftKind::Unmanaged => continue,
+        };
+
+        // Records still missing since the last refresh are left as they are
+        let updated = match drift.resource {
+            ResourceKind::Instance => state.instances.get_mut(&id)
+                .filter(|i| i.status != InstanceStatus::from(status))
+                .map(|i| i.update_status(InstanceStatus::from(status))),
+            ResourceKind::Volume => state.volumes.get_mut(&id)
+                .filter(|v| v.status != VolumeStatus::from(status))
+                .map(|v| v.update_status(VolumeStatus::from(status))),
+            ResourceKind::Network => state.networks.get_mut(&id)
+                .filter(|n| n.status != NetworkStatus::from(status))
+                .map(|n| n.update_status(NetworkStatus::from(status))),
+        };
+        changed += usize::from(updated.is_some());
+    }
+
+    changed
+}
+
+/// Map a Proxmox VM status onto an instance status
+pub fn proxmox_status(status: &str) -> InstanceStatus {
+    match status {
+        "running" => InstanceStatus::Running,
+        "stopped" => InstanceStatus::Stopped,
+        _ => InstanceStatus::Unknown,
+    }
+}
+
+/// Refresh service comparing stored records with live providers
+pub struct RefreshService {
+    store: Arc<dyn StateStore>,
+    provider_service: ProviderService,
+}
+
+impl RefreshService {
+    /// Create a new refresh service backed by the state store
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Ok(Self::with_store(provider_service, Arc::new(FileStateStore::open()?)))
+    }
+
+    /// Create a new refresh service backed by a specific state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Self {
+        Self { store, provider_service }
+    }
+
+    /// Read the resources a provider reports
+    pub async fn inventory(&self, provider_name: &str) -> Result<Inventory> {
+        let provider = self.provider_service.get_provider(provider_name)
+            .ok_or_else(|| anyhow!("Provider not found: {}", provider_name))?;
+
+        match provider.provider_type {
+            ProviderType::Proxmox => self.proxmox_inventory(provider_name).await,
+            ProviderType::VyOS => self.vyos_inventory(provider_name).await,
+        }
+    }
+
+    /// Read VMs and disk images from a Proxmox cluster
+    async fn proxmox_inventory(&self, provider_name: &str) -> Result<Inventory> {
+        let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+        client.login().await?;
+
+        let mut inventory = Inventory::default();
+        let vms = client.get_resources(Some("vm")).await?;
+        // Templates are never instances, import skips them too
+        for vm in vms.as_array().into_iter().flatten().filter(|vm| vm["type"] == "qemu" && vm["template"].as_u64() != Some(1)) {
+            let Some(vmid) = vm["vmid"].as_u64() else {
+                continue;
+            };
+            inventory.instances.insert(vmid.to_string(), LiveInstance {
+                name: vm["name"].as_str().unwrap_or_default().to_string(),
+                status: proxmox_status(vm["status"].as_str().unwrap_or_default()),
+            });
+        }
+
+        let storages = client.get_resources(Some("storage")).await?;
+        for storage in storages.as_array().into_iter().flatten() {
+            let (Some(node), Some(name)) = (storage["node"].as_str(), storage["storage"].as_str()) else {
+                continue;
+            };
+            if storage["status"].as_str().is_some_and(|s| s != "available") {
+                continue;
+            }
+            // Only VM disks, not ISOs, backups or container templates
+            let content = client.get_storage_content(node, name).await?;
+            inventory.volumes.extend(
+                content.as_array().into_iter().flatten()
+                    .filter(|item| item["content"] == "images")
+                    .filter_map(|item| {
+                        let owner = item["vmid"].as_u64().map(|vmid| vmid.to_string())
+                            .or_else(|| item["vmid"].as_str().map(str::to_string));
+                        item["volid"].as_str().map(|volid| (volid.to_string(), owner))
+                    }),
+            );
+        }
+
+        Ok(inventory)
+    }
+
+    /// Read containers, DHCP shared networks and volume directories from a VyOS router
+    async fn vyos_inventory(&self, provider_name: &str) -> Result<Inventory> {
+        let mut client = self.provider_service.get_vyos_client(provider_name)?;
+        let mut inventory = Inventory::default();
+
+        let containers = client.get_config_commands(&["container", "name"]).await?;
+        for command in &containers {
+            if let [_, _, name, rest @ ..] = command.path() {
+                let disabled = rest.first().is_some_and(|s| s == "disable");
+                let live = inventory.instances.entry(name.clone()).or_insert(LiveInstance {
+                    name: name.clone(),
+                    status: InstanceStatus::Running,
+                });
+                if disabled {
+                    live.status = InstanceStatus::Stopped;
+                }
+            }
+        }
+
+        let shared = client.get_config_commands(&["service", "dhcp-server", "shared-network-name"]).await?;
+        inventory.networks = Some(shared.iter()
+            .filter_map(|command| command.path().get(3).cloned())
+            .collect());
+
+        let listing = client.execute_ssh_command(&format!("ls -1 {} 2>/dev/null || true", VYOS_VOLUME_ROOT)).await?;
+        inventory.volumes = listing.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|dir| (format!("{}/{}", VYOS_VOLUME_ROOT, dir), None))
+            .collect();
+
+        Ok(inventory)
+    }
+
+    /// Compare every provider, or one, with the stored records
+    ///
+    /// Providers that cannot be read are reported and their records left
+    /// alone, so an unreachable provider never marks its resources missing.
+    pub async fn detect(&self, provider: Option<&str>) -> Result<RefreshReport> {
+        let state = self.store.load()?;
+        let mut names: Vec<&String> = self.provider_service.get_providers().keys().collect();
+        names.sort();
+        if let Some(provider) = provider {
+            if self.provider_service.get_provider(provider).is_none() {
+                return Err(anyhow!("Provider not found: {}", provider));
+            }
+            names.retain(|name| *name == provider);
+        }
+
+        let mut report = RefreshReport::default();
+        for name in names {
+            let inventory = match self.inventory(name).await {
+                Ok(inventory) => inventory,
+                Err(e) => {
+                    warn!("Failed to read provider '{}': {:#}", name, e);
+                    report.errors.push((name.clone(), format!("{:#}", e)));
+                    continue;
+                }
+            };
+
+            let instances: Vec<&Instance> = state.instances.values()
+                .filter(|i| self.provider_service.find_provider_name(i).is_ok_and(|n| n == *name))
+                .collect();
+            let volumes: Vec<&Volume> = state.volumes.values()
+                .filter(|v| v.tags.get(PROVIDER_TAG) == Some(name))
+                .collect();
+            let networks: Vec<&Network> = state.networks.values()
+                .filter(|n| self.provider_service.find_network_provider_name(n).is_ok_and(|p| p == *name))
+                .collect();
+
+            report.drifts.extend(detect_drift(name, &inventory, &instances, &volumes, &networks));
+        }
+
+        Ok(report)
+    }
+
+    /// Record the drift of a report in the state, see [`apply_drift`]
+    ///
+    /// With `fix`, the DHCP static mappings and DNS records of dropped
+    /// instances are removed from the routers too. The records are fixed
+    /// even when a router cannot be updated, which is only logged.
+    pub async fn apply(&self, report: &RefreshReport, fix: bool) -> Result<usize> {
+        let mut changed = 0;
+        let mut released = BTreeSet::new();
+        self.store.transaction(&mut |state| {
+            if fix {
+                released = released_networks(state, &report.drifts);
+            }
+            changed = apply_drift(state, &report.drifts, fix);
+            Ok(())
+        })?;
+        info!("Refresh updated {} records", changed);
+
+        if !released.is_empty() {
+            let mut networks = NetworkService::with_store(self.provider_service.clone(), self.store.clone())?;
+            // Networks dropped by the same fix have nothing left to sync
+            released.retain(|id| networks.get_network(id).is_some());
+            for id in &released {
+                if let Err(e) = networks.sync_network_dhcp(id).await {
+                    warn!("Failed to sync DHCP of network {}: {:#}", id, e);
+                }
+            }
+            if let Err(e) = networks.sync_dns().await {
+                warn!("Failed to sync DNS: {:#}", e);
+            }
+        }
+
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::instance::InstanceSize;
+    use crate::models::network::NetworkType;
+    use crate::models::volume::VolumeType;
+
+    fn proxmox_records() -> (Instance, Volume) {
+        let size = InstanceSize { cpu: 1, memory_gb: 1, disk_gb: 10 };
+        let mut instance = Instance::new("web".to_string(), ProviderType::Proxmox, "lab".to_string(), size);
+        instance.provider_id = "100".to_string();
+        instance.status = InstanceStatus::Running;
+        let mut volume = Volume::new("data".to_string(), ProviderType::Proxmox, "lab".to_string(), 20, VolumeType::Standard);
+        volume.provider_id = "local-lvm:vm-100-disk-1".to_string();
+        (instance, volume)
+    }
+
+    #[test]
+    fn fix_drops_records_flagged_by_an_earlier_refresh() {
+        let (instance, volume) = proxmox_records();
+        let mut state = State::default();
+        state.instances.insert(instance.id, instance);
+        state.volumes.insert(volume.id, volume);
+        let inventory = Inventory::default();
+
+        let detect = |state: &State| {
+            let instances: Vec<_> = state.instances.values().collect();
+            let volumes: Vec<_> = state.volumes.values().collect();
+            detect_drift("pve", &inventory, &instances, &volumes, &[])
+        };
+
+        let drifts = detect(&state);
+        assert_eq!(apply_drift(&mut state, &drifts, false), 2);
+        assert!(state.instances.values().all(|i| i.status == InstanceStatus::Missing));
+        assert!(state.volumes.values().all(|v| v.status == VolumeStatus::Missing));
+
+        let drifts = detect(&state);
+        assert_eq!(apply_drift(&mut state, &drifts, false), 0);
+        assert_eq!(apply_drift(&mut state, &drifts, true), 2);
+        assert!(state.instances.is_empty() && state.volumes.is_empty());
+    }
+
+    #[test]
+    fn fix_releases_addresses_and_volumes_of_dropped_instances() {
+        let (instance, mut volume) = proxmox_records();
+        volume.attach(instance.id, Some("scsi1".to_string()));
+        let mut network = Network::new("lan".to_string(), ProviderType::Proxmox, "lab".to_string(),
+            "10.0.0.0/24".to_string(), NetworkType::Bridged);
+        network.connect_instance(instance.id);
+        network.allocate_ip("10.0.0.10".parse().unwrap(), instance.id, None, Some("web".to_string()), false).unwrap();
+
+        let mut state = State::default();
+        state.instances.insert(instance.id, instance.clone());
+        state.volumes.insert(volume.id, volume.clone());
+        state.networks.insert(network.id, network.clone());
+
+        // The volume's disk is still there, only the instance went missing
+        let mut inventory = Inventory::default();
+        inventory.volumes.insert(volume.provider_id.clone(), None);
+        let drifts = detect_drift("pve", &inventory, &[&instance], &[&volume], &[]);
+        assert_eq!(released_networks(&state, &drifts), BTreeSet::from([network.id]));
+
+        assert_eq!(apply_drift(&mut state, &drifts, true), 1);
+        assert!(state.instances.is_empty());
+        let network = &state.networks[&network.id];
+        assert!(network.instances.is_empty() && network.ip_allocations.is_empty());
+        let volume = &state.volumes[&volume.id];
+        assert_eq!(volume.attached_to, None);
+        assert_eq!(volume.status, VolumeStatus::Available);
+    }
+
+    #[test]
+    fn disks_of_known_instances_are_not_unmanaged() {
+        let (instance, volume) = proxmox_records();
+        let mut inventory = Inventory::default();
+        inventory.instances.insert("100".to_string(), LiveInstance {
+            name: "web".to_string(),
+            status: InstanceStatus::Running,
+        });
+        inventory.volumes.insert("local-lvm:vm-100-disk-0".to_string(), Some("100".to_string()));
+        inventory.volumes.insert(volume.provider_id.clone(), Some("100".to_string()));
+        inventory.volumes.insert("local-lvm:vm-200-disk-0".to_string(), Some("200".to_string()));
+
+        let drifts = detect_drift("pve", &inventory, &[&instance], &[&volume], &[]);
+        let unmanaged: Vec<_> = drifts.iter().map(|d| d.provider_id.as_str()).collect();
+        assert_eq!(unmanaged, ["local-lvm:vm-200-disk-0"]);
+    }
+}
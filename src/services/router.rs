@@ -0,0 +1,132 @@
+use anyhow::{Result, Context, anyhow};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::api::vyos::VyOSClient;
+use crate::api::vyos_op::{
+    parse_arp, parse_bgp_summary, parse_conntrack_statistics, parse_interfaces, parse_ospf_neighbors, parse_routes,
+    ArpEntry, BgpNeighbor, ConntrackStats, InterfaceStatus, OspfNeighbor, Route,
+};
+use crate::models::provider::ProviderType;
+use crate::services::provider::ProviderService;
+use crate::services::site::OspfAdjacency;
+
+/// Operational state of a VyOS router
+///
+/// Every section is collected independently; sections that could not be read
+/// are left empty and the reason is recorded in `errors`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouterStatus {
+    /// Provider name of the router
+    pub router: String,
+    /// VRF the routing table was read from (default VRF when absent)
+    pub vrf: Option<String>,
+    /// Interfaces
+    pub interfaces: Vec<InterfaceStatus>,
+    /// IPv4 routing table
+    pub routes: Vec<Route>,
+    /// BGP neighbors
+    pub bgp_neighbors: Vec<BgpNeighbor>,
+    /// OSPF neighbors
+    pub ospf_neighbors: Vec<OspfNeighbor>,
+    /// Adjacencies expected by a site description, when one was checked
+    #[serde(default)]
+    pub ospf_adjacencies: Vec<OspfAdjacency>,
+    /// ARP table
+    pub arp: Vec<ArpEntry>,
+    /// Connection tracking counters per CPU
+    pub conntrack: Vec<ConntrackStats>,
+    /// Sections that failed, as `section: error`
+    pub errors: Vec<String>,
+}
+
+/// Router service for reading structured operational data from VyOS routers
+pub struct RouterService {
+    provider_service: ProviderService,
+}
+
+impl RouterService {
+    /// Create a new router service
+    pub fn new(provider_service: ProviderService) -> Self {
+        Self { provider_service }
+    }
+
+    /// Get the names of all VyOS routers
+    pub fn routers(&self) -> Vec<String> {
+        let mut routers: Vec<String> = self.provider_service.get_providers().iter()
+            .filter(|(_, p)| p.provider_type == ProviderType::VyOS)
+            .map(|(name, _)| name.clone())
+            .collect();
+        routers.sort();
+        routers
+    }
+
+    /// Collect the operational state of a router
+    pub async fn status(&self, router: &str, vrf: Option<&str>) -> Result<RouterStatus> {
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let mut status = RouterStatus {
+            router: router.to_string(),
+            vrf: vrf.map(str::to_string),
+            ..Default::default()
+        };
+
+        let route_path: Vec<&str> = match vrf {
+            Some(vrf) => vec!["ip", "route", "vrf", vrf],
+            None => vec!["ip", "route"],
+        };
+
+        match run_show(&mut client, &["interfaces"]).await {
+            Ok(output) => status.interfaces = parse_interfaces(&output),
+            Err(e) => status.errors.push(format!("interfaces: {}", e)),
+        }
+        match run_show(&mut client, &route_path).await {
+            Ok(output) => status.routes = parse_routes(&output),
+            Err(e) => status.errors.push(format!("routes: {}", e)),
+        }
+        match run_show(&mut client, &["ip", "bgp", "summary"]).await {
+            Ok(output) => status.bgp_neighbors = parse_bgp_summary(&output),
+            Err(e) => status.errors.push(format!("bgp: {}", e)),
+        }
+        match run_show(&mut client, &["ip", "ospf", "neighbor"]).await {
+            Ok(output) => status.ospf_neighbors = parse_ospf_neighbors(&output),
+            Err(e) => status.errors.push(format!("ospf: {}", e)),
+        }
+        match run_show(&mut client, &["arp"]).await {
+            Ok(output) => status.arp = parse_arp(&output),
+            Err(e) => status.errors.push(format!("arp: {}", e)),
+        }
+        match run_show(&mut client, &["conntrack", "statistics"]).await {
+            Ok(output) => status.conntrack = parse_conntrack_statistics(&output),
+            Err(e) => status.errors.push(format!("conntrack: {}", e)),
+        }
+
+        // A router that answers nothing at all is unreachable rather than degraded
+        if status.errors.len() == 6 {
+            return Err(anyhow!("Router '{}' is unreachable: {}", router, status.errors.join("; ")));
+        }
+
+        Ok(status)
+    }
+
+    /// Collect the operational state of every VyOS router
+    pub async fn status_all(&self) -> Vec<(String, Result<RouterStatus>)> {
+        let mut results = Vec::new();
+        for router in self.routers() {
+            let result = self.status(&router, None).await;
+            results.push((router, result));
+        }
+        results
+    }
+}
+
+/// Run an op-mode `show` command over the HTTP API, falling back to SSH
+async fn run_show(client: &mut VyOSClient, path: &[&str]) -> Result<String> {
+    match client.show(path).await {
+        Ok(output) => Ok(output),
+        Err(e) => {
+            debug!("API show {} failed, falling back to SSH: {}", path.join(" "), e);
+            client.execute_op_command(&format!("show {}", path.join(" "))).await
+                .context(format!("Failed to run 'show {}'", path.join(" ")))
+        }
+    }
+}
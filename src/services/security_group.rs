@@ -0,0 +1,599 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info, error};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use uuid::Uuid;
+
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::config::security_groups::SecurityGroups;
+use crate::models::instance::Instance;
+use crate::models::provider::ProviderType;
+use crate::models::security_group::{RuleDirection, RulePeer, SecurityGroup, SecurityGroupRule};
+use crate::services::provider::ProviderService;
+
+/// Prefix of the VyOS address groups and rulesets generated for security groups
+pub const VYOS_PREFIX: &str = "sg-";
+
+/// Comment prefix marking Proxmox firewall rules owned by bbctl
+pub const PROXMOX_COMMENT_PREFIX: &str = "bbctl-sg:";
+
+/// First forward filter rule used for security group jumps on VyOS
+const VYOS_JUMP_RULE_BASE: u32 = 5000;
+
+/// First forward filter rule used for the per-group drop rules on VyOS
+const VYOS_DROP_RULE_BASE: u32 = 6000;
+
+/// Last forward filter rule owned by security groups on VyOS
+const VYOS_RULE_MAX: u32 = 6999;
+
+/// Outcome of enforcing security groups on one target (VM or router)
+#[derive(Debug, Clone)]
+pub struct EnforcementResult {
+    /// Human readable target, e.g. `pve/qemu/101` or `router1`
+    pub target: String,
+    /// Error, if enforcement failed
+    pub error: Option<String>,
+}
+
+/// Check whether a VyOS config path is owned by security groups
+pub fn is_managed_vyos_path(path: &[String]) -> bool {
+    match path {
+        [firewall, group, kind, name, ..]
+            if firewall == "firewall" && group == "group" && kind == "address-group" => name.starts_with(VYOS_PREFIX),
+        [firewall, family, name, ruleset, ..]
+            if firewall == "firewall" && family == "ipv4" && name == "name" => ruleset.starts_with(VYOS_PREFIX),
+        [firewall, family, forward, filter, rule, number, ..]
+            if firewall == "firewall" && family == "ipv4" && forward == "forward" && filter == "filter" && rule == "rule" => {
+            number.parse::<u32>()
+                .map(|n| (VYOS_JUMP_RULE_BASE..=VYOS_RULE_MAX).contains(&n))
+                .unwrap_or(false)
+        },
+        _ => false,
+    }
+}
+
+/// Get the IP addresses of the instances a security group applies to
+pub fn member_ips(group: &SecurityGroup, instances: &[Instance]) -> Vec<String> {
+    let ips: BTreeSet<String> = instances.iter()
+        .filter(|i| group.applies_to(i))
+        .flat_map(|i| i.networks.iter().filter_map(|n| n.ip.clone()))
+        .collect();
+
+    ips.into_iter().collect()
+}
+
+/// Render the VyOS firewall configuration enforcing a set of security groups
+///
+/// VyOS rejects references to address-groups that do not exist, and an
+/// address-group only exists once it has an address. Groups without member
+/// addresses are therefore skipped, as are rules whose peer group has none.
+pub fn render_vyos_config(groups: &[&SecurityGroup], instances: &[Instance]) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+
+    let mut sorted: Vec<&SecurityGroup> = groups.to_vec();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let members: HashMap<&str, Vec<String>> = sorted.iter()
+        .map(|g| (g.name.as_str(), member_ips(g, instances)))
+        .filter(|(_, ips)| !ips.is_empty())
+        .collect();
+
+    for (idx, group) in sorted.iter().enumerate() {
+        let Some(ips) = members.get(group.name.as_str()) else {
+            continue;
+        };
+
+        let address_group = format!("{}{}", VYOS_PREFIX, group.name);
+        for ip in ips {
+            commands.push(ConfigCommand::set([
+                "firewall", "group", "address-group", address_group.as_str(), "address", ip.as_str(),
+            ]));
+        }
+
+        for direction in [RuleDirection::Ingress, RuleDirection::Egress] {
+            let ruleset = format!("{}{}-{}", VYOS_PREFIX, group.name, direction);
+            let base = ["firewall", "ipv4", "name", ruleset.as_str()];
+
+            // Unmatched traffic falls through to the drop rules in the forward filter
+            commands.push(ConfigCommand::set(base.iter().copied().chain(["default-action", "return"])));
+
+            for (rule_idx, rule) in group.rules(direction).iter().enumerate() {
+                if let RulePeer::Group(peer) = &rule.peer {
+                    if !members.contains_key(peer.as_str()) {
+                        continue;
+                    }
+                }
+                let number = ((rule_idx as u32) + 1) * 10;
+                commands.extend(render_vyos_rule(&base, number, direction, rule));
+            }
+
+            // Jump into the ruleset for traffic to (ingress) or from (egress) members
+            let jump = (VYOS_JUMP_RULE_BASE + (idx as u32) * 2 + direction_offset(direction)).to_string();
+            let side = match direction {
+                RuleDirection::Ingress => "destination",
+                RuleDirection::Egress => "source",
+            };
+            let filter = ["firewall", "ipv4", "forward", "filter", "rule", jump.as_str()];
+            commands.push(ConfigCommand::set(filter.iter().copied().chain(["action", "jump"])));
+            commands.push(ConfigCommand::set(filter.iter().copied().chain(["jump-target", ruleset.as_str()])));
+            commands.push(ConfigCommand::set(
+                filter.iter().copied().chain([side, "group", "address-group", address_group.as_str()]),
+            ));
+        }
+
+        // Whatever the rulesets did not accept is dropped; egress is only
+        // restricted once the group has egress rules
+        let drops: &[(RuleDirection, &str)] = &[
+            (RuleDirection::Ingress, "destination"),
+            (RuleDirection::Egress, "source"),
+        ];
+        for (direction, side) in drops {
+            if *direction == RuleDirection::Egress && group.egress.is_empty() {
+                continue;
+            }
+
+            let number = (VYOS_DROP_RULE_BASE + (idx as u32) * 2 + direction_offset(*direction)).to_string();
+            let filter = ["firewall", "ipv4", "forward", "filter", "rule", number.as_str()];
+            commands.push(ConfigCommand::set(filter.iter().copied().chain(["action", "drop"])));
+            commands.push(ConfigCommand::set(
+                filter.iter().copied().chain([*side, "group", "address-group", address_group.as_str()]),
+            ));
+        }
+    }
+
+    commands
+}
+
+fn direction_offset(direction: RuleDirection) -> u32 {
+    match direction {
+        RuleDirection::Ingress => 0,
+        RuleDirection::Egress => 1,
+    }
+}
+
+fn render_vyos_rule(base: &[&str], number: u32, direction: RuleDirection, rule: &SecurityGroupRule) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+    let number = number.to_string();
+    let prefix: Vec<&str> = base.iter().copied().chain(["rule", number.as_str()]).collect();
+
+    commands.push(ConfigCommand::set(prefix.iter().copied().chain(["action", "accept"])));
+
+    if rule.protocol != "all" {
+        commands.push(ConfigCommand::set(prefix.iter().copied().chain(["protocol", rule.protocol.as_str()])));
+    }
+
+    if let Some(ports) = rule.port_range("-") {
+        commands.push(ConfigCommand::set(prefix.iter().copied().chain(["destination", "port", ports.as_str()])));
+    }
+
+    // The peer is the source of ingress traffic and the destination of egress traffic
+    let side = match direction {
+        RuleDirection::Ingress => "source",
+        RuleDirection::Egress => "destination",
+    };
+    match &rule.peer {
+        RulePeer::Cidr(cidr) => {
+            commands.push(ConfigCommand::set(prefix.iter().copied().chain([side, "address", cidr.as_str()])));
+        },
+        RulePeer::Group(peer) => {
+            let address_group = format!("{}{}", VYOS_PREFIX, peer);
+            commands.push(ConfigCommand::set(
+                prefix.iter().copied().chain([side, "group", "address-group", address_group.as_str()]),
+            ));
+        },
+    }
+
+    if let Some(description) = &rule.description {
+        commands.push(ConfigCommand::set(prefix.iter().copied().chain(["description", description.as_str()])));
+    }
+
+    commands
+}
+
+/// Security group service for managing and enforcing instance firewall rules
+pub struct SecurityGroupService {
+    groups: SecurityGroups,
+    provider_service: ProviderService,
+}
+
+impl SecurityGroupService {
+    /// Create a new security group service
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        let groups = SecurityGroups::load()?;
+
+        Ok(Self {
+            groups,
+            provider_service,
+        })
+    }
+
+    /// List all security groups
+    pub fn list_groups(&self) -> Vec<&SecurityGroup> {
+        let mut groups: Vec<&SecurityGroup> = self.groups.get_all_groups().values().collect();
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+        groups
+    }
+
+    /// Get a security group by name
+    pub fn get_group(&self, name: &str) -> Option<&SecurityGroup> {
+        self.groups.get_group(name)
+    }
+
+    /// Create a new security group
+    pub fn create_group(&mut self, name: &str, description: Option<String>) -> Result<Uuid> {
+        let group = SecurityGroup::new(name.to_string(), description);
+        let id = group.id;
+
+        self.groups.add_group(group)?;
+        self.groups.save()?;
+
+        info!("Created security group: {}", name);
+        Ok(id)
+    }
+
+    /// Delete a security group
+    pub fn delete_group(&mut self, name: &str) -> Result<SecurityGroup> {
+        // Refuse to delete groups other groups still reference as a peer
+        let referenced_by: Vec<&str> = self.groups.get_all_groups().values()
+            .filter(|g| g.name != name)
+            .filter(|g| {
+                g.ingress.iter().chain(g.egress.iter())
+                    .any(|r| r.peer == RulePeer::Group(name.to_string()))
+            })
+            .map(|g| g.name.as_str())
+            .collect();
+
+        if !referenced_by.is_empty() {
+            return Err(anyhow!(
+                "Security group '{}' is referenced by: {}", name, referenced_by.join(", ")
+            ));
+        }
+
+        let group = self.groups.remove_group(name)?;
+        self.groups.save()?;
+
+        info!("Deleted security group: {}", name);
+        Ok(group)
+    }
+
+    /// Add a rule to a security group
+    pub fn add_rule(&mut self, name: &str, direction: RuleDirection, rule: SecurityGroupRule) -> Result<()> {
+        if let RulePeer::Group(peer) = &rule.peer {
+            if self.groups.get_group(peer).is_none() {
+                return Err(anyhow!("Peer security group '{}' does not exist", peer));
+            }
+        }
+
+        let group = self.groups.get_group_mut(name)
+            .ok_or_else(|| anyhow!("Security group not found: {}", name))?;
+        group.add_rule(direction, rule);
+        self.groups.save()?;
+
+        info!("Added {} rule to security group: {}", direction, name);
+        Ok(())
+    }
+
+    /// Remove a rule from a security group by position
+    pub fn remove_rule(&mut self, name: &str, direction: RuleDirection, index: usize) -> Result<SecurityGroupRule> {
+        let group = self.groups.get_group_mut(name)
+            .ok_or_else(|| anyhow!("Security group not found: {}", name))?;
+        let rule = group.remove_rule(direction, index).map_err(|e| anyhow!(e))?;
+        self.groups.save()?;
+
+        info!("Removed {} rule {} from security group: {}", direction, index, name);
+        Ok(rule)
+    }
+
+    /// Replace the description and all rules of a security group
+    pub fn replace_rules(
+        &mut self,
+        name: &str,
+        description: Option<String>,
+        ingress: Vec<SecurityGroupRule>,
+        egress: Vec<SecurityGroupRule>,
+    ) -> Result<()> {
+        for rule in ingress.iter().chain(egress.iter()) {
+            if let RulePeer::Group(peer) = &rule.peer {
+                if peer != name && self.groups.get_group(peer).is_none() {
+                    return Err(anyhow!("Peer security group '{}' does not exist", peer));
+                }
+            }
+        }
+
+        let group = self.groups.get_group_mut(name)
+            .ok_or_else(|| anyhow!("Security group not found: {}", name))?;
+        group.description = description;
+        group.ingress = ingress;
+        group.egress = egress;
+        group.updated_at = chrono::Utc::now();
+        self.groups.save()?;
+
+        info!("Replaced the rules of security group: {}", name);
+        Ok(())
+    }
+
+    /// Set a tag on a security group
+    pub fn set_tag(&mut self, name: &str, key: &str, value: &str) -> Result<()> {
+        let group = self.groups.get_group_mut(name)
+            .ok_or_else(|| anyhow!("Security group not found: {}", name))?;
+        group.add_tag(key.to_string(), value.to_string());
+        self.groups.save()
+    }
+
+    /// Attach an instance to a security group
+    pub fn attach_instance(&mut self, name: &str, instance_id: Uuid) -> Result<()> {
+        let group = self.groups.get_group_mut(name)
+            .ok_or_else(|| anyhow!("Security group not found: {}", name))?;
+
+        if !group.attach_instance(instance_id) {
+            return Err(anyhow!("Instance {} is already attached to '{}'", instance_id, name));
+        }
+        self.groups.save()?;
+
+        info!("Attached instance {} to security group: {}", instance_id, name);
+        Ok(())
+    }
+
+    /// Detach an instance from a security group
+    pub fn detach_instance(&mut self, name: &str, instance_id: &Uuid) -> Result<()> {
+        let group = self.groups.get_group_mut(name)
+            .ok_or_else(|| anyhow!("Security group not found: {}", name))?;
+
+        if !group.detach_instance(instance_id) {
+            return Err(anyhow!("Instance {} is not attached to '{}'", instance_id, name));
+        }
+        self.groups.save()?;
+
+        info!("Detached instance {} from security group: {}", instance_id, name);
+        Ok(())
+    }
+
+    /// Get the security groups that apply to an instance
+    pub fn groups_for_instance(&self, instance: &Instance) -> Vec<&SecurityGroup> {
+        self.list_groups().into_iter()
+            .filter(|g| g.applies_to(instance))
+            .collect()
+    }
+
+    /// Re-enforce a group on every member instance
+    ///
+    /// `previous_members` lists instances that may have just left the group
+    /// (e.g. after a detach) so their rules are cleaned up as well.
+    pub async fn sync_group(&self, name: &str, instances: &[Instance], previous_members: &[Uuid]) -> Result<Vec<EnforcementResult>> {
+        let members: Vec<&Instance> = match self.groups.get_group(name) {
+            Some(group) => instances.iter()
+                .filter(|i| group.applies_to(i) || previous_members.contains(&i.id))
+                .collect(),
+            None => instances.iter()
+                .filter(|i| previous_members.contains(&i.id))
+                .collect(),
+        };
+
+        debug!("Syncing security group '{}' to {} instances", name, members.len());
+        self.enforce(&members, instances).await
+    }
+
+    /// Enforce security groups on the given instances
+    ///
+    /// Proxmox VMs get per-VM firewall rules; instances living on a VyOS
+    /// router are covered by rulesets on that router.
+    pub async fn enforce(&self, targets: &[&Instance], instances: &[Instance]) -> Result<Vec<EnforcementResult>> {
+        let mut results = Vec::new();
+        let mut routers = BTreeSet::new();
+
+        for instance in targets {
+            let provider_name = match self.provider_service.find_provider_name(instance) {
+                Ok(name) => name,
+                Err(e) => {
+                    results.push(EnforcementResult {
+                        target: instance.name.clone(),
+                        error: Some(e.to_string()),
+                    });
+                    continue;
+                }
+            };
+
+            match instance.provider {
+                ProviderType::Proxmox => {
+                    let node = self.provider_service.proxmox_node(&provider_name, instance);
+                    let target = format!("{}/{}/qemu/{}", provider_name, node, instance.provider_id);
+
+                    let error = match self.enforce_proxmox(&provider_name, &node, instance, instances).await {
+                        Ok(_) => None,
+                        Err(e) => {
+                            error!("Failed to enforce security groups on {}: {}", target, e);
+                            Some(e.to_string())
+                        }
+                    };
+                    results.push(EnforcementResult { target, error });
+                },
+                ProviderType::VyOS => {
+                    routers.insert(provider_name);
+                },
+            }
+        }
+
+        for router in routers {
+            let error = match self.enforce_vyos(&router, instances).await {
+                Ok(_) => None,
+                Err(e) => {
+                    error!("Failed to enforce security groups on router '{}': {}", router, e);
+                    Some(e.to_string())
+                }
+            };
+            results.push(EnforcementResult { target: router, error });
+        }
+
+        Ok(results)
+    }
+
+    /// Replace the bbctl-owned firewall rules of a Proxmox VM
+    async fn enforce_proxmox(&self, provider_name: &str, node: &str, instance: &Instance, instances: &[Instance]) -> Result<()> {
+        let vmid = instance.provider_id.parse::<u64>()
+            .context("Invalid VMID in provider_id")?;
+        let groups = self.groups_for_instance(instance);
+
+        let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+        client.login().await?;
+
+        // Remove the rules we created previously, highest position first
+        let existing = client.get_vm_firewall_rules(node, vmid).await?;
+        let mut owned: Vec<u64> = existing.as_array()
+            .map(|rules| {
+                rules.iter()
+                    .filter(|r| {
+                        r["comment"].as_str()
+                            .map(|c| c.starts_with(PROXMOX_COMMENT_PREFIX))
+                            .unwrap_or(false)
+                    })
+                    .filter_map(|r| r["pos"].as_u64())
+                    .collect()
+            })
+            .unwrap_or_default();
+        owned.sort_unstable_by(|a, b| b.cmp(a));
+
+        for pos in owned {
+            client.delete_vm_firewall_rule(node, vmid, pos).await?;
+        }
+
+        let mut restrict_egress = false;
+        for group in &groups {
+            for direction in [RuleDirection::Ingress, RuleDirection::Egress] {
+                for rule in group.rules(direction) {
+                    let peer = match &rule.peer {
+                        RulePeer::Cidr(cidr) => cidr.clone(),
+                        RulePeer::Group(peer) => {
+                            let ips = self.groups.get_group(peer)
+                                .map(|g| member_ips(g, instances))
+                                .unwrap_or_default();
+                            if ips.is_empty() {
+                                debug!("Peer group '{}' has no addresses, skipping rule", peer);
+                                continue;
+                            }
+                            ips.join(",")
+                        },
+                    };
+
+                    let mut params = json!({
+                        "type": if direction == RuleDirection::Ingress { "in" } else { "out" },
+                        "action": "ACCEPT",
+                        "enable": 1,
+                        "comment": format!("{}{}", PROXMOX_COMMENT_PREFIX, group.name),
+                    });
+                    let peer_field = if direction == RuleDirection::Ingress { "source" } else { "dest" };
+                    params[peer_field] = json!(peer);
+                    if rule.protocol != "all" {
+                        params["proto"] = json!(rule.protocol);
+                    }
+                    if let Some(ports) = rule.port_range(":") {
+                        params["dport"] = json!(ports);
+                    }
+
+                    client.add_vm_firewall_rule(node, vmid, params).await?;
+                }
+
+                if direction == RuleDirection::Egress && !group.egress.is_empty() {
+                    restrict_egress = true;
+                }
+            }
+        }
+
+        // Default-deny inbound once any group applies; outbound only with egress rules
+        let options = json!({
+            "enable": 1,
+            "policy_in": if groups.is_empty() { "ACCEPT" } else { "DROP" },
+            "policy_out": if restrict_egress { "DROP" } else { "ACCEPT" },
+        });
+        client.set_vm_firewall_options(node, vmid, options).await?;
+
+        info!("Enforced {} security groups on VM {}", groups.len(), vmid);
+        Ok(())
+    }
+
+    /// Bring the security group rulesets on a VyOS router up to date
+    async fn enforce_vyos(&self, router: &str, instances: &[Instance]) -> Result<()> {
+        // Every group with a member on this router is rendered
+        let groups: Vec<&SecurityGroup> = self.list_groups().into_iter()
+            .filter(|g| {
+                instances.iter().any(|i| {
+                    i.provider == ProviderType::VyOS
+                        && g.applies_to(i)
+                        && self.provider_service.find_provider_name(i).map(|n| n == router).unwrap_or(false)
+                })
+            })
+            .collect();
+
+        let desired = render_vyos_config(&groups, instances);
+
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["firewall"]).await?
+            .into_iter()
+            .filter(|c| is_managed_vyos_path(c.path()))
+            .collect();
+
+        let diff = ConfigDiff::between(&current, &desired);
+        if diff.is_empty() {
+            debug!("Security groups on router '{}' are up to date", router);
+            return Ok(());
+        }
+
+        client.configure(&diff.commands()).await?;
+        client.save().await?;
+
+        info!("Applied {} security group changes to router '{}'", diff.commands().len(), router);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::instance::InstanceSize;
+
+    fn instance(ip: Option<&str>) -> Instance {
+        let size = InstanceSize { cpu: 1, memory_gb: 1, disk_gb: 10 };
+        let mut instance = Instance::new("web".to_string(), ProviderType::VyOS, "nyc".to_string(), size);
+        instance.add_network(Uuid::new_v4().to_string(), ip.map(str::to_string), None, None);
+        instance
+    }
+
+    fn rule(peer: RulePeer) -> SecurityGroupRule {
+        SecurityGroupRule { protocol: "tcp".to_string(), port_from: Some(22), port_to: Some(22), peer, description: None }
+    }
+
+    fn render(groups: &[&SecurityGroup], instances: &[Instance]) -> Vec<String> {
+        render_vyos_config(groups, instances).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn groups_without_addresses_are_not_referenced() {
+        let unaddressed = instance(None);
+        let mut web = SecurityGroup::new("web".to_string(), None);
+        web.attach_instance(unaddressed.id);
+        web.add_rule(RuleDirection::Ingress, rule(RulePeer::Cidr("0.0.0.0/0".to_string())));
+
+        assert!(render(&[&web], &[unaddressed]).is_empty());
+    }
+
+    #[test]
+    fn rules_from_empty_peer_groups_are_skipped() {
+        let member = instance(Some("10.0.0.5"));
+        let mut web = SecurityGroup::new("web".to_string(), None);
+        web.attach_instance(member.id);
+        web.add_rule(RuleDirection::Ingress, rule(RulePeer::Group("admin".to_string())));
+        web.add_rule(RuleDirection::Ingress, rule(RulePeer::Cidr("192.0.2.0/24".to_string())));
+        let admin = SecurityGroup::new("admin".to_string(), None);
+
+        let commands = render(&[&web, &admin], &[member]);
+        assert!(commands.contains(&"set firewall group address-group sg-web address 10.0.0.5".to_string()));
+        assert!(commands.iter().all(|c| !c.contains("sg-admin")));
+        assert!(commands.contains(&"set firewall ipv4 name sg-web-ingress rule 20 source address 192.0.2.0/24".to_string()));
+    }
+
+    #[test]
+    fn directions_reject_typos() {
+        assert_eq!("Egress".parse::<RuleDirection>(), Ok(RuleDirection::Egress));
+        assert_eq!("in".parse::<RuleDirection>(), Ok(RuleDirection::Ingress));
+        assert!("egres".parse::<RuleDirection>().is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::api::vyos_op::OspfNeighbor;
+use crate::models::site::{OspfAuthentication, Site, SiteRouter, BACKBONE_AREA};
+use crate::services::provider::ProviderService;
+
+/// Longest password OSPF simple authentication carries
+const MAX_PLAINTEXT_PASSWORD: usize = 8;
+
+/// Longest key OSPF MD5 authentication carries
+const MAX_MD5_KEY: usize = 16;
+
+/// Expected OSPF adjacency of a router and its observed state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OspfAdjacency {
+    /// Router ID of the expected neighbor
+    pub neighbor_id: Ipv4Addr,
+    /// Local interface of the link
+    pub interface: String,
+    /// Adjacency state reported by the router, if the neighbor was seen
+    pub state: Option<String>,
+}
+
+impl OspfAdjacency {
+    /// Check whether the adjacency is fully formed
+    pub fn is_healthy(&self) -> bool {
+        self.state.as_deref().is_some_and(|s| s.starts_with("Full"))
+    }
+}
+
+/// Load a site description from a TOML file
+pub fn load_site(path: &Path) -> Result<Site> {
+    let content = fs::read_to_string(path)
+        .context(format!("Failed to read site file: {}", path.display()))?;
+
+    let site: Site = toml::from_str(&content)
+        .context("Failed to parse site TOML")?;
+
+    validate_site(&site)?;
+    Ok(site)
+}
+
+/// Check that an area ID is a number or dotted quad
+fn is_valid_area_id(id: &str) -> bool {
+    id.parse::<u32>().is_ok() || id.parse::<Ipv4Addr>().is_ok()
+}
+
+/// Validate a site description
+pub fn validate_site(site: &Site) -> Result<()> {
+    if site.routers.is_empty() {
+        return Err(anyhow!("Site '{}' has no routers", site.name));
+    }
+
+    let mut areas = HashSet::new();
+    for area in &site.areas {
+        if !is_valid_area_id(&area.id) {
+            return Err(anyhow!("Invalid OSPF area ID '{}'", area.id));
+        }
+        if !areas.insert(area.id.as_str()) {
+            return Err(anyhow!("Area {} is defined more than once", area.id));
+        }
+        match &area.authentication {
+            Some(OspfAuthentication::Plaintext { password }) if password.len() > MAX_PLAINTEXT_PASSWORD => {
+                return Err(anyhow!("Password of area {} is longer than {} characters", area.id, MAX_PLAINTEXT_PASSWORD));
+            },
+            Some(OspfAuthentication::Md5 { key_id, key }) => {
+                if *key_id == 0 {
+                    return Err(anyhow!("MD5 key ID of area {} must be between 1 and 255", area.id));
+                }
+                if key.len() > MAX_MD5_KEY {
+                    return Err(anyhow!("MD5 key of area {} is longer than {} characters", area.id, MAX_MD5_KEY));
+                }
+            },
+            _ => {},
+        }
+    }
+
+    let check_area = |id: &str| -> Result<()> {
+        if id == BACKBONE_AREA || areas.contains(id) {
+            Ok(())
+        } else if is_valid_area_id(id) {
+            Err(anyhow!("Area {} is not defined in the site", id))
+        } else {
+            Err(anyhow!("Invalid OSPF area ID '{}'", id))
+        }
+    };
+
+    let mut providers = HashSet::new();
+    let mut router_ids = HashSet::new();
+    let mut interfaces = HashSet::new();
+    for router in &site.routers {
+        if !providers.insert(router.provider.as_str()) {
+            return Err(anyhow!("Router '{}' is listed more than once", router.provider));
+        }
+        if !router_ids.insert(router.router_id) {
+            return Err(anyhow!("Router ID {} is used by more than one router", router.router_id));
+        }
+        check_area(&router.area)?;
+
+        for interface in std::iter::once(&router.loopback_interface).chain(&router.passive_interfaces) {
+            if !interfaces.insert((router.provider.as_str(), interface.as_str())) {
+                return Err(anyhow!("Interface {} of '{}' is used more than once", interface, router.provider));
+            }
+        }
+    }
+
+    for link in &site.links {
+        let [a, b] = &link.endpoints;
+        if a.router == b.router {
+            return Err(anyhow!("Link on '{}' connects the router to itself", a.router));
+        }
+        check_area(&link.area)?;
+
+        for endpoint in [a, b] {
+            if !providers.contains(endpoint.router.as_str()) {
+                return Err(anyhow!("Link references unknown router '{}'", endpoint.router));
+            }
+            if !interfaces.insert((endpoint.router.as_str(), endpoint.interface.as_str())) {
+                return Err(anyhow!("Interface {} of '{}' is used more than once", endpoint.interface, endpoint.router));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if a configuration path is generated from the site description
+///
+/// bbctl owns the whole `protocols ospf` subtree of site routers.
+pub fn is_managed_ospf_path(path: &[String]) -> bool {
+    matches!(path, [protocols, ospf, ..] if protocols == "protocols" && ospf == "ospf")
+}
+
+/// Render the authentication commands of an interface in an area
+fn render_interface_authentication(site: &Site, interface: &str, area: &str) -> Vec<ConfigCommand> {
+    let base = ["protocols", "ospf", "interface", interface, "authentication"];
+
+    match site.get_area(area).and_then(|a| a.authentication.as_ref()) {
+        Some(OspfAuthentication::Plaintext { password }) => vec![
+            ConfigCommand::set(base.iter().copied().chain(["plaintext-password", password.as_str()])),
+        ],
+        Some(OspfAuthentication::Md5 { key_id, key }) => {
+            let key_id = key_id.to_string();
+            vec![ConfigCommand::set(
+                base.iter().copied().chain(["md5", "key-id", key_id.as_str(), "md5-key", key.as_str()]),
+            )]
+        },
+        None => Vec::new(),
+    }
+}
+
+/// Render the loopback address carrying the router ID
+fn render_loopback(router: &SiteRouter) -> ConfigCommand {
+    ConfigCommand::set([
+        "interfaces", "dummy", &router.loopback_interface,
+        "address", &format!("{}/32", router.router_id),
+    ])
+}
+
+/// Render the OSPF configuration for one router of the site
+pub fn render_ospf_config(site: &Site, router: &SiteRouter) -> Vec<ConfigCommand> {
+    let mut commands = Vec::new();
+    let router_id = router.router_id.to_string();
+
+    commands.push(ConfigCommand::set(["protocols", "ospf", "parameters", "router-id", &router_id]));
+
+    // Area-wide authentication type, keys are set per interface
+    for area in &site.areas {
+        let kind = match &area.authentication {
+            Some(OspfAuthentication::Plaintext { .. }) => "plaintext-password",
+            Some(OspfAuthentication::Md5 { .. }) => "md5",
+            None => continue,
+        };
+        commands.push(ConfigCommand::set(["protocols", "ospf", "area", &area.id, "authentication", kind]));
+    }
+
+    // Loopback and stub interfaces are advertised but never form adjacencies
+    for interface in std::iter::once(&router.loopback_interface).chain(&router.passive_interfaces) {
+        commands.push(ConfigCommand::set(["protocols", "ospf", "interface", interface, "area", &router.area]));
+        commands.push(ConfigCommand::set(["protocols", "ospf", "interface", interface, "passive"]));
+    }
+
+    for link in &site.links {
+        let Some((local, _)) = link.endpoints_for(&router.provider) else {
+            continue;
+        };
+        let interface = local.interface.as_str();
+
+        commands.push(ConfigCommand::set(["protocols", "ospf", "interface", interface, "area", &link.area]));
+        if let Some(cost) = link.cost {
+            commands.push(ConfigCommand::set(["protocols", "ospf", "interface", interface, "cost", &cost.to_string()]));
+        }
+        if link.point_to_point {
+            commands.push(ConfigCommand::set(["protocols", "ospf", "interface", interface, "network", "point-to-point"]));
+        }
+        commands.extend(render_interface_authentication(site, interface, &link.area));
+    }
+
+    commands
+}
+
+/// Compare the adjacencies a router should have with the neighbors it reports
+pub fn adjacency_health(site: &Site, provider: &str, neighbors: &[OspfNeighbor]) -> Vec<OspfAdjacency> {
+    site.expected_neighbors(provider).into_iter()
+        .map(|(neighbor_id, interface)| {
+            let state = neighbors.iter()
+                .find(|n| n.neighbor_id == neighbor_id.to_string())
+                .map(|n| n.state.clone());
+            OspfAdjacency { neighbor_id, interface, state }
+        })
+        .collect()
+}
+
+/// Site service for managing the OSPF underlay between VyOS routers
+pub struct SiteService {
+    provider_service: ProviderService,
+}
+
+impl SiteService {
+    /// Create a new site service
+    pub fn new(provider_service: ProviderService) -> Self {
+        Self { provider_service }
+    }
+
+    /// Compute the configuration changes the site description makes on a router
+    pub async fn plan(&self, site: &Site, provider_name: &str) -> Result<ConfigDiff> {
+        let router = site.get_router(provider_name)
+            .ok_or_else(|| anyhow!("Router '{}' is not part of site '{}'", provider_name, site.name))?;
+        let mut desired = render_ospf_config(site, router);
+        let loopback = render_loopback(router);
+
+        let mut client = self.provider_service.get_vyos_client(provider_name)?;
+        let mut current: Vec<ConfigCommand> = client.get_config_commands(&["protocols", "ospf"]).await
+            .context(format!("Failed to read OSPF configuration from '{}'", provider_name))?
+            .into_iter()
+            .filter(|c| is_managed_ospf_path(c.path()))
+            .collect();
+
+        // The loopback interface may carry other addresses, only ours is compared
+        let has_loopback = client.get_config_commands(&["interfaces", "dummy"]).await
+            .context(format!("Failed to read interfaces from '{}'", provider_name))?
+            .contains(&loopback);
+        if has_loopback {
+            current.push(loopback.clone());
+        }
+        desired.insert(0, loopback);
+
+        debug!("Router '{}' has {} managed OSPF paths", provider_name, current.len());
+        Ok(ConfigDiff::between(&current, &desired))
+    }
+
+    /// Apply a previously computed plan to a router
+    pub async fn apply(&self, provider_name: &str, diff: &ConfigDiff) -> Result<()> {
+        if diff.is_empty() {
+            info!("OSPF on '{}' is already up to date", provider_name);
+            return Ok(());
+        }
+
+        let mut client = self.provider_service.get_vyos_client(provider_name)?;
+        client.configure(&diff.commands()).await
+            .context(format!("Failed to configure OSPF on '{}'", provider_name))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", provider_name))?;
+
+        info!("Applied {} OSPF changes to '{}'", diff.commands().len(), provider_name);
+        Ok(())
+    }
+}
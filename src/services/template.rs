@@ -0,0 +1,205 @@
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::models::instance::PROVIDER_TAG;
+use crate::models::provider::ProviderType;
+use crate::models::template::{InstanceTemplate, TEMPLATE_TAG};
+use crate::models::volume::VolumeType;
+use crate::services::network::NetworkService;
+use crate::services::provider::ProviderService;
+use crate::services::volume::VolumeService;
+use crate::state::{FileStateStore, State, StateStore};
+
+/// Problems that would stop an instance being created from a template
+///
+/// Networks are looked up in the state on the template's provider type,
+/// when it has a provider.
+pub fn check_template(template: &InstanceTemplate, provider_service: &ProviderService, state: &State) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Err(e) = template.validate() {
+        problems.push(e.to_string());
+    }
+
+    let provider_type = match &template.provider {
+        Some(name) => match provider_service.get_provider(name) {
+            Some(config) => Some(config.provider_type),
+            None => {
+                problems.push(format!("Provider '{}' not found", name));
+                None
+            },
+        },
+        None => None,
+    };
+
+    if let (Some(region), Some(provider_type)) = (&template.region, provider_type) {
+        if let Some(config) = provider_service.get_regions().get(region) {
+            if config.provider != provider_type {
+                problems.push(format!("Region '{}' is a {} region", region, config.provider));
+            }
+        }
+    }
+    if provider_type == Some(ProviderType::VyOS) && template.image.is_none() {
+        problems.push("VyOS providers need an image".to_string());
+    }
+
+    for network in &template.networks {
+        let found = state.networks.values()
+            .any(|n| n.name == *network && provider_type.is_none_or(|t| n.provider == t));
+        if !found {
+            problems.push(format!("Network '{}' not found", network));
+        }
+    }
+
+    problems
+}
+
+/// Service creating the networks and volumes of template instances
+pub struct TemplateService {
+    store: Arc<dyn StateStore>,
+    provider_service: ProviderService,
+}
+
+impl TemplateService {
+    /// Create a new template service
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Self::with_store(provider_service, Arc::new(FileStateStore::open()?))
+    }
+
+    /// Create a template service on an existing state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Result<Self> {
+        Ok(Self { store, provider_service })
+    }
+
+    /// Connect a new instance to the template's networks and create its volumes
+    ///
+    /// Each network's NIC is picked by [`NetworkService::connect_instance`].
+    /// Volumes are named `<instance>-<volume>` and attached once created. When
+    /// a step fails, the networks connected and volumes created so far are
+    /// undone, leaving the instance as it was; the error says what could not
+    /// be undone.
+    pub async fn provision(&self, id: &Uuid, name: Option<&str>, template: &InstanceTemplate) -> Result<()> {
+        let mut done = Provisioned::default();
+        let Err(e) = self.provision_resources(id, name, template, &mut done).await else {
+            return Ok(());
+        };
+
+        warn!("Provisioning instance {} failed, rolling back: {:#}", id, e);
+        let failures = self.roll_back(id, name, &done).await;
+        if failures.is_empty() {
+            Err(e.context("Template resources were rolled back"))
+        } else {
+            Err(anyhow!("{:#}; rollback failed: {}", e, failures.join("; ")))
+        }
+    }
+
+    /// Create the resources of a template, recording each one made
+    async fn provision_resources(&self, id: &Uuid, name: Option<&str>, template: &InstanceTemplate, done: &mut Provisioned) -> Result<()> {
+        let state = self.store.load()?;
+        let instance = state.instances.get(id).cloned()
+            .ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+        let provider = instance.tags.get(PROVIDER_TAG).cloned()
+            .ok_or_else(|| anyhow!("Instance '{}' has no provider", instance.name))?;
+
+        if let Some(name) = name {
+            self.store.transaction(&mut |state| {
+                let instance = state.instances.get_mut(id).ok_or_else(|| anyhow!("Instance not found: {}", id))?;
+                instance.add_tag(TEMPLATE_TAG.to_string(), name.to_string());
+                Ok(())
+            })?;
+        }
+
+        let mut networks = NetworkService::with_store(self.provider_service.clone(), self.store.clone())?;
+        for network in &template.networks {
+            let network_id = state.networks.values()
+                .find(|n| n.name == *network && n.provider == instance.provider)
+                .map(|n| n.id)
+                .ok_or_else(|| anyhow!("Network not found: {}", network))?;
+            let result = networks.connect_instance(&network_id, id, None).await;
+            // The connection is recorded before the routers are synced, which may still fail
+            if networks.get_network(&network_id).is_some_and(|n| n.instances.contains(id)) {
+                done.networks.push(network_id);
+            }
+            result?;
+        }
+
+        let mut volumes = VolumeService::with_store(self.provider_service.clone(), self.store.clone())?;
+        for volume in &template.volumes {
+            let volume_name = InstanceTemplate::volume_name(&instance.name, volume);
+            let volume_id = volumes.create_volume(&volume_name, &provider, &instance.region, volume.size_gb, volume.storage.as_deref()).await?;
+            done.volumes.push((volume_id, false));
+            self.store.transaction(&mut |state| {
+                let record = state.volumes.get_mut(&volume_id).ok_or_else(|| anyhow!("Volume not found: {}", volume_id))?;
+                record.volume_type = volume.volume_type.as_deref().map(VolumeType::from).unwrap_or(VolumeType::Standard);
+                if let Some(name) = name {
+                    record.add_tag(TEMPLATE_TAG.to_string(), name.to_string());
+                }
+                Ok(())
+            })?;
+            volumes.attach_volume(&volume_id, id, volume.mount_path.as_deref()).await?;
+            if let Some(created) = done.volumes.last_mut() {
+                created.1 = true;
+            }
+            info!("Created volume '{}' for instance '{}'", volume_name, instance.name);
+        }
+
+        Ok(())
+    }
+
+    /// Undo a failed provisioning, returning what could not be undone
+    async fn roll_back(&self, id: &Uuid, name: Option<&str>, done: &Provisioned) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        match VolumeService::with_store(self.provider_service.clone(), self.store.clone()) {
+            Ok(mut volumes) => {
+                for (volume_id, attached) in done.volumes.iter().rev() {
+                    let result = async {
+                        if *attached {
+                            volumes.detach_volume(volume_id).await?;
+                        }
+                        volumes.delete_volume(volume_id).await
+                    }.await;
+                    if let Err(e) = result {
+                        failures.push(format!("volume {}: {:#}", volume_id, e));
+                    }
+                }
+            },
+            Err(e) => failures.push(format!("volumes: {:#}", e)),
+        }
+
+        match NetworkService::with_store(self.provider_service.clone(), self.store.clone()) {
+            Ok(mut networks) => {
+                for network_id in done.networks.iter().rev() {
+                    if let Err(e) = networks.disconnect_instance(network_id, id).await {
+                        failures.push(format!("network {}: {:#}", network_id, e));
+                    }
+                }
+            },
+            Err(e) => failures.push(format!("networks: {:#}", e)),
+        }
+
+        if name.is_some() {
+            let result = self.store.transaction(&mut |state| {
+                if let Some(instance) = state.instances.get_mut(id) {
+                    instance.remove_tag(TEMPLATE_TAG);
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                failures.push(format!("template tag: {:#}", e));
+            }
+        }
+
+        failures
+    }
+}
+
+/// Resources provisioned for an instance, undone when provisioning fails
+#[derive(Debug, Default)]
+struct Provisioned {
+    /// Networks connected
+    networks: Vec<Uuid>,
+    /// Volumes created, and whether they were attached
+    volumes: Vec<(Uuid, bool)>,
+}
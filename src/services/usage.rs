@@ -0,0 +1,473 @@
+use anyhow::{Result, Context, anyhow};
+use chrono::{DateTime, TimeZone, Utc};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+use crate::api::ipfix::{FlowRecord, IpfixDecoder};
+use crate::api::vyos::{ConfigCommand, ConfigDiff};
+use crate::config::get_config_dir;
+use crate::models::network::{Ipv4Cidr, Network};
+use crate::models::usage::TrafficSample;
+use crate::services::provider::ProviderService;
+
+/// Directory under the config directory holding the traffic time series
+pub const USAGE_DIR: &str = "usage";
+
+/// Width of a time-series bucket in seconds
+pub const BUCKET_SECONDS: i64 = 300;
+
+/// Seconds after which routers export active flows
+pub const FLOW_EXPIRY_INTERVAL: u32 = 60;
+
+/// Check whether a VyOS config path is owned by flow accounting
+pub fn is_managed_flow_accounting_path(path: &[String]) -> bool {
+    matches!(path, [system, flow, ..] if system == "system" && flow == "flow-accounting")
+}
+
+/// Render the flow-accounting configuration exporting IPFIX to a collector
+pub fn render_flow_accounting_config(collector: IpAddr, port: u16, interfaces: &[String]) -> Vec<ConfigCommand> {
+    let base = ["system", "flow-accounting"];
+    let collector = collector.to_string();
+    let port = port.to_string();
+    let expiry = FLOW_EXPIRY_INTERVAL.to_string();
+
+    let mut commands = Vec::new();
+    for interface in interfaces {
+        commands.push(ConfigCommand::set(base.iter().copied().chain(["interface", interface.as_str()])));
+    }
+    commands.push(ConfigCommand::set(base.iter().copied().chain(["netflow", "version", "10"])));
+    commands.push(ConfigCommand::set(
+        base.iter().copied().chain(["netflow", "server", collector.as_str(), "port", port.as_str()]),
+    ));
+    commands.push(ConfigCommand::set(
+        base.iter().copied().chain(["netflow", "timeout", "expiry-interval", expiry.as_str()]),
+    ));
+
+    commands
+}
+
+/// Start of the bucket a timestamp falls into
+fn bucket_start(seconds: i64) -> DateTime<Utc> {
+    let start = seconds - seconds.rem_euclid(BUCKET_SECONDS);
+    Utc.timestamp_opt(start, 0).single().unwrap_or_default()
+}
+
+/// Mapping of tenant networks, used to attribute flows to tenants
+#[derive(Debug, Clone, Default)]
+pub struct TenantMap {
+    entries: Vec<(String, Ipv4Cidr)>,
+}
+
+impl TenantMap {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a map from the CIDR blocks of networks
+    pub fn from_networks(networks: &[&Network]) -> Self {
+        let mut map = Self::new();
+        for network in networks {
+            match Ipv4Cidr::parse(&network.cidr) {
+                Ok(cidr) => map.add(network.tenant(), cidr),
+                Err(e) => warn!("Ignoring network '{}' with invalid CIDR: {}", network.name, e),
+            }
+        }
+        map
+    }
+
+    /// Add a tenant network
+    pub fn add(&mut self, tenant: &str, cidr: Ipv4Cidr) {
+        self.entries.push((tenant.to_string(), cidr));
+    }
+
+    /// Parse and add a tenant network from `tenant=cidr`
+    pub fn add_spec(&mut self, spec: &str) -> Result<()> {
+        let (tenant, cidr) = spec.split_once('=')
+            .ok_or_else(|| anyhow!("Tenant network must be <tenant>=<cidr>, got '{}'", spec))?;
+        let cidr = Ipv4Cidr::parse(cidr).map_err(|e| anyhow!("Invalid CIDR for tenant '{}': {}", tenant, e))?;
+
+        self.add(tenant, cidr);
+        Ok(())
+    }
+
+    /// Check whether the map has no networks
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of networks in the map
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Find the tenant owning an address, preferring the most specific network
+    pub fn tenant_of(&self, ip: &IpAddr) -> Option<&str> {
+        let IpAddr::V4(ip) = ip else {
+            return None;
+        };
+
+        self.entries.iter()
+            .filter(|(_, cidr)| cidr.contains(ip))
+            .max_by_key(|(_, cidr)| cidr.prefix_len)
+            .map(|(tenant, _)| tenant.as_str())
+    }
+}
+
+/// Per-bucket traffic counters that have not been written yet
+#[derive(Debug, Default)]
+pub struct UsageAggregator {
+    samples: HashMap<(DateTime<Utc>, String), TrafficSample>,
+}
+
+impl UsageAggregator {
+    /// Create an empty aggregator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Account a flow to the tenants at either end
+    ///
+    /// A source inside a tenant network is traffic the tenant sent, a
+    /// destination inside one is traffic it received. Returns whether the flow
+    /// matched any tenant.
+    pub fn record(&mut self, flow: &FlowRecord, tenants: &TenantMap) -> bool {
+        let bucket = bucket_start(i64::from(flow.end_seconds));
+        let source = flow.source.as_ref().and_then(|ip| tenants.tenant_of(ip));
+        let destination = flow.destination.as_ref().and_then(|ip| tenants.tenant_of(ip));
+
+        if let Some(tenant) = source {
+            let sample = self.sample(bucket, tenant);
+            sample.bytes_out += flow.octets;
+            sample.packets_out += flow.packets;
+            sample.flows += 1;
+        }
+        if let Some(tenant) = destination {
+            let sample = self.sample(bucket, tenant);
+            sample.bytes_in += flow.octets;
+            sample.packets_in += flow.packets;
+            if source != Some(tenant) {
+                sample.flows += 1;
+            }
+        }
+
+        source.is_some() || destination.is_some()
+    }
+
+    /// Check whether there is nothing to write
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Take the pending samples, ordered by bucket and tenant
+    pub fn drain(&mut self) -> Vec<TrafficSample> {
+        let mut samples: Vec<TrafficSample> = self.samples.drain().map(|(_, sample)| sample).collect();
+        samples.sort_by(|a, b| a.bucket.cmp(&b.bucket).then_with(|| a.tenant.cmp(&b.tenant)));
+        samples
+    }
+
+    fn sample(&mut self, bucket: DateTime<Utc>, tenant: &str) -> &mut TrafficSample {
+        self.samples.entry((bucket, tenant.to_string()))
+            .or_insert_with(|| TrafficSample::new(bucket, tenant))
+    }
+}
+
+/// Local time-series store of tenant traffic
+///
+/// Samples are appended as JSON lines to one file per day; a bucket may
+/// appear several times and is summed when queried.
+#[derive(Debug, Clone)]
+pub struct UsageStore {
+    dir: PathBuf,
+}
+
+impl UsageStore {
+    /// Open the store in the bbctl config directory
+    pub fn open() -> Result<Self> {
+        Ok(Self::with_dir(get_config_dir()?.join(USAGE_DIR)))
+    }
+
+    /// Open a store in a specific directory
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Directory holding the time series
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append samples to the store
+    pub fn append(&self, samples: &[TrafficSample]) -> Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .context(format!("Failed to create usage directory {}", self.dir.display()))?;
+
+        // Group by day so each file is opened once
+        let mut days: BTreeMap<String, Vec<&TrafficSample>> = BTreeMap::new();
+        for sample in samples {
+            days.entry(sample.bucket.format("%Y-%m-%d").to_string()).or_default().push(sample);
+        }
+
+        for (day, samples) in days {
+            let path = self.dir.join(format!("{}.jsonl", day));
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)
+                .context(format!("Failed to open {}", path.display()))?;
+
+            let mut lines = String::new();
+            for sample in samples {
+                lines.push_str(&serde_json::to_string(sample)?);
+                lines.push('\n');
+            }
+            file.write_all(lines.as_bytes())
+                .context(format!("Failed to write {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the samples since a point in time, merged per bucket and tenant
+    pub fn query(&self, tenant: Option<&str>, since: DateTime<Utc>) -> Result<Vec<TrafficSample>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let first_day = since.format("%Y-%m-%d").to_string();
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .context(format!("Failed to read usage directory {}", self.dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == "jsonl")
+                    && path.file_stem().and_then(|s| s.to_str()).is_some_and(|day| day >= first_day.as_str())
+            })
+            .collect();
+        files.sort();
+
+        let mut merged: BTreeMap<(DateTime<Utc>, String), TrafficSample> = BTreeMap::new();
+        for path in files {
+            let content = fs::read_to_string(&path)
+                .context(format!("Failed to read {}", path.display()))?;
+
+            for (idx, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let sample: TrafficSample = match serde_json::from_str(line) {
+                    Ok(sample) => sample,
+                    Err(e) => {
+                        warn!("Skipping invalid sample at {}:{}: {}", path.display(), idx + 1, e);
+                        continue;
+                    },
+                };
+
+                if sample.bucket < bucket_start(since.timestamp()) || tenant.is_some_and(|t| t != sample.tenant) {
+                    continue;
+                }
+                merged.entry((sample.bucket, sample.tenant.clone()))
+                    .or_insert_with(|| TrafficSample::new(sample.bucket, &sample.tenant))
+                    .add(&sample);
+            }
+        }
+
+        Ok(merged.into_values().collect())
+    }
+
+    /// Total traffic per tenant since a point in time
+    pub fn totals(&self, tenant: Option<&str>, since: DateTime<Utc>) -> Result<Vec<TrafficSample>> {
+        let mut totals: BTreeMap<String, TrafficSample> = BTreeMap::new();
+        for sample in self.query(tenant, since)? {
+            totals.entry(sample.tenant.clone())
+                .or_insert_with(|| TrafficSample::new(since, &sample.tenant))
+                .add(&sample);
+        }
+
+        Ok(totals.into_values().collect())
+    }
+}
+
+/// IPFIX collector aggregating flows into the usage store
+pub struct Collector {
+    socket: UdpSocket,
+    decoder: IpfixDecoder,
+    tenants: TenantMap,
+    aggregator: UsageAggregator,
+    store: UsageStore,
+    buffer: Vec<u8>,
+}
+
+impl Collector {
+    /// Bind a collector to a UDP address
+    pub async fn bind(listen: SocketAddr, tenants: TenantMap, store: UsageStore) -> Result<Self> {
+        let socket = UdpSocket::bind(listen).await
+            .context(format!("Failed to bind collector to {}", listen))?;
+
+        Ok(Self {
+            socket,
+            decoder: IpfixDecoder::new(),
+            tenants,
+            aggregator: UsageAggregator::new(),
+            store,
+            buffer: vec![0; 65535],
+        })
+    }
+
+    /// Address the collector is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Decode a message and account its flows, returning the matched flow count
+    pub fn handle_packet(&mut self, exporter: &str, message: &[u8]) -> Result<usize> {
+        let flows = self.decoder.decode(exporter, message)?;
+
+        let matched = flows.iter()
+            .filter(|flow| self.aggregator.record(flow, &self.tenants))
+            .count();
+        debug!("Accounted {} of {} flows from {}", matched, flows.len(), exporter);
+        Ok(matched)
+    }
+
+    /// Receive and handle one datagram
+    pub async fn receive(&mut self) -> Result<usize> {
+        let (len, exporter) = self.socket.recv_from(&mut self.buffer).await
+            .context("Failed to receive flow export")?;
+
+        let message = self.buffer[..len].to_vec();
+        self.handle_packet(&exporter.ip().to_string(), &message)
+    }
+
+    /// Write the pending samples to the store, returning how many were written
+    pub fn flush(&mut self) -> Result<usize> {
+        let samples = self.aggregator.drain();
+        self.store.append(&samples)?;
+        Ok(samples.len())
+    }
+
+    /// Collect flows until interrupted, flushing periodically
+    pub async fn run(&mut self, flush_interval: Duration) -> Result<()> {
+        let mut ticker = tokio::time::interval(flush_interval);
+        ticker.tick().await;
+
+        loop {
+            tokio::select! {
+                result = self.receive() => {
+                    if let Err(e) = result {
+                        warn!("Dropping flow export: {:#}", e);
+                    }
+                },
+                _ = ticker.tick() => {
+                    let written = self.flush()?;
+                    if written > 0 {
+                        info!("Wrote {} usage samples to {}", written, self.store.dir().display());
+                    }
+                },
+                _ = tokio::signal::ctrl_c() => {
+                    let written = self.flush()?;
+                    info!("Collector stopped, wrote {} usage samples", written);
+                    return Ok(());
+                },
+            }
+        }
+    }
+}
+
+/// Service configuring flow export on VyOS routers
+pub struct UsageService {
+    provider_service: ProviderService,
+}
+
+impl UsageService {
+    /// Create a new usage service
+    pub fn new(provider_service: ProviderService) -> Self {
+        Self { provider_service }
+    }
+
+    /// Configure a router to export flows on some interfaces to a collector
+    ///
+    /// Any other flow-accounting configuration on the router is replaced.
+    pub async fn configure_router(
+        &self,
+        router: &str,
+        collector: Ipv4Addr,
+        port: u16,
+        interfaces: &[String],
+    ) -> Result<usize> {
+        if interfaces.is_empty() {
+            return Err(anyhow!("At least one interface must be accounted"));
+        }
+        let desired = render_flow_accounting_config(IpAddr::V4(collector), port, interfaces);
+
+        let mut client = self.provider_service.get_vyos_client(router)?;
+        let current: Vec<ConfigCommand> = client.get_config_commands(&["system", "flow-accounting"]).await
+            .context(format!("Failed to read flow accounting from '{}'", router))?
+            .into_iter()
+            .filter(|c| is_managed_flow_accounting_path(c.path()))
+            .collect();
+
+        let diff = ConfigDiff::between(&current, &desired);
+        if diff.is_empty() {
+            info!("Flow accounting on '{}' is already up to date", router);
+            return Ok(0);
+        }
+
+        let commands = diff.commands();
+        client.configure(&commands).await
+            .context(format!("Failed to configure flow accounting on '{}'", router))?;
+        client.save().await
+            .context(format!("Failed to save configuration on '{}'", router))?;
+
+        info!("Applied {} flow accounting changes to '{}'", commands.len(), router);
+        Ok(commands.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ipfix::encode_message;
+
+    #[tokio::test]
+    async fn collector_accounts_loopback_exports_per_tenant() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = UsageStore::with_dir(dir.path());
+        let mut tenants = TenantMap::new();
+        tenants.add_spec("acme=10.1.0.0/24").unwrap();
+        tenants.add_spec("globex=10.2.0.0/24").unwrap();
+
+        let mut collector = Collector::bind("127.0.0.1:0".parse().unwrap(), tenants, store.clone()).await.unwrap();
+        let collector_addr = collector.local_addr().unwrap();
+        let exporter = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        let now = Utc::now();
+        let export_time = now.timestamp() as u32;
+        let (acme, globex, internet) = (Ipv4Addr::new(10, 1, 0, 5), Ipv4Addr::new(10, 2, 0, 7), Ipv4Addr::new(203, 0, 113, 9));
+        let messages = [
+            encode_message(1, export_time, 0, 256, &[(acme, internet, 1000, 10), (internet, globex, 4000, 4)]),
+            encode_message(1, export_time, 2, 256, &[(acme, globex, 500, 5), (internet, internet, 9000, 9)]),
+        ];
+        for (message, matched) in messages.iter().zip([2, 1]) {
+            exporter.send_to(message, collector_addr).unwrap();
+            assert_eq!(collector.receive().await.unwrap(), matched);
+        }
+        assert_eq!(collector.flush().unwrap(), 2);
+
+        let totals = store.totals(None, now - chrono::Duration::hours(1)).unwrap();
+        assert_eq!(totals.len(), 2);
+        let (acme, globex) = (&totals[0], &totals[1]);
+        assert_eq!(acme.tenant, "acme");
+        assert_eq!((acme.bytes_out, acme.packets_out, acme.bytes_in, acme.flows), (1500, 15, 0, 2));
+        assert_eq!(globex.tenant, "globex");
+        assert_eq!((globex.bytes_in, globex.packets_in, globex.bytes_out, globex.flows), (4500, 9, 0, 2));
+
+        let acme_only = store.totals(Some("acme"), now - chrono::Duration::hours(1)).unwrap();
+        assert_eq!(acme_only.len(), 1);
+    }
+}
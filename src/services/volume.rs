@@ -0,0 +1,387 @@
+use anyhow::{Result, Context, anyhow};
+use log::{debug, info};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::vyos::ConfigCommand;
+use crate::models::dns::sanitize_label;
+use crate::models::instance::{Instance, NODE_TAG, PROVIDER_TAG};
+use crate::models::provider::ProviderType;
+use crate::models::volume::{Volume, VolumeStatus, VolumeType};
+use crate::services::container::CONTAINER_PREFIX;
+use crate::services::provider::ProviderService;
+use crate::state::{FileStateStore, StateStore};
+
+/// Directory on VyOS routers holding volume directories
+pub const VYOS_VOLUME_ROOT: &str = "/config/bbctl/volumes";
+
+/// Tag recording the Proxmox storage a volume is allocated on
+pub const STORAGE_TAG: &str = "storage";
+
+/// Tag recording the Proxmox VM that owns a volume's disk
+pub const OWNER_TAG: &str = "owner";
+
+/// Default Proxmox storage for new volumes
+pub const DEFAULT_PROXMOX_STORAGE: &str = "local-lvm";
+
+/// Highest SCSI slot a volume is attached on
+const MAX_SCSI_SLOT: u8 = 30;
+
+/// Name of the container volume a bbctl volume is mounted as
+pub fn container_volume_name(volume: &Volume) -> String {
+    format!("{}{}", CONTAINER_PREFIX, sanitize_label(&volume.name))
+}
+
+/// Render the container configuration mounting a volume into a container
+pub fn render_volume_mount(container: &str, volume: &Volume, mount_path: &str) -> Vec<ConfigCommand> {
+    let name = container_volume_name(volume);
+    let base = ["container", "name", container, "volume", name.as_str()];
+
+    vec![
+        ConfigCommand::set(base.iter().copied().chain(["source", volume.provider_id.as_str()])),
+        ConfigCommand::set(base.iter().copied().chain(["destination", mount_path])),
+        ConfigCommand::set(base.iter().copied().chain(["mode", "rw"])),
+    ]
+}
+
+/// First SCSI slot not used in a Proxmox VM configuration
+fn free_scsi_slot(config: &serde_json::Value) -> Option<String> {
+    (1..=MAX_SCSI_SLOT)
+        .map(|n| format!("scsi{}", n))
+        .find(|slot| config.get(slot).is_none())
+}
+
+/// Proxmox volume ID in a disk option such as `local-lvm:vm-100-disk-1,size=10G`
+pub fn disk_volume_id(option: &str) -> &str {
+    option.split(',').next().unwrap_or(option)
+}
+
+/// Storage for volume data, persisted in the state store
+#[derive(Debug)]
+pub struct VolumeStorage {
+    store: Arc<dyn StateStore>,
+    volumes: BTreeMap<Uuid, Volume>,
+    instances: BTreeMap<Uuid, Instance>,
+}
+
+impl VolumeStorage {
+    /// Create a volume storage backed by a state store
+    pub fn new(store: Arc<dyn StateStore>) -> Result<Self> {
+        let state = store.load()?;
+        Ok(Self { store, volumes: state.volumes, instances: state.instances })
+    }
+
+    /// Add a volume
+    pub fn add_volume(&mut self, volume: Volume) -> Result<()> {
+        let state = self.store.transaction(&mut |state| {
+            state.volumes.insert(volume.id, volume.clone());
+            Ok(())
+        })?;
+        self.volumes = state.volumes;
+        self.instances = state.instances;
+        Ok(())
+    }
+
+    /// Get a volume by ID
+    pub fn get_volume(&self, id: &Uuid) -> Option<&Volume> {
+        self.volumes.get(id)
+    }
+
+    /// Change a stored volume and write it back
+    pub fn update_volume<T>(&mut self, id: &Uuid, change: impl FnOnce(&mut Volume) -> Result<T>) -> Result<T> {
+        let mut change = Some(change);
+        let mut output = None;
+        let state = self.store.transaction(&mut |state| {
+            let volume = state.volumes.get_mut(id)
+                .ok_or_else(|| anyhow!("Volume not found: {}", id))?;
+            let change = change.take().ok_or_else(|| anyhow!("Volume update applied twice"))?;
+            output = Some(change(volume)?);
+            Ok(())
+        })?;
+        self.volumes = state.volumes;
+        self.instances = state.instances;
+        output.ok_or_else(|| anyhow!("Volume update was not applied"))
+    }
+
+    /// Remove a volume
+    pub fn remove_volume(&mut self, id: &Uuid) -> Result<Option<Volume>> {
+        let mut removed = None;
+        let state = self.store.transaction(&mut |state| {
+            removed = state.volumes.remove(id);
+            Ok(())
+        })?;
+        self.volumes = state.volumes;
+        self.instances = state.instances;
+        Ok(removed)
+    }
+
+    /// Get all volumes
+    pub fn get_all_volumes(&self) -> Vec<&Volume> {
+        self.volumes.values().collect()
+    }
+
+    /// Get an instance volumes can be attached to
+    pub fn get_instance(&self, id: &Uuid) -> Option<&Instance> {
+        self.instances.get(id)
+    }
+}
+
+/// Volume service for managing block storage and container volumes
+///
+/// On VyOS a volume is a directory on the router mounted into the
+/// instance's container. On Proxmox it is a VM disk, allocated the first
+/// time the volume is attached.
+pub struct VolumeService {
+    storage: VolumeStorage,
+    provider_service: ProviderService,
+}
+
+impl VolumeService {
+    /// Create a new volume service backed by the state store
+    pub fn new(provider_service: ProviderService) -> Result<Self> {
+        Self::with_store(provider_service, Arc::new(FileStateStore::open()?))
+    }
+
+    /// Create a new volume service backed by a specific state store
+    pub fn with_store(provider_service: ProviderService, store: Arc<dyn StateStore>) -> Result<Self> {
+        Ok(Self {
+            storage: VolumeStorage::new(store)?,
+            provider_service,
+        })
+    }
+
+    /// List all volumes
+    pub fn list_volumes(&self) -> Vec<&Volume> {
+        self.storage.get_all_volumes()
+    }
+
+    /// Get a volume by ID
+    pub fn get_volume(&self, id: &Uuid) -> Option<&Volume> {
+        self.storage.get_volume(id)
+    }
+
+    /// Get the instance a volume is attached to
+    pub fn attached_instance(&self, volume: &Volume) -> Option<&Instance> {
+        volume.attached_to.and_then(|id| self.storage.get_instance(&id))
+    }
+
+    /// Create a volume on a provider
+    ///
+    /// `storage` selects the Proxmox storage and is ignored on VyOS.
+    pub async fn create_volume(
+        &mut self,
+        name: &str,
+        provider_name: &str,
+        region: &str,
+        size_gb: u16,
+        storage: Option<&str>,
+    ) -> Result<Uuid> {
+        let provider = self.provider_service.get_provider(provider_name)
+            .ok_or_else(|| anyhow!("Provider not found: {}", provider_name))?;
+        if sanitize_label(name).is_empty() {
+            return Err(anyhow!("Volume name '{}' is not valid", name));
+        }
+        if size_gb == 0 {
+            return Err(anyhow!("Volume size must be at least 1 GB"));
+        }
+
+        let mut volume = Volume::new(name.to_string(), provider.provider_type, region.to_string(), size_gb, VolumeType::Standard);
+        volume.add_tag(PROVIDER_TAG.to_string(), provider_name.to_string());
+
+        match provider.provider_type {
+            ProviderType::VyOS => {
+                let path = format!("{}/{}", VYOS_VOLUME_ROOT, volume.id);
+                let client = self.provider_service.get_vyos_client(provider_name)?;
+                client.execute_ssh_command(&format!("mkdir -p {}", path)).await
+                    .context(format!("Failed to create volume directory on '{}'", provider_name))?;
+                volume.provider_id = path;
+            },
+            ProviderType::Proxmox => {
+                let storage = storage.unwrap_or(DEFAULT_PROXMOX_STORAGE);
+                volume.add_tag(STORAGE_TAG.to_string(), storage.to_string());
+            },
+        }
+
+        volume.update_status(VolumeStatus::Available);
+        let id = volume.id;
+        self.storage.add_volume(volume)?;
+
+        info!("Created volume '{}' ({} GB) on '{}'", name, size_gb, provider_name);
+        Ok(id)
+    }
+
+    /// Attach a volume to an instance on the same provider
+    ///
+    /// VyOS containers mount the volume at `mount_path`, Proxmox VMs get it
+    /// on the first free SCSI slot. Returns the mount path or slot.
+    pub async fn attach_volume(&mut self, id: &Uuid, instance_id: &Uuid, mount_path: Option<&str>) -> Result<String> {
+        let volume = self.storage.get_volume(id)
+            .ok_or_else(|| anyhow!("Volume not found: {}", id))?
+            .clone();
+        let instance = self.storage.get_instance(instance_id)
+            .ok_or_else(|| anyhow!("Instance not found: {}", instance_id))?
+            .clone();
+
+        if let Some(attached) = volume.attached_to {
+            return Err(anyhow!("Volume '{}' is already attached to {}", volume.name, attached));
+        }
+        let provider_name = volume.tags.get(PROVIDER_TAG)
+            .ok_or_else(|| anyhow!("Volume '{}' has no provider", volume.name))?;
+        if instance.tags.get(PROVIDER_TAG) != Some(provider_name) {
+            return Err(anyhow!("Instance '{}' is not on provider '{}'", instance.name, provider_name));
+        }
+
+        let device = match volume.provider {
+            ProviderType::VyOS => {
+                let mount_path = mount_path.map(str::to_string)
+                    .unwrap_or_else(|| format!("/mnt/{}", sanitize_label(&volume.name)));
+                if !mount_path.starts_with('/') {
+                    return Err(anyhow!("Mount path must be absolute"));
+                }
+
+                let mut client = self.provider_service.get_vyos_client(provider_name)?;
+                client.configure(&render_volume_mount(&instance.provider_id, &volume, &mount_path)).await
+                    .context(format!("Failed to mount volume on '{}'", provider_name))?;
+                client.save().await?;
+                client.execute_op_command(&format!("restart container {}", instance.provider_id)).await?;
+                mount_path
+            },
+            ProviderType::Proxmox => {
+                let vmid = instance.provider_id.parse::<u64>()
+                    .context("Invalid VMID in provider_id")?;
+                let node = self.provider_service.proxmox_node(provider_name, &instance);
+                if let Some(owner) = volume.tags.get(OWNER_TAG) {
+                    if *owner != instance.provider_id {
+                        return Err(anyhow!("Volume '{}' belongs to VM {} and can only be attached to it", volume.name, owner));
+                    }
+                }
+
+                let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+                client.login().await?;
+                let config = client.get_vm_config(&node, vmid).await?;
+                let slot = free_scsi_slot(&config)
+                    .ok_or_else(|| anyhow!("Instance '{}' has no free SCSI slot", instance.name))?;
+
+                // A new volume is allocated on its storage, an existing one re-linked
+                let disk = if volume.provider_id.is_empty() {
+                    let storage = volume.tags.get(STORAGE_TAG).map(String::as_str).unwrap_or(DEFAULT_PROXMOX_STORAGE);
+                    format!("{}:{}", storage, volume.size_gb)
+                } else {
+                    volume.provider_id.clone()
+                };
+                client.update_vm_config(&node, vmid, json!({ slot.clone(): disk })).await
+                    .context(format!("Failed to attach volume to VM {}", vmid))?;
+
+                let config = client.get_vm_config(&node, vmid).await?;
+                let volume_id = config.get(&slot).and_then(|v| v.as_str())
+                    .map(disk_volume_id)
+                    .ok_or_else(|| anyhow!("VM {} does not show the attached disk on {}", vmid, slot))?
+                    .to_string();
+                debug!("Volume {} is Proxmox disk {}", id, volume_id);
+
+                self.storage.update_volume(id, |volume| {
+                    volume.provider_id = volume_id;
+                    volume.add_tag(OWNER_TAG.to_string(), instance.provider_id.clone());
+                    volume.add_tag(NODE_TAG.to_string(), node);
+                    Ok(())
+                })?;
+                slot
+            },
+        };
+
+        self.storage.update_volume(id, |volume| {
+            volume.attach(*instance_id, Some(device.clone()));
+            Ok(())
+        })?;
+
+        info!("Attached volume '{}' to instance '{}' as {}", volume.name, instance.name, device);
+        Ok(device)
+    }
+
+    /// Detach a volume from its instance, keeping its data
+    pub async fn detach_volume(&mut self, id: &Uuid) -> Result<()> {
+        let volume = self.storage.get_volume(id)
+            .ok_or_else(|| anyhow!("Volume not found: {}", id))?
+            .clone();
+        let instance_id = volume.attached_to
+            .ok_or_else(|| anyhow!("Volume '{}' is not attached", volume.name))?;
+        let provider_name = volume.tags.get(PROVIDER_TAG)
+            .ok_or_else(|| anyhow!("Volume '{}' has no provider", volume.name))?;
+
+        // An instance deleted behind the volume's back leaves nothing to unmount
+        if let Some(instance) = self.storage.get_instance(&instance_id).cloned() {
+            match volume.provider {
+                ProviderType::VyOS => {
+                    let name = container_volume_name(&volume);
+                    let mut client = self.provider_service.get_vyos_client(provider_name)?;
+                    client.configure(&[ConfigCommand::delete(["container", "name", instance.provider_id.as_str(), "volume", name.as_str()])]).await
+                        .context(format!("Failed to unmount volume on '{}'", provider_name))?;
+                    client.save().await?;
+                    client.execute_op_command(&format!("restart container {}", instance.provider_id)).await?;
+                },
+                ProviderType::Proxmox => {
+                    let vmid = instance.provider_id.parse::<u64>()
+                        .context("Invalid VMID in provider_id")?;
+                    let node = self.provider_service.proxmox_node(provider_name, &instance);
+                    let slot = volume.device.clone()
+                        .ok_or_else(|| anyhow!("Volume '{}' has no SCSI slot recorded", volume.name))?;
+
+                    // The disk stays on the VM as an unused disk
+                    let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+                    client.login().await?;
+                    client.update_vm_config(&node, vmid, json!({ "delete": slot })).await
+                        .context(format!("Failed to detach volume from VM {}", vmid))?;
+                },
+            }
+        }
+
+        self.storage.update_volume(id, |volume| {
+            volume.detach();
+            Ok(())
+        })?;
+
+        info!("Detached volume '{}' from instance {}", volume.name, instance_id);
+        Ok(())
+    }
+
+    /// Delete a detached volume and its data
+    pub async fn delete_volume(&mut self, id: &Uuid) -> Result<()> {
+        let volume = self.storage.get_volume(id)
+            .ok_or_else(|| anyhow!("Volume not found: {}", id))?
+            .clone();
+        if volume.attached_to.is_some() {
+            return Err(anyhow!("Volume '{}' is attached, detach it first", volume.name));
+        }
+        let provider_name = volume.tags.get(PROVIDER_TAG)
+            .ok_or_else(|| anyhow!("Volume '{}' has no provider", volume.name))?;
+
+        match volume.provider {
+            ProviderType::VyOS => {
+                if !volume.provider_id.starts_with(VYOS_VOLUME_ROOT) {
+                    return Err(anyhow!("Refusing to delete '{}' outside {}", volume.provider_id, VYOS_VOLUME_ROOT));
+                }
+                let client = self.provider_service.get_vyos_client(provider_name)?;
+                client.execute_ssh_command(&format!("rm -rf {}", volume.provider_id)).await
+                    .context(format!("Failed to delete volume directory on '{}'", provider_name))?;
+            },
+            ProviderType::Proxmox => {
+                // A volume that was never attached has no disk yet
+                if !volume.provider_id.is_empty() {
+                    let storage = volume.tags.get(STORAGE_TAG).map(String::as_str).unwrap_or(DEFAULT_PROXMOX_STORAGE);
+                    let node = self.provider_service.tagged_proxmox_node(provider_name, &volume.tags)
+                        .ok_or_else(|| anyhow!("Volume '{}' has no node and provider '{}' has no default node", volume.name, provider_name))?;
+                    let mut client = self.provider_service.get_proxmox_client(provider_name)?;
+                    client.login().await?;
+                    client.delete_storage_content(&node, storage, &volume.provider_id).await
+                        .context(format!("Failed to delete disk {}", volume.provider_id))?;
+                }
+            },
+        }
+
+        self.storage.remove_volume(id)?;
+        info!("Deleted volume '{}'", volume.name);
+        Ok(())
+    }
+}
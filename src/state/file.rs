@@ -0,0 +1,193 @@
+use anyhow::{Result, Context, anyhow};
+use log::debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::get_config_dir;
+use crate::state::migrations::{migrate, SCHEMA_VERSION};
+use crate::state::{State, StateStore, STATE_DIR};
+
+/// File holding the state document
+pub const STATE_FILE: &str = "state.json";
+
+/// File locked while the state is read or written
+pub const LOCK_FILE: &str = "state.lock";
+
+/// State store kept as a JSON document under `~/.bbctl/state/`
+///
+/// Readers take a shared lock and writers an exclusive lock on a separate
+/// lock file, so concurrent bbctl processes serialize their transactions.
+/// Writes go to a temporary file that is synced and renamed over the state
+/// file, so a crash never leaves a partially written document behind.
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    dir: PathBuf,
+}
+
+impl FileStateStore {
+    /// Open the store in the default state directory
+    pub fn open() -> Result<Self> {
+        Self::with_dir(get_config_dir()?.join(STATE_DIR))
+    }
+
+    /// Open the store in a specific directory
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create state directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    /// Directory holding the state
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Open the lock file
+    fn lock_file(&self) -> Result<File> {
+        let path = self.dir.join(LOCK_FILE);
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open state lock {}", path.display()))
+    }
+
+    /// Read and migrate the state document, the caller holds the lock
+    fn read(&self) -> Result<State> {
+        let path = self.dir.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(State::new());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read state file {}", path.display()))?;
+        let mut document: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse state file {}", path.display()))?;
+
+        if migrate(&mut document)? {
+            debug!("Migrated state file {} to schema version {}", path.display(), SCHEMA_VERSION);
+        }
+
+        serde_json::from_value(document)
+            .with_context(|| format!("Failed to load state file {}", path.display()))
+    }
+
+    /// Atomically replace the state document, the caller holds the lock
+    fn write(&self, state: &State) -> Result<()> {
+        let path = self.dir.join(STATE_FILE);
+        let tmp_path = self.dir.join(format!("{}.tmp", STATE_FILE));
+
+        let content = serde_json::to_vec_pretty(state)
+            .context("Failed to serialize state")?;
+
+        let mut file = File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        file.write_all(&content)
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to replace state file {}", path.display()))?;
+
+        // Sync the directory so the rename itself survives a crash
+        if let Ok(dir) = File::open(&self.dir) {
+            let _ = dir.sync_all();
+        }
+
+        Ok(())
+    }
+}
+
+impl StateStore for FileStateStore {
+    fn load(&self) -> Result<State> {
+        let lock = self.lock_file()?;
+        lock.lock_shared()
+            .map_err(|e| anyhow!("Failed to lock state: {}", e))?;
+        self.read()
+    }
+
+    fn transaction(&self, change: &mut dyn FnMut(&mut State) -> Result<()>) -> Result<State> {
+        let lock = self.lock_file()?;
+        lock.lock()
+            .map_err(|e| anyhow!("Failed to lock state: {}", e))?;
+
+        let mut state = self.read()?;
+        change(&mut state)?;
+        state.schema_version = SCHEMA_VERSION;
+        self.write(&state)?;
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tenant::TenantLimits;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn transactions_replace_the_state_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStateStore::with_dir(dir.path()).unwrap();
+        assert!(store.load().unwrap().releases.is_empty());
+
+        store.transaction(&mut |state| {
+            state.releases.insert("web".to_string(), Vec::new());
+            Ok(())
+        }).unwrap();
+        assert!(store.load().unwrap().releases.contains_key("web"));
+        assert!(!dir.path().join(format!("{}.tmp", STATE_FILE)).exists());
+
+        // A failed change leaves the file as it was
+        let before = fs::read(dir.path().join(STATE_FILE)).unwrap();
+        let result = store.transaction(&mut |state| {
+            state.releases.clear();
+            Err(anyhow!("rejected"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read(dir.path().join(STATE_FILE)).unwrap(), before);
+    }
+
+    #[test]
+    fn concurrent_transactions_keep_every_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(FileStateStore::with_dir(dir.path()).unwrap());
+
+        let writers: Vec<_> = (0..4).map(|writer| {
+            // Each writer opens the store on its own, like separate bbctl processes
+            let store = FileStateStore::with_dir(store.dir()).unwrap();
+            thread::spawn(move || {
+                for number in 0..10 {
+                    store.transaction(&mut |state| {
+                        state.tenants.insert(format!("tenant-{}-{}", writer, number), TenantLimits::default());
+                        Ok(())
+                    }).unwrap();
+                }
+            })
+        }).collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(store.load().unwrap().tenants.len(), 40);
+    }
+
+    #[test]
+    fn older_documents_are_migrated_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(STATE_FILE), r#"{"instances": null, "volumes": {}}"#).unwrap();
+        let store = FileStateStore::with_dir(dir.path()).unwrap();
+
+        let state = store.load().unwrap();
+        assert_eq!(state.schema_version, SCHEMA_VERSION);
+        assert!(state.instances.is_empty() && state.networks.is_empty());
+
+        fs::write(dir.path().join(STATE_FILE), format!(r#"{{"schema_version": {}}}"#, SCHEMA_VERSION + 1)).unwrap();
+        assert!(store.load().is_err());
+    }
+}
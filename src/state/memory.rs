@@ -0,0 +1,64 @@
+use anyhow::{Result, anyhow};
+use std::sync::Mutex;
+
+use crate::state::{State, StateStore};
+
+/// State store that lives only as long as the process, for tests
+#[derive(Debug, Default)]
+pub struct MemoryStateStore {
+    state: Mutex<State>,
+}
+
+impl MemoryStateStore {
+    /// Create an empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an in-memory store holding the given state
+    pub fn with_state(state: State) -> Self {
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+}
+
+impl StateStore for MemoryStateStore {
+    fn load(&self) -> Result<State> {
+        let state = self.state.lock().map_err(|_| anyhow!("State lock poisoned"))?;
+        Ok(state.clone())
+    }
+
+    fn transaction(&self, change: &mut dyn FnMut(&mut State) -> Result<()>) -> Result<State> {
+        let mut state = self.state.lock().map_err(|_| anyhow!("State lock poisoned"))?;
+
+        // Work on a copy so a failed change leaves the state untouched
+        let mut updated = state.clone();
+        change(&mut updated)?;
+        *state = updated.clone();
+
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::tenant::TenantLimits;
+
+    #[test]
+    fn failed_changes_leave_the_state_untouched() {
+        let store = MemoryStateStore::new();
+        store.transaction(&mut |state| {
+            state.tenants.insert("acme".to_string(), TenantLimits::default());
+            Ok(())
+        }).unwrap();
+
+        let result = store.transaction(&mut |state| {
+            state.tenants.clear();
+            Err(anyhow!("rejected"))
+        });
+        assert!(result.is_err());
+        assert!(store.load().unwrap().tenants.contains_key("acme"));
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{Result, anyhow};
+use log::info;
+use serde_json::{Map, Value};
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Migration from the version at its index to the next version
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// Migrations in order, `MIGRATIONS[n]` upgrades version `n` to `n + 1`
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [migrate_v0, migrate_v1, migrate_v2];
+
+/// Upgrade a state document to the current schema version
+///
+/// Documents without a version are version 0. Returns whether the document
+/// changed, and refuses state written by a newer bbctl rather than risk
+/// dropping fields it does not know about.
+pub fn migrate(document: &mut Value) -> Result<bool> {
+    let object = document.as_object_mut()
+        .ok_or_else(|| anyhow!("State document is not a JSON object"))?;
+
+    let version = match object.get("schema_version") {
+        None => 0,
+        Some(value) => value.as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow!("Invalid state schema version: {}", value))?,
+    };
+
+    if version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "State schema version {} is newer than the supported version {}, upgrade bbctl",
+            version, SCHEMA_VERSION
+        ));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        info!("Migrating state from schema version {} to {}", from, from + 1);
+        migration(object)?;
+        object.insert("schema_version".to_string(), Value::from(from as u32 + 1));
+    }
+
+    Ok(version < SCHEMA_VERSION)
+}
+
+/// Version 0 is an unversioned document, fill in any missing collection
+fn migrate_v0(object: &mut Map<String, Value>) -> Result<()> {
+    for collection in ["instances", "volumes", "networks"] {
+        match object.get(collection) {
+            None | Some(Value::Null) => {
+                object.insert(collection.to_string(), Value::Object(Map::new()));
+            },
+            Some(Value::Object(_)) => {},
+            Some(_) => return Err(anyhow!("State collection '{}' is not a JSON object", collection)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Version 2 records application releases
+fn migrate_v1(object: &mut Map<String, Value>) -> Result<()> {
+    object.entry("releases").or_insert_with(|| Value::Object(Map::new()));
+    Ok(())
+}
+
+/// Version 3 records tenant bandwidth limits
+fn migrate_v2(object: &mut Map<String, Value>) -> Result<()> {
+    object.entry("tenants").or_insert_with(|| Value::Object(Map::new()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn unversioned_documents_get_every_collection() {
+        let mut document = json!({ "instances": null, "networks": {} });
+        assert!(migrate(&mut document).unwrap());
+        assert_eq!(document, json!({
+            "schema_version": SCHEMA_VERSION,
+            "instances": {},
+            "volumes": {},
+            "networks": {},
+            "releases": {},
+            "tenants": {},
+        }));
+        assert!(!migrate(&mut document).unwrap());
+    }
+
+    #[test]
+    fn later_migrations_keep_existing_data() {
+        let mut document = json!({ "schema_version": 1, "instances": {}, "volumes": {}, "networks": {}, "releases": { "web": [] } });
+        assert!(migrate(&mut document).unwrap());
+        assert_eq!(document["schema_version"], SCHEMA_VERSION);
+        assert_eq!(document["releases"], json!({ "web": [] }));
+        assert_eq!(document["tenants"], json!({}));
+    }
+
+    #[test]
+    fn invalid_and_newer_documents_are_refused() {
+        assert!(migrate(&mut json!([])).is_err());
+        assert!(migrate(&mut json!({ "instances": [] })).is_err());
+        assert!(migrate(&mut json!({ "schema_version": "2" })).is_err());
+        assert!(migrate(&mut json!({ "schema_version": SCHEMA_VERSION + 1 })).is_err());
+    }
+}
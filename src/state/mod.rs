@@ -0,0 +1,93 @@
+pub mod file;
+pub mod memory;
+pub mod migrations;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::models::app::Release;
+use crate::models::instance::Instance;
+use crate::models::network::Network;
+use crate::models::tenant::TenantLimits;
+use crate::models::volume::Volume;
+
+pub use file::FileStateStore;
+pub use memory::MemoryStateStore;
+pub use migrations::SCHEMA_VERSION;
+
+/// Directory under the config directory that holds the state store
+pub const STATE_DIR: &str = "state";
+
+/// Resources bbctl has created, shared by every bbctl process
+///
+/// IP allocations are kept on their network, so they are stored and
+/// locked together with the network they belong to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct State {
+    /// Schema version the state was written with
+    pub schema_version: u32,
+    /// Instances by ID
+    #[serde(default)]
+    pub instances: BTreeMap<Uuid, Instance>,
+    /// Volumes by ID
+    #[serde(default)]
+    pub volumes: BTreeMap<Uuid, Volume>,
+    /// Networks, with their IP allocations, by ID
+    #[serde(default)]
+    pub networks: BTreeMap<Uuid, Network>,
+    /// Releases of each application, oldest first
+    #[serde(default)]
+    pub releases: BTreeMap<String, Vec<Release>>,
+    /// Bandwidth limits of the tenants that have any, by tenant name
+    #[serde(default)]
+    pub tenants: BTreeMap<String, TenantLimits>,
+}
+
+impl State {
+    /// Create an empty state at the current schema version
+    pub fn new() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            instances: BTreeMap::new(),
+            volumes: BTreeMap::new(),
+            networks: BTreeMap::new(),
+            releases: BTreeMap::new(),
+            tenants: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Backend that persists the resource state
+///
+/// A transaction reads the latest state, applies the change and writes it
+/// back while holding the store's lock, so concurrent writers never lose
+/// each other's changes. If the change fails nothing is written.
+pub trait StateStore: std::fmt::Debug + Send + Sync {
+    /// Read the current state
+    fn load(&self) -> Result<State>;
+
+    /// Apply a change to the current state and return the state it produced
+    fn transaction(&self, change: &mut dyn FnMut(&mut State) -> Result<()>) -> Result<State>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_states_round_trip_at_the_current_version() {
+        let document = serde_json::to_value(State::new()).unwrap();
+        assert_eq!(document["schema_version"], SCHEMA_VERSION);
+
+        let state: State = serde_json::from_value(serde_json::json!({ "schema_version": SCHEMA_VERSION })).unwrap();
+        assert!(state.instances.is_empty() && state.releases.is_empty() && state.tenants.is_empty());
+    }
+}
@@ -7,6 +7,7 @@ use ratatui::{
 };
 
 use crate::app::{App, AppMode};
+use crate::models::logs::Severity;
 
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame) {
@@ -25,7 +26,7 @@ pub fn render(app: &mut App, frame: &mut Frame) {
         .split(frame.area());
 
     // Render the title bar
-    let titles = vec!["Home", "Instances", "Volumes", "Networks", "Settings", "Help"];
+    let titles = ["Home", "Instances", "Volumes", "Networks", "Logs", "Settings", "Help"];
     let tabs = Tabs::new(
         titles
             .iter()
@@ -50,6 +51,7 @@ pub fn render(app: &mut App, frame: &mut Frame) {
         AppMode::Instances => render_instances(app, frame, chunks[1]),
         AppMode::Volumes => render_volumes(app, frame, chunks[1]),
         AppMode::Networks => render_networks(app, frame, chunks[1]),
+        AppMode::Logs => render_logs(app, frame, chunks[1]),
         AppMode::Settings => render_settings(app, frame, chunks[1]),
         AppMode::Help => render_help(app, frame, chunks[1]),
     }
@@ -205,6 +207,14 @@ fn render_volumes(app: &mut App, frame: &mut Frame, area: Rect) {
 }
 
 fn render_networks(app: &mut App, frame: &mut Frame, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+
+    render_routers(app, frame, chunks[1]);
+    let area = chunks[0];
+
     let networks = Block::bordered()
         .title("Networks")
         .border_type(BorderType::Rounded);
@@ -254,8 +264,116 @@ fn render_networks(app: &mut App, frame: &mut Frame, area: Rect) {
     frame.render_stateful_widget(list, area, &mut state);
 }
 
+fn render_routers(app: &mut App, frame: &mut Frame, area: Rect) {
+    let routers = Block::bordered()
+        .title("Routers")
+        .border_type(BorderType::Rounded);
+
+    if app.routers.is_empty() {
+        let text = if app.loading_routers {
+            Text::from("Loading router status...")
+        } else {
+            Text::from("Press 'r' to load router status.")
+        };
+        let paragraph = Paragraph::new(text)
+            .block(routers)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let mut lines = Vec::new();
+    for (name, status) in &app.routers {
+        lines.push(Line::from(Span::styled(name.clone(), Style::default().fg(Color::Cyan))));
+
+        match status {
+            Ok(status) => {
+                let interfaces_up = status.interfaces.iter().filter(|i| i.is_up()).count();
+                let bgp_up = status.bgp_neighbors.iter().filter(|n| n.is_established()).count();
+                let ospf_up = status.ospf_neighbors.iter().filter(|n| n.is_full()).count();
+                let health = |up: usize, total: usize| {
+                    if up == total { Style::default().fg(Color::Green) } else { Style::default().fg(Color::Red) }
+                };
+
+                lines.push(Line::from(vec![
+                    Span::raw("  Interfaces: "),
+                    Span::styled(format!("{}/{} up", interfaces_up, status.interfaces.len()),
+                                 health(interfaces_up, status.interfaces.len())),
+                    Span::raw(format!("  Routes: {}", status.routes.iter().filter(|r| r.selected).count())),
+                ]));
+                lines.push(Line::from(vec![
+                    Span::raw("  BGP: "),
+                    Span::styled(format!("{}/{} established", bgp_up, status.bgp_neighbors.len()),
+                                 health(bgp_up, status.bgp_neighbors.len())),
+                    Span::raw("  OSPF: "),
+                    Span::styled(format!("{}/{} full", ospf_up, status.ospf_neighbors.len()),
+                                 health(ospf_up, status.ospf_neighbors.len())),
+                ]));
+                lines.push(Line::from(format!("  ARP entries: {}", status.arp.len())));
+                for error in &status.errors {
+                    lines.push(Line::from(Span::styled(format!("  {}", error), Style::default().fg(Color::Yellow))));
+                }
+            }
+            Err(e) => {
+                lines.push(Line::from(Span::styled(format!("  {}", e), Style::default().fg(Color::Red))));
+            }
+        }
+        lines.push(Line::from(""));
+    }
+
+    let paragraph = Paragraph::new(Text::from(lines)).block(routers);
+    frame.render_widget(paragraph, area);
+}
+
+fn render_logs(app: &mut App, frame: &mut Frame, area: Rect) {
+    let logs = Block::bordered()
+        .title("Logs")
+        .border_type(BorderType::Rounded);
+
+    if app.logs.is_empty() {
+        let text = Text::from("No logs collected. Run 'bbctl logs collect' to receive router and hypervisor logs.");
+        let paragraph = Paragraph::new(text)
+            .block(logs)
+            .alignment(Alignment::Center);
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .logs
+        .iter()
+        .map(|entry| {
+            let severity_style = match entry.severity {
+                Severity::Emergency | Severity::Alert | Severity::Critical | Severity::Error => {
+                    Style::default().fg(Color::Red)
+                }
+                Severity::Warning => Style::default().fg(Color::Yellow),
+                Severity::Debug => Style::default().fg(Color::DarkGray),
+                _ => Style::default(),
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{} ", entry.timestamp.unwrap_or(entry.received_at).format("%m-%d %H:%M:%S"))),
+                Span::styled(format!("{:<12} ", entry.sender()), Style::default().fg(Color::Cyan)),
+                Span::styled(format!("{:<7} ", entry.severity), severity_style),
+                Span::raw(format!("{}: {}", entry.app_name.as_deref().unwrap_or("-"), entry.message)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(logs)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    // Use a stateful widget
+    let mut state = ListState::default();
+    state.select(Some(app.selected_index));
+
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
 fn render_settings(_app: &mut App, frame: &mut Frame, area: Rect) {
-    let settings = vec![
+    let settings = [
         ("API Endpoint", "https://api.bitbuilder.io"),
         ("Default Region", "nyc"),
         ("Default Provider", "vyos"),
@@ -285,7 +403,7 @@ fn render_settings(_app: &mut App, frame: &mut Frame, area: Rect) {
 
 fn render_help(_app: &mut App, frame: &mut Frame, area: Rect) {
     let keys = vec![
-        ("1-5", "Switch tabs"),
+        ("1-6", "Switch tabs"),
         ("Tab/Shift+Tab", "Next/Previous tab"),
         ("j/k or ↑/↓", "Navigate items"),
         ("a", "Add new item"),
@@ -312,10 +430,11 @@ fn render_help(_app: &mut App, frame: &mut Frame, area: Rect) {
 
 fn render_footer(app: &mut App, frame: &mut Frame, area: Rect) {
     let text = match app.mode {
-        AppMode::Home => "1-5: Navigate | q: Quit",
+        AppMode::Home => "1-6: Navigate | q: Quit",
         AppMode::Instances => "a: Add | d: Delete | e: Edit | r: Restart | s: Stop | ↑/↓: Navigate",
         AppMode::Volumes => "a: Add | d: Delete | e: Edit | a: Attach | d: Detach | ↑/↓: Navigate",
-        AppMode::Networks => "a: Add | d: Delete | e: Edit | c: Connect VM | ↑/↓: Navigate",
+        AppMode::Networks => "a: Add | d: Delete | e: Edit | c: Connect VM | r: Router status | ↑/↓: Navigate",
+        AppMode::Logs => "r: Reload | ↑/↓: Scroll",
         AppMode::Settings => "e: Edit Setting | r: Reset to Default",
         AppMode::Help => "Press any key to return",
     };